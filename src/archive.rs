@@ -0,0 +1,209 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::storage::{self, StorageError};
+
+///The size of the blocks of a tar archive
+const BLOCK_SIZE: usize = 512;
+///The longest name of a file in the archive
+const MAX_NAME_LENGTH: usize = 100;
+///The position of the size of the file in a header
+const SIZE_OFFSET: usize = 124;
+///The position of the checksum in a header
+const CHECKSUM_OFFSET: usize = 148;
+///The position of the type of the entry in a header
+const TYPE_OFFSET: usize = 156;
+///The position of the magic value in a header
+const MAGIC_OFFSET: usize = 257;
+
+///A file of the archive: its path relative to the suite directory and its content
+type Entry = (String, Vec<u8>);
+
+///Retrieve every json file of a directory and of its sub-directories
+fn collect(dir: &Path, prefix: &str, entries: &mut Vec<Entry>) -> Result<(), StorageError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    for path in paths {
+        let name = format!(
+            "{prefix}{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        if path.is_dir() {
+            collect(&path, &format!("{name}/"), entries)?;
+        } else if path.extension().is_some_and(|e| e == "json") {
+            entries.push((name, std::fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+///Write a value as a null terminated octal number
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+fn read_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+///The checksum of a header: the sum of its bytes, the checksum counting as spaces
+fn checksum(header: &[u8; BLOCK_SIZE]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            if (CHECKSUM_OFFSET..CHECKSUM_OFFSET + 8).contains(&i) {
+                b' ' as u64
+            } else {
+                *b as u64
+            }
+        })
+        .sum()
+}
+
+fn header(name: &str, size: usize) -> Result<[u8; BLOCK_SIZE], StorageError> {
+    if name.len() >= MAX_NAME_LENGTH {
+        return Err(StorageError::InvalidArchive(format!(
+            "name too long: {name}"
+        )));
+    }
+    let mut header = [0_u8; BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[SIZE_OFFSET..SIZE_OFFSET + 12], size as u64);
+    write_octal(&mut header[136..148], 0);
+    header[TYPE_OFFSET] = b'0';
+    header[MAGIC_OFFSET..MAGIC_OFFSET + 8].copy_from_slice(b"ustar\x0000");
+    let sum = checksum(&header);
+    write_octal(&mut header[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 7], sum);
+    header[CHECKSUM_OFFSET + 7] = b' ';
+    Ok(header)
+}
+
+///The number of bytes needed to fill the last block of a file
+fn padding(size: usize) -> usize {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+fn write_archive<W: Write>(out: &mut W, entries: &[Entry]) -> Result<(), StorageError> {
+    for (name, content) in entries {
+        out.write_all(&header(name, content.len())?)?;
+        out.write_all(content)?;
+        out.write_all(&vec![0; padding(content.len())])?;
+    }
+    //the end of the archive is marked by two empty blocks
+    out.write_all(&[0; 2 * BLOCK_SIZE])?;
+    Ok(())
+}
+
+///Only the files in the suite directory can be written
+fn is_safe(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('/')
+        && !name.contains('\\')
+        && !name
+            .split('/')
+            .any(|part| part == ".." || part.contains(':'))
+}
+
+fn read_archive<R: Read>(input: &mut R) -> Result<Vec<Entry>, StorageError> {
+    let mut entries = Vec::new();
+    let mut header = [0_u8; BLOCK_SIZE];
+    loop {
+        input.read_exact(&mut header)?;
+        if header.iter().all(|b| *b == 0) {
+            return Ok(entries);
+        }
+        let expected = read_octal(&header[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 8]);
+        if expected != Some(checksum(&header)) {
+            return Err(StorageError::InvalidArchive(String::from(
+                "not a tar archive",
+            )));
+        }
+        let size = read_octal(&header[SIZE_OFFSET..SIZE_OFFSET + 12])
+            .ok_or_else(|| StorageError::InvalidArchive(String::from("invalid file size")))?
+            as usize;
+        let mut content = vec![0; size + padding(size)];
+        input.read_exact(&mut content)?;
+        content.truncate(size);
+        let name_end = header[..MAX_NAME_LENGTH]
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(MAX_NAME_LENGTH);
+        let name = String::from_utf8_lossy(&header[..name_end]).into_owned();
+        //only the regular files are extracted
+        if header[TYPE_OFFSET] == b'0' || header[TYPE_OFFSET] == 0 {
+            if !is_safe(&name) {
+                return Err(StorageError::InvalidArchive(format!(
+                    "invalid file name: {name}"
+                )));
+            }
+            entries.push((name, content));
+        }
+    }
+}
+
+///Write the whole setup of the suite (presets, mappings, shortcuts, scenes,
+/// sessions...) into a single tar archive. Return the number of files.
+pub fn export(path: &Path) -> Result<usize, StorageError> {
+    let mut entries = Vec::new();
+    collect(&storage::config_dir()?, "", &mut entries)?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_archive(&mut out, &entries)?;
+    out.flush()?;
+    Ok(entries.len())
+}
+
+///Extract an archive made by [export] in the suite directory. The existing
+/// files are replaced. Return the number of files.
+pub fn import(path: &Path) -> Result<usize, StorageError> {
+    let mut input = std::io::BufReader::new(std::fs::File::open(path)?);
+    let entries = read_archive(&mut input)?;
+    let dir = storage::config_dir()?;
+    for (name, content) in &entries {
+        let mut destination = dir.clone();
+        destination.extend(name.split('/'));
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        storage::write(&destination, content)?;
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_archive, write_archive};
+
+    #[test]
+    fn archive_round_trip() {
+        let entries = vec![
+            (String::from("presets.json"), b"{}".to_vec()),
+            (String::from("scenes/intro/kick.json"), vec![b'1'; 700]),
+        ];
+        let mut archive = Vec::new();
+        write_archive(&mut archive, &entries).unwrap();
+        assert_eq!(archive.len() % 512, 0);
+        assert_eq!(read_archive(&mut archive.as_slice()).unwrap(), entries);
+
+        archive[0] = b'x';
+        assert!(read_archive(&mut archive.as_slice()).is_err());
+
+        let mut unsafe_archive = Vec::new();
+        let evil = vec![(String::from("../evil.json"), b"{}".to_vec())];
+        write_archive(&mut unsafe_archive, &evil).unwrap();
+        assert!(read_archive(&mut unsafe_archive.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use rsuite::synth::{clap, render_clap, RenderOptions};
+
+#[cfg(target_os = "windows")]
+#[link(name = "C:/Program Files/JACK2/lib/libjack64")]
+extern "C" {}
+
+fn main() {
+    match RenderOptions::from_args() {
+        Ok(Some(options)) => {
+            if let Err(e) = render_clap(&options) {
+                println!("Error: {e}");
+            }
+        }
+        Ok(None) => {
+            if let Err(e) = clap() {
+                println!("Error: {e}");
+            }
+        }
+        Err(e) => println!("Error: {e}"),
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use rsuite::synth::{hihat, render_hihat, RenderOptions};
+
+#[cfg(target_os = "windows")]
+#[link(name = "C:/Program Files/JACK2/lib/libjack64")]
+extern "C" {}
+
+fn main() {
+    match RenderOptions::from_args() {
+        Ok(Some(options)) => {
+            if let Err(e) = render_hihat(&options) {
+                println!("Error: {e}");
+            }
+        }
+        Ok(None) => {
+            if let Err(e) = hihat() {
+                println!("Error: {e}");
+            }
+        }
+        Err(e) => println!("Error: {e}"),
+    }
+}
@@ -1,13 +1,23 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use rsuite::synth::kick;
+use rsuite::synth::{kick, render_kick, RenderOptions};
 
 #[cfg(target_os = "windows")]
 #[link(name = "C:/Program Files/JACK2/lib/libjack64")]
 extern "C" {}
 
 fn main() {
-    if let Err(e) = kick() {
-        println!("Error: {e}");
+    match RenderOptions::from_args() {
+        Ok(Some(options)) => {
+            if let Err(e) = render_kick(&options) {
+                println!("Error: {e}");
+            }
+        }
+        Ok(None) => {
+            if let Err(e) = kick() {
+                println!("Error: {e}");
+            }
+        }
+        Err(e) => println!("Error: {e}"),
     }
 }
@@ -58,7 +58,10 @@ fn main() {
     //spawn a non-real-time thread that prints out the midi messages we get
     std::thread::spawn(move || {
         while let Ok(m) = receiver.recv() {
-            println!("{m:?}");
+            match m.0.drum_name() {
+                Some(drum) => println!("{m:?} {drum}"),
+                None => println!("{m:?}"),
+            }
         }
     });
 
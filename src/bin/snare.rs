@@ -1,13 +1,23 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use rsuite::synth::snare;
+use rsuite::synth::{render_snare, snare, RenderOptions};
 
 #[cfg(target_os = "windows")]
 #[link(name = "C:/Program Files/JACK2/lib/libjack64")]
 extern "C" {}
 
 fn main() {
-    if let Err(e) = snare() {
-        println!("Error: {e}");
+    match RenderOptions::from_args() {
+        Ok(Some(options)) => {
+            if let Err(e) = render_snare(&options) {
+                println!("Error: {e}");
+            }
+        }
+        Ok(None) => {
+            if let Err(e) = snare() {
+                println!("Error: {e}");
+            }
+        }
+        Err(e) => println!("Error: {e}"),
     }
 }
@@ -1,12 +1,73 @@
-use std::ops::RangeInclusive;
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    dsp::{db_to_gain, gain_to_db},
+    i18n::tr,
+    spsc::MessageSender,
     synth::{hardware::KeyBoardKey, wavetype::WaveType},
     utils::{create_keyboard_select, KeyBoardKeySetter},
 };
 
+///The unit in which a value is shown to the user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    ///The raw value
+    #[default]
+    None,
+    ///A frequency, in Hz
+    Hertz,
+    ///A duration in seconds, shown in milliseconds
+    Milliseconds,
+    ///A duration in frames at the given audio rate, shown in milliseconds
+    Frames(usize),
+    ///A linear gain, shown in decibels
+    Decibels,
+    ///A value between 0 and 1, shown as a percentage
+    Percent,
+}
+
+impl Unit {
+    ///Format a value for the user interface
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            Unit::None => format!("{value}"),
+            Unit::Hertz if value >= 1000.0 => format!("{:.2} kHz", value / 1000.0),
+            Unit::Hertz => format!("{value:.1} Hz"),
+            Unit::Milliseconds => format!("{:.1} ms", value * 1000.0),
+            Unit::Frames(rate) => format!("{:.1} ms", value * 1000.0 / *rate as f64),
+            Unit::Decibels if value <= 0.0 => String::from("-inf dB"),
+            Unit::Decibels => format!("{:.1} dB", gain_to_db(value)),
+            Unit::Percent => format!("{:.0} %", value * 100.0),
+        }
+    }
+
+    ///Parse a value typed by the user, with or without its unit
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        let number_end = text
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(text.len());
+        let suffix = text[number_end..].trim().to_lowercase();
+        if *self == Unit::Decibels && text.starts_with("-inf") {
+            return Some(0.0);
+        }
+        let number: f64 = text[..number_end].parse().ok()?;
+        Some(match self {
+            Unit::None => number,
+            Unit::Hertz if suffix.starts_with('k') => number * 1000.0,
+            Unit::Hertz => number,
+            Unit::Milliseconds => number / 1000.0,
+            Unit::Frames(rate) => number * *rate as f64 / 1000.0,
+            Unit::Decibels => db_to_gain(number),
+            Unit::Percent => number / 100.0,
+        })
+    }
+}
+
 /// Represent a float value that must be whithin a range
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FloatValueInRange {
     ///The current value
     value: f64,
@@ -16,6 +77,12 @@ pub struct FloatValueInRange {
     name: String,
     ///The keyboard key that we want to map to the value
     key: KeyBoardKey,
+    ///The unit used to show the value
+    #[serde(default)]
+    unit: Unit,
+    ///If true, the slider and the midi control follow a logarithmic scale
+    #[serde(default)]
+    logarithmic: bool,
 }
 
 impl FloatValueInRange {
@@ -32,14 +99,34 @@ impl FloatValueInRange {
             range: RangeInclusive::new(range_start, range_end),
             name: String::from(name),
             key,
+            unit: Unit::None,
+            logarithmic: false,
         }
     }
 
+    ///Show the value in the given unit
+    pub fn with_unit(mut self, unit: Unit) -> FloatValueInRange {
+        self.unit = unit;
+        self
+    }
+
+    ///Use a logarithmic scale for the slider and the midi control.
+    /// Only effective if the range is strictly positive.
+    pub fn logarithmic(mut self) -> FloatValueInRange {
+        self.logarithmic = true;
+        self
+    }
+
     ///Change the value based on the value retrieved by the midi key
     pub fn from_midi_value(&mut self, value: u8) -> bool {
-        let nv = value as f64 * (self.range.end() - self.range.start()) / 128.0;
+        let position = value as f64 / 128.0;
+        let nv = if self.logarithmic && *self.range.start() > 0.0 {
+            self.range.start() * (self.range.end() / self.range.start()).powf(position)
+        } else {
+            self.range.start() + position * (self.range.end() - self.range.start())
+        };
         if nv != self.value {
-            self.value = value as f64 * (self.range.end() - self.range.start()) / 128.0;
+            self.value = nv;
             return true;
         }
         false
@@ -55,27 +142,38 @@ impl FloatValueInRange {
         self.key
     }
 
-    ///Draw the value
-    fn draw(&mut self, ui: &mut eframe::egui::Ui) {
+    ///Draw the value, followed by 'add_contents' on the same row
+    fn draw(
+        &mut self,
+        ui: &mut eframe::egui::Ui,
+        add_contents: impl FnOnce(&mut eframe::egui::Ui),
+    ) {
         ui.horizontal(|ui| {
-            ui.label(&self.name);
+            ui.label(tr(&self.name));
             ui.add_enabled(
                 true,
-                eframe::egui::Slider::new(&mut self.value, self.range.clone()).show_value(false),
+                eframe::egui::Slider::new(&mut self.value, self.range.clone())
+                    .logarithmic(self.logarithmic)
+                    .show_value(false),
             );
             let speed = self.range.end() - self.range.start();
-            ui.add_enabled(
-                true,
-                eframe::egui::DragValue::new(&mut self.value)
-                    .range(self.range.clone())
-                    .speed(speed / 128.0),
-            );
+            let unit = self.unit;
+            let mut drag = eframe::egui::DragValue::new(&mut self.value)
+                .range(self.range.clone())
+                .speed(speed / 128.0);
+            if unit != Unit::None {
+                drag = drag
+                    .custom_formatter(move |v, _| unit.format(v))
+                    .custom_parser(move |text| unit.parse(text));
+            }
+            ui.add_enabled(true, drag);
+            add_contents(ui);
         });
     }
 }
 
 /// Represent a value that must be whithin a range
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UsizeValueInRange {
     ///The current value
     value: usize,
@@ -85,6 +183,9 @@ pub struct UsizeValueInRange {
     name: String,
     ///The keyboard key that we want to map to the value
     key: KeyBoardKey,
+    ///The unit used to show the value
+    #[serde(default)]
+    unit: Unit,
 }
 
 impl UsizeValueInRange {
@@ -100,12 +201,20 @@ impl UsizeValueInRange {
             range: RangeInclusive::new(range_start, range_end),
             name: String::from(name),
             key,
+            unit: Unit::None,
         }
     }
 
+    ///Show the value in the given unit
+    pub fn with_unit(mut self, unit: Unit) -> UsizeValueInRange {
+        self.unit = unit;
+        self
+    }
+
     ///Change the value based on the value retrieved by the midi key
     pub fn from_midi_value(&mut self, value: u8) -> bool {
-        let nv = value as usize * (self.range.end() - self.range.start()) / 128;
+        let nv =
+            self.range.start() + value as usize * (self.range.end() - self.range.start()) / 128;
         if nv != self.value {
             self.value = nv;
             return true;
@@ -121,26 +230,35 @@ impl UsizeValueInRange {
         self.key
     }
 
-    fn draw(&mut self, ui: &mut eframe::egui::Ui) {
+    fn draw(
+        &mut self,
+        ui: &mut eframe::egui::Ui,
+        add_contents: impl FnOnce(&mut eframe::egui::Ui),
+    ) {
         ui.horizontal(|ui| {
-            ui.label(&self.name);
+            ui.label(tr(&self.name));
             ui.add_enabled(
                 true,
                 eframe::egui::Slider::new(&mut self.value, self.range.clone()).show_value(false),
             );
             let speed = self.range.end() - self.range.start();
-            ui.add_enabled(
-                true,
-                eframe::egui::DragValue::new(&mut self.value)
-                    .range(self.range.clone())
-                    .speed(speed as f64 / 128.0),
-            );
+            let unit = self.unit;
+            let mut drag = eframe::egui::DragValue::new(&mut self.value)
+                .range(self.range.clone())
+                .speed(speed as f64 / 128.0);
+            if unit != Unit::None {
+                drag = drag
+                    .custom_formatter(move |v, _| unit.format(v))
+                    .custom_parser(move |text| unit.parse(text));
+            }
+            ui.add_enabled(true, drag);
+            add_contents(ui);
         });
     }
 }
 
 /// Represent a value that must be whithin a range
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WaveTypeValue {
     ///The current value
     value: WaveType,
@@ -169,19 +287,24 @@ impl WaveTypeValue {
     }
 
     pub fn get_value(&self) -> WaveType {
-        self.value
+        self.value.clone()
     }
 
     fn get_keyboard_key(&self) -> KeyBoardKey {
         self.key
     }
 
-    fn draw(&mut self, ui: &mut eframe::egui::Ui) {
+    fn draw(
+        &mut self,
+        ui: &mut eframe::egui::Ui,
+        add_contents: impl FnOnce(&mut eframe::egui::Ui),
+    ) {
         ui.horizontal(|ui| {
-            ui.label("Wave type:");
+            ui.label(tr("Wave type:"));
             if ui.button(format!("{}", self.value)).clicked() {
                 self.value = self.value.cycle();
             }
+            add_contents(ui);
         });
         let line = crate::utils::create_plot_line(&self.value);
         egui_plot::Plot::new(format!("Wave type: {}", self.value))
@@ -190,20 +313,159 @@ impl WaveTypeValue {
     }
 }
 
+/// Represent a value that is either on or off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoolValue {
+    ///The current value
+    value: bool,
+    ///The name of the value
+    name: String,
+    ///The keyboard key that we want to map to the value
+    key: KeyBoardKey,
+}
+
+impl BoolValue {
+    pub fn new(value: bool, name: &str, key: KeyBoardKey) -> BoolValue {
+        BoolValue {
+            value,
+            name: String::from(name),
+            key,
+        }
+    }
+
+    ///Change the value based on the value retrieved by the midi key
+    pub fn from_midi_value(&mut self, value: u8) -> bool {
+        if value > 0 {
+            self.value = !self.value;
+            return true;
+        }
+        false
+    }
+
+    pub fn get_value(&self) -> bool {
+        self.value
+    }
+
+    fn get_keyboard_key(&self) -> KeyBoardKey {
+        self.key
+    }
+
+    fn draw(
+        &mut self,
+        ui: &mut eframe::egui::Ui,
+        add_contents: impl FnOnce(&mut eframe::egui::Ui),
+    ) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.value, tr(&self.name));
+            add_contents(ui);
+        });
+    }
+}
+
+///A midi control, as received from a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiControl {
+    ///The midi channel, from 0 to 15
+    pub channel: u8,
+    ///The controller number
+    pub control: u8,
+}
+
+impl std::fmt::Display for MidiControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CC {} (ch. {})", self.control, self.channel + 1)
+    }
+}
+
+///The midi controls mapped to the keyboard keys, as known by the user interface
+#[derive(Debug, Clone, Default)]
+pub struct MidiMappings {
+    controls: HashMap<KeyBoardKey, MidiControl>,
+}
+
+impl MidiMappings {
+    pub fn new() -> MidiMappings {
+        MidiMappings {
+            controls: HashMap::new(),
+        }
+    }
+
+    ///Record the control mapped to a key, None if the key is not mapped anymore
+    pub fn set(&mut self, key: KeyBoardKey, control: Option<MidiControl>) {
+        match control {
+            Some(control) => {
+                //a control can only be mapped to a single key
+                self.controls.retain(|_, c| c.control != control.control);
+                self.controls.insert(key, control);
+            }
+            None => {
+                self.controls.remove(&key);
+            }
+        }
+    }
+
+    ///Retrieve the control mapped to a key
+    pub fn get(&self, key: KeyBoardKey) -> Option<MidiControl> {
+        self.controls.get(&key).copied()
+    }
+}
+
+///Show the control mapped to a key. A right click on it allows to learn or clear the control.
+fn draw_mapping<T, S>(
+    ui: &mut eframe::egui::Ui,
+    keyboard_key: KeyBoardKey,
+    control: Option<MidiControl>,
+    sender: &mut S,
+    messages: &mut Vec<String>,
+) where
+    T: From<KeyBoardKeySetter>,
+    S: MessageSender<T>,
+{
+    let text = match control {
+        Some(control) => format!("{control}"),
+        None => String::from(tr("no CC")),
+    };
+    let label = eframe::egui::Label::new(eframe::egui::RichText::new(text).weak())
+        .sense(eframe::egui::Sense::click());
+    ui.add(label)
+        .on_hover_text(tr("Right click to learn or clear the midi control"))
+        .context_menu(|ui| {
+            let setter = if ui.button(tr("Learn")).clicked() {
+                Some(KeyBoardKeySetter::Set(keyboard_key))
+            } else if ui.button(tr("Clear")).clicked() {
+                Some(KeyBoardKeySetter::Clear(keyboard_key))
+            } else {
+                None
+            };
+            if let Some(setter) = setter {
+                if let Err(e) = sender.send(T::from(setter)) {
+                    messages.push(format!("Unable to send message for {keyboard_key}: {e}"));
+                }
+                ui.close_menu();
+            }
+        });
+}
+
 ///A value stored in a configuration
 pub enum ConfigurationValue<'conf> {
     Float(&'conf mut FloatValueInRange),
     USize(&'conf mut UsizeValueInRange),
     WaveType(&'conf mut WaveTypeValue),
+    Bool(&'conf mut BoolValue),
 }
 
 impl<'conf> ConfigurationValue<'conf> {
-    ///Draw the configurable value
-    fn draw(&mut self, ui: &mut eframe::egui::Ui) {
+    ///Draw the configurable value, followed by 'add_contents' on the same row
+    fn draw(
+        &mut self,
+        ui: &mut eframe::egui::Ui,
+        add_contents: impl FnOnce(&mut eframe::egui::Ui),
+    ) {
         match self {
-            ConfigurationValue::Float(value) => value.draw(ui),
-            ConfigurationValue::USize(value) => value.draw(ui),
-            ConfigurationValue::WaveType(value) => value.draw(ui),
+            ConfigurationValue::Float(value) => value.draw(ui, add_contents),
+            ConfigurationValue::USize(value) => value.draw(ui, add_contents),
+            ConfigurationValue::WaveType(value) => value.draw(ui, add_contents),
+            ConfigurationValue::Bool(value) => value.draw(ui, add_contents),
         }
     }
 
@@ -213,6 +475,7 @@ impl<'conf> ConfigurationValue<'conf> {
             ConfigurationValue::Float(value) => value.get_keyboard_key(),
             ConfigurationValue::USize(value) => value.get_keyboard_key(),
             ConfigurationValue::WaveType(value) => value.get_keyboard_key(),
+            ConfigurationValue::Bool(value) => value.get_keyboard_key(),
         }
     }
 
@@ -222,6 +485,7 @@ impl<'conf> ConfigurationValue<'conf> {
             ConfigurationValue::Float(value) => &value.name,
             ConfigurationValue::USize(value) => &value.name,
             ConfigurationValue::WaveType(value) => &value.name,
+            ConfigurationValue::Bool(value) => &value.name,
         }
     }
 
@@ -231,6 +495,7 @@ impl<'conf> ConfigurationValue<'conf> {
             ConfigurationValue::Float(value) => value.from_midi_value(midi_value),
             ConfigurationValue::USize(value) => value.from_midi_value(midi_value),
             ConfigurationValue::WaveType(value) => value.from_midi_value(midi_value),
+            ConfigurationValue::Bool(value) => value.from_midi_value(midi_value),
         }
     }
 }
@@ -247,7 +512,27 @@ where
     ///Draw the configuration on the user interface
     fn draw(&'c mut self, ui: &mut eframe::egui::Ui) {
         for mut e in self.elements() {
-            e.draw(ui);
+            e.draw(ui, |_| {});
+        }
+    }
+
+    ///Draw the configuration on the user interface, showing next to every
+    /// value the midi control mapped to it
+    fn draw_with_mappings<T, S>(
+        &'c mut self,
+        ui: &mut eframe::egui::Ui,
+        mappings: &MidiMappings,
+        sender: &mut S,
+        messages: &mut Vec<String>,
+    ) where
+        T: From<KeyBoardKeySetter>,
+        S: MessageSender<T>,
+    {
+        for mut e in self.elements() {
+            let key = e.key();
+            e.draw(ui, |ui| {
+                draw_mapping(ui, key, mappings.get(key), sender, messages)
+            });
         }
     }
 
@@ -264,16 +549,44 @@ where
 
     ///Create the menu entries to change the controller key
     /// mapped to a configuration element
-    fn create_menu_keyboard_settings<T>(
+    fn create_menu_keyboard_settings<T, S>(
         &'c mut self,
         ui: &mut eframe::egui::Ui,
-        sender: &mut std::sync::mpsc::Sender<T>,
+        sender: &mut S,
         messages: &mut Vec<String>,
     ) where
         T: From<KeyBoardKeySetter>,
+        S: MessageSender<T>,
     {
         for gen_val in self.elements() {
             create_keyboard_select(ui, gen_val.name(), gen_val.key(), sender, messages);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Unit;
+
+    #[test]
+    fn unit_round_trip() {
+        let units = [
+            Unit::Hertz,
+            Unit::Milliseconds,
+            Unit::Frames(1000),
+            Unit::Decibels,
+            Unit::Percent,
+        ];
+        for unit in units {
+            for value in [10.0, 1500.0] {
+                let parsed = unit.parse(&unit.format(value)).unwrap();
+                assert!((parsed - value).abs() / value < 0.01, "{unit:?} {value}");
+            }
+        }
+        assert_eq!(Unit::Hertz.parse("2 kHz"), Some(2000.0));
+        assert_eq!(Unit::Decibels.parse("0 dB"), Some(1.0));
+        assert_eq!(Unit::Decibels.parse("-inf dB"), Some(0.0));
+        assert_eq!(Unit::Percent.parse("50"), Some(0.5));
+        assert_eq!(Unit::None.parse("abc"), None);
+    }
+}
@@ -0,0 +1,76 @@
+use super::{Compressor, CompressorSettings};
+
+///A bus summing several sources, for instance the drums of a kit: a
+/// compressor gluing them together, then the level of the outputs
+pub struct Bus {
+    compressor: Compressor,
+    ///If false, the compressor is bypassed
+    compress: bool,
+    ///The gain of the outputs
+    level: f64,
+}
+
+impl Bus {
+    ///A bus leaving the signal untouched, until the compressor is turned on
+    pub fn new(rate: usize) -> Bus {
+        Bus {
+            compressor: Compressor::new(rate, CompressorSettings::default()),
+            compress: false,
+            level: 1.0,
+        }
+    }
+
+    ///Turn the compressor on or off, and change its parameters
+    pub fn set_compressor(&mut self, compress: bool, settings: CompressorSettings) {
+        self.compress = compress;
+        self.compressor.set_settings(settings);
+    }
+
+    ///Change the gain of the outputs, as a linear factor
+    pub fn set_level(&mut self, level: f64) {
+        self.level = level;
+    }
+
+    ///Process a frame of the mono, left and right outputs. The compressor is
+    /// linked on left and right, and the mono output is reduced by the same
+    /// amount, so that every output sounds the same.
+    pub fn process(&mut self, [mono, left, right]: [f64; 3]) -> [f64; 3] {
+        let gain = if self.compress {
+            self.compressor.gain(left.abs().max(right.abs()))
+        } else {
+            1.0
+        };
+        let gain = gain * self.level;
+        [mono * gain, left * gain, right * gain]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Bus;
+    use crate::dsp::CompressorSettings;
+
+    #[test]
+    fn compressor_then_level() {
+        let mut bus = Bus::new(48000);
+        assert_eq!(bus.process([1.0, 1.0, -0.5]), [1.0, 1.0, -0.5]);
+
+        bus.set_level(0.5);
+        assert_eq!(bus.process([1.0, 1.0, -0.5]), [0.5, 0.5, -0.25]);
+
+        let settings = CompressorSettings {
+            threshold: -20.0,
+            ratio: 4.0,
+            attack: 0.0,
+            release: 0.0,
+            makeup: 0.0,
+        };
+        bus.set_compressor(true, settings);
+        //0 dB in, 20 dB over the threshold: 15 dB of reduction on every output
+        let gain = 10.0_f64.powf(-15.0 / 20.0) * 0.5;
+        let [mono, left, right] = bus.process([0.8, 1.0, -0.5]);
+        assert!((mono - 0.8 * gain).abs() < 1e-9);
+        assert!((left - gain).abs() < 1e-9);
+        assert!((right + 0.5 * gain).abs() < 1e-9);
+    }
+}
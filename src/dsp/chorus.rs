@@ -0,0 +1,85 @@
+///The shortest delay of the chorus, in seconds
+const BASE_DELAY: f64 = 0.007;
+///The largest change of the delay at full depth, in seconds
+const MAX_SWEEP: f64 = 0.008;
+
+///A chorus: the signal is mixed with two copies of itself, delayed by a time
+/// swept by a slow oscillator. The two copies are swept in opposite
+/// directions, to sound fuller.
+pub struct Chorus {
+    ///The number of frames per second
+    rate: usize,
+    ///The last frames of the signal
+    buffer: Vec<f32>,
+    ///The position of the next frame in the buffer
+    position: usize,
+    ///The phase of the oscillator sweeping the delay
+    phase: f64,
+}
+
+impl Chorus {
+    pub fn new(rate: usize) -> Chorus {
+        let length = ((BASE_DELAY + MAX_SWEEP) * rate as f64) as usize + 2;
+        Chorus {
+            rate,
+            buffer: vec![0.0; length],
+            position: 0,
+            phase: 0.0,
+        }
+    }
+
+    ///Forget the signal received so far
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    ///The signal 'delay' frames ago, interpolated between two frames
+    fn read(&self, delay: f64) -> f64 {
+        let length = self.buffer.len();
+        let whole = delay.floor();
+        let fraction = delay - whole;
+        let newer = (self.position + length - whole as usize) % length;
+        let older = (newer + length - 1) % length;
+        self.buffer[newer] as f64 * (1.0 - fraction) + self.buffer[older] as f64 * fraction
+    }
+
+    ///Process a frame. 'speed' is the frequency of the sweep (in Hz), 'depth'
+    /// the amount of sweep and 'mix' the part of the delayed signal in the
+    /// output, both from 0 to 1.
+    pub fn process(&mut self, input: f64, speed: f64, depth: f64, mix: f64) -> f64 {
+        self.buffer[self.position] = input as f32;
+        let mut wet = 0.0;
+        for offset in [0.0, std::f64::consts::PI] {
+            let sweep = 0.5 * (1.0 + (self.phase + offset).sin());
+            let delay = (BASE_DELAY + MAX_SWEEP * depth.clamp(0.0, 1.0) * sweep) * self.rate as f64;
+            wet += self.read(delay);
+        }
+        self.position = (self.position + 1) % self.buffer.len();
+        self.phase = (self.phase + 2.0 * std::f64::consts::PI * speed / self.rate as f64)
+            % (2.0 * std::f64::consts::PI);
+        (1.0 - mix) * input + mix * wet / 2.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chorus, BASE_DELAY};
+
+    #[test]
+    fn delayed_copy() {
+        let rate = 1000;
+        let mut chorus = Chorus::new(rate);
+        assert_eq!(chorus.process(0.5, 1.0, 1.0, 0.0), 0.5);
+
+        //without sweep, an impulse comes back after the base delay
+        let mut chorus = Chorus::new(rate);
+        let mut output = Vec::new();
+        for i in 0..20 {
+            let input = if i == 0 { 1.0 } else { 0.0 };
+            output.push(chorus.process(input, 1.0, 0.0, 1.0));
+        }
+        let delay = (BASE_DELAY * rate as f64) as usize;
+        assert!((output[delay] - 1.0).abs() < 1e-6);
+        assert_eq!(output.iter().filter(|v| v.abs() > 1e-6).count(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use super::{db_to_gain, gain_to_db};
+
+///The parameters of a compressor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CompressorSettings {
+    ///The level above which the signal is compressed, in dB
+    pub threshold: f64,
+    ///The amount of compression: above the threshold, 'ratio' dB in gives 1 dB out
+    pub ratio: f64,
+    ///The time to react to a louder signal, in seconds
+    pub attack: f64,
+    ///The time to recover once the signal gets quieter, in seconds
+    pub release: f64,
+    ///The gain applied after the compression, in dB
+    pub makeup: f64,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold: -12.0,
+            ratio: 4.0,
+            attack: 0.01,
+            release: 0.1,
+            makeup: 0.0,
+        }
+    }
+}
+
+///A feed-forward compressor. For stereo signals, both channels are reduced
+/// by the same amount so that the stereo image is kept.
+pub struct Compressor {
+    settings: CompressorSettings,
+    ///The number of frames per second
+    rate: usize,
+    ///Smoothing factor used when the level goes up
+    attack_coef: f64,
+    ///Smoothing factor used when the level goes down
+    release_coef: f64,
+    ///The current gain reduction, in dB (always positive or zero)
+    reduction: f64,
+}
+
+impl Compressor {
+    pub fn new(rate: usize, settings: CompressorSettings) -> Compressor {
+        let mut compressor = Compressor {
+            settings,
+            rate,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            reduction: 0.0,
+        };
+        compressor.set_settings(settings);
+        compressor
+    }
+
+    fn coefficient(rate: usize, duration: f64) -> f64 {
+        if duration <= 0.0 {
+            0.0
+        } else {
+            (-1.0 / (duration * rate as f64)).exp()
+        }
+    }
+
+    ///Change the parameters of the compressor
+    pub fn set_settings(&mut self, settings: CompressorSettings) {
+        self.settings = settings;
+        self.attack_coef = Self::coefficient(self.rate, settings.attack);
+        self.release_coef = Self::coefficient(self.rate, settings.release);
+    }
+
+    ///Retrieve the parameters of the compressor
+    pub fn settings(&self) -> CompressorSettings {
+        self.settings
+    }
+
+    ///The current gain reduction, in dB
+    pub fn reduction(&self) -> f64 {
+        self.reduction
+    }
+
+    ///Compute the gain to apply for a frame whose peak level is 'level'
+    pub fn gain(&mut self, level: f64) -> f64 {
+        let over = gain_to_db(level) - self.settings.threshold;
+        let target = if over > 0.0 && self.settings.ratio > 1.0 {
+            over - over / self.settings.ratio
+        } else {
+            0.0
+        };
+        let coef = if target > self.reduction {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.reduction = target + coef * (self.reduction - target);
+        db_to_gain(self.settings.makeup - self.reduction)
+    }
+
+    ///Compress a single mono frame
+    pub fn process(&mut self, value: f64) -> f64 {
+        value * self.gain(value.abs())
+    }
+
+    ///Compress a single mono frame, by the amount needed for 'detector' (the
+    /// sidechain), for instance to duck a pad under a kick
+    pub fn process_sidechain(&mut self, value: f64, detector: f64) -> f64 {
+        value * self.gain(detector.abs())
+    }
+
+    ///Compress a single stereo frame
+    pub fn process_stereo(&mut self, left: f64, right: f64) -> (f64, f64) {
+        let gain = self.gain(left.abs().max(right.abs()));
+        (left * gain, right * gain)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compressor, CompressorSettings};
+
+    #[test]
+    fn quiet_signal_is_untouched() {
+        let mut compressor = Compressor::new(48000, CompressorSettings::default());
+        for _ in 0..1000 {
+            assert!((compressor.process(0.1) - 0.1).abs() < 1e-9);
+        }
+        assert_eq!(compressor.reduction(), 0.0);
+    }
+
+    #[test]
+    fn loud_signal_is_reduced() {
+        let settings = CompressorSettings {
+            threshold: -20.0,
+            ratio: 4.0,
+            attack: 0.0,
+            release: 0.0,
+            makeup: 0.0,
+        };
+        let mut compressor = Compressor::new(48000, settings);
+        //0 dB in, 20 dB over the threshold: 15 dB of reduction
+        let (left, right) = compressor.process_stereo(1.0, -0.5);
+        assert!((compressor.reduction() - 15.0).abs() < 1e-9);
+        assert!((left - 10.0_f64.powf(-15.0 / 20.0)).abs() < 1e-9);
+        assert!((right * 2.0 + left).abs() < 1e-9);
+        //a quiet signal is reduced as much as the loud sidechain
+        let value = compressor.process_sidechain(0.01, 1.0);
+        assert!((value - 0.01 * 10.0_f64.powf(-15.0 / 20.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,75 @@
+use super::{svf::BUTTERWORTH, StateVariableFilter, SvfCoefficients};
+
+///A crossover splitting a signal into three bands: low, mid and high. Every
+/// band is the part of the signal that the lower ones did not keep, so that
+/// the sum of the bands is always the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Crossover {
+    ///The filter keeping the low band
+    low: StateVariableFilter,
+    ///The filter keeping the mid band, from what is above the low band
+    mid: StateVariableFilter,
+}
+
+impl Crossover {
+    pub fn new() -> Crossover {
+        Crossover {
+            low: StateVariableFilter::new(),
+            mid: StateVariableFilter::new(),
+        }
+    }
+
+    ///The coefficients of the filter for a split at 'frequency' (in Hz)
+    pub fn coefficients(rate: usize, frequency: f64) -> SvfCoefficients {
+        SvfCoefficients::new(rate, frequency, BUTTERWORTH)
+    }
+
+    ///Forget the previous samples
+    pub fn reset(&mut self) {
+        self.low.reset();
+        self.mid.reset();
+    }
+
+    ///Split a single sample into the low, mid and high bands
+    pub fn split(&mut self, input: f64, low: &SvfCoefficients, high: &SvfCoefficients) -> [f64; 3] {
+        let low_band = self.low.low_pass(input, low);
+        let rest = input - low_band;
+        let mid_band = self.mid.low_pass(rest, high);
+        [low_band, mid_band, rest - mid_band]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Crossover;
+
+    #[test]
+    fn bands_add_up() {
+        let rate = 48000;
+        let low = Crossover::coefficients(rate, 200.0);
+        let high = Crossover::coefficients(rate, 2000.0);
+        let mut crossover = Crossover::new();
+        for i in 0..rate {
+            let input = (i as f64 * 0.01).sin() + if i % 2 == 0 { 0.5 } else { -0.5 };
+            let bands = crossover.split(input, &low, &high);
+            assert!((bands.iter().sum::<f64>() - input).abs() < 1e-9);
+        }
+
+        //a signal alternating at the Nyquist frequency ends up in the high band
+        crossover.reset();
+        let mut bands = [0.0; 3];
+        for i in 0..rate {
+            bands = crossover.split(if i % 2 == 0 { 1.0 } else { -1.0 }, &low, &high);
+        }
+        assert!(bands[0].abs() < 0.01);
+        assert!(bands[1].abs() < 0.01);
+
+        //a constant signal ends up in the low band
+        crossover.reset();
+        for _ in 0..rate {
+            bands = crossover.split(1.0, &low, &high);
+        }
+        assert!((bands[0] - 1.0).abs() < 1e-6);
+        assert!(bands[2].abs() < 1e-6);
+    }
+}
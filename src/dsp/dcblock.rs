@@ -0,0 +1,75 @@
+///The cutoff frequency of the DC blocker, in Hz: low enough to keep the
+/// lowest notes of a kick
+const DC_CUTOFF: f64 = 5.0;
+///The magnitude below which a value is considered silent
+const DENORMAL_THRESHOLD: f64 = 1e-20;
+
+///Set the values too small to be heard to zero. The decaying states of the
+/// filters and of the fades otherwise end up as denormal numbers, which are
+/// very slow to compute with.
+pub fn flush_denormal(value: f64) -> f64 {
+    if value.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        value
+    }
+}
+
+///A one-pole high-pass filter removing the constant offset (DC) of a signal
+pub struct DcBlocker {
+    ///The feedback factor, depending on the rate
+    coef: f64,
+    ///The previous input
+    input: f64,
+    ///The previous output
+    output: f64,
+}
+
+impl DcBlocker {
+    pub fn new(rate: usize) -> DcBlocker {
+        DcBlocker {
+            coef: (-2.0 * std::f64::consts::PI * DC_CUTOFF / rate as f64).exp(),
+            input: 0.0,
+            output: 0.0,
+        }
+    }
+
+    ///Forget the previous values
+    pub fn reset(&mut self) {
+        self.input = 0.0;
+        self.output = 0.0;
+    }
+
+    ///Filter a single frame
+    pub fn process(&mut self, value: f64) -> f64 {
+        self.output = flush_denormal(value - self.input + self.coef * self.output);
+        self.input = value;
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{flush_denormal, DcBlocker};
+
+    #[test]
+    fn removes_the_offset() {
+        let rate = 48000;
+        let mut blocker = DcBlocker::new(rate);
+        //a constant offset fades away, a tone goes through (slightly shifted)
+        let mut last = 0.0;
+        for i in 0..rate {
+            let tone = (2.0 * std::f64::consts::PI * 440.0 * i as f64 / rate as f64).sin();
+            last = blocker.process(0.5 + tone) - tone;
+        }
+        assert!(last.abs() < 0.02, "{last}");
+        blocker.reset();
+        //the output of a silence decays to exactly zero
+        for _ in 0..rate {
+            last = blocker.process(0.0);
+        }
+        assert_eq!(flush_denormal(1e-30), 0.0);
+        assert_eq!(flush_denormal(-0.5), -0.5);
+        assert_eq!(last, 0.0);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use super::{db_to_gain, gain_to_db};
+
+///The parameters of a gate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GateSettings {
+    ///The level below which the gate closes, in dB
+    pub threshold: f64,
+    ///The time to open once the signal gets louder, in seconds
+    pub attack: f64,
+    ///The time to close once the signal gets quieter, in seconds
+    pub release: f64,
+    ///The reduction applied when the gate is closed, in dB
+    pub range: f64,
+}
+
+impl Default for GateSettings {
+    fn default() -> Self {
+        Self {
+            threshold: -40.0,
+            attack: 0.001,
+            release: 0.1,
+            range: 60.0,
+        }
+    }
+}
+
+///A noise gate: the signal is reduced while its level stays below the
+/// threshold. The level can come from another signal (sidechain).
+pub struct Gate {
+    settings: GateSettings,
+    ///The number of frames per second
+    rate: usize,
+    ///Smoothing factor used when the gate opens
+    attack_coef: f64,
+    ///Smoothing factor used when the gate closes
+    release_coef: f64,
+    ///The current gain, from the range (closed) to 1 (open)
+    gain: f64,
+}
+
+impl Gate {
+    pub fn new(rate: usize, settings: GateSettings) -> Gate {
+        let mut gate = Gate {
+            settings,
+            rate,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            gain: 1.0,
+        };
+        gate.set_settings(settings);
+        gate
+    }
+
+    fn coefficient(rate: usize, duration: f64) -> f64 {
+        if duration <= 0.0 {
+            0.0
+        } else {
+            (-1.0 / (duration * rate as f64)).exp()
+        }
+    }
+
+    ///Change the parameters of the gate
+    pub fn set_settings(&mut self, settings: GateSettings) {
+        self.settings = settings;
+        self.attack_coef = Self::coefficient(self.rate, settings.attack);
+        self.release_coef = Self::coefficient(self.rate, settings.release);
+    }
+
+    ///The current gain reduction, in dB
+    pub fn reduction(&self) -> f64 {
+        -gain_to_db(self.gain)
+    }
+
+    ///Compute the gain to apply for a frame whose peak level is 'level'
+    pub fn gain(&mut self, level: f64) -> f64 {
+        let target = if gain_to_db(level) >= self.settings.threshold {
+            1.0
+        } else {
+            db_to_gain(-self.settings.range.abs())
+        };
+        let coef = if target > self.gain {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.gain = target + coef * (self.gain - target);
+        self.gain
+    }
+
+    ///Gate a single mono frame
+    pub fn process(&mut self, value: f64) -> f64 {
+        value * self.gain(value.abs())
+    }
+
+    ///Gate a single mono frame, opening when 'detector' (the sidechain) is
+    /// loud enough
+    pub fn process_sidechain(&mut self, value: f64, detector: f64) -> f64 {
+        value * self.gain(detector.abs())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Gate, GateSettings};
+
+    #[test]
+    fn opens_with_the_detector() {
+        let settings = GateSettings {
+            threshold: -20.0,
+            attack: 0.0,
+            release: 0.0,
+            range: 40.0,
+        };
+        let mut gate = Gate::new(48000, settings);
+        assert_eq!(gate.process(0.5), 0.5);
+        assert!((gate.process(0.01) - 0.0001).abs() < 1e-9);
+        assert!((gate.reduction() - 40.0).abs() < 1e-9);
+        //a quiet signal goes through when the sidechain is loud
+        assert_eq!(gate.process_sidechain(0.01, 1.0), 0.01);
+        assert!((gate.process_sidechain(0.5, 0.0) - 0.005).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,50 @@
+mod bus;
+mod chorus;
+mod compressor;
+mod crossover;
+mod dcblock;
+mod gate;
+mod oversampling;
+mod svf;
+
+pub use bus::Bus;
+pub use chorus::Chorus;
+pub use compressor::{Compressor, CompressorSettings};
+pub use crossover::Crossover;
+pub use dcblock::{flush_denormal, DcBlocker};
+pub use gate::{Gate, GateSettings};
+pub use oversampling::Oversampler;
+pub use svf::{StateVariableFilter, SvfCoefficients};
+
+///Convert a gain in decibels to a linear factor
+pub fn db_to_gain(db: f64) -> f64 {
+    10.0_f64.powf(db / 20.0)
+}
+
+///Convert a linear factor to a gain in decibels
+pub fn gain_to_db(gain: f64) -> f64 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+///Bend a signal smoothly so that it never goes beyond 'ceiling' (a linear
+/// factor), instead of clipping it harshly. Quiet signals are barely changed.
+pub fn soft_clip(value: f64, ceiling: f64) -> f64 {
+    if ceiling <= 0.0 {
+        return 0.0;
+    }
+    ceiling * (value / ceiling).tanh()
+}
+
+#[cfg(test)]
+mod test {
+    use super::soft_clip;
+
+    #[test]
+    fn soft_clipping() {
+        assert!((soft_clip(0.01, 1.0) - 0.01).abs() < 1e-6);
+        assert!(soft_clip(10.0, 0.5) <= 0.5);
+        assert!(soft_clip(-10.0, 0.5) >= -0.5);
+        assert!(soft_clip(2.0, 1.0) > soft_clip(1.0, 1.0));
+        assert_eq!(soft_clip(1.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,134 @@
+use super::{svf::BUTTERWORTH, StateVariableFilter, SvfCoefficients};
+
+///The cutoff of the anti-aliasing filters, relative to the original sample
+/// rate: just below the highest frequency it can hold
+const CUTOFF: f64 = 0.45;
+
+///A low-pass filter at the oversampled rate, removing what the original
+/// sample rate can not hold
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AntiAliasing {
+    ///Two filters in a row, for a steeper slope
+    filters: [StateVariableFilter; 2],
+    coefs: SvfCoefficients,
+}
+
+impl AntiAliasing {
+    fn new(rate: usize, factor: usize) -> AntiAliasing {
+        AntiAliasing {
+            filters: [StateVariableFilter::new(); 2],
+            coefs: SvfCoefficients::new(rate * factor, rate as f64 * CUTOFF, BUTTERWORTH),
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let [first, second] = &mut self.filters;
+        second.low_pass(first.low_pass(input, &self.coefs), &self.coefs)
+    }
+}
+
+///Run a stage at a multiple of the sample rate, so that the high frequencies
+/// it creates are filtered out instead of folding back as aliasing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oversampler {
+    ///How many samples are computed for every sample at the original rate
+    factor: usize,
+    ///The filter of the upsampled signal, before the stage
+    up: AntiAliasing,
+    ///The filter of the output of the stage, before decimation
+    down: AntiAliasing,
+}
+
+impl Oversampler {
+    ///An oversampler running at 'factor' times 'rate' (1 meaning no
+    /// oversampling)
+    pub fn new(rate: usize, factor: usize) -> Oversampler {
+        let factor = factor.max(1);
+        Oversampler {
+            factor,
+            up: AntiAliasing::new(rate, factor),
+            down: AntiAliasing::new(rate, factor),
+        }
+    }
+
+    ///How many samples are computed for every sample at the original rate
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    ///Process a sample through 'stage', run on the upsampled signal
+    pub fn process(&mut self, input: f64, mut stage: impl FnMut(f64) -> f64) -> f64 {
+        if self.factor == 1 {
+            return stage(input);
+        }
+        let mut output = 0.0;
+        for index in 0..self.factor {
+            //zero stuffing: the sample is followed by silence, the filter
+            //fills the gaps
+            let stuffed = if index == 0 {
+                input * self.factor as f64
+            } else {
+                0.0
+            };
+            output = self.down.process(stage(self.up.process(stuffed)));
+        }
+        output
+    }
+
+    ///Bring the samples generated at the oversampled rate for a single sample
+    /// back to the original rate
+    pub fn decimate(&mut self, samples: &[f32]) -> f64 {
+        if self.factor == 1 {
+            return samples.first().map_or(0.0, |s| *s as f64);
+        }
+        let mut output = 0.0;
+        for sample in samples {
+            output = self.down.process(*sample as f64);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Oversampler;
+
+    #[test]
+    fn low_frequencies_pass() {
+        let rate = 48000;
+        let mut oversampler = Oversampler::new(rate, 4);
+        let mut value = 0.0;
+        for _ in 0..rate {
+            value = oversampler.process(0.5, |x| x);
+        }
+        assert!((value - 0.5).abs() < 1e-3);
+        let mut oversampler = Oversampler::new(rate, 1);
+        assert_eq!(oversampler.process(0.5, |x| 2.0 * x), 1.0);
+        assert_eq!(oversampler.decimate(&[0.5]), 0.5);
+    }
+
+    #[test]
+    fn decimation_removes_high_frequencies() {
+        let rate = 48000;
+        let factor = 4;
+        //a tone above what the original rate can hold
+        let frequency = 40000.0;
+        let mut oversampler = Oversampler::new(rate, factor);
+        let mut peak: f64 = 0.0;
+        let mut time = 0;
+        for frame in 0..rate {
+            let samples: Vec<f32> = (0..factor)
+                .map(|_| {
+                    time += 1;
+                    let t = time as f64 / (rate * factor) as f64;
+                    (2.0 * std::f64::consts::PI * frequency * t).sin() as f32
+                })
+                .collect();
+            let value = oversampler.decimate(&samples);
+            if frame > rate / 2 {
+                peak = peak.max(value.abs());
+            }
+        }
+        assert!(peak < 0.15, "{peak}");
+    }
+}
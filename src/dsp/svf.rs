@@ -0,0 +1,199 @@
+///The resonance giving a Butterworth response (no bump at the cutoff)
+pub(super) const BUTTERWORTH: f64 = (2.0 - std::f64::consts::SQRT_2) / 1.98;
+
+///The coefficients of a state variable filter, computed from its cutoff and resonance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvfCoefficients {
+    k: f64,
+    a1: f64,
+    a2: f64,
+    a3: f64,
+}
+
+impl SvfCoefficients {
+    ///Compute the coefficients for a cutoff (in Hz) and a resonance (from 0 to 1)
+    pub fn new(rate: usize, cutoff: f64, resonance: f64) -> SvfCoefficients {
+        Self::with_damping(rate, cutoff, Self::damping(resonance))
+    }
+
+    ///Compute the coefficients for a cutoff (in Hz) and a quality factor: the
+    /// cutoff divided by the width of the band kept by the band-pass filter
+    pub fn with_q(rate: usize, cutoff: f64, q: f64) -> SvfCoefficients {
+        Self::with_damping(rate, cutoff, 1.0 / q.max(0.01))
+    }
+
+    fn with_damping(rate: usize, cutoff: f64, k: f64) -> SvfCoefficients {
+        let cutoff = cutoff.clamp(1.0, rate as f64 * 0.49);
+        let g = (std::f64::consts::PI * cutoff / rate as f64).tan();
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        SvfCoefficients { k, a1, a2, a3 }
+    }
+
+    ///Translate the resonance (from 0 to 1) into the damping of the filter
+    fn damping(resonance: f64) -> f64 {
+        2.0 - 1.98 * resonance.clamp(0.0, 1.0)
+    }
+
+    ///The gain of a low-pass filter at 'frequency', for a given cutoff and resonance
+    pub fn low_pass_magnitude(frequency: f64, cutoff: f64, resonance: f64) -> f64 {
+        let w = frequency / cutoff.max(1.0);
+        let k = Self::damping(resonance);
+        1.0 / ((1.0 - w * w).powi(2) + (k * w).powi(2)).sqrt()
+    }
+}
+
+///A state variable filter (trapezoidal integration), which stays stable when
+/// its cutoff changes at every frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StateVariableFilter {
+    ic1eq: f64,
+    ic2eq: f64,
+}
+
+impl StateVariableFilter {
+    pub fn new() -> StateVariableFilter {
+        StateVariableFilter {
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+        }
+    }
+
+    ///Forget the previous samples
+    pub fn reset(&mut self) {
+        self.ic1eq = 0.0;
+        self.ic2eq = 0.0;
+    }
+
+    ///Filter a single sample, returning the band-pass and low-pass outputs
+    fn tick(&mut self, input: f64, coefs: &SvfCoefficients) -> (f64, f64) {
+        let v3 = input - self.ic2eq;
+        let v1 = coefs.a1 * self.ic1eq + coefs.a2 * v3;
+        let v2 = self.ic2eq + coefs.a2 * self.ic1eq + coefs.a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+        (v1, v2)
+    }
+
+    ///Filter a single sample, keeping the low frequencies
+    pub fn low_pass(&mut self, input: f64, coefs: &SvfCoefficients) -> f64 {
+        self.tick(input, coefs).1
+    }
+
+    ///Filter a single sample, keeping the frequencies around the cutoff
+    pub fn band_pass(&mut self, input: f64, coefs: &SvfCoefficients) -> f64 {
+        self.tick(input, coefs).0
+    }
+
+    ///Filter a single sample, keeping the frequencies around the cutoff with a
+    /// gain of one at the cutoff, whatever the resonance
+    pub fn peak_band_pass(&mut self, input: f64, coefs: &SvfCoefficients) -> f64 {
+        coefs.k * self.tick(input, coefs).0
+    }
+
+    ///Filter a single sample, keeping the high frequencies
+    pub fn high_pass(&mut self, input: f64, coefs: &SvfCoefficients) -> f64 {
+        let (band, low) = self.tick(input, coefs);
+        input - coefs.k * band - low
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StateVariableFilter, SvfCoefficients};
+
+    #[test]
+    fn low_pass_keeps_low_frequencies() {
+        let rate = 48000;
+        let coefs = SvfCoefficients::new(rate, 1000.0, 0.0);
+        let mut filter = StateVariableFilter::new();
+        let mut value = 0.0;
+        for _ in 0..rate {
+            value = filter.low_pass(1.0, &coefs);
+        }
+        assert!((value - 1.0).abs() < 1e-6);
+
+        //a signal alternating at the Nyquist frequency is removed
+        filter.reset();
+        let mut peak: f64 = 0.0;
+        for i in 0..rate {
+            let input = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let output = filter.low_pass(input, &coefs);
+            if i > rate / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!(peak < 0.01);
+        assert!(SvfCoefficients::low_pass_magnitude(1000.0, 1000.0, 1.0) > 10.0);
+    }
+
+    #[test]
+    fn band_pass_removes_both_ends() {
+        let rate = 48000;
+        let coefs = SvfCoefficients::new(rate, 1000.0, 0.0);
+        let mut filter = StateVariableFilter::new();
+        let mut value = 1.0;
+        for _ in 0..rate {
+            value = filter.band_pass(1.0, &coefs);
+        }
+        assert!(value.abs() < 1e-6);
+
+        filter.reset();
+        let mut peak: f64 = 0.0;
+        for i in 0..rate {
+            let input = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let output = filter.band_pass(input, &coefs);
+            if i > rate / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!(peak < 0.1);
+    }
+
+    #[test]
+    fn band_pass_quality() {
+        let rate = 48000;
+        let peak = |q: f64, frequency: f64| {
+            let coefs = SvfCoefficients::with_q(rate, 1000.0, q);
+            let mut filter = StateVariableFilter::new();
+            let mut peak: f64 = 0.0;
+            for i in 0..rate {
+                let phase = std::f64::consts::TAU * frequency * i as f64 / rate as f64;
+                let output = filter.peak_band_pass(phase.sin(), &coefs);
+                if i > rate / 2 {
+                    peak = peak.max(output.abs());
+                }
+            }
+            peak
+        };
+        //a gain of one at the center, whatever the quality factor
+        assert!((peak(0.7, 1000.0) - 1.0).abs() < 0.01);
+        assert!((peak(8.0, 1000.0) - 1.0).abs() < 0.01);
+        //a higher quality factor keeps a narrower band
+        assert!(peak(8.0, 2000.0) < peak(0.7, 2000.0) / 4.0);
+    }
+
+    #[test]
+    fn high_pass_keeps_high_frequencies() {
+        let rate = 48000;
+        let coefs = SvfCoefficients::new(rate, 1000.0, 0.0);
+        let mut filter = StateVariableFilter::new();
+        let mut value = 1.0;
+        for _ in 0..rate {
+            value = filter.high_pass(1.0, &coefs);
+        }
+        assert!(value.abs() < 1e-6);
+
+        filter.reset();
+        let mut peak: f64 = 0.0;
+        for i in 0..rate {
+            let input = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let output = filter.high_pass(input, &coefs);
+            if i > rate / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!((peak - 1.0).abs() < 0.01);
+    }
+}
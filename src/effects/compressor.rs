@@ -0,0 +1,402 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dsp::{Compressor, CompressorSettings, Gate, GateSettings},
+    i18n::tr,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    utils::{
+        meters::{self, Level},
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus,
+    },
+};
+
+const TOOL_NAME: &str = "compressor";
+///The number of levels sent to the user interface every second
+const LEVELS_PER_SECOND: usize = 20;
+
+///The settings of the gate and of the compressor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct CompressorConfiguration {
+    ///If true, the gate is applied before the compressor
+    gate_enabled: bool,
+    gate: GateSettings,
+    ///If true, the gate opens with the sidechain input instead of the signal
+    gate_sidechain: bool,
+    compression: CompressorSettings,
+    ///If true, the compression follows the sidechain input instead of the
+    /// signal, for instance to duck a pad under a kick
+    compression_sidechain: bool,
+}
+
+///What happened since the last report
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Activity {
+    ///The level of the input
+    input: Level,
+    ///The level of the sidechain input
+    sidechain: Level,
+    ///The gain reduction of the gate, in dB
+    gate_reduction: f64,
+    ///The gain reduction of the compressor, in dB
+    reduction: f64,
+}
+
+struct CompressorEffect {
+    ///The number of frames per second
+    rate: usize,
+    ///The current settings
+    conf: CompressorConfiguration,
+    gate: Gate,
+    compressor: Compressor,
+    ///The levels measured since the last report
+    activity: Activity,
+    ///The number of frames measured since the last report
+    level_frames: usize,
+    /// The input audio port
+    audio_mono_in: jack::Port<jack::AudioIn>,
+    /// The signal driving the gate and the compressor, when asked
+    sidechain_in: jack::Port<jack::AudioIn>,
+    /// The output audio port
+    audio_mono_out: jack::Port<jack::AudioOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToCompressor, spsc::Receiver<MessageToCompressor>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToCompressorUI>,
+}
+
+impl CompressorEffect {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToCompressor>,
+        messages_out: spsc::Sender<MessageToCompressorUI>,
+    ) -> Result<CompressorEffect, CommonError> {
+        let a_in = match client.register_port("music_in", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let sidechain_in = match client.register_port("sidechain_in", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let a_out = match client.register_port("music_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let rate = client.sample_rate();
+        let conf = CompressorConfiguration::default();
+        Ok(CompressorEffect {
+            rate,
+            conf,
+            gate: Gate::new(rate, conf.gate),
+            compressor: Compressor::new(rate, conf.compression),
+            activity: Activity::default(),
+            level_frames: 0,
+            audio_mono_in: a_in,
+            sidechain_in,
+            audio_mono_out: a_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+
+    fn set_configuration(&mut self, conf: CompressorConfiguration) {
+        self.conf = conf;
+        self.gate.set_settings(conf.gate);
+        self.compressor.set_settings(conf.compression);
+    }
+}
+
+impl jack::ProcessHandler for CompressorEffect {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToCompressor::NewConfiguration(conf) => self.set_configuration(conf),
+            }
+        }
+
+        let audio_in = self.audio_mono_in.as_slice(ps);
+        let sidechain = self.sidechain_in.as_slice(ps);
+        let audio_out = self.audio_mono_out.as_mut_slice(ps);
+        for ((input, detector), output) in audio_in.iter().zip(sidechain).zip(audio_out) {
+            let mut value = *input as f64;
+            if self.conf.gate_enabled {
+                value = if self.conf.gate_sidechain {
+                    self.gate.process_sidechain(value, *detector as f64)
+                } else {
+                    self.gate.process(value)
+                };
+            }
+            value = if self.conf.compression_sidechain {
+                self.compressor.process_sidechain(value, *detector as f64)
+            } else {
+                self.compressor.process(value)
+            };
+            *output = value as f32;
+        }
+
+        let (input, side) = (Level::measure(audio_in), Level::measure(sidechain));
+        if self.level_frames == 0 {
+            self.activity.input = input;
+            self.activity.sidechain = side;
+        } else {
+            self.activity.input = self.activity.input.merge(&input);
+            self.activity.sidechain = self.activity.sidechain.merge(&side);
+        }
+        self.level_frames += audio_in.len();
+        if self.level_frames >= self.rate / LEVELS_PER_SECOND {
+            self.activity.gate_reduction = if self.conf.gate_enabled {
+                self.gate.reduction()
+            } else {
+                0.0
+            };
+            self.activity.reduction = self.compressor.reduction();
+            if let Err(e) = self
+                .messages_out
+                .send(MessageToCompressorUI::Activity(self.activity))
+            {
+                println!("Error: {e}");
+            }
+            self.level_frames = 0;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToCompressor {
+    NewConfiguration(CompressorConfiguration),
+}
+
+#[derive(Debug)]
+enum MessageToCompressorUI {
+    Activity(Activity),
+}
+
+struct CompressorUI {
+    messages_in: spsc::Receiver<MessageToCompressorUI>,
+    message_out: spsc::Sender<MessageToCompressor>,
+    messages: Vec<String>,
+    conf: CompressorConfiguration,
+    ///The last activity of the effect
+    activity: Activity,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<CompressorConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl CompressorUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToCompressorUI>,
+        messages_out: spsc::Sender<MessageToCompressor>,
+    ) -> CompressorUI {
+        CompressorUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            conf: CompressorConfiguration::default(),
+            activity: Activity::default(),
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_gate(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.conf.gate_enabled, tr("Gate"));
+            ui.checkbox(&mut self.conf.gate_sidechain, tr("Sidechain"));
+        });
+        if !self.conf.gate_enabled {
+            return;
+        }
+        crate::utils::create_f64_slider(
+            ui,
+            "threshold (dB): ",
+            &mut self.conf.gate.threshold,
+            std::ops::RangeInclusive::new(-80.0, 0.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "attack (s): ",
+            &mut self.conf.gate.attack,
+            std::ops::RangeInclusive::new(0.0, 0.05),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "release (s): ",
+            &mut self.conf.gate.release,
+            std::ops::RangeInclusive::new(0.01, 1.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "range (dB): ",
+            &mut self.conf.gate.range,
+            std::ops::RangeInclusive::new(0.0, 80.0),
+        );
+        meters::gain_reduction_meter(ui, self.activity.gate_reduction);
+    }
+
+    fn create_compression(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr("Compressor"));
+            ui.checkbox(&mut self.conf.compression_sidechain, tr("Sidechain"));
+        });
+        crate::utils::create_f64_slider(
+            ui,
+            "threshold (dB): ",
+            &mut self.conf.compression.threshold,
+            std::ops::RangeInclusive::new(-60.0, 0.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "ratio: ",
+            &mut self.conf.compression.ratio,
+            std::ops::RangeInclusive::new(1.0, 20.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "attack (s): ",
+            &mut self.conf.compression.attack,
+            std::ops::RangeInclusive::new(0.0, 0.2),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "release (s): ",
+            &mut self.conf.compression.release,
+            std::ops::RangeInclusive::new(0.01, 1.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "makeup (dB): ",
+            &mut self.conf.compression.makeup,
+            std::ops::RangeInclusive::new(0.0, 24.0),
+        );
+        meters::gain_reduction_meter(ui, self.activity.reduction);
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let initial = self.conf;
+        ui.label(tr("Input level: "));
+        meters::vu_meter(ui, &self.activity.input);
+        ui.label(tr("Sidechain level: "));
+        meters::vu_meter(ui, &self.activity.sidechain);
+        ui.separator();
+        self.create_gate(ui);
+        ui.separator();
+        self.create_compression(ui);
+        if initial != self.conf {
+            self.send_message(MessageToCompressor::NewConfiguration(self.conf));
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send_message(&mut self, msg: MessageToCompressor) {
+        if let Err(e) = self.message_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToCompressorUI::Activity(activity) => self.activity = activity,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_message(MessageToCompressor::NewConfiguration(self.conf));
+        }
+    }
+}
+
+impl eframe::App for CompressorUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn compressor() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the compressor and the UI
+    let (send_to_compressor, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_compressor) = spsc::channel(MESSAGES_CAPACITY);
+
+    let compressor = CompressorEffect::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client =
+        match client.activate_async(status.notifications(), status.monitor(compressor)) {
+            Ok(client) => client,
+            Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+        };
+
+    match eframe::run_native(
+        "Compressor",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 760.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(CompressorUI::new(
+                cc,
+                status,
+                rcv_from_compressor,
+                send_to_compressor,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
@@ -0,0 +1,413 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dsp::{Compressor, CompressorSettings, Crossover, SvfCoefficients},
+    i18n::tr,
+    scene::SceneListener,
+    session::Autosave,
+    utils::{
+        meters::{self, Level},
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus,
+    },
+};
+
+const TOOL_NAME: &str = "deesser";
+///The number of levels sent to the user interface every second
+const LEVELS_PER_SECOND: usize = 20;
+///The lowest edge of the band, in Hz
+const MIN_FREQUENCY: f64 = 500.0;
+///The highest edge of the band, in Hz
+const MAX_FREQUENCY: f64 = 16000.0;
+
+///The settings of the de-esser
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct DeEsserConfiguration {
+    ///The lowest frequency of the band that is compressed, in Hz
+    low_frequency: f64,
+    ///The highest frequency of the band that is compressed, in Hz
+    high_frequency: f64,
+    ///The compression of the band
+    compression: CompressorSettings,
+    ///If true, only the band is sent to the output, to find the frequencies
+    /// to reduce
+    listen: bool,
+}
+
+impl Default for DeEsserConfiguration {
+    fn default() -> Self {
+        Self {
+            low_frequency: 5000.0,
+            high_frequency: 10000.0,
+            compression: CompressorSettings {
+                threshold: -30.0,
+                ratio: 6.0,
+                attack: 0.001,
+                release: 0.05,
+                makeup: 0.0,
+            },
+            listen: false,
+        }
+    }
+}
+
+impl DeEsserConfiguration {
+    ///The configuration with the band within the range, the high edge always
+    /// above the low one
+    fn sanitized(mut self) -> DeEsserConfiguration {
+        self.low_frequency = self.low_frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+        self.high_frequency = self.high_frequency.clamp(self.low_frequency, MAX_FREQUENCY);
+        self
+    }
+
+    ///Mix the three bands of a frame: only the middle one is compressed, by
+    /// the given gain. When listening, only the compressed band is kept.
+    fn mix(&self, bands: [f64; 3], gain: f64) -> f64 {
+        if self.listen {
+            bands[1] * gain
+        } else {
+            bands[0] + bands[1] * gain + bands[2]
+        }
+    }
+}
+
+struct DeEsser {
+    ///The number of frames per second
+    rate: usize,
+    ///The current settings
+    conf: DeEsserConfiguration,
+    ///The filters isolating the band
+    crossover: Crossover,
+    ///The coefficients of the low and the high edge of the band
+    edges: [SvfCoefficients; 2],
+    ///The compressor of the band
+    compressor: Compressor,
+    ///The peak of the band since the last report
+    peak: f32,
+    ///The sum of the squares of the band since the last report
+    squares: f32,
+    ///The number of frames measured since the last report
+    level_frames: usize,
+    /// The input audio port
+    audio_mono_in: jack::Port<jack::AudioIn>,
+    /// The output audio port
+    audio_mono_out: jack::Port<jack::AudioOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToDeEsser>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToDeEsserUI>,
+}
+
+impl DeEsser {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToDeEsser>,
+        messages_out: std::sync::mpsc::Sender<MessageToDeEsserUI>,
+    ) -> Result<DeEsser, CommonError> {
+        let a_in = match client.register_port("music_in", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let a_out = match client.register_port("music_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let rate = client.sample_rate();
+        let conf = DeEsserConfiguration::default();
+        Ok(DeEsser {
+            rate,
+            conf,
+            crossover: Crossover::new(),
+            edges: [
+                Crossover::coefficients(rate, conf.low_frequency),
+                Crossover::coefficients(rate, conf.high_frequency),
+            ],
+            compressor: Compressor::new(rate, conf.compression),
+            peak: 0.0,
+            squares: 0.0,
+            level_frames: 0,
+            audio_mono_in: a_in,
+            audio_mono_out: a_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+
+    fn set_configuration(&mut self, conf: DeEsserConfiguration) {
+        self.conf = conf.sanitized();
+        self.edges = [
+            Crossover::coefficients(self.rate, self.conf.low_frequency),
+            Crossover::coefficients(self.rate, self.conf.high_frequency),
+        ];
+        self.compressor.set_settings(self.conf.compression);
+    }
+
+    ///Send the activity of the band to the UI, and start a new measure
+    fn report(&mut self) {
+        let level = Level {
+            peak: self.peak,
+            rms: (self.squares / self.level_frames.max(1) as f32).sqrt(),
+        };
+        if let Err(e) = self.messages_out.send(MessageToDeEsserUI::Activity(
+            level,
+            self.compressor.reduction(),
+        )) {
+            println!("Error: {e}");
+        }
+        self.peak = 0.0;
+        self.squares = 0.0;
+        self.level_frames = 0;
+    }
+}
+
+impl jack::ProcessHandler for DeEsser {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToDeEsser::NewConfiguration(conf) => self.set_configuration(conf),
+            }
+        }
+
+        let audio_in = self.audio_mono_in.as_slice(ps);
+        let audio_out = self.audio_mono_out.as_mut_slice(ps);
+        for (input, output) in audio_in.iter().zip(audio_out.iter_mut()) {
+            let bands = self
+                .crossover
+                .split(*input as f64, &self.edges[0], &self.edges[1]);
+            let band = bands[1] as f32;
+            self.peak = self.peak.max(band.abs());
+            self.squares += band * band;
+            let gain = self.compressor.gain(bands[1].abs());
+            *output = self.conf.mix(bands, gain) as f32;
+        }
+        self.level_frames += audio_in.len();
+        if self.level_frames >= self.rate / LEVELS_PER_SECOND {
+            self.report();
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToDeEsser {
+    NewConfiguration(DeEsserConfiguration),
+}
+
+#[derive(Debug)]
+enum MessageToDeEsserUI {
+    ///The level of the band and its gain reduction (in dB)
+    Activity(Level, f64),
+}
+
+struct DeEsserUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToDeEsserUI>,
+    message_out: std::sync::mpsc::Sender<MessageToDeEsser>,
+    messages: Vec<String>,
+    conf: DeEsserConfiguration,
+    ///The last level of the band
+    level: Level,
+    ///The last gain reduction of the band, in dB
+    reduction: f64,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<DeEsserConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl DeEsserUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToDeEsserUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToDeEsser>,
+    ) -> DeEsserUI {
+        DeEsserUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            conf: DeEsserConfiguration::default(),
+            level: Level::default(),
+            reduction: 0.0,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let initial = self.conf;
+        ui.label(tr("Band (Hz): "));
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Slider::new(
+                    &mut self.conf.low_frequency,
+                    std::ops::RangeInclusive::new(MIN_FREQUENCY, MAX_FREQUENCY),
+                )
+                .logarithmic(true),
+            );
+            ui.add(
+                egui::Slider::new(
+                    &mut self.conf.high_frequency,
+                    std::ops::RangeInclusive::new(MIN_FREQUENCY, MAX_FREQUENCY),
+                )
+                .logarithmic(true),
+            );
+        });
+        self.conf = self.conf.sanitized();
+        ui.toggle_value(&mut self.conf.listen, tr("Listen to the band"));
+        crate::utils::create_f64_slider(
+            ui,
+            "threshold (dB): ",
+            &mut self.conf.compression.threshold,
+            std::ops::RangeInclusive::new(-60.0, 0.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "ratio: ",
+            &mut self.conf.compression.ratio,
+            std::ops::RangeInclusive::new(1.0, 20.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "attack (s): ",
+            &mut self.conf.compression.attack,
+            std::ops::RangeInclusive::new(0.0, 0.05),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "release (s): ",
+            &mut self.conf.compression.release,
+            std::ops::RangeInclusive::new(0.01, 0.5),
+        );
+        ui.label(tr("Band level: "));
+        meters::vu_meter(ui, &self.level);
+        ui.label(tr("Gain reduction: "));
+        meters::gain_reduction_meter(ui, self.reduction);
+        if initial != self.conf {
+            self.send_message(MessageToDeEsser::NewConfiguration(self.conf));
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send_message(&mut self, msg: MessageToDeEsser) {
+        if let Err(e) = self.message_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToDeEsserUI::Activity(level, reduction) => {
+                    self.level = level;
+                    self.reduction = reduction;
+                }
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf.sanitized();
+            self.send_message(MessageToDeEsser::NewConfiguration(self.conf));
+        }
+    }
+}
+
+impl eframe::App for DeEsserUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn deesser() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the de-esser and the UI
+    let (send_to_deesser, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_deesser) = std::sync::mpsc::channel();
+
+    let deesser = DeEsser::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(deesser))
+    {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "De-esser",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(DeEsserUI::new(
+                cc,
+                status,
+                rcv_from_deesser,
+                send_to_deesser,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeEsserConfiguration;
+
+    #[test]
+    fn listen_to_the_band() {
+        let mut conf = DeEsserConfiguration::default();
+        let bands = [0.25, 0.5, 0.125];
+        assert_eq!(conf.mix(bands, 1.0), 0.875);
+        assert_eq!(conf.mix(bands, 0.5), 0.625);
+        conf.listen = true;
+        assert_eq!(conf.mix(bands, 0.5), 0.25);
+    }
+}
@@ -1,3 +1,9 @@
+mod compressor;
+mod deesser;
+mod multiband;
 mod smooth;
 
+pub use compressor::compressor;
+pub use deesser::deesser;
+pub use multiband::multiband;
 pub use smooth::smooth;
@@ -0,0 +1,429 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dsp::{Compressor, CompressorSettings, Crossover, SvfCoefficients},
+    i18n::tr,
+    scene::SceneListener,
+    session::Autosave,
+    utils::{
+        meters::{self, Level},
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus,
+    },
+};
+
+const TOOL_NAME: &str = "multiband";
+///The number of band levels sent to the user interface every second
+const LEVELS_PER_SECOND: usize = 20;
+///The lowest split between the low and the mid band, in Hz
+const MIN_LOW_CROSSOVER: f64 = 40.0;
+///The highest split between the low and the mid band, in Hz
+const MAX_LOW_CROSSOVER: f64 = 1000.0;
+///The highest split between the mid and the high band, in Hz
+const MAX_HIGH_CROSSOVER: f64 = 12000.0;
+///The names of the bands, from the lowest
+const BAND_NAMES: [&str; 3] = ["Low band", "Mid band", "High band"];
+
+///The settings of the multiband compressor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct MultibandConfiguration {
+    ///The split between the low and the mid band, in Hz
+    low_crossover: f64,
+    ///The split between the mid and the high band, in Hz
+    high_crossover: f64,
+    ///The compression of every band, from the lowest
+    bands: [CompressorSettings; 3],
+}
+
+impl Default for MultibandConfiguration {
+    fn default() -> Self {
+        Self {
+            low_crossover: 200.0,
+            high_crossover: 2000.0,
+            bands: [CompressorSettings::default(); 3],
+        }
+    }
+}
+
+impl MultibandConfiguration {
+    ///The configuration with the splits within their ranges, the high one
+    /// always above the low one
+    fn sanitized(mut self) -> MultibandConfiguration {
+        self.low_crossover = self
+            .low_crossover
+            .clamp(MIN_LOW_CROSSOVER, MAX_LOW_CROSSOVER);
+        self.high_crossover = self
+            .high_crossover
+            .clamp(self.low_crossover, MAX_HIGH_CROSSOVER);
+        self
+    }
+}
+
+///What happened in a band since the last report
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct BandActivity {
+    ///The level of the band, before the compression
+    level: Level,
+    ///The gain reduction of the band, in dB
+    reduction: f64,
+}
+
+struct Multiband {
+    ///The number of frames per second
+    rate: usize,
+    ///The filters splitting the input into bands
+    crossover: Crossover,
+    ///The coefficients of the low and the high split
+    splits: [SvfCoefficients; 2],
+    ///The compressor of every band
+    compressors: [Compressor; 3],
+    ///The peak of every band since the last report
+    peaks: [f32; 3],
+    ///The sum of the squares of every band since the last report
+    squares: [f32; 3],
+    ///The number of frames measured since the last report
+    level_frames: usize,
+    /// The input audio port
+    audio_mono_in: jack::Port<jack::AudioIn>,
+    /// The output audio port
+    audio_mono_out: jack::Port<jack::AudioOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToMultiband>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToMultibandUI>,
+}
+
+impl Multiband {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToMultiband>,
+        messages_out: std::sync::mpsc::Sender<MessageToMultibandUI>,
+    ) -> Result<Multiband, CommonError> {
+        let a_in = match client.register_port("music_in", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let a_out = match client.register_port("music_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let rate = client.sample_rate();
+        let conf = MultibandConfiguration::default();
+        let mut multiband = Multiband {
+            rate,
+            crossover: Crossover::new(),
+            splits: [
+                Crossover::coefficients(rate, conf.low_crossover),
+                Crossover::coefficients(rate, conf.high_crossover),
+            ],
+            compressors: conf.bands.map(|settings| Compressor::new(rate, settings)),
+            peaks: [0.0; 3],
+            squares: [0.0; 3],
+            level_frames: 0,
+            audio_mono_in: a_in,
+            audio_mono_out: a_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        };
+        multiband.set_configuration(conf);
+        Ok(multiband)
+    }
+
+    fn set_configuration(&mut self, conf: MultibandConfiguration) {
+        let conf = conf.sanitized();
+        self.splits = [
+            Crossover::coefficients(self.rate, conf.low_crossover),
+            Crossover::coefficients(self.rate, conf.high_crossover),
+        ];
+        for (compressor, settings) in self.compressors.iter_mut().zip(conf.bands) {
+            compressor.set_settings(settings);
+        }
+    }
+
+    ///Send the activity of the bands to the UI, and start a new measure
+    fn report(&mut self) {
+        let mut activity = [BandActivity::default(); 3];
+        for (band, a) in activity.iter_mut().enumerate() {
+            a.level = Level {
+                peak: self.peaks[band],
+                rms: (self.squares[band] / self.level_frames.max(1) as f32).sqrt(),
+            };
+            a.reduction = self.compressors[band].reduction();
+        }
+        if let Err(e) = self
+            .messages_out
+            .send(MessageToMultibandUI::Activity(activity))
+        {
+            println!("Error: {e}");
+        }
+        self.peaks = [0.0; 3];
+        self.squares = [0.0; 3];
+        self.level_frames = 0;
+    }
+}
+
+impl jack::ProcessHandler for Multiband {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToMultiband::NewConfiguration(conf) => self.set_configuration(conf),
+            }
+        }
+
+        let audio_in = self.audio_mono_in.as_slice(ps);
+        let audio_out = self.audio_mono_out.as_mut_slice(ps);
+        for (input, output) in audio_in.iter().zip(audio_out.iter_mut()) {
+            let bands = self
+                .crossover
+                .split(*input as f64, &self.splits[0], &self.splits[1]);
+            let mut sum = 0.0;
+            for (band, value) in bands.iter().enumerate() {
+                let value32 = *value as f32;
+                self.peaks[band] = self.peaks[band].max(value32.abs());
+                self.squares[band] += value32 * value32;
+                sum += self.compressors[band].process(*value);
+            }
+            *output = sum as f32;
+        }
+        self.level_frames += audio_in.len();
+        if self.level_frames >= self.rate / LEVELS_PER_SECOND {
+            self.report();
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToMultiband {
+    NewConfiguration(MultibandConfiguration),
+}
+
+#[derive(Debug)]
+enum MessageToMultibandUI {
+    Activity([BandActivity; 3]),
+}
+
+struct MultibandUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToMultibandUI>,
+    message_out: std::sync::mpsc::Sender<MessageToMultiband>,
+    messages: Vec<String>,
+    conf: MultibandConfiguration,
+    ///The last activity of every band
+    activity: [BandActivity; 3],
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<MultibandConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl MultibandUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToMultibandUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToMultiband>,
+    ) -> MultibandUI {
+        MultibandUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            conf: MultibandConfiguration::default(),
+            activity: [BandActivity::default(); 3],
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    ///Show the controls and the activity of a band
+    fn create_band(ui: &mut egui::Ui, settings: &mut CompressorSettings, activity: &BandActivity) {
+        meters::vu_meter(ui, &activity.level);
+        meters::gain_reduction_meter(ui, activity.reduction);
+        crate::utils::create_f64_slider(
+            ui,
+            "threshold (dB): ",
+            &mut settings.threshold,
+            std::ops::RangeInclusive::new(-60.0, 0.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "ratio: ",
+            &mut settings.ratio,
+            std::ops::RangeInclusive::new(1.0, 20.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "attack (s): ",
+            &mut settings.attack,
+            std::ops::RangeInclusive::new(0.0, 0.2),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "release (s): ",
+            &mut settings.release,
+            std::ops::RangeInclusive::new(0.01, 1.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "makeup (dB): ",
+            &mut settings.makeup,
+            std::ops::RangeInclusive::new(0.0, 24.0),
+        );
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let initial = self.conf;
+        ui.label(tr("Crossover (Hz): "));
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Slider::new(
+                    &mut self.conf.low_crossover,
+                    std::ops::RangeInclusive::new(MIN_LOW_CROSSOVER, MAX_LOW_CROSSOVER),
+                )
+                .logarithmic(true),
+            );
+            ui.add(
+                egui::Slider::new(
+                    &mut self.conf.high_crossover,
+                    std::ops::RangeInclusive::new(MIN_LOW_CROSSOVER, MAX_HIGH_CROSSOVER),
+                )
+                .logarithmic(true),
+            );
+        });
+        self.conf = self.conf.sanitized();
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() * 0.7)
+            .show(ui, |ui| {
+                for (band, name) in BAND_NAMES.iter().enumerate() {
+                    ui.separator();
+                    ui.label(tr(name));
+                    Self::create_band(ui, &mut self.conf.bands[band], &self.activity[band]);
+                }
+            });
+        if initial != self.conf {
+            self.send_message(MessageToMultiband::NewConfiguration(self.conf));
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send_message(&mut self, msg: MessageToMultiband) {
+        if let Err(e) = self.message_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToMultibandUI::Activity(activity) => self.activity = activity,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf.sanitized();
+            self.send_message(MessageToMultiband::NewConfiguration(self.conf));
+        }
+    }
+}
+
+impl eframe::App for MultibandUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn multiband() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the compressor and the UI
+    let (send_to_multiband, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_multiband) = std::sync::mpsc::channel();
+
+    let multiband = Multiband::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client =
+        match client.activate_async(status.notifications(), status.monitor(multiband)) {
+            Ok(client) => client,
+            Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+        };
+
+    match eframe::run_native(
+        "Multiband",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(360.0, 760.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(MultibandUI::new(
+                cc,
+                status,
+                rcv_from_multiband,
+                send_to_multiband,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MultibandConfiguration;
+
+    #[test]
+    fn crossovers_in_order() {
+        let conf = MultibandConfiguration {
+            low_crossover: 5000.0,
+            high_crossover: 300.0,
+            ..Default::default()
+        }
+        .sanitized();
+        assert_eq!(conf.low_crossover, 1000.0);
+        assert_eq!(conf.high_crossover, 1000.0);
+    }
+}
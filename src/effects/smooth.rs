@@ -1,5 +1,10 @@
 use eframe::egui::{self, ViewportBuilder};
 
+use crate::{
+    dsp::{flush_denormal, DcBlocker},
+    utils::{ChannelWatchdog, JackStatus},
+};
+
 #[derive(Debug)]
 pub enum Connection {
     MidiIn,
@@ -47,6 +52,8 @@ struct Smooth {
     alpha: f64,
     beta: f64,
     avg: f64,
+    /// Remove the offset left by the averaging
+    dc_blocker: DcBlocker,
     /// The input midi port
     _midi_in: jack::Port<jack::MidiIn>,
     /// The input audio port
@@ -54,7 +61,7 @@ struct Smooth {
     /// The output audio port
     audio_mono_out: jack::Port<jack::AudioOut>,
     /// The incoming messages
-    messages_in: std::sync::mpsc::Receiver<SmoothMessages>,
+    messages_in: ChannelWatchdog<SmoothMessages>,
 }
 
 const MIN_ALPHA: f64 = 0.000001;
@@ -90,17 +97,18 @@ impl Smooth {
             alpha: a,
             beta: 1.0 - a,
             avg: 0.0,
+            dc_blocker: DcBlocker::new(client.sample_rate()),
             _midi_in: m_in,
             audio_mono_in: a_in,
             audio_mono_out: a_out,
-            messages_in: messages,
+            messages_in: ChannelWatchdog::new("smooth", messages),
         })
     }
 }
 
 impl jack::ProcessHandler for Smooth {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(msg) = self.messages_in.try_recv() {
+        if let Some(msg) = self.messages_in.try_recv() {
             match msg {
                 SmoothMessages::NewAlpha(alpha) => {
                     self.alpha = alpha;
@@ -112,8 +120,9 @@ impl jack::ProcessHandler for Smooth {
         let audio_out = self.audio_mono_out.as_mut_slice(ps);
         audio_out.copy_from_slice(audio_in);
         for v in audio_out {
-            self.avg = (*v) as f64 * self.alpha + self.avg * self.beta;
-            *v = self.avg as f32;
+            //the average of a silence decays forever
+            self.avg = flush_denormal((*v) as f64 * self.alpha + self.avg * self.beta);
+            *v = self.dc_blocker.process(self.avg) as f32;
         }
         jack::Control::Continue
     }
@@ -125,18 +134,28 @@ struct SmoothUI {
     sent_alpha: f64,
     /// The log messages
     messages: Vec<String>,
+    ///The scene requests we are listening to
+    scenes: crate::scene::SceneListener,
+    ///The value saved for crash recovery
+    session: crate::session::Autosave<f64>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl SmoothUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
         messages: std::sync::mpsc::Sender<SmoothMessages>,
     ) -> SmoothUI {
         SmoothUI {
+            status,
             message_out: messages,
             current_alpha: ALPHA_DEFAULT,
             sent_alpha: -1.0,
             messages: Vec::new(),
+            scenes: crate::scene::SceneListener::new(),
+            session: crate::session::Autosave::new("smooth"),
         }
     }
     fn create_menu(&mut self, ui: &mut egui::Ui) {
@@ -146,6 +165,7 @@ impl SmoothUI {
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
         if self.sent_alpha < self.current_alpha || self.sent_alpha > self.current_alpha {
             //value has changed
             if self.send_message(SmoothMessages::NewAlpha(self.current_alpha)) {
@@ -175,6 +195,19 @@ impl eframe::App for SmoothUI {
         //can be called whenever there is someting in the queue
         ctx.request_repaint();
 
+        let session = self.session.update(&self.current_alpha, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(alpha) = self
+            .scenes
+            .update("smooth", &self.current_alpha, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.current_alpha = alpha.clamp(MIN_ALPHA, MAX_ALPHA);
+        }
+
+        self.status.show(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.create_menu(ui);
@@ -193,12 +226,14 @@ pub fn smooth() -> Result<(), Error> {
             return Err(Error::UnableToStartClient(e));
         }
     };
+    crate::storage::set_instance("smooth", client.name());
 
     //open a message channel for the recorder and the UI
     let (send, rcv) = std::sync::mpsc::channel();
 
     let synth = Smooth::new(ALPHA_DEFAULT, &client, rcv)?;
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(Error::UnableToActivateTheClient(e)),
     };
@@ -210,7 +245,7 @@ pub fn smooth() -> Result<(), Error> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(SmoothUI::new(cc, send)))),
+        Box::new(|cc| Ok(Box::new(SmoothUI::new(cc, status, send)))),
     ) {
         Ok(_) => {}
         Err(e) => return Err(Error::UnableToStartUserInterface(e)),
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        OnceLock,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, StorageError};
+
+///The name of the file containing the language chosen by the user
+const LANGUAGE_FILE: &str = "language.json";
+///The value of the current language before it is loaded
+const UNKNOWN_LANGUAGE: u8 = u8::MAX;
+///The french translations, one `"english" = "french"` entry per line
+const FRENCH: &str = include_str!("i18n/fr.txt");
+
+///The current language of the user interfaces
+static CURRENT: AtomicU8 = AtomicU8::new(UNKNOWN_LANGUAGE);
+///The french translations, parsed the first time they are needed
+static FRENCH_TABLE: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+
+///The languages of the user interfaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    English,
+    French,
+}
+
+impl Language {
+    ///Every known language
+    pub const ALL: [Language; 2] = [Language::English, Language::French];
+
+    fn index(&self) -> u8 {
+        match self {
+            Language::English => 0,
+            Language::French => 1,
+        }
+    }
+
+    fn from_index(index: u8) -> Language {
+        match index {
+            1 => Language::French,
+            _ => Language::English,
+        }
+    }
+
+    ///Guess the language from the environment of the user
+    fn from_env() -> Language {
+        match std::env::var("LANG") {
+            Ok(lang) if lang.starts_with("fr") => Language::French,
+            _ => Language::English,
+        }
+    }
+
+    ///The translations from english, None for english itself
+    fn table(&self) -> Option<&'static HashMap<&'static str, &'static str>> {
+        match self {
+            Language::English => None,
+            Language::French => Some(FRENCH_TABLE.get_or_init(|| parse(FRENCH))),
+        }
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Language::English => write!(f, "English"),
+            Language::French => write!(f, "Français"),
+        }
+    }
+}
+
+///Parse a translation table. Every line contains a quoted english text, an
+/// equal sign and the quoted translation. Empty lines and lines starting
+/// with '#' are ignored, as well as invalid lines.
+fn parse(content: &'static str) -> HashMap<&'static str, &'static str> {
+    let mut table = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((text, translation)) = line.split_once("\" = \"") {
+            if let (Some(text), Some(translation)) =
+                (text.strip_prefix('"'), translation.strip_suffix('"'))
+            {
+                table.insert(text, translation);
+            }
+        }
+    }
+    table
+}
+
+fn path() -> Result<PathBuf, StorageError> {
+    let mut path = storage::config_dir()?;
+    path.push(LANGUAGE_FILE);
+    Ok(path)
+}
+
+///Retrieve the current language: the one chosen by the user, or the one of the environment
+pub fn language() -> Language {
+    match CURRENT.load(Ordering::Relaxed) {
+        UNKNOWN_LANGUAGE => {
+            let language = match path() {
+                Ok(path) if path.exists() => storage::load(&path).unwrap_or(Language::from_env()),
+                _ => Language::from_env(),
+            };
+            CURRENT.store(language.index(), Ordering::Relaxed);
+            language
+        }
+        index => Language::from_index(index),
+    }
+}
+
+///Change the language of the user interfaces, and remember it for the next programs started
+pub fn set_language(language: Language) -> Result<(), StorageError> {
+    CURRENT.store(language.index(), Ordering::Relaxed);
+    storage::save(&path()?, &language)
+}
+
+///Translate a text of the user interface in the current language. The text
+/// is returned unchanged if there is no translation.
+pub fn tr(text: &str) -> &str {
+    match language().table().and_then(|table| table.get(text)) {
+        Some(translation) => translation,
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, FRENCH};
+
+    #[test]
+    fn parse_table() {
+        let table =
+            parse("# comment\n\n\"Clear\" = \"Effacer\"\n\"Gain: \" = \"Gain : \"\ninvalid\n");
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get("Clear"), Some(&"Effacer"));
+        assert_eq!(table.get("Gain: "), Some(&"Gain : "));
+    }
+
+    #[test]
+    fn french_table_is_valid() {
+        let entries = FRENCH
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !l.trim().starts_with('#'))
+            .count();
+        let table = parse(FRENCH);
+        assert_eq!(table.len(), entries);
+        assert!(table.values().all(|translation| !translation.is_empty()));
+    }
+}
@@ -1,6 +1,18 @@
+pub mod archive;
 pub mod configuration;
+pub mod dsp;
 pub mod effects;
+pub mod i18n;
 pub mod midiinput;
+pub mod mmc;
+pub mod mtc;
+pub mod mts;
+pub mod scene;
+pub mod session;
+pub mod spsc;
+pub mod storage;
 pub mod synth;
+pub mod tool;
 pub mod utils;
+pub mod wavreader;
 pub mod wavwriter;
@@ -1,6 +1,71 @@
 use std::convert::From;
 
 const MAX_MIDI: usize = 3;
+///The controller stopping every sound at once, without release
+pub const ALL_SOUND_OFF: u8 = 120;
+///The controller releasing every playing note
+pub const ALL_NOTES_OFF: u8 = 123;
+///The channel of the drums in general midi (channel 10, counted from 0)
+pub const GM_DRUM_CHANNEL: u8 = 9;
+///The highest velocity of a midi note
+const MAX_VELOCITY: u8 = 127;
+///The number of note indexes below the midi note 0: the index of a note is
+/// its midi number, one octave higher
+const NOTE_INDEX_OFFSET: usize = 12;
+///The midi number of the A at 440 Hz
+const A4: f64 = 69.0;
+///The first midi note of the general midi drum map
+const GM_FIRST_DRUM: usize = 35;
+///The instruments of the general midi drum map, from the note 35
+const GM_DRUMS: [&str; 47] = [
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+];
 
 #[derive(Clone, PartialEq)]
 pub enum MidiInput {
@@ -24,6 +89,21 @@ pub enum MidiInput {
     PitchBend {
         value: u8,
     },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ///The pressure applied on a single key (polyphonic aftertouch)
+    KeyPressure {
+        channel: u8,
+        note_index: usize,
+        value: u8,
+    },
+    ///The pressure applied on the keys (channel aftertouch)
+    ChannelPressure {
+        channel: u8,
+        value: u8,
+    },
     Unknown {
         d1: u8,
         d2: u8,
@@ -31,6 +111,82 @@ pub enum MidiInput {
     },
 }
 
+///A midi note number, from 0 to 127. The notes are given to the programs by
+/// their index (see [MidiInput]), this is the only place converting between
+/// the index of a note and its midi number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MidiNote(u8);
+
+impl MidiNote {
+    ///The highest midi note
+    pub const MAX: u8 = 127;
+
+    ///The note of a midi number, if it is a valid one
+    pub fn new(number: u8) -> Option<MidiNote> {
+        (number <= Self::MAX).then_some(MidiNote(number))
+    }
+
+    ///The note of an index, if it is a valid midi note
+    pub fn from_index(note_index: usize) -> Option<MidiNote> {
+        let number = note_index.checked_sub(NOTE_INDEX_OFFSET)?;
+        Self::new(u8::try_from(number).ok()?)
+    }
+
+    ///The midi number of the note
+    pub fn number(self) -> u8 {
+        self.0
+    }
+
+    ///The index of the note
+    pub fn index(self) -> usize {
+        self.0 as usize + NOTE_INDEX_OFFSET
+    }
+
+    ///Move the note by some semitones, if it is still a midi note
+    pub fn transpose(self, semitones: i32) -> Option<MidiNote> {
+        let number = i32::from(self.0).checked_add(semitones)?;
+        Self::new(u8::try_from(number).ok()?)
+    }
+
+    ///Move the note by some octaves (negative to go down), if it is still a
+    /// midi note
+    pub fn shift_octaves(self, octaves: i32) -> Option<MidiNote> {
+        self.transpose(octaves.checked_mul(12)?)
+    }
+
+    ///The index of a note given by the data byte of a message. The byte is not
+    /// checked: a malformed message gives an index above the midi notes.
+    pub fn raw_index(byte: u8) -> usize {
+        byte as usize + NOTE_INDEX_OFFSET
+    }
+
+    ///The data byte of a note index, the opposite of 'raw_index'. The indexes
+    /// below the midi note 0 give the note 0 instead of wrapping around.
+    pub fn raw_byte(note_index: usize) -> u8 {
+        note_index
+            .saturating_sub(NOTE_INDEX_OFFSET)
+            .min(u8::MAX as usize) as u8
+    }
+}
+
+///The frequency of a pitch given as index of note, in equal temperament. The
+/// pitch can be between two notes, e.g. when retuned.
+pub fn frequency(pitch: f64) -> f64 {
+    440.0 * 2.0_f64.powf((pitch - NOTE_INDEX_OFFSET as f64 - A4) / 12.0)
+}
+
+///The velocity of a note, from 0 to 1, given its midi value. Only the seven
+/// bits of a midi data byte are read.
+pub fn velocity_from_midi(value: u8) -> f64 {
+    (value & MAX_VELOCITY) as f64 / MAX_VELOCITY as f64
+}
+
+///The midi value of a velocity from 0 to 1, the opposite of
+/// 'velocity_from_midi'. The value never reaches the status bit.
+pub fn velocity_to_midi(velocity: f64) -> u8 {
+    (velocity.clamp(0.0, 1.0) * MAX_VELOCITY as f64).round() as u8
+}
+
 /// Function to retrieve the name of a note based on its index
 fn index_to_name(index: usize) -> &'static str {
     match index % 12 {
@@ -58,7 +214,82 @@ fn index_to_octave(index: usize) -> usize {
     index / 12
 }
 
+/// Function to retrieve the name and the octave of a note based on its index
+pub fn note_name(index: usize) -> String {
+    format!(
+        "{}{}",
+        index_to_name(index).trim_end(),
+        index_to_octave(index)
+    )
+}
+
+/// Function to retrieve the instrument of the general midi drum map played by
+/// a note based on its index
+pub fn drum_name(index: usize) -> Option<&'static str> {
+    let note = MidiNote::from_index(index)?;
+    (note.number() as usize)
+        .checked_sub(GM_FIRST_DRUM)
+        .and_then(|drum| GM_DRUMS.get(drum))
+        .copied()
+}
+
 impl MidiInput {
+    ///The channel of the message, if it has one
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            MidiInput::NoteStart { channel, .. }
+            | MidiInput::NoteEnd { channel, .. }
+            | MidiInput::Controller { channel, .. }
+            | MidiInput::ProgramChange { channel, .. }
+            | MidiInput::KeyPressure { channel, .. }
+            | MidiInput::ChannelPressure { channel, .. } => Some(*channel),
+            MidiInput::PitchBend { .. } | MidiInput::Unknown { .. } => None,
+        }
+    }
+
+    ///Bend the velocity of a note start, as a hook for the programs wanting a
+    /// velocity response: 'curve' is 0 for a linear response, negative to get
+    /// loud sooner and positive to get loud later. Other events are kept.
+    pub fn with_velocity_curve(self, curve: f64) -> MidiInput {
+        match self {
+            MidiInput::NoteStart {
+                channel,
+                note_index,
+                timing,
+                velocity,
+            } => MidiInput::NoteStart {
+                channel,
+                note_index,
+                timing,
+                velocity: velocity.clamp(0.0, 1.0).powf(4.0_f64.powf(curve)),
+            },
+            other => other,
+        }
+    }
+
+    ///The drum of the general midi drum map played by a note sent on the drum
+    /// channel
+    pub fn drum_name(&self) -> Option<&'static str> {
+        match self {
+            MidiInput::NoteStart {
+                channel,
+                note_index,
+                ..
+            }
+            | MidiInput::NoteEnd {
+                channel,
+                note_index,
+                ..
+            }
+            | MidiInput::KeyPressure {
+                channel,
+                note_index,
+                ..
+            } if *channel == GM_DRUM_CHANNEL => drum_name(*note_index),
+            _ => None,
+        }
+    }
+
     pub fn to_raw<'data>(&self, bytes: &'data mut [u8]) -> jack::RawMidi<'data> {
         match self {
             MidiInput::NoteStart {
@@ -68,8 +299,8 @@ impl MidiInput {
                 velocity,
             } => {
                 bytes[0] = 0x90 | (*channel & 0x0F);
-                bytes[1] = (*note_index - 12) as u8;
-                bytes[2] = (velocity * 256.0) as u8;
+                bytes[1] = MidiNote::raw_byte(*note_index);
+                bytes[2] = velocity_to_midi(*velocity);
                 jack::RawMidi {
                     time: *timing,
                     bytes,
@@ -82,9 +313,8 @@ impl MidiInput {
                 velocity,
             } => {
                 bytes[0] = 0x80 | (*channel & 0x0F);
-                assert!(*note_index >= 12);
-                bytes[1] = (*note_index - 12) as u8;
-                bytes[2] = (velocity * 256.0) as u8;
+                bytes[1] = MidiNote::raw_byte(*note_index);
+                bytes[2] = velocity_to_midi(*velocity);
                 jack::RawMidi {
                     time: *timing,
                     bytes,
@@ -105,6 +335,36 @@ impl MidiInput {
                 bytes[2] = *value;
                 jack::RawMidi { time: 0, bytes }
             }
+            MidiInput::ProgramChange { channel, program } => {
+                bytes[0] = 0xC0 | (*channel & 0x0F);
+                bytes[1] = *program;
+                //a program change only has one data byte
+                let bytes: &'data [u8] = bytes;
+                jack::RawMidi {
+                    time: 0,
+                    bytes: &bytes[..2],
+                }
+            }
+            MidiInput::KeyPressure {
+                channel,
+                note_index,
+                value,
+            } => {
+                bytes[0] = 0xA0 | (*channel & 0x0F);
+                bytes[1] = MidiNote::raw_byte(*note_index);
+                bytes[2] = *value;
+                jack::RawMidi { time: 0, bytes }
+            }
+            MidiInput::ChannelPressure { channel, value } => {
+                bytes[0] = 0xD0 | (*channel & 0x0F);
+                bytes[1] = *value;
+                //a channel pressure only has one data byte
+                let bytes: &'data [u8] = bytes;
+                jack::RawMidi {
+                    time: 0,
+                    bytes: &bytes[..2],
+                }
+            }
             MidiInput::Unknown { d1, d2, d3 } => {
                 bytes[0] = *d1;
                 bytes[1] = *d2;
@@ -164,6 +424,34 @@ impl std::fmt::Debug for MidiInput {
             MidiInput::PitchBend { value } => {
                 write!(f, "Pitch bend {{ {} }}", value)
             }
+            MidiInput::ProgramChange { channel, program } => {
+                write!(
+                    f,
+                    "Program change {{ channel: {}, program: {} }}",
+                    channel, program
+                )
+            }
+            MidiInput::KeyPressure {
+                channel,
+                note_index,
+                value,
+            } => {
+                write!(
+                    f,
+                    "Key pressure {{ channel: {}, note: {}{}, value: {} }}",
+                    channel,
+                    index_to_name(*note_index),
+                    index_to_octave(*note_index),
+                    value
+                )
+            }
+            MidiInput::ChannelPressure { channel, value } => {
+                write!(
+                    f,
+                    "Channel pressure {{ channel: {}, value: {} }}",
+                    channel, value
+                )
+            }
             MidiInput::Unknown { d1, d2, d3 } => {
                 write!(f, "Unknown {{ d1: {}, d2: {}, d3: {} }}", d1, d2, d3)
             }
@@ -179,17 +467,17 @@ impl From<jack::RawMidi<'_>> for MidiInput {
             assert!(len > 2);
             MidiInput::NoteStart {
                 channel: 0x0F & header_byte,
-                note_index: 12 + (midi.bytes[1] as usize),
+                note_index: MidiNote::raw_index(midi.bytes[1]),
                 timing: midi.time,
-                velocity: (midi.bytes[2] as f64) / 256.0,
+                velocity: velocity_from_midi(midi.bytes[2]),
             }
         } else if (0xF0 & header_byte) == (0x80 as u8) {
             assert!(len > 1);
             MidiInput::NoteEnd {
                 channel: 0x0F & header_byte,
-                note_index: 12 + (midi.bytes[1] as usize),
+                note_index: MidiNote::raw_index(midi.bytes[1]),
                 timing: midi.time,
-                velocity: (midi.bytes[2] as f64) / 256.0,
+                velocity: velocity_from_midi(midi.bytes[2]),
             }
         } else if (0xF0 & header_byte) == (0xB0 as u8) {
             let channel = (0x0F & header_byte) as u8;
@@ -199,6 +487,25 @@ impl From<jack::RawMidi<'_>> for MidiInput {
                 control: controller,
                 value: midi.bytes[2],
             }
+        } else if (0xF0 & header_byte) == 0xC0 {
+            assert!(len > 1);
+            MidiInput::ProgramChange {
+                channel: 0x0F & header_byte,
+                program: midi.bytes[1],
+            }
+        } else if (0xF0 & header_byte) == 0xA0 {
+            assert!(len > 2);
+            MidiInput::KeyPressure {
+                channel: 0x0F & header_byte,
+                note_index: MidiNote::raw_index(midi.bytes[1]),
+                value: midi.bytes[2],
+            }
+        } else if (0xF0 & header_byte) == 0xD0 {
+            assert!(len > 1);
+            MidiInput::ChannelPressure {
+                channel: 0x0F & header_byte,
+                value: midi.bytes[1],
+            }
         } else if (0xF0 & header_byte) == (0xE0 as u8) {
             assert!(len > 2);
             MidiInput::PitchBend {
@@ -218,7 +525,83 @@ impl From<jack::RawMidi<'_>> for MidiInput {
 mod test {
     use jack::RawMidi;
 
-    use super::MidiInput;
+    use super::{drum_name, frequency, velocity_from_midi, velocity_to_midi, MidiInput, MidiNote};
+
+    #[test]
+    fn drum_names() {
+        assert_eq!(drum_name(12 + 38), Some("Acoustic Snare"));
+        assert_eq!(drum_name(12 + 81), Some("Open Triangle"));
+        assert_eq!(drum_name(12 + 34), None);
+        assert_eq!(drum_name(12 + 82), None);
+        let note = MidiInput::NoteStart {
+            channel: 9,
+            note_index: 12 + 36,
+            timing: 0,
+            velocity: 0.5,
+        };
+        assert_eq!(note.drum_name(), Some("Bass Drum 1"));
+    }
+
+    #[test]
+    fn midi_notes() {
+        let a4 = MidiNote::new(69).unwrap();
+        assert_eq!(a4.index(), 12 + 69);
+        assert_eq!(MidiNote::from_index(12 + 69), Some(a4));
+        assert_eq!(MidiNote::from_index(11), None);
+        assert_eq!(MidiNote::from_index(12 + 128), None);
+        assert_eq!(MidiNote::new(128), None);
+        assert_eq!(a4.shift_octaves(-1).map(MidiNote::number), Some(57));
+        assert_eq!(a4.shift_octaves(5), None);
+        assert_eq!(a4.transpose(58).map(MidiNote::number), Some(127));
+        assert_eq!(a4.transpose(-70), None);
+        assert_eq!(frequency(a4.index() as f64), 440.0);
+        assert_eq!(
+            frequency(a4.shift_octaves(1).unwrap().index() as f64),
+            880.0
+        );
+        //the indexes below the midi note 0 do not wrap around
+        assert_eq!(MidiNote::raw_byte(3), 0);
+        let note = MidiInput::NoteEnd {
+            channel: 0,
+            note_index: 3,
+            timing: 0,
+            velocity: 0.0,
+        };
+        let mut bytes = vec![0; 3];
+        assert_eq!(note.to_raw(&mut bytes).bytes, &[0x80, 0, 0]);
+    }
+
+    #[test]
+    fn velocity_round_trip() {
+        assert_eq!(velocity_from_midi(127), 1.0);
+        assert_eq!(velocity_from_midi(0), 0.0);
+        for value in 0..=u8::MAX {
+            assert_eq!(velocity_to_midi(velocity_from_midi(value)), value & 0x7F);
+        }
+        //the status bit is never set
+        assert_eq!(velocity_to_midi(1.5), 127);
+        let note = MidiInput::from(RawMidi {
+            time: 0,
+            bytes: &[0x90, 60, 127],
+        });
+        let mut bytes = vec![0; 3];
+        assert_eq!(note.to_raw(&mut bytes).bytes, &[0x90, 60, 127]);
+    }
+
+    #[test]
+    fn velocity_curve() {
+        let note = |velocity| MidiInput::NoteStart {
+            channel: 0,
+            note_index: 60,
+            timing: 0,
+            velocity,
+        };
+        assert_eq!(note(0.5).with_velocity_curve(0.0), note(0.5));
+        assert_eq!(note(0.5).with_velocity_curve(0.5), note(0.25));
+        assert_eq!(note(1.0).with_velocity_curve(-1.0), note(1.0));
+        let bend = MidiInput::PitchBend { value: 3 };
+        assert_eq!(bend.clone().with_velocity_curve(1.0), bend);
+    }
 
     #[test]
     fn midi_to_raw() {
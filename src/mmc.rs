@@ -0,0 +1,98 @@
+use crate::synth::hardware::KeyBoardKey;
+
+///The device id addressing every device
+pub const ALL_DEVICES: u8 = 0x7F;
+///The length of a midi machine control command
+const COMMAND_LENGTH: usize = 6;
+
+///The transport commands of the midi machine control, sent as system
+/// exclusive messages by the transport buttons of the hardware:
+/// `F0 7F <device> 06 <command> F7`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    ///Start recording (punch in)
+    RecordStrobe,
+    ///Stop recording (punch out)
+    RecordExit,
+    Pause,
+}
+
+impl MmcCommand {
+    fn code(&self) -> u8 {
+        match self {
+            MmcCommand::Stop => 0x01,
+            MmcCommand::Play => 0x02,
+            MmcCommand::DeferredPlay => 0x03,
+            MmcCommand::RecordStrobe => 0x06,
+            MmcCommand::RecordExit => 0x07,
+            MmcCommand::Pause => 0x09,
+        }
+    }
+
+    ///Read a command from a raw midi message. Other messages, and commands
+    /// that are not handled, give None.
+    pub fn parse(bytes: &[u8]) -> Option<MmcCommand> {
+        match bytes {
+            [0xF0, 0x7F, _device, 0x06, command, 0xF7] => match command {
+                0x01 => Some(MmcCommand::Stop),
+                0x02 => Some(MmcCommand::Play),
+                0x03 => Some(MmcCommand::DeferredPlay),
+                0x06 => Some(MmcCommand::RecordStrobe),
+                0x07 => Some(MmcCommand::RecordExit),
+                0x09 => Some(MmcCommand::Pause),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    ///The system exclusive message of the command, for the given device
+    pub fn to_sysex(&self, device: u8) -> [u8; COMMAND_LENGTH] {
+        [0xF0, 0x7F, device & 0x7F, 0x06, self.code(), 0xF7]
+    }
+
+    ///The key of the hardware mapping triggered by the command, so that a
+    /// transport button behaves the same whether it sends a controller or a
+    /// machine control command
+    pub fn key(&self) -> Option<KeyBoardKey> {
+        match self {
+            MmcCommand::Stop | MmcCommand::Pause | MmcCommand::RecordExit => {
+                Some(KeyBoardKey::Stop)
+            }
+            MmcCommand::Play | MmcCommand::DeferredPlay => Some(KeyBoardKey::Play),
+            MmcCommand::RecordStrobe => Some(KeyBoardKey::Record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MmcCommand, ALL_DEVICES};
+
+    #[test]
+    fn sysex_round_trip() {
+        for command in [
+            MmcCommand::Stop,
+            MmcCommand::Play,
+            MmcCommand::DeferredPlay,
+            MmcCommand::RecordStrobe,
+            MmcCommand::RecordExit,
+            MmcCommand::Pause,
+        ] {
+            let sysex = command.to_sysex(ALL_DEVICES);
+            assert_eq!(MmcCommand::parse(&sysex), Some(command));
+        }
+        assert_eq!(
+            MmcCommand::parse(&[0xF0, 0x7F, 0x10, 0x06, 0x02, 0xF7]),
+            Some(MmcCommand::Play)
+        );
+        assert_eq!(
+            MmcCommand::parse(&[0xF0, 0x7F, 0x10, 0x06, 0x44, 0xF7]),
+            None
+        );
+        assert_eq!(MmcCommand::parse(&[0xB0, 0x76, 0x7F]), None);
+    }
+}
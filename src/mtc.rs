@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+///The number of frames per second of the generated time code
+pub const FRAMES_PER_SECOND: u64 = 25;
+///The status byte of a quarter frame message
+const QUARTER_FRAME: u8 = 0xF1;
+///The code of the 25 frames per second rate, sent in the last quarter frame
+const RATE_25_FPS: u8 = 1;
+///The number of quarter frames needed to send a whole time code
+const NB_PIECES: u64 = 8;
+
+///A position given by the midi time code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+}
+
+impl Timecode {
+    ///The time code of the given number of frames since the start
+    pub fn from_frames(frames: u64) -> Timecode {
+        let seconds = frames / FRAMES_PER_SECOND;
+        Timecode {
+            hours: ((seconds / 3600) % 24) as u8,
+            minutes: ((seconds / 60) % 60) as u8,
+            seconds: (seconds % 60) as u8,
+            frames: (frames % FRAMES_PER_SECOND) as u8,
+        }
+    }
+
+    ///The number of frames since the start
+    pub fn to_frames(&self) -> u64 {
+        let seconds = self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64;
+        seconds * FRAMES_PER_SECOND + self.frames as u64
+    }
+
+    ///The 4 bits of the time code sent by the quarter frame 'piece'
+    fn nibble(&self, piece: u8) -> u8 {
+        match piece {
+            0 => self.frames & 0x0F,
+            1 => (self.frames >> 4) & 0x01,
+            2 => self.seconds & 0x0F,
+            3 => (self.seconds >> 4) & 0x03,
+            4 => self.minutes & 0x0F,
+            5 => (self.minutes >> 4) & 0x03,
+            6 => self.hours & 0x0F,
+            _ => (RATE_25_FPS << 1) | ((self.hours >> 4) & 0x01),
+        }
+    }
+
+    fn from_nibbles(nibbles: &[u8; NB_PIECES as usize]) -> Timecode {
+        Timecode {
+            frames: nibbles[0] | ((nibbles[1] & 0x01) << 4),
+            seconds: nibbles[2] | ((nibbles[3] & 0x03) << 4),
+            minutes: nibbles[4] | ((nibbles[5] & 0x03) << 4),
+            hours: nibbles[6] | ((nibbles[7] & 0x01) << 4),
+        }
+    }
+}
+
+impl Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+///Generate the quarter frames of the midi time code, starting at 00:00:00:00,
+/// so that external multitrackers and video software follow the suite
+pub struct MtcGenerator {
+    ///The audio rate (number of audio frames per seconds)
+    rate: u64,
+    ///The number of audio frames generated since the start
+    elapsed: u64,
+    ///The index of the next quarter frame to send
+    quarter: u64,
+    ///The time code being sent
+    timecode: Timecode,
+}
+
+impl MtcGenerator {
+    pub fn new(rate: usize) -> MtcGenerator {
+        MtcGenerator {
+            rate: rate as u64,
+            elapsed: 0,
+            quarter: 0,
+            timecode: Timecode::default(),
+        }
+    }
+
+    ///Go back to 00:00:00:00
+    pub fn reset(&mut self) {
+        self.elapsed = 0;
+        self.quarter = 0;
+    }
+
+    ///Give to 'send' every quarter frame of the next 'nb_frames' audio frames,
+    /// with its position in the buffer
+    pub fn generate<F>(&mut self, nb_frames: usize, mut send: F)
+    where
+        F: FnMut(usize, [u8; 2]),
+    {
+        let end = self.elapsed + nb_frames as u64;
+        loop {
+            let at = self.quarter * self.rate / (4 * FRAMES_PER_SECOND);
+            if at >= end {
+                break;
+            }
+            let piece = (self.quarter % NB_PIECES) as u8;
+            if piece == 0 {
+                self.timecode = Timecode::from_frames(self.quarter / 4);
+            }
+            let data = (piece << 4) | self.timecode.nibble(piece);
+            send((at - self.elapsed) as usize, [QUARTER_FRAME, data]);
+            self.quarter += 1;
+        }
+        self.elapsed = end;
+    }
+}
+
+///Read the midi time code sent by another device
+#[derive(Debug, Default)]
+pub struct MtcReader {
+    ///The part of the time code sent by every quarter frame
+    nibbles: [u8; NB_PIECES as usize],
+    ///The quarter frames received since the last complete time code
+    received: u8,
+}
+
+impl MtcReader {
+    ///Read a midi message. When a complete time code is known (after eight
+    /// quarter frames, or from a full frame message), it is returned.
+    pub fn read(&mut self, bytes: &[u8]) -> Option<Timecode> {
+        match bytes {
+            [QUARTER_FRAME, data] => {
+                let piece = (data >> 4) & 0x07;
+                self.nibbles[piece as usize] = data & 0x0F;
+                self.received |= 1 << piece;
+                if piece as u64 == NB_PIECES - 1 && self.received == 0xFF {
+                    self.received = 0;
+                    //the time code was sent over two frames
+                    let timecode = Timecode::from_nibbles(&self.nibbles);
+                    Some(Timecode::from_frames(timecode.to_frames() + 2))
+                } else {
+                    None
+                }
+            }
+            [0xF0, 0x7F, _device, 0x01, 0x01, hours, minutes, seconds, frames, 0xF7] => {
+                Some(Timecode {
+                    hours: hours & 0x1F,
+                    minutes: *minutes,
+                    seconds: *seconds,
+                    frames: *frames,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MtcGenerator, MtcReader, Timecode, FRAMES_PER_SECOND};
+
+    #[test]
+    fn timecode_round_trip() {
+        let rate = 48000;
+        let mut generator = MtcGenerator::new(rate);
+        let mut reader = MtcReader::default();
+        let mut received = Vec::new();
+        //ten seconds, in buffers of 256 frames
+        for _ in 0..(10 * rate / 256) {
+            generator.generate(256, |offset, message| {
+                assert!(offset < 256);
+                if let Some(timecode) = reader.read(&message) {
+                    received.push(timecode);
+                }
+            });
+        }
+        //a complete time code every two frames
+        assert!(received.len() as u64 >= 10 * FRAMES_PER_SECOND / 2 - 1);
+        assert_eq!(received[0], Timecode::from_frames(2));
+        assert_eq!(received[12].to_string(), "00:00:01:01");
+        assert_eq!(
+            Timecode::from_frames(90061 * FRAMES_PER_SECOND + 3).to_string(),
+            "01:01:01:03"
+        );
+
+        let full_frame = [0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x21, 0x02, 0x03, 0x04, 0xF7];
+        assert_eq!(reader.read(&full_frame).unwrap().to_string(), "01:02:03:04");
+    }
+}
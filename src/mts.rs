@@ -0,0 +1,169 @@
+use crate::midiinput::MidiNote;
+
+///The number of keys of a tuning
+const NB_KEYS: usize = 128;
+///The length of the name of a tuning in a bulk dump
+const NAME_LENGTH: usize = 16;
+///The length of a bulk dump: header, name, frequencies, checksum and end
+pub const BULK_DUMP_LENGTH: usize = 6 + NAME_LENGTH + 3 * NB_KEYS + 2;
+///The name given to the tuning sent by the suite
+const TUNING_NAME: &[u8; NAME_LENGTH] = b"rsuite          ";
+///The frequency data meaning that a key is not retuned
+const NO_CHANGE: [u8; 3] = [0x7F, 0x7F, 0x7F];
+
+///What a midi tuning standard message changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtsEvent {
+    ///Some keys were retuned
+    Retuned,
+    ///Another device asked for the tuning of the given program
+    DumpRequested(u8),
+}
+
+///The pitch of every midi key, changed on the fly by the midi tuning standard
+/// (MTS) system exclusive messages, for microtonal workflows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    ///The pitch of every key, in semitones: key 69 plays 69.0 when not retuned
+    pitch: [f64; NB_KEYS],
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        let mut pitch = [0.0; NB_KEYS];
+        for (key, p) in pitch.iter_mut().enumerate() {
+            *p = key as f64;
+        }
+        Self { pitch }
+    }
+}
+
+impl Tuning {
+    ///The pitch of a note, as index of the note: the index of the note itself
+    /// when it is not retuned
+    pub fn pitch(&self, note_index: usize) -> f64 {
+        match MidiNote::from_index(note_index) {
+            //the pitch of the key is given as midi number
+            Some(key) => {
+                self.pitch[key.number() as usize] - key.number() as f64 + key.index() as f64
+            }
+            None => note_index as f64,
+        }
+    }
+
+    ///The number of keys that are not at their equal temperament pitch
+    pub fn retuned(&self) -> usize {
+        self.pitch
+            .iter()
+            .enumerate()
+            .filter(|(key, pitch)| (**pitch - *key as f64).abs() > 1e-6)
+            .count()
+    }
+
+    ///Change the pitch of a key from the 3 bytes of frequency data
+    fn set(&mut self, key: u8, data: &[u8]) {
+        if data == NO_CHANGE || key as usize >= NB_KEYS {
+            return;
+        }
+        let fraction = ((data[1] as u16) << 7 | data[2] as u16) as f64 / 16384.0;
+        self.pitch[key as usize] = data[0] as f64 + fraction;
+    }
+
+    ///The 3 bytes of frequency data of a key
+    fn get(&self, key: usize) -> [u8; 3] {
+        let pitch = self.pitch[key].clamp(0.0, 127.0);
+        let semitone = pitch.floor();
+        let fraction = (((pitch - semitone) * 16384.0).round() as u16).min(0x3FFF);
+        [
+            semitone as u8,
+            (fraction >> 7) as u8,
+            (fraction & 0x7F) as u8,
+        ]
+    }
+
+    ///Apply a midi tuning standard message: single note tuning changes (with
+    /// or without bank) and bulk dumps. Other messages are ignored.
+    pub fn read(&mut self, bytes: &[u8]) -> Option<MtsEvent> {
+        match bytes {
+            //real time single note tuning change
+            [0xF0, 0x7F, _device, 0x08, 0x02, _program, count, changes @ .., 0xF7] => {
+                self.read_changes(*count, changes)
+            }
+            //single note tuning change with bank
+            [0xF0, 0x7E | 0x7F, _device, 0x08, 0x07, _bank, _program, count, changes @ .., 0xF7] => {
+                self.read_changes(*count, changes)
+            }
+            //bulk dump
+            [0xF0, 0x7E, _device, 0x08, 0x01, _program, rest @ ..]
+                if rest.len() == NAME_LENGTH + 3 * NB_KEYS + 2 =>
+            {
+                let data = &rest[NAME_LENGTH..NAME_LENGTH + 3 * NB_KEYS];
+                for (key, frequency) in data.chunks(3).enumerate() {
+                    self.set(key as u8, frequency);
+                }
+                Some(MtsEvent::Retuned)
+            }
+            //bulk dump request
+            [0xF0, 0x7E, _device, 0x08, 0x00, program, 0xF7] => {
+                Some(MtsEvent::DumpRequested(*program))
+            }
+            _ => None,
+        }
+    }
+
+    fn read_changes(&mut self, count: u8, changes: &[u8]) -> Option<MtsEvent> {
+        for change in changes.chunks_exact(4).take(count as usize) {
+            self.set(change[0], &change[1..]);
+        }
+        Some(MtsEvent::Retuned)
+    }
+
+    ///The bulk dump of the tuning, sent to the given device
+    pub fn bulk_dump(&self, device: u8, program: u8) -> [u8; BULK_DUMP_LENGTH] {
+        let mut dump = [0; BULK_DUMP_LENGTH];
+        dump[..6].copy_from_slice(&[0xF0, 0x7E, device & 0x7F, 0x08, 0x01, program & 0x7F]);
+        dump[6..6 + NAME_LENGTH].copy_from_slice(TUNING_NAME);
+        for key in 0..NB_KEYS {
+            let start = 6 + NAME_LENGTH + 3 * key;
+            dump[start..start + 3].copy_from_slice(&self.get(key));
+        }
+        //the checksum is the exclusive or of the message, without the status
+        dump[BULK_DUMP_LENGTH - 2] = dump[1..BULK_DUMP_LENGTH - 2]
+            .iter()
+            .fold(0, |sum, b| sum ^ b)
+            & 0x7F;
+        dump[BULK_DUMP_LENGTH - 1] = 0xF7;
+        dump
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MtsEvent, Tuning};
+
+    #[test]
+    fn retune() {
+        let mut tuning = Tuning::default();
+        assert_eq!(tuning.pitch(12 + 69), 12.0 + 69.0);
+        //key 69 a quarter tone up, key 60 unchanged
+        let change = [
+            0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x00, 0x02, 69, 69, 0x40, 0x00, 60, 0x7F, 0x7F, 0x7F,
+            0xF7,
+        ];
+        assert_eq!(tuning.read(&change), Some(MtsEvent::Retuned));
+        assert_eq!(tuning.pitch(12 + 69), 12.0 + 69.5);
+        assert_eq!(tuning.pitch(12 + 60), 12.0 + 60.0);
+        assert_eq!(tuning.retuned(), 1);
+
+        let mut copy = Tuning::default();
+        assert_eq!(
+            copy.read(&tuning.bulk_dump(0x7F, 0)),
+            Some(MtsEvent::Retuned)
+        );
+        assert_eq!(copy, tuning);
+        assert_eq!(
+            copy.read(&[0xF0, 0x7E, 0x7F, 0x08, 0x00, 0x03, 0xF7]),
+            Some(MtsEvent::DumpRequested(3))
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::{path::PathBuf, time::Instant};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::storage::{self, StorageError};
+
+///The name of the directory containing the scenes
+const SCENES_DIRECTORY: &str = "scenes";
+///The name of the file containing the last request
+const REQUEST_FILE: &str = "request.json";
+///The minimum delay between two checks of the request file, in milliseconds
+const POLL_INTERVAL_MS: u128 = 250;
+
+///The actions that can be requested to every running tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SceneAction {
+    ///Every tool stores its current configuration in the scene
+    Capture,
+    ///Every tool part of the scene loads its configuration from it
+    Recall,
+}
+
+///A request sent to every running tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneRequest {
+    ///Identify the request, so that it is only handled once
+    pub id: i64,
+    ///The action to perform
+    pub action: SceneAction,
+    ///The name of the scene
+    pub scene: String,
+}
+
+fn scenes_dir() -> Result<PathBuf, StorageError> {
+    storage::sub_dir(SCENES_DIRECTORY)
+}
+
+fn scene_dir(scene: &str) -> Result<PathBuf, StorageError> {
+    let mut dir = scenes_dir()?;
+    dir.push(storage::file_name(scene));
+    Ok(dir)
+}
+
+fn tool_file(scene: &str, tool: &str) -> Result<PathBuf, StorageError> {
+    let mut path = scene_dir(scene)?;
+    path.push(format!("{}.json", storage::file_name(tool)));
+    Ok(path)
+}
+
+fn read_request() -> Option<SceneRequest> {
+    let mut path = scenes_dir().ok()?;
+    path.push(REQUEST_FILE);
+    storage::load(&path).ok()
+}
+
+///Retrieve the name of every known scene, sorted alphabetically
+pub fn list() -> Result<Vec<String>, StorageError> {
+    let mut scenes = Vec::new();
+    for entry in std::fs::read_dir(scenes_dir()?)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            scenes.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    scenes.sort();
+    Ok(scenes)
+}
+
+///Ask every running tool to perform an action on a scene. A scene captures
+/// the configuration of every running tool at once: it is a directory
+/// containing one file per tool. As the tools are separate processes, the
+/// request is written to a file that every tool polls.
+pub fn request(action: SceneAction, scene: &str) -> Result<(), StorageError> {
+    std::fs::create_dir_all(scene_dir(scene)?)?;
+    let mut path = scenes_dir()?;
+    path.push(REQUEST_FILE);
+    let request = SceneRequest {
+        id: chrono::offset::Local::now().timestamp_micros(),
+        action,
+        scene: storage::file_name(scene),
+    };
+    storage::save(&path, &request)
+}
+
+///Store the configuration of a tool in a scene
+pub fn store<T>(scene: &str, tool: &str, configuration: &T) -> Result<(), StorageError>
+where
+    T: Serialize,
+{
+    std::fs::create_dir_all(scene_dir(scene)?)?;
+    storage::save(&tool_file(scene, tool)?, configuration)
+}
+
+///Load the configuration of a tool from a scene. If the tool is not part of
+/// the scene, None is returned.
+pub fn load<T>(scene: &str, tool: &str) -> Result<Option<T>, StorageError>
+where
+    T: DeserializeOwned,
+{
+    let path = tool_file(scene, tool)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(storage::load(&path)?))
+}
+
+///Watch the scene requests, to be used from the user interface of a tool
+pub struct SceneListener {
+    ///The last time the request file was read
+    last_check: Instant,
+    ///The identifier of the last request that was handled
+    last_id: Option<i64>,
+}
+
+impl Default for SceneListener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneListener {
+    ///Create a new listener. Requests emitted before its creation are ignored.
+    pub fn new() -> Self {
+        Self {
+            last_check: Instant::now(),
+            last_id: read_request().map(|r| r.id),
+        }
+    }
+
+    ///Retrieve the request that was not handled yet, if any
+    pub fn poll(&mut self) -> Option<SceneRequest> {
+        if self.last_check.elapsed().as_millis() < POLL_INTERVAL_MS {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let request = read_request()?;
+        if self.last_id == Some(request.id) {
+            return None;
+        }
+        self.last_id = Some(request.id);
+        Some(request)
+    }
+
+    ///Handle the pending request for a tool: on capture, the current
+    /// configuration is stored, on recall, the configuration of the scene is
+    /// returned. Every instance of the tool has its own file in the scene.
+    /// Errors are added to 'messages'.
+    pub fn update<T>(&mut self, tool: &str, current: &T, messages: &mut Vec<String>) -> Option<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let request = self.poll()?;
+        let tool = &storage::instance_name(tool);
+        match request.action {
+            SceneAction::Capture => {
+                if let Err(e) = store(&request.scene, tool, current) {
+                    messages.push(format!("Unable to store scene '{}': {e}", request.scene));
+                }
+                None
+            }
+            SceneAction::Recall => match load(&request.scene, tool) {
+                Ok(conf) => conf,
+                Err(e) => {
+                    messages.push(format!("Unable to recall scene '{}': {e}", request.scene));
+                    None
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    i18n::tr,
+    storage::{self, StorageError},
+};
+
+///The name of the directory containing the sessions
+const SESSIONS_DIRECTORY: &str = "sessions";
+///The delay between the first unsaved change of a configuration and its save
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(5);
+
+fn session_file(tool: &str) -> Result<PathBuf, StorageError> {
+    let mut path = storage::sub_dir(SESSIONS_DIRECTORY)?;
+    path.push(format!(
+        "{}.json",
+        storage::file_name(&storage::instance_name(tool))
+    ));
+    Ok(path)
+}
+
+///Load the configuration of a tool saved during its last session. If the
+/// tool was never used, None is returned.
+pub fn last_session<T>(tool: &str) -> Result<Option<T>, StorageError>
+where
+    T: DeserializeOwned,
+{
+    let path = session_file(tool)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(storage::load(&path)?))
+}
+
+///Save the configuration of a tool a few seconds after it changed, so that
+/// it survives a crash. When the tool starts, the configuration of the last
+/// session is offered for restoration.
+pub struct Autosave<T> {
+    ///The name of the tool, used as file name
+    tool: &'static str,
+    ///The last configuration written to disk
+    saved: Option<T>,
+    ///The moment of the first change that was not saved yet
+    changed: Option<Instant>,
+    ///The configuration of the last session, until it is restored or discarded
+    previous: Option<T>,
+    ///Set when the user asked to restore the last session
+    restore: bool,
+}
+
+impl<T> Autosave<T>
+where
+    T: Serialize + DeserializeOwned + PartialEq + Clone,
+{
+    pub fn new(tool: &'static str) -> Autosave<T> {
+        let previous: Option<T> = last_session(tool).ok().flatten();
+        Autosave {
+            tool,
+            saved: previous.clone(),
+            changed: None,
+            previous,
+            restore: false,
+        }
+    }
+
+    ///Save the current configuration of the tool once it has been left
+    /// unsaved for a few seconds. Nothing is saved while the last session is
+    /// offered, so that it is not lost. If the user asked to restore the last
+    /// session, its configuration is returned. Errors are added to 'messages'.
+    pub fn update(&mut self, current: &T, messages: &mut Vec<String>) -> Option<T> {
+        if self.restore {
+            self.restore = false;
+            return self.previous.take();
+        }
+        if self.previous.as_ref() == Some(current) {
+            self.previous = None;
+        }
+        if self.previous.is_some() || self.saved.as_ref() == Some(current) {
+            self.changed = None;
+            return None;
+        }
+        let changed = *self.changed.get_or_insert_with(Instant::now);
+        if changed.elapsed() >= AUTOSAVE_DELAY {
+            self.changed = None;
+            if let Err(e) = session_file(self.tool).and_then(|path| storage::save(&path, current)) {
+                messages.push(format!("Unable to save the session: {e}"));
+            }
+            self.saved = Some(current.clone());
+        }
+        None
+    }
+
+    ///Offer to restore the configuration of the last session, if any
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.previous.is_none() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label(tr("The last session can be restored."));
+            if ui.button(tr("Restore last session")).clicked() {
+                self.restore = true;
+            }
+            if ui.button(tr("Discard")).clicked() {
+                self.previous = None;
+            }
+        });
+        ui.separator();
+    }
+}
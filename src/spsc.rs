@@ -0,0 +1,256 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{TryRecvError, TrySendError},
+        Arc,
+    },
+};
+
+///The number of messages that can wait in the channels between the user
+/// interfaces and the audio threads
+pub const MESSAGES_CAPACITY: usize = 1024;
+
+///The ring buffer shared by the two ends of a channel
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    ///The number of messages received so far, only changed by the receiver
+    head: AtomicUsize,
+    ///The number of messages sent so far, only changed by the sender
+    tail: AtomicUsize,
+    ///True once one of the ends is dropped
+    disconnected: AtomicBool,
+}
+
+impl<T> Ring<T> {
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index % self.slots.len()].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let tail = *self.tail.get_mut();
+        let mut head = *self.head.get_mut();
+        while head != tail {
+            //the messages between the head and the tail were written and never read
+            unsafe { (*self.slot(head)).assume_init_drop() };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+///The sending end of a channel
+pub struct Sender<T> {
+    ring: Arc<Ring<T>>,
+    ///A sender is not shared between threads, only moved
+    _not_sync: PhantomData<std::cell::Cell<()>>,
+}
+
+///The receiving end of a channel
+pub struct Receiver<T> {
+    ring: Arc<Ring<T>>,
+    ///A receiver is not shared between threads, only moved
+    _not_sync: PhantomData<std::cell::Cell<()>>,
+}
+
+//The sender only writes the slots that the receiver has released, and the
+// receiver only reads the slots that the sender has published: the two ends
+// can be moved to different threads.
+unsafe impl<T: Send> Send for Sender<T> {}
+unsafe impl<T: Send> Send for Receiver<T> {}
+
+///Create a bounded channel between exactly one sender and one receiver,
+/// holding at most 'capacity' messages. Sending and receiving never lock nor
+/// allocate, so that the audio threads can use it: the messages are moved in
+/// a ring buffer allocated here.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let slots = (0..capacity.max(1))
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+    let ring = Arc::new(Ring {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        disconnected: AtomicBool::new(false),
+    });
+    (
+        Sender {
+            ring: ring.clone(),
+            _not_sync: PhantomData,
+        },
+        Receiver {
+            ring,
+            _not_sync: PhantomData,
+        },
+    )
+}
+
+impl<T> Sender<T> {
+    ///Send a message, unless the channel is full or the receiver is gone.
+    /// The message is given back in the error.
+    pub fn send(&self, message: T) -> Result<(), TrySendError<T>> {
+        let ring = &self.ring;
+        if ring.disconnected.load(Ordering::Acquire) {
+            return Err(TrySendError::Disconnected(message));
+        }
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let head = ring.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= ring.slots.len() {
+            return Err(TrySendError::Full(message));
+        }
+        unsafe { (*ring.slot(tail)).write(message) };
+        ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T> Receiver<T> {
+    ///Retrieve the next message, if any. The channel is disconnected once
+    /// the sender is gone and every message it sent was received.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let ring = &self.ring;
+        //read before the tail, so that no message sent before the sender
+        // was dropped is missed
+        let disconnected = ring.disconnected.load(Ordering::Acquire);
+        let head = ring.head.load(Ordering::Relaxed);
+        let tail = ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return Err(if disconnected {
+                TryRecvError::Disconnected
+            } else {
+                TryRecvError::Empty
+            });
+        }
+        let message = unsafe { (*ring.slot(head)).assume_init_read() };
+        ring.head.store(head.wrapping_add(1), Ordering::Release);
+        Ok(message)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.ring.disconnected.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.ring.disconnected.store(true, Ordering::Release);
+    }
+}
+
+///The sending end of a channel, either a [Sender] or a standard one, so that
+/// the helpers of the user interfaces work with both
+pub trait MessageSender<T> {
+    type Error: std::fmt::Display;
+
+    fn send(&self, message: T) -> Result<(), Self::Error>;
+}
+
+impl<T> MessageSender<T> for Sender<T> {
+    type Error = TrySendError<T>;
+
+    fn send(&self, message: T) -> Result<(), Self::Error> {
+        Sender::send(self, message)
+    }
+}
+
+impl<T> MessageSender<T> for std::sync::mpsc::Sender<T> {
+    type Error = std::sync::mpsc::SendError<T>;
+
+    fn send(&self, message: T) -> Result<(), Self::Error> {
+        std::sync::mpsc::Sender::send(self, message)
+    }
+}
+
+///The receiving end of a channel, either a [Receiver] or a standard one
+pub trait MessageReceiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError>;
+}
+
+impl<T> MessageReceiver<T> for Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        Receiver::try_recv(self)
+    }
+}
+
+impl<T> MessageReceiver<T> for std::sync::mpsc::Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        std::sync::mpsc::Receiver::try_recv(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        mpsc::{TryRecvError, TrySendError},
+        Arc,
+    };
+
+    use super::channel;
+
+    #[test]
+    fn bounded_and_ordered() {
+        let (sender, receiver) = channel(2);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+        assert_eq!(sender.send(3), Err(TrySendError::Full(3)));
+        assert_eq!(receiver.try_recv(), Ok(1));
+        sender.send(3).unwrap();
+        assert_eq!(receiver.try_recv(), Ok(2));
+        assert_eq!(receiver.try_recv(), Ok(3));
+
+        //the messages sent before the sender is dropped are still received
+        sender.send(4).unwrap();
+        drop(sender);
+        assert_eq!(receiver.try_recv(), Ok(4));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+
+        let (sender, receiver) = channel(2);
+        drop(receiver);
+        assert_eq!(sender.send(1), Err(TrySendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn across_threads() {
+        let (sender, receiver) = channel(16);
+        let writer = std::thread::spawn(move || {
+            for i in 0..10000 {
+                let mut message = Arc::new(i);
+                loop {
+                    match sender.send(message) {
+                        Ok(()) => break,
+                        Err(TrySendError::Full(m)) => message = m,
+                        Err(e) => panic!("{e}"),
+                    }
+                }
+            }
+        });
+        let mut expected = 0;
+        loop {
+            match receiver.try_recv() {
+                Ok(value) => {
+                    assert_eq!(*value, expected);
+                    assert_eq!(Arc::strong_count(&value), 1);
+                    expected += 1;
+                }
+                Err(TryRecvError::Empty) => std::thread::yield_now(),
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        writer.join().unwrap();
+        assert_eq!(expected, 10000);
+
+        //the messages never received are dropped with the channel
+        let message = Arc::new(0);
+        let (sender, receiver) = channel(4);
+        sender.send(message.clone()).unwrap();
+        drop(sender);
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    fmt::Display,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime},
+};
+
+///The name of the directory containing the files of the suite
+const DIRECTORY_NAME: &str = "rsuite";
+///The minimum delay between two checks of a watched file, in milliseconds
+const WATCH_INTERVAL_MS: u128 = 1000;
+///The longest wait for a file locked by another instance
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+///The delay between two attempts to lock a file
+const LOCK_RETRY: Duration = Duration::from_millis(10);
+///The age of a lock left by an instance that crashed while holding it
+const STALE_LOCK: Duration = Duration::from_secs(10);
+
+///What jack added to the name of the client of this instance, when another
+/// instance of the same tool already runs (e.g. "-01")
+static INSTANCE_SUFFIX: OnceLock<String> = OnceLock::new();
+
+///The errors that can occur while persisting data
+#[derive(Debug)]
+pub enum StorageError {
+    NoConfigurationDirectory,
+    FileError(std::io::Error),
+    FormatError(serde_json::Error),
+    InvalidArchive(String),
+    ///The file is used by another instance of a tool
+    Locked(PathBuf),
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(value: std::io::Error) -> Self {
+        StorageError::FileError(value)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(value: serde_json::Error) -> Self {
+        StorageError::FormatError(value)
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::NoConfigurationDirectory => {
+                write!(f, "Unable to find the user configuration directory")
+            }
+            StorageError::FileError(e) => write!(f, "{e}"),
+            StorageError::FormatError(e) => write!(f, "Invalid content: {e}"),
+            StorageError::InvalidArchive(e) => write!(f, "Invalid archive: {e}"),
+            StorageError::Locked(path) => {
+                write!(f, "{} is used by another instance", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+///Retrieve the directory in which the suite keeps its files. The directory
+/// is created if it does not exist yet.
+pub fn config_dir() -> Result<PathBuf, StorageError> {
+    let mut dir = dirs::config_dir().ok_or(StorageError::NoConfigurationDirectory)?;
+    dir.push(DIRECTORY_NAME);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+///Retrieve a sub-directory of the suite directory, creating it if needed
+pub fn sub_dir(name: &str) -> Result<PathBuf, StorageError> {
+    let mut dir = config_dir()?;
+    dir.push(name);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+///Transform a user provided name into something that can safely be used as file name
+pub fn file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+///Register the name given by jack to the client of this instance of a tool,
+/// which was created with the name 'requested'. When several instances of a
+/// tool run, jack renames the client of the others, and their state is stored
+/// under that name, see [instance_name].
+pub fn set_instance(requested: &str, client_name: &str) {
+    let suffix = client_name.strip_prefix(requested).unwrap_or_default();
+    //a single client per program: only the first one is registered
+    let _ = INSTANCE_SUFFIX.set(suffix.to_string());
+}
+
+///The name under which the state of a tool is stored: the name of the tool
+/// itself, followed by what jack added to the name of the client if another
+/// instance of the tool already runs
+pub fn instance_name(tool: &str) -> String {
+    match INSTANCE_SUFFIX.get() {
+        Some(suffix) => format!("{tool}{suffix}"),
+        None => tool.to_string(),
+    }
+}
+
+///The name of the tool owning a jack client, without what jack added to it
+/// to tell the instances apart, see [instance_name]
+pub fn tool_name(client_name: &str) -> &str {
+    match INSTANCE_SUFFIX.get() {
+        Some(suffix) if !suffix.is_empty() => client_name
+            .strip_suffix(suffix.as_str())
+            .unwrap_or(client_name),
+        _ => client_name,
+    }
+}
+
+///Write a file. It is first written next to its destination and then
+/// renamed, so that readers never see a partially written file. Every
+/// process has its own temporary file, so that two instances writing the
+/// same file at once do not mix their content.
+pub fn write(path: &Path, content: &[u8]) -> Result<(), StorageError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+///A lock on a file, shared by the instances of the tools. The lock is held
+/// while the lock file next to the locked one exists, and released when the
+/// lock is dropped.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    ///Lock a file, waiting for the other instances to release it. A lock
+    /// left by an instance that crashed is taken over. As it may wait for
+    /// seconds, it must never be called from a jack thread: [save] and
+    /// [update] belong to the user interface.
+    pub fn new(path: &Path) -> Result<FileLock, StorageError> {
+        let mut lock = path.as_os_str().to_owned();
+        lock.push(".lock");
+        let lock = PathBuf::from(lock);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&lock) {
+                Ok(_) => return Ok(FileLock { path: lock }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = std::fs::metadata(&lock)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if age.is_some_and(|age| age > STALE_LOCK) {
+                        let _ = std::fs::remove_file(&lock);
+                    } else if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(StorageError::Locked(path.to_path_buf()));
+                    } else {
+                        std::thread::sleep(LOCK_RETRY);
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+///Save a value as json, see [write]. The file is locked during the write,
+/// so that the instances of the tools save one after the other.
+pub fn save<T>(path: &Path, value: &T) -> Result<(), StorageError>
+where
+    T: serde::Serialize,
+{
+    let _lock = FileLock::new(path)?;
+    save_locked(path, value)
+}
+
+fn save_locked<T>(path: &Path, value: &T) -> Result<(), StorageError>
+where
+    T: serde::Serialize,
+{
+    let content = serde_json::to_string_pretty(value)?;
+    write(path, content.as_bytes())
+}
+
+///Load a value saved with [save]
+pub fn load<T>(path: &Path) -> Result<T, StorageError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+///Change a value saved as json, without losing the changes made at the same
+/// time by another instance: the file is locked from the load to the save.
+/// If the file does not exist yet, 'default' is changed.
+pub fn update<T>(path: &Path, default: T, change: impl FnOnce(&mut T)) -> Result<(), StorageError>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let _lock = FileLock::new(path)?;
+    let mut value = if path.exists() { load(path)? } else { default };
+    change(&mut value);
+    save_locked(path, &value)
+}
+
+///Detect the changes made to a file, for instance when it is edited by hand
+/// or synchronised from another computer. The modification time of the file
+/// is polled, as the tools already poll the scene requests.
+pub struct FileWatcher {
+    path: PathBuf,
+    ///The last time the file was checked
+    last_check: Instant,
+    ///The modification time of the file when it was last checked
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(path: PathBuf) -> FileWatcher {
+        let modified = Self::modification_time(&path);
+        FileWatcher {
+            path,
+            last_check: Instant::now(),
+            modified,
+        }
+    }
+
+    fn modification_time(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    ///True if the file was written since the last call. A removed file is not
+    /// reported as a change.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed().as_millis() < WATCH_INTERVAL_MS {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let modified = Self::modification_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{instance_name, update, FileLock, StorageError};
+
+    #[test]
+    fn locked_updates() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("rsuite_lock_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let threads: Vec<_> = (0..4)
+            .map(|index| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    update(&path, BTreeMap::new(), |values| {
+                        values.insert(index, index * 2);
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+        //every change was kept
+        let values: BTreeMap<u32, u32> = super::load(&path).unwrap();
+        assert_eq!(values.len(), 4);
+
+        let lock = FileLock::new(&path).unwrap();
+        assert!(matches!(
+            update(&path, BTreeMap::<u32, u32>::new(), |_| {}),
+            Err(StorageError::Locked(_))
+        ));
+        drop(lock);
+        assert!(update(&path, BTreeMap::<u32, u32>::new(), |_| {}).is_ok());
+        let _ = std::fs::remove_file(&path);
+
+        //without registered client, the tool keeps its name
+        assert_eq!(instance_name("kick"), "kick");
+    }
+}
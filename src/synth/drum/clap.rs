@@ -0,0 +1,723 @@
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    dsp::{StateVariableFilter, SvfCoefficients},
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "clap";
+///How fast the bursts and the tail fade: at the end of a burst, the volume is
+/// e^-SHARPNESS of its start
+const SHARPNESS: f64 = 5.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
+    /// The number of hands clapping, heard as short bursts before the tail
+    bursts: UsizeValueInRange,
+    /// The number of frames between two bursts
+    spacing: UsizeValueInRange,
+    /// The number of frames of the tail, after the last burst
+    decay: UsizeValueInRange,
+    /// The volume of the clap
+    volume: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+    /// The center of the band of noise that is kept
+    tone: FloatValueInRange,
+    /// How narrow the band of noise is
+    resonance: FloatValueInRange,
+}
+
+impl Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
+        Self {
+            bursts: UsizeValueInRange::new(3, 1, 8, "bursts", KeyBoardKey::Parameter),
+            spacing: UsizeValueInRange::new(
+                rate / 100,
+                1,
+                rate / 20,
+                "spacing",
+                KeyBoardKey::ParameterB,
+            )
+            .with_unit(Unit::Frames(rate)),
+            decay: UsizeValueInRange::new(rate / 5, 1, rate, "decay", KeyBoardKey::FadeOutDuration)
+                .with_unit(Unit::Frames(rate)),
+            volume: FloatValueInRange::new(1.0, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+            tone: FloatValueInRange::new(1200.0, 300.0, 8000.0, "tone", KeyBoardKey::FilterCutoff)
+                .with_unit(Unit::Hertz)
+                .logarithmic(),
+            resonance: FloatValueInRange::new(
+                0.4,
+                0.0,
+                0.95,
+                "resonance",
+                KeyBoardKey::FilterResonance,
+            )
+            .with_unit(Unit::Percent),
+        }
+    }
+
+    ///The coefficients of the band-pass filter applied to the noise
+    fn filter(&self, rate: usize) -> SvfCoefficients {
+        SvfCoefficients::new(rate, self.tone.get_value(), self.resonance.get_value())
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for Configuration {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::Float(&mut self.volume),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
+            ConfigurationValue::USize(&mut self.bursts),
+            ConfigurationValue::USize(&mut self.spacing),
+            ConfigurationValue::USize(&mut self.decay),
+            ConfigurationValue::Float(&mut self.tone),
+            ConfigurationValue::Float(&mut self.resonance),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
+        ]
+    }
+}
+
+///The claps being played
+pub(super) struct Voice {
+    /// The sample rate of the audio
+    rate: usize,
+    /// The number of frames that still needs to be played
+    nb_frames_left: Vec<usize>,
+    /// Velocity of the last clap
+    velocity: Vec<f64>,
+    /// The pan of every clap, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// The band-pass filter of the noise of every clap
+    filters: Vec<StateVariableFilter>,
+    /// Choose the pan of the next clap
+    spread: Spread,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        Voice {
+            rate,
+            nb_frames_left: vec![0; 128],
+            velocity: vec![0.0; 128],
+            pan: vec![0.0; 128],
+            filters: vec![StateVariableFilter::new(); 128],
+            spread: Spread::new(),
+            conf: Configuration::new(rate),
+        }
+    }
+
+    ///The number of frames before the tail starts
+    fn tail_start(conf: &Configuration) -> usize {
+        (conf.bursts.get_value().max(1) - 1) * conf.spacing.get_value().max(1)
+    }
+
+    ///The number of frames of a hit
+    fn total_frames(conf: &Configuration) -> usize {
+        Self::tail_start(conf) + conf.decay.get_value().max(1)
+    }
+
+    ///The volume of a clap 'elapsed_frames' after its start: a quickly
+    /// fading burst for every hand but the last one, then the tail
+    fn envelope(conf: &Configuration, elapsed_frames: usize) -> f64 {
+        let tail_start = Self::tail_start(conf);
+        if elapsed_frames < tail_start {
+            let spacing = conf.spacing.get_value().max(1);
+            let position = (elapsed_frames % spacing) as f64 / spacing as f64;
+            (-SHARPNESS * position).exp()
+        } else {
+            let decay = conf.decay.get_value().max(1);
+            let position = (elapsed_frames - tail_start) as f64 / decay as f64;
+            //the linear fade makes sure the tail ends in silence
+            (-SHARPNESS * position).exp() * (1.0 - position)
+        }
+    }
+
+    ///The sample of a clap played at full velocity, 'frames_left' frames
+    /// before its end, 'noise' being the filtered noise
+    fn hit_sample(conf: &Configuration, frames_left: usize, noise: f64) -> f64 {
+        let elapsed_frames = Self::total_frames(conf) - frames_left;
+        noise * Self::envelope(conf, elapsed_frames) * conf.volume.get_value()
+    }
+}
+
+impl DrumVoice for Voice {
+    fn start(&mut self, _note_index: usize, velocity: f64) {
+        let total_frames = Self::total_frames(&self.conf);
+        let velocity = super::velocity::response(
+            velocity,
+            self.conf.velocity_curve.get_value(),
+            self.conf.dynamics.get_value(),
+        );
+        let pan = self.spread.next_pan(
+            self.conf.spread.get_value(),
+            self.conf.spread_alternate.get_value(),
+        );
+        match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => {
+                self.nb_frames_left[index] = total_frames;
+                self.velocity[index] = velocity;
+                self.pan[index] = pan;
+                self.filters[index].reset();
+            }
+            None => {
+                self.nb_frames_left.push(total_frames);
+                self.velocity.push(velocity);
+                self.pan.push(pan);
+                self.filters.push(StateVariableFilter::new());
+            }
+        }
+    }
+
+    fn panic(&mut self) {
+        self.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let total_frames = Self::total_frames(&self.conf);
+        let coefs = self.conf.filter(self.rate);
+        let mut frame = [0.0; 3];
+        for clap_index in 0..self.nb_frames_left.len() {
+            let frames_left = self.nb_frames_left[clap_index];
+            //a shorter decay may have been set while the clap was playing
+            if frames_left == 0 || frames_left > total_frames {
+                self.nb_frames_left[clap_index] = 0;
+                continue;
+            }
+            let noise = self.filters[clap_index].band_pass(1.0 - random::<f64>() * 2.0, &coefs);
+            let value =
+                Self::hit_sample(&self.conf, frames_left, noise) * self.velocity[clap_index];
+
+            let (gain_left, gain_right) = pan_gains(self.pan[clap_index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            self.nb_frames_left[clap_index] = frames_left - 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct Clap {
+    /// The claps being played
+    voice: Voice,
+    /// The midi input to listen to
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The audio output
+    audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToClap, spsc::Receiver<MessageToClap>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToClapUI>,
+    ///If true, the next control will be used as key to start/stop the recording
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
+}
+
+impl Clap {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToClap>,
+        mut messages_out: spsc::Sender<MessageToClapUI>,
+    ) -> Result<Clap, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        let sample_rate = client.sample_rate();
+
+        let voice = Voice::new(sample_rate);
+
+        //initialize the configuration on the UI side
+        Self::send_message(
+            MessageToClapUI::NewConfig(Box::new(voice.conf.clone())),
+            &mut messages_out,
+        );
+
+        Ok(Clap {
+            voice,
+            midi_in: m_in,
+            audio_out: a_out,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+        })
+    }
+
+    fn send_message(msg: MessageToClapUI, messages_out: &mut spsc::Sender<MessageToClapUI>) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Clap {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToClap::ChangeActivationMidiKey(key) => self.key_change = Some(key),
+                MessageToClap::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToClapUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToClap::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToClap::Channel(channel) => self.channel = channel,
+                MessageToClap::Panic => self.voice.panic(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+            match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
+                            Self::send_message(
+                                MessageToClapUI::NewConfig(Box::new(self.voice.conf.clone())),
+                                &mut self.messages_out,
+                            );
+                        }
+                    }
+
+                    if let Some(k) = self.key_change {
+                        self.keyboard.update_key(k, control);
+                        self.key_change = None;
+                        Self::send_message(
+                            MessageToClapUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
+                    }
+                }
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index,
+                    timing: _,
+                    velocity,
+                } => {
+                    crate::utils::statistics::note_played();
+                    self.voice.start(note_index, velocity);
+                }
+                _ => {}
+            }
+        }
+
+        let out = self.audio_out.as_mut_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToClap {
+    ChangeActivationMidiKey(KeyBoardKey),
+    ClearActiviationMidiKey(KeyBoardKey),
+    NewConfig(Box<Configuration>),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToClap {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToClap::ChangeActivationMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToClap::ClearActiviationMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToClapUI {
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
+}
+
+struct ClapUI {
+    messages_in: spsc::Receiver<MessageToClapUI>,
+    message_out: spsc::Sender<MessageToClap>,
+    messages: Vec<String>,
+    current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl ClapUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToClapUI>,
+        messages_out: spsc::Sender<MessageToClap>,
+    ) -> ClapUI {
+        ClapUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            if let Some(config) = &mut self.current_config {
+                ui.menu_button(tr("Settings"), |ui| {
+                    configuration::Configuration::create_menu_keyboard_settings(
+                        config,
+                        ui,
+                        &mut self.message_out,
+                        &mut self.messages,
+                    );
+                    self.shortcuts
+                        .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                    if crate::utils::create_channel_select(
+                        ui,
+                        TOOL_NAME,
+                        &mut self.channel,
+                        &mut self.messages,
+                    ) {
+                        if let Err(e) = self.message_out.send(MessageToClap::Channel(self.channel))
+                        {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                });
+            }
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        if let Some(current_config) = &self.current_config {
+            let mut conf = current_config.clone();
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
+
+            if !self.current_config.as_ref().eq(&Some(&conf)) {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToClap::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToClap::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToClap::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        //read message queue
+        match self.messages_in.try_recv() {
+            Err(e) => match e {
+                std::sync::mpsc::TryRecvError::Empty => {}
+                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(String::from(
+                    "Internal error: lost connection between UI and logic",
+                )),
+            },
+            Ok(v) => match v {
+                MessageToClapUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToClapUI::KeyMapped(key, control) => self.mappings.set(key, control),
+            },
+        }
+    }
+}
+
+impl eframe::App for ClapUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+///Render a single clap to a wav file, without starting the jack client. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_clap(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let velocity = super::velocity::response(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let coefs = conf.filter(options.rate);
+    let mut filter = StateVariableFilter::new();
+    let mut samples: Vec<f64> = (1..=Voice::total_frames(&conf))
+        .rev()
+        .map(|frames_left| {
+            let noise = filter.band_pass(1.0 - random::<f64>() * 2.0, &coefs);
+            Voice::hit_sample(&conf, frames_left, noise) * velocity
+        })
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
+pub fn clap() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the clap and the UI
+    let (send_to_clap, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_clap) = spsc::channel(MESSAGES_CAPACITY);
+
+    let synth = Clap::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Clap",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(ClapUI::new(
+                cc,
+                status,
+                rcv_from_clap,
+                send_to_clap,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Configuration, Voice};
+
+    const RATE: usize = 48000;
+
+    #[test]
+    fn bursts_then_tail() {
+        let conf = Configuration::new(RATE);
+        let total_frames = Voice::total_frames(&conf);
+        //every burst but the first one starts again at full volume
+        let mut restarts = 0;
+        let mut last = Voice::envelope(&conf, 0);
+        assert_eq!(last, 1.0);
+        for elapsed in 1..total_frames {
+            let envelope = Voice::envelope(&conf, elapsed);
+            if envelope > last {
+                restarts += 1;
+            }
+            last = envelope;
+        }
+        assert_eq!(restarts, conf.bursts.get_value() - 1);
+        //the tail ends in silence
+        assert!(last < 1e-3);
+        assert!(Voice::hit_sample(&conf, 1, 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn a_single_burst() {
+        let mut conf = Configuration::new(RATE);
+        conf.bursts.from_midi_value(0);
+        assert_eq!(conf.bursts.get_value(), 1);
+        assert_eq!(Voice::total_frames(&conf), conf.decay.get_value());
+        assert_eq!(
+            Voice::hit_sample(&conf, Voice::total_frames(&conf), 0.5),
+            0.5
+        );
+    }
+}
@@ -0,0 +1,800 @@
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    dsp::{StateVariableFilter, SvfCoefficients},
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "cymbal";
+///The level, relative to the start of a hit, reached at the end of its decay
+const DECAY_END_LEVEL: f64 = 0.001;
+///The frequencies of the square oscillators, relative to the pitch: the
+/// inharmonic ratios of the classic analog cymbals
+const RATIOS: [f64; 6] = [1.0, 1.4827, 1.8003, 2.5460, 2.6303, 3.8968];
+///The direction in which every oscillator is detuned, from -1 to 1
+const DETUNE_OFFSETS: [f64; 6] = [-1.0, 0.6, -0.2, 1.0, -0.6, 0.2];
+///The highest detune of an oscillator, relative to its frequency
+const DETUNE_RANGE: f64 = 0.05;
+
+///The kinds of cymbal, each with its own pitch, tone, noise and decay
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CymbalKind {
+    Crash,
+    Ride,
+}
+
+impl CymbalKind {
+    const ALL: [CymbalKind; 2] = [CymbalKind::Crash, CymbalKind::Ride];
+}
+
+impl std::fmt::Display for CymbalKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CymbalKind::Crash => write!(f, "Crash"),
+            CymbalKind::Ride => write!(f, "Ride"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
+    /// The number of frames to completely stop the sound
+    decay: UsizeValueInRange,
+    /// The volume of the cymbal
+    volume: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+    /// The frequency of the lowest square oscillator
+    pitch: FloatValueInRange,
+    /// How far the oscillators are moved away from their ratios
+    detune: FloatValueInRange,
+    /// The volume of the noise, relative to the oscillators
+    noise: FloatValueInRange,
+    /// The cutoff of the high pass filter, in Hz
+    tone: FloatValueInRange,
+}
+
+impl Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
+        Self::preset(CymbalKind::Crash, rate)
+    }
+
+    ///The configuration of a kind of cymbal
+    fn preset(kind: CymbalKind, rate: usize) -> Configuration {
+        //pitch, cutoff of the filter, noise and duration of the decay
+        let (pitch, tone, noise, decay) = match kind {
+            CymbalKind::Crash => (300.0, 5000.0, 0.6, 1.5),
+            CymbalKind::Ride => (420.0, 7000.0, 0.2, 2.5),
+        };
+        Self {
+            decay: UsizeValueInRange::new(
+                (decay * rate as f64) as usize,
+                1,
+                4 * rate,
+                "decay",
+                KeyBoardKey::FadeOutDuration,
+            )
+            .with_unit(Unit::Frames(rate)),
+            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+            pitch: FloatValueInRange::new(pitch, 100.0, 1000.0, "pitch", KeyBoardKey::Parameter)
+                .with_unit(Unit::Hertz)
+                .logarithmic(),
+            detune: FloatValueInRange::new(0.3, 0.0, 1.0, "detune", KeyBoardKey::ParameterB)
+                .with_unit(Unit::Percent),
+            noise: FloatValueInRange::new(noise, 0.0, 1.0, "noise", KeyBoardKey::Noise)
+                .with_unit(Unit::Percent),
+            tone: FloatValueInRange::new(tone, 1000.0, 15000.0, "tone", KeyBoardKey::FilterCutoff)
+                .with_unit(Unit::Hertz)
+                .logarithmic(),
+        }
+    }
+
+    ///Take the pitch, the tone, the noise and the decay of a kind of cymbal,
+    /// keeping the other values
+    fn apply_preset(&mut self, kind: CymbalKind, rate: usize) {
+        let preset = Self::preset(kind, rate);
+        self.pitch = preset.pitch;
+        self.tone = preset.tone;
+        self.noise = preset.noise;
+        self.decay = preset.decay;
+    }
+
+    ///The coefficients of the high pass filter applied to the oscillators
+    /// and the noise
+    fn filter(&self, rate: usize) -> SvfCoefficients {
+        SvfCoefficients::new(rate, self.tone.get_value(), 0.0)
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for Configuration {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::Float(&mut self.volume),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
+            ConfigurationValue::Float(&mut self.pitch),
+            ConfigurationValue::Float(&mut self.detune),
+            ConfigurationValue::Float(&mut self.noise),
+            ConfigurationValue::Float(&mut self.tone),
+            ConfigurationValue::USize(&mut self.decay),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
+        ]
+    }
+}
+
+///The cymbals being played
+pub(super) struct Voice {
+    /// The sample rate of the audio
+    rate: usize,
+    /// The number of frames that still needs to be played
+    nb_frames_left: Vec<usize>,
+    /// Velocity of the last cymbal
+    velocity: Vec<f64>,
+    /// The pan of every cymbal, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// The high pass filter of every cymbal
+    filters: Vec<StateVariableFilter>,
+    /// Choose the pan of the next cymbal
+    spread: Spread,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        Voice {
+            rate,
+            nb_frames_left: vec![0; 128],
+            velocity: vec![0.0; 128],
+            pan: vec![0.0; 128],
+            filters: vec![StateVariableFilter::new(); 128],
+            spread: Spread::new(),
+            conf: Configuration::new(rate),
+        }
+    }
+
+    ///The number of frames of a hit
+    fn total_frames(conf: &Configuration) -> usize {
+        conf.decay.get_value().max(1)
+    }
+
+    ///The sound of the cymbal before the filter, 'elapsed_frames' after
+    /// the start of the hit, 'noise' being a random value between -1 and 1
+    fn source(conf: &Configuration, rate: usize, elapsed_frames: usize, noise: f64) -> f64 {
+        let time = elapsed_frames as f64 / rate as f64;
+        let detune = conf.detune.get_value() * DETUNE_RANGE;
+        let squares: f64 = RATIOS
+            .iter()
+            .zip(DETUNE_OFFSETS)
+            .map(|(ratio, offset)| {
+                let frequency = conf.pitch.get_value() * ratio * (1.0 + detune * offset);
+                if (time * frequency).fract() < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            })
+            .sum();
+        let noise_level = conf.noise.get_value();
+        squares / RATIOS.len() as f64 * (1.0 - noise_level) + noise * noise_level
+    }
+
+    ///The volume of a cymbal 'frames_left' frames before its end
+    fn envelope(conf: &Configuration, frames_left: usize) -> f64 {
+        let position = frames_left as f64 / Self::total_frames(conf) as f64;
+        //the linear fade makes sure the hit ends in silence
+        DECAY_END_LEVEL.powf(1.0 - position) * position
+    }
+}
+
+impl DrumVoice for Voice {
+    fn start(&mut self, _note_index: usize, velocity: f64) {
+        let total_frames = Self::total_frames(&self.conf);
+        let velocity = super::velocity::response(
+            velocity,
+            self.conf.velocity_curve.get_value(),
+            self.conf.dynamics.get_value(),
+        );
+        let pan = self.spread.next_pan(
+            self.conf.spread.get_value(),
+            self.conf.spread_alternate.get_value(),
+        );
+        match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => {
+                self.nb_frames_left[index] = total_frames;
+                self.velocity[index] = velocity;
+                self.pan[index] = pan;
+                self.filters[index].reset();
+            }
+            None => {
+                self.nb_frames_left.push(total_frames);
+                self.velocity.push(velocity);
+                self.pan.push(pan);
+                self.filters.push(StateVariableFilter::new());
+            }
+        }
+    }
+
+    fn panic(&mut self) {
+        self.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let total_frames = Self::total_frames(&self.conf);
+        let coefs = self.conf.filter(self.rate);
+        let mut frame = [0.0; 3];
+        for cymbal_index in 0..self.nb_frames_left.len() {
+            let frames_left = self.nb_frames_left[cymbal_index];
+            //a shorter decay may have been set while the cymbal was playing
+            if frames_left == 0 || frames_left > total_frames {
+                self.nb_frames_left[cymbal_index] = 0;
+                continue;
+            }
+            let noise = 1.0 - random::<f64>() * 2.0;
+            let source = Self::source(&self.conf, self.rate, total_frames - frames_left, noise);
+            let value = self.filters[cymbal_index].high_pass(source, &coefs)
+                * Self::envelope(&self.conf, frames_left)
+                * self.conf.volume.get_value()
+                * self.velocity[cymbal_index];
+
+            let (gain_left, gain_right) = pan_gains(self.pan[cymbal_index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            self.nb_frames_left[cymbal_index] = frames_left - 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct Cymbal {
+    /// The cymbals being played
+    voice: Voice,
+    /// The midi input to listen to
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The audio output
+    audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToCymbal, spsc::Receiver<MessageToCymbal>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToCymbalUI>,
+    ///If true, the next control will be used as key to start/stop the recording
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
+}
+
+impl Cymbal {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToCymbal>,
+        mut messages_out: spsc::Sender<MessageToCymbalUI>,
+    ) -> Result<Cymbal, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        let sample_rate = client.sample_rate();
+
+        let voice = Voice::new(sample_rate);
+
+        //initialize the configuration on the UI side
+        Self::send_message(
+            MessageToCymbalUI::NewConfig(Box::new(voice.conf.clone())),
+            &mut messages_out,
+        );
+
+        Ok(Cymbal {
+            voice,
+            midi_in: m_in,
+            audio_out: a_out,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+        })
+    }
+
+    fn send_message(msg: MessageToCymbalUI, messages_out: &mut spsc::Sender<MessageToCymbalUI>) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Cymbal {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToCymbal::ChangeActivationMidiKey(key) => self.key_change = Some(key),
+                MessageToCymbal::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToCymbalUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToCymbal::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToCymbal::Preset(kind) => {
+                    self.voice.conf.apply_preset(kind, self.voice.rate);
+                    Self::send_message(
+                        MessageToCymbalUI::NewConfig(Box::new(self.voice.conf.clone())),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToCymbal::Channel(channel) => self.channel = channel,
+                MessageToCymbal::Panic => self.voice.panic(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+            match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
+                            Self::send_message(
+                                MessageToCymbalUI::NewConfig(Box::new(self.voice.conf.clone())),
+                                &mut self.messages_out,
+                            );
+                        }
+                    }
+
+                    if let Some(k) = self.key_change {
+                        self.keyboard.update_key(k, control);
+                        self.key_change = None;
+                        Self::send_message(
+                            MessageToCymbalUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
+                    }
+                }
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index,
+                    timing: _,
+                    velocity,
+                } => {
+                    crate::utils::statistics::note_played();
+                    self.voice.start(note_index, velocity);
+                }
+                _ => {}
+            }
+        }
+
+        let out = self.audio_out.as_mut_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToCymbal {
+    ChangeActivationMidiKey(KeyBoardKey),
+    ClearActiviationMidiKey(KeyBoardKey),
+    NewConfig(Box<Configuration>),
+    ///Take the pitch, the tone, the noise and the decay of a kind of cymbal
+    Preset(CymbalKind),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToCymbal {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToCymbal::ChangeActivationMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToCymbal::ClearActiviationMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToCymbalUI {
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
+}
+
+struct CymbalUI {
+    messages_in: spsc::Receiver<MessageToCymbalUI>,
+    message_out: spsc::Sender<MessageToCymbal>,
+    messages: Vec<String>,
+    current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl CymbalUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToCymbalUI>,
+        messages_out: spsc::Sender<MessageToCymbal>,
+    ) -> CymbalUI {
+        CymbalUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            if let Some(config) = &mut self.current_config {
+                ui.menu_button(tr("Settings"), |ui| {
+                    configuration::Configuration::create_menu_keyboard_settings(
+                        config,
+                        ui,
+                        &mut self.message_out,
+                        &mut self.messages,
+                    );
+                    self.shortcuts
+                        .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                    if crate::utils::create_channel_select(
+                        ui,
+                        TOOL_NAME,
+                        &mut self.channel,
+                        &mut self.messages,
+                    ) {
+                        if let Err(e) = self
+                            .message_out
+                            .send(MessageToCymbal::Channel(self.channel))
+                        {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                });
+            }
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        if let Some(current_config) = &self.current_config {
+            ui.horizontal(|ui| {
+                ui.label(tr("Preset: "));
+                for kind in CymbalKind::ALL {
+                    if ui.button(tr(&kind.to_string())).clicked() {
+                        if let Err(e) = self.message_out.send(MessageToCymbal::Preset(kind)) {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                }
+            });
+
+            let mut conf = current_config.clone();
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
+
+            if !self.current_config.as_ref().eq(&Some(&conf)) {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToCymbal::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToCymbal::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToCymbal::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        //read message queue
+        match self.messages_in.try_recv() {
+            Err(e) => match e {
+                std::sync::mpsc::TryRecvError::Empty => {}
+                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(String::from(
+                    "Internal error: lost connection between UI and logic",
+                )),
+            },
+            Ok(v) => match v {
+                MessageToCymbalUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToCymbalUI::KeyMapped(key, control) => self.mappings.set(key, control),
+            },
+        }
+    }
+}
+
+impl eframe::App for CymbalUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+///Render a single cymbal to a wav file, without starting the jack client. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_cymbal(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let velocity = super::velocity::response(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let coefs = conf.filter(options.rate);
+    let mut filter = StateVariableFilter::new();
+    let total_frames = Voice::total_frames(&conf);
+    let mut samples: Vec<f64> = (1..=total_frames)
+        .rev()
+        .map(|frames_left| {
+            let noise = 1.0 - random::<f64>() * 2.0;
+            let source = Voice::source(&conf, options.rate, total_frames - frames_left, noise);
+            filter.high_pass(source, &coefs)
+                * Voice::envelope(&conf, frames_left)
+                * conf.volume.get_value()
+                * velocity
+        })
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
+pub fn cymbal() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the cymbal and the UI
+    let (send_to_cymbal, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_cymbal) = spsc::channel(MESSAGES_CAPACITY);
+
+    let synth = Cymbal::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Cymbal",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(CymbalUI::new(
+                cc,
+                status,
+                rcv_from_cymbal,
+                send_to_cymbal,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Configuration, CymbalKind, Voice};
+
+    const RATE: usize = 48000;
+
+    #[test]
+    fn presets() {
+        let crash = Configuration::preset(CymbalKind::Crash, RATE);
+        let ride = Configuration::preset(CymbalKind::Ride, RATE);
+        assert!(ride.decay.get_value() > crash.decay.get_value());
+        assert!(ride.noise.get_value() < crash.noise.get_value());
+
+        let mut conf = crash.clone();
+        conf.detune.from_midi_value(0);
+        conf.apply_preset(CymbalKind::Ride, RATE);
+        assert_eq!(conf.tone, ride.tone);
+        assert_eq!(conf.detune.get_value(), 0.0);
+    }
+
+    #[test]
+    fn squares_and_noise() {
+        let mut conf = Configuration::new(RATE);
+        conf.noise.from_midi_value(0);
+        //all the squares start high
+        assert_eq!(Voice::source(&conf, RATE, 0, 1.0), 1.0);
+        for elapsed in 0..RATE / 10 {
+            assert!(Voice::source(&conf, RATE, elapsed, 1.0).abs() <= 1.0);
+        }
+        //only the noise is left
+        conf.noise.from_midi_value(127);
+        assert!((Voice::source(&conf, RATE, 100, 0.25) - 0.25).abs() < 1e-2);
+    }
+
+    #[test]
+    fn hit_ends_silent() {
+        let conf = Configuration::new(RATE);
+        let total_frames = Voice::total_frames(&conf);
+        assert_eq!(Voice::envelope(&conf, total_frames), 1.0);
+        assert!(Voice::envelope(&conf, total_frames / 2) < 0.05);
+        assert!(Voice::envelope(&conf, 1) < 1e-6);
+    }
+}
@@ -0,0 +1,1039 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    dsp::{db_to_gain, gain_to_db, Bus, CompressorSettings},
+    i18n::tr,
+    midiinput::{MidiInput, MidiNote},
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    clap, cymbal, hihat, kick,
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    snare, tom,
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "drumkit";
+///The highest choke group of a drum
+const MAX_CHOKE_GROUP: usize = 8;
+///The time a drum takes to stop once choked, in seconds
+const CHOKE_TIME: f64 = 0.01;
+
+///The drums of the kit
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Drum {
+    Kick,
+    Snare,
+    HiHat,
+    Tom,
+    Clap,
+    Cymbal,
+}
+
+impl Drum {
+    const ALL: [Drum; 6] = [
+        Drum::Kick,
+        Drum::Snare,
+        Drum::HiHat,
+        Drum::Tom,
+        Drum::Clap,
+        Drum::Cymbal,
+    ];
+
+    ///The position of the drum in [Drum::ALL]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::fmt::Display for Drum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drum::Kick => write!(f, "Kick"),
+            Drum::Snare => write!(f, "Snare"),
+            Drum::HiHat => write!(f, "Hi-hat"),
+            Drum::Tom => write!(f, "Tom"),
+            Drum::Clap => write!(f, "Clap"),
+            Drum::Cymbal => write!(f, "Cymbal"),
+        }
+    }
+}
+
+///The midi notes playing the drums of the kit. The notes of the hi-hat are
+/// part of its own configuration, as it has one for the open and one for the
+/// closed hat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Notes {
+    kick: UsizeValueInRange,
+    snare: UsizeValueInRange,
+    tom: UsizeValueInRange,
+    clap: UsizeValueInRange,
+    cymbal: UsizeValueInRange,
+}
+
+impl Notes {
+    ///The notes of the general midi drums
+    fn new() -> Notes {
+        let note =
+            |value, name| UsizeValueInRange::new(value, 0, 127, name, KeyBoardKey::Parameter);
+        Notes {
+            kick: note(36, "kick note"),
+            snare: note(38, "snare note"),
+            tom: note(45, "tom note"),
+            clap: note(39, "clap note"),
+            cymbal: note(49, "cymbal note"),
+        }
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for Notes {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::USize(&mut self.kick),
+            ConfigurationValue::USize(&mut self.snare),
+            ConfigurationValue::USize(&mut self.tom),
+            ConfigurationValue::USize(&mut self.clap),
+            ConfigurationValue::USize(&mut self.cymbal),
+        ]
+    }
+}
+
+///The bus summing the drums of the kit: a compressor gluing the hits
+/// together, then the level of the outputs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BusConfiguration {
+    compressor: BoolValue,
+    threshold: FloatValueInRange,
+    ratio: FloatValueInRange,
+    attack: FloatValueInRange,
+    release: FloatValueInRange,
+    level: FloatValueInRange,
+}
+
+impl BusConfiguration {
+    ///The bus leaving the kit untouched, until the compressor is turned on
+    fn new() -> BusConfiguration {
+        let parameter = |value, start, end, name| {
+            FloatValueInRange::new(value, start, end, name, KeyBoardKey::Parameter)
+        };
+        BusConfiguration {
+            compressor: BoolValue::new(false, "bus compressor", KeyBoardKey::Parameter),
+            threshold: parameter(db_to_gain(-12.0), db_to_gain(-40.0), 1.0, "threshold")
+                .with_unit(Unit::Decibels),
+            ratio: parameter(4.0, 1.0, 20.0, "ratio"),
+            attack: parameter(0.01, 0.0, 0.1, "attack").with_unit(Unit::Milliseconds),
+            release: parameter(0.1, 0.01, 1.0, "release").with_unit(Unit::Milliseconds),
+            level: FloatValueInRange::new(
+                1.0,
+                db_to_gain(-24.0),
+                db_to_gain(12.0),
+                "output level",
+                KeyBoardKey::Gain,
+            )
+            .with_unit(Unit::Decibels),
+        }
+    }
+
+    fn settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            threshold: gain_to_db(self.threshold.get_value()),
+            ratio: self.ratio.get_value(),
+            attack: self.attack.get_value(),
+            release: self.release.get_value(),
+            makeup: 0.0,
+        }
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for BusConfiguration {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::Bool(&mut self.compressor),
+            ConfigurationValue::Float(&mut self.threshold),
+            ConfigurationValue::Float(&mut self.ratio),
+            ConfigurationValue::Float(&mut self.attack),
+            ConfigurationValue::Float(&mut self.release),
+            ConfigurationValue::Float(&mut self.level),
+        ]
+    }
+}
+
+///The choke groups of the drums of the kit: a hit stops the other drums of
+/// its group still ringing, as an open hi-hat cut by a cymbal. The drums of
+/// the group 0 never choke nor are choked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChokeGroups {
+    kick: UsizeValueInRange,
+    snare: UsizeValueInRange,
+    hihat: UsizeValueInRange,
+    tom: UsizeValueInRange,
+    clap: UsizeValueInRange,
+    cymbal: UsizeValueInRange,
+}
+
+impl ChokeGroups {
+    fn new() -> ChokeGroups {
+        let group =
+            |name| UsizeValueInRange::new(0, 0, MAX_CHOKE_GROUP, name, KeyBoardKey::Parameter);
+        ChokeGroups {
+            kick: group("kick group"),
+            snare: group("snare group"),
+            hihat: group("hi-hat group"),
+            tom: group("tom group"),
+            clap: group("clap group"),
+            cymbal: group("cymbal group"),
+        }
+    }
+
+    ///The group of every drum, in the order of [Drum::ALL]
+    fn groups(&self) -> [usize; 6] {
+        [
+            self.kick.get_value(),
+            self.snare.get_value(),
+            self.hihat.get_value(),
+            self.tom.get_value(),
+            self.clap.get_value(),
+            self.cymbal.get_value(),
+        ]
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for ChokeGroups {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::USize(&mut self.kick),
+            ConfigurationValue::USize(&mut self.snare),
+            ConfigurationValue::USize(&mut self.hihat),
+            ConfigurationValue::USize(&mut self.tom),
+            ConfigurationValue::USize(&mut self.clap),
+            ConfigurationValue::USize(&mut self.cymbal),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Configuration {
+    kick: kick::Configuration,
+    snare: snare::Configuration,
+    hihat: hihat::Configuration,
+    tom: tom::Configuration,
+    clap: clap::Configuration,
+    cymbal: cymbal::Configuration,
+    /// The midi notes playing the drums
+    notes: Notes,
+    /// The compressor and the level of the summed drums
+    bus: BusConfiguration,
+    /// The drums stopping each other
+    #[serde(default = "ChokeGroups::new")]
+    chokes: ChokeGroups,
+}
+
+impl Configuration {
+    fn new(rate: usize) -> Configuration {
+        Configuration {
+            kick: kick::Configuration::new(rate),
+            snare: snare::Configuration::new(rate),
+            hihat: hihat::Configuration::new(rate),
+            tom: tom::Configuration::new(rate),
+            clap: clap::Configuration::new(rate),
+            cymbal: cymbal::Configuration::new(rate),
+            notes: Notes::new(),
+            bus: BusConfiguration::new(),
+            chokes: ChokeGroups::new(),
+        }
+    }
+
+    ///The drum played by a note, if any. When several drums share the same
+    /// note, the first one of the kit plays it.
+    fn route(&self, note_index: usize) -> Option<Drum> {
+        let note = MidiNote::from_index(note_index)?.number() as usize;
+        let notes = [
+            (Drum::Kick, &self.notes.kick),
+            (Drum::Snare, &self.notes.snare),
+            (Drum::Tom, &self.notes.tom),
+            (Drum::Clap, &self.notes.clap),
+            (Drum::Cymbal, &self.notes.cymbal),
+        ];
+        match notes.iter().find(|(_, value)| value.get_value() == note) {
+            Some((drum, _)) => Some(*drum),
+            None => self.hihat.hat(note_index).map(|_| Drum::HiHat),
+        }
+    }
+
+    ///Apply a midi control key to the configuration of a drum. If the
+    /// configuration changes, true will be returned. False otherwise
+    fn apply_midi(&mut self, drum: Drum, key: KeyBoardKey, value: u8) -> bool {
+        match drum {
+            Drum::Kick => configuration::Configuration::apply_midi(&mut self.kick, key, value),
+            Drum::Snare => configuration::Configuration::apply_midi(&mut self.snare, key, value),
+            Drum::HiHat => configuration::Configuration::apply_midi(&mut self.hihat, key, value),
+            Drum::Tom => configuration::Configuration::apply_midi(&mut self.tom, key, value),
+            Drum::Clap => configuration::Configuration::apply_midi(&mut self.clap, key, value),
+            Drum::Cymbal => configuration::Configuration::apply_midi(&mut self.cymbal, key, value),
+        }
+    }
+}
+
+///The voices of every drum of the kit
+struct Voices {
+    kick: kick::Voice,
+    snare: snare::Voice,
+    hihat: hihat::Voice,
+    tom: tom::Voice,
+    clap: clap::Voice,
+    cymbal: cymbal::Voice,
+    /// The compressor and the level of the mix
+    bus: Bus,
+    /// The choke group of every drum, in the order of [Drum::ALL]
+    groups: [usize; 6],
+    /// The number of frames left before every choked drum stops
+    chokes: [Option<usize>; 6],
+    /// The number of frames a choked drum takes to stop
+    choke_frames: usize,
+}
+
+impl Voices {
+    fn new(rate: usize) -> Voices {
+        Voices {
+            kick: kick::Voice::new(rate),
+            snare: snare::Voice::new(rate),
+            hihat: hihat::Voice::new(rate),
+            tom: tom::Voice::new(rate),
+            clap: clap::Voice::new(rate),
+            cymbal: cymbal::Voice::new(rate),
+            bus: Bus::new(rate),
+            groups: [0; 6],
+            chokes: [None; 6],
+            choke_frames: ((CHOKE_TIME * rate as f64) as usize).max(1),
+        }
+    }
+
+    fn get(&mut self, drum: Drum) -> &mut dyn DrumVoice {
+        match drum {
+            Drum::Kick => &mut self.kick,
+            Drum::Snare => &mut self.snare,
+            Drum::HiHat => &mut self.hihat,
+            Drum::Tom => &mut self.tom,
+            Drum::Clap => &mut self.clap,
+            Drum::Cymbal => &mut self.cymbal,
+        }
+    }
+
+    ///Give the configuration of the kit to the voices
+    fn configure(&mut self, configuration: &Configuration) {
+        self.kick.conf = configuration.kick.clone();
+        self.snare.conf = configuration.snare.clone();
+        self.hihat.conf = configuration.hihat.clone();
+        self.tom.conf = configuration.tom.clone();
+        self.clap.conf = configuration.clap.clone();
+        self.cymbal.conf = configuration.cymbal.clone();
+        let bus = &configuration.bus;
+        self.bus
+            .set_compressor(bus.compressor.get_value(), bus.settings());
+        self.bus.set_level(bus.level.get_value());
+        self.groups = configuration.chokes.groups();
+    }
+
+    ///Start a hit of a drum, choking the other drums of its group
+    fn start(&mut self, drum: Drum, note_index: usize, velocity: f64) {
+        let group = self.groups[drum.index()];
+        if group != 0 {
+            for other in Drum::ALL {
+                if other != drum
+                    && self.groups[other.index()] == group
+                    && self.chokes[other.index()].is_none()
+                    && self.get(other).playing()
+                {
+                    self.chokes[other.index()] = Some(self.choke_frames);
+                }
+            }
+        }
+        //a drum hit again while it is being choked stops at once
+        if self.chokes[drum.index()].take().is_some() {
+            self.get(drum).panic();
+        }
+        self.get(drum).start(note_index, velocity);
+    }
+
+    ///Stop every drum at once
+    fn panic(&mut self) {
+        for drum in Drum::ALL {
+            self.get(drum).panic();
+        }
+        self.chokes = [None; 6];
+    }
+
+    ///The next frame of the kit: every drum is bent below its own ceiling
+    /// before being mixed with the others, then the mix goes through the bus
+    fn next_frame(&mut self) -> [f64; 3] {
+        let mut mix = [0.0; 3];
+        for drum in Drum::ALL {
+            let choke = self.chokes[drum.index()];
+            let choke_frames = self.choke_frames;
+            let voice = self.get(drum);
+            let ceiling = voice.ceiling();
+            //a choked drum fades out quickly
+            let gain = choke.map_or(1.0, |left| left as f64 / choke_frames as f64);
+            for (mixed, value) in mix.iter_mut().zip(voice.next_frame()) {
+                *mixed += super::output::clip(value, ceiling) * gain;
+            }
+            self.chokes[drum.index()] = match choke {
+                Some(left) if left > 1 => Some(left - 1),
+                Some(_) => {
+                    voice.panic();
+                    None
+                }
+                None => None,
+            };
+        }
+        self.bus.process(mix)
+    }
+}
+
+struct DrumKit {
+    /// The drums being played
+    voices: Voices,
+    /// The configuration of the kit, sent to the UI
+    conf: Configuration,
+    /// The drum the midi controls are applied to
+    selected: Drum,
+    /// The midi input to listen to
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The audio output
+    audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToDrumKit, spsc::Receiver<MessageToDrumKit>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToDrumKitUI>,
+    ///If true, the next control will be used as key to start/stop the recording
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
+}
+
+impl DrumKit {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToDrumKit>,
+        mut messages_out: spsc::Sender<MessageToDrumKitUI>,
+    ) -> Result<DrumKit, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        let sample_rate = client.sample_rate();
+
+        let conf = Configuration::new(sample_rate);
+        let mut voices = Voices::new(sample_rate);
+        voices.configure(&conf);
+
+        //initialize the configuration on the UI side
+        Self::send_message(
+            MessageToDrumKitUI::NewConfig(Box::new(conf.clone())),
+            &mut messages_out,
+        );
+
+        Ok(DrumKit {
+            voices,
+            conf,
+            selected: Drum::Kick,
+            midi_in: m_in,
+            audio_out: a_out,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+        })
+    }
+
+    fn send_message(msg: MessageToDrumKitUI, messages_out: &mut spsc::Sender<MessageToDrumKitUI>) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for DrumKit {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToDrumKit::ChangeActivationMidiKey(key) => self.key_change = Some(key),
+                MessageToDrumKit::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToDrumKitUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToDrumKit::NewConfig(configuration) => {
+                    self.voices.configure(&configuration);
+                    self.conf = *configuration;
+                }
+                MessageToDrumKit::Select(drum) => self.selected = drum,
+                MessageToDrumKit::Channel(channel) => self.channel = channel,
+                MessageToDrumKit::Panic => self.voices.panic(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+            match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                        if self.conf.apply_midi(self.selected, key, value) {
+                            self.voices.configure(&self.conf);
+                            Self::send_message(
+                                MessageToDrumKitUI::NewConfig(Box::new(self.conf.clone())),
+                                &mut self.messages_out,
+                            );
+                        }
+                    }
+
+                    if let Some(k) = self.key_change {
+                        self.keyboard.update_key(k, control);
+                        self.key_change = None;
+                        Self::send_message(
+                            MessageToDrumKitUI::KeyMapped(
+                                k,
+                                Some(MidiControl { channel, control }),
+                            ),
+                            &mut self.messages_out,
+                        );
+                    }
+                }
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index,
+                    timing: _,
+                    velocity,
+                } => {
+                    if let Some(drum) = self.conf.route(note_index) {
+                        crate::utils::statistics::note_played();
+                        self.voices.start(drum, note_index, velocity);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let out = self.audio_out.as_mut_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voices.next_frame(), None);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToDrumKit {
+    ChangeActivationMidiKey(KeyBoardKey),
+    ClearActiviationMidiKey(KeyBoardKey),
+    NewConfig(Box<Configuration>),
+    ///Apply the midi controls to another drum
+    Select(Drum),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToDrumKit {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToDrumKit::ChangeActivationMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToDrumKit::ClearActiviationMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToDrumKitUI {
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
+}
+
+struct DrumKitUI {
+    messages_in: spsc::Receiver<MessageToDrumKitUI>,
+    message_out: spsc::Sender<MessageToDrumKit>,
+    messages: Vec<String>,
+    current_config: Option<Configuration>,
+    ///The tab shown, whose drum receives the midi controls
+    selected: Drum,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl DrumKitUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToDrumKitUI>,
+        messages_out: spsc::Sender<MessageToDrumKit>,
+    ) -> DrumKitUI {
+        DrumKitUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            current_config: None,
+            selected: Drum::Kick,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            if let Some(config) = &mut self.current_config {
+                ui.menu_button(tr("Settings"), |ui| {
+                    let sender = &mut self.message_out;
+                    let messages = &mut self.messages;
+                    match self.selected {
+                        Drum::Kick => configuration::Configuration::create_menu_keyboard_settings(
+                            &mut config.kick,
+                            ui,
+                            sender,
+                            messages,
+                        ),
+                        Drum::Snare => configuration::Configuration::create_menu_keyboard_settings(
+                            &mut config.snare,
+                            ui,
+                            sender,
+                            messages,
+                        ),
+                        Drum::HiHat => configuration::Configuration::create_menu_keyboard_settings(
+                            &mut config.hihat,
+                            ui,
+                            sender,
+                            messages,
+                        ),
+                        Drum::Tom => configuration::Configuration::create_menu_keyboard_settings(
+                            &mut config.tom,
+                            ui,
+                            sender,
+                            messages,
+                        ),
+                        Drum::Clap => configuration::Configuration::create_menu_keyboard_settings(
+                            &mut config.clap,
+                            ui,
+                            sender,
+                            messages,
+                        ),
+                        Drum::Cymbal => {
+                            configuration::Configuration::create_menu_keyboard_settings(
+                                &mut config.cymbal,
+                                ui,
+                                sender,
+                                messages,
+                            )
+                        }
+                    }
+                    self.shortcuts
+                        .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                    if crate::utils::create_channel_select(
+                        ui,
+                        TOOL_NAME,
+                        &mut self.channel,
+                        &mut self.messages,
+                    ) {
+                        let channel = MessageToDrumKit::Channel(self.channel);
+                        if let Err(e) = self.message_out.send(channel) {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                });
+            }
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        if let Some(current_config) = &self.current_config {
+            let mut conf = current_config.clone();
+
+            egui::CollapsingHeader::new(tr("Notes")).show(ui, |ui| {
+                configuration::Configuration::draw(&mut conf.notes, ui);
+            });
+            egui::CollapsingHeader::new(tr("Bus")).show(ui, |ui| {
+                configuration::Configuration::draw(&mut conf.bus, ui);
+            });
+            egui::CollapsingHeader::new(tr("Choke groups")).show(ui, |ui| {
+                configuration::Configuration::draw(&mut conf.chokes, ui);
+            });
+
+            let selected = self.selected;
+            ui.horizontal(|ui| {
+                for drum in Drum::ALL {
+                    ui.selectable_value(&mut self.selected, drum, tr(&drum.to_string()));
+                }
+            });
+            if selected != self.selected {
+                self.send(MessageToDrumKit::Select(self.selected));
+            }
+
+            let sender = &mut self.message_out;
+            let mappings = &self.mappings;
+            let messages = &mut self.messages;
+            match self.selected {
+                Drum::Kick => {
+                    conf.kick.velocity_response.draw(ui);
+                    configuration::Configuration::draw_with_mappings(
+                        &mut conf.kick,
+                        ui,
+                        mappings,
+                        sender,
+                        messages,
+                    )
+                }
+                Drum::Snare => {
+                    conf.snare.velocity_response.draw(ui);
+                    configuration::Configuration::draw_with_mappings(
+                        &mut conf.snare,
+                        ui,
+                        mappings,
+                        sender,
+                        messages,
+                    )
+                }
+                Drum::HiHat => configuration::Configuration::draw_with_mappings(
+                    &mut conf.hihat,
+                    ui,
+                    mappings,
+                    sender,
+                    messages,
+                ),
+                Drum::Tom => configuration::Configuration::draw_with_mappings(
+                    &mut conf.tom,
+                    ui,
+                    mappings,
+                    sender,
+                    messages,
+                ),
+                Drum::Clap => configuration::Configuration::draw_with_mappings(
+                    &mut conf.clap,
+                    ui,
+                    mappings,
+                    sender,
+                    messages,
+                ),
+                Drum::Cymbal => configuration::Configuration::draw_with_mappings(
+                    &mut conf.cymbal,
+                    ui,
+                    mappings,
+                    sender,
+                    messages,
+                ),
+            }
+
+            if !self.current_config.as_ref().eq(&Some(&conf)) {
+                self.send(MessageToDrumKit::NewConfig(Box::new(conf.clone())));
+                self.current_config = Some(conf);
+            }
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send(&mut self, message: MessageToDrumKit) {
+        if let Err(e) = self.message_out.send(message) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                self.send(MessageToDrumKit::Panic);
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                self.send(MessageToDrumKit::NewConfig(Box::new(conf.clone())));
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        //read message queue
+        match self.messages_in.try_recv() {
+            Err(e) => match e {
+                std::sync::mpsc::TryRecvError::Empty => {}
+                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(String::from(
+                    "Internal error: lost connection between UI and logic",
+                )),
+            },
+            Ok(v) => match v {
+                MessageToDrumKitUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToDrumKitUI::KeyMapped(key, control) => self.mappings.set(key, control),
+            },
+        }
+    }
+}
+
+impl eframe::App for DrumKitUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+///Render the drum of the kit played by the note to a wav file, without
+/// starting the jack client. The configuration of the last session is used,
+/// unless another one is given.
+pub fn render_drumkit(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let note_index = MidiNote::raw_index(options.note);
+    let drum = match conf.route(note_index) {
+        Some(drum) => drum,
+        None => {
+            return Err(RenderError::InvalidArgument(format!(
+                "No drum of the kit is played by the note {}",
+                options.note
+            )))
+        }
+    };
+    let mut voices = Voices::new(options.rate);
+    voices.configure(&conf);
+    let voice = voices.get(drum);
+    voice.start(note_index, options.note_velocity());
+    let ceiling = voice.ceiling();
+    let mut samples = Vec::new();
+    while voice.playing() {
+        let [mono, _, _] = voice.next_frame();
+        samples.push(super::output::clip(mono, ceiling));
+    }
+    options.write(&samples)
+}
+
+pub fn drumkit() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the kit and the UI
+    let (send_to_kit, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_kit) = spsc::channel(MESSAGES_CAPACITY);
+
+    let synth = DrumKit::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Drum kit",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(400.0, 720.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(DrumKitUI::new(
+                cc,
+                status,
+                rcv_from_kit,
+                send_to_kit,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        configuration::{BoolValue, FloatValueInRange, UsizeValueInRange},
+        dsp::db_to_gain,
+        synth::hardware::KeyBoardKey,
+    };
+
+    use super::{Configuration, Drum, Voices, MAX_CHOKE_GROUP};
+
+    const RATE: usize = 48000;
+
+    #[test]
+    fn general_midi_routing() {
+        let conf = Configuration::new(RATE);
+        //the note indexes are one octave above the midi notes
+        assert_eq!(conf.route(36 + 12), Some(Drum::Kick));
+        assert_eq!(conf.route(38 + 12), Some(Drum::Snare));
+        assert_eq!(conf.route(42 + 12), Some(Drum::HiHat));
+        assert_eq!(conf.route(46 + 12), Some(Drum::HiHat));
+        assert_eq!(conf.route(45 + 12), Some(Drum::Tom));
+        assert_eq!(conf.route(39 + 12), Some(Drum::Clap));
+        assert_eq!(conf.route(49 + 12), Some(Drum::Cymbal));
+        assert_eq!(conf.route(60 + 12), None);
+        assert_eq!(conf.route(3), None);
+    }
+
+    #[test]
+    fn only_the_routed_drum_plays() {
+        let conf = Configuration::new(RATE);
+        let mut voices = Voices::new(RATE);
+        voices.configure(&conf);
+        voices.get(Drum::Snare).start(38 + 12, 0.5);
+        for drum in Drum::ALL {
+            assert_eq!(voices.get(drum).playing(), drum == Drum::Snare);
+        }
+        voices.get(Drum::Snare).panic();
+        assert!(!voices.get(Drum::Snare).playing());
+    }
+
+    ///The loudest left output of a kick hit through the bus
+    fn kick_peak(conf: &Configuration) -> f64 {
+        let mut voices = Voices::new(RATE);
+        voices.configure(conf);
+        voices.get(Drum::Kick).start(36 + 12, 1.0);
+        (0..RATE / 10)
+            .map(|_| voices.next_frame()[1].abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn bus_compression() {
+        let mut conf = Configuration::new(RATE);
+        let untouched = kick_peak(&conf);
+        conf.bus.compressor = BoolValue::new(true, "bus compressor", KeyBoardKey::Parameter);
+        conf.bus.threshold = FloatValueInRange::new(
+            db_to_gain(-30.0),
+            0.0,
+            1.0,
+            "threshold",
+            KeyBoardKey::Parameter,
+        );
+        let compressed = kick_peak(&conf);
+        assert!(compressed < untouched * 0.9, "{compressed} {untouched}");
+        //the level applies after the compressor
+        conf.bus.level = FloatValueInRange::new(0.5, 0.0, 1.0, "level", KeyBoardKey::Gain);
+        assert!((kick_peak(&conf) - compressed * 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn choke_groups() {
+        let group = |value| {
+            UsizeValueInRange::new(value, 0, MAX_CHOKE_GROUP, "group", KeyBoardKey::Parameter)
+        };
+        let mut conf = Configuration::new(RATE);
+        conf.chokes.hihat = group(1);
+        conf.chokes.cymbal = group(1);
+        let mut voices = Voices::new(RATE);
+        voices.configure(&conf);
+        voices.start(Drum::Cymbal, 49 + 12, 1.0);
+        voices.start(Drum::Snare, 38 + 12, 1.0);
+        voices.next_frame();
+        //the hi-hat cuts the cymbal, but not the snare outside of the group
+        voices.start(Drum::HiHat, 42 + 12, 1.0);
+        for _ in 0..voices.choke_frames {
+            voices.next_frame();
+        }
+        assert!(!voices.get(Drum::Cymbal).playing());
+        assert!(voices.get(Drum::HiHat).playing());
+        assert!(voices.get(Drum::Snare).playing());
+    }
+}
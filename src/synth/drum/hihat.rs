@@ -0,0 +1,788 @@
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    i18n::tr,
+    midiinput::{MidiInput, MidiNote},
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "hihat";
+///The level, relative to the start of a hit, reached at the end of its decay
+const DECAY_END_LEVEL: f64 = 0.001;
+///The time an open hat takes to stop once choked, in seconds
+const CHOKE_TIME: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
+    /// The midi note playing the closed hat
+    closed_note: UsizeValueInRange,
+    /// The midi note playing the open hat
+    open_note: UsizeValueInRange,
+    /// The number of frames of the decay of the closed hat
+    closed_decay: UsizeValueInRange,
+    /// The number of frames of the decay of the open hat
+    open_decay: UsizeValueInRange,
+    /// The cutoff of the high pass filter of the noise, in Hz
+    tone: FloatValueInRange,
+    /// The volume of the hi-hat
+    volume: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+}
+
+impl Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
+        Self {
+            closed_note: UsizeValueInRange::new(42, 0, 127, "closed note", KeyBoardKey::Parameter),
+            open_note: UsizeValueInRange::new(46, 0, 127, "open note", KeyBoardKey::ParameterB),
+            closed_decay: UsizeValueInRange::new(
+                rate / 20,
+                0,
+                rate / 2,
+                "closed decay",
+                KeyBoardKey::FadeOutDuration,
+            )
+            .with_unit(Unit::Frames(rate)),
+            open_decay: UsizeValueInRange::new(
+                rate / 2,
+                0,
+                rate * 2,
+                "open decay",
+                KeyBoardKey::Duration,
+            )
+            .with_unit(Unit::Frames(rate)),
+            tone: FloatValueInRange::new(
+                7000.0,
+                2000.0,
+                16000.0,
+                "tone",
+                KeyBoardKey::FilterCutoff,
+            )
+            .with_unit(Unit::Hertz)
+            .logarithmic(),
+            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+        }
+    }
+
+    ///The hat played by a note, if any: true for the open hat
+    pub(super) fn hat(&self, note_index: usize) -> Option<bool> {
+        let note = MidiNote::from_index(note_index)?.number() as usize;
+        if note == self.open_note.get_value() {
+            Some(true)
+        } else if note == self.closed_note.get_value() {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    ///The number of frames of the decay of a hat
+    fn decay(&self, open: bool) -> usize {
+        if open {
+            self.open_decay.get_value()
+        } else {
+            self.closed_decay.get_value()
+        }
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for Configuration {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::USize(&mut self.closed_note),
+            ConfigurationValue::USize(&mut self.open_note),
+            ConfigurationValue::USize(&mut self.closed_decay),
+            ConfigurationValue::USize(&mut self.open_decay),
+            ConfigurationValue::Float(&mut self.tone),
+            ConfigurationValue::Float(&mut self.volume),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
+        ]
+    }
+}
+
+///The hits being played
+struct Hits {
+    /// The number of frames that still needs to be played
+    nb_frames_left: Vec<usize>,
+    /// Velocity of every hit
+    velocity: Vec<f64>,
+    /// The pan of every hit, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// True if the hit is an open hat, that can be choked
+    open: Vec<bool>,
+    /// The current level of the envelope of every hit
+    envelope: Vec<f64>,
+    /// The factor applied to the envelope of every hit at every frame
+    coefficient: Vec<f64>,
+    /// The value before the last value pushed to the buffer
+    last_input: Vec<f64>,
+    /// The last value pushed to the buffer
+    last_output: Vec<f64>,
+}
+
+impl Hits {
+    fn new(capacity: usize) -> Hits {
+        Hits {
+            nb_frames_left: vec![0; capacity],
+            velocity: vec![0.0; capacity],
+            pan: vec![0.0; capacity],
+            open: vec![false; capacity],
+            envelope: vec![0.0; capacity],
+            coefficient: vec![0.0; capacity],
+            last_input: vec![0.0; capacity],
+            last_output: vec![0.0; capacity],
+        }
+    }
+
+    ///Stop the open hats still ringing within 'frames' frames, as when the
+    /// hi-hat is closed
+    fn choke(&mut self, frames: usize) {
+        let coefficient = Voice::decay_coefficient(frames);
+        for index in 0..self.nb_frames_left.len() {
+            if self.open[index] && self.nb_frames_left[index] > frames {
+                self.nb_frames_left[index] = frames;
+                self.coefficient[index] = coefficient;
+            }
+        }
+    }
+
+    ///Start a new hit lasting 'frames' frames
+    fn start(&mut self, open: bool, velocity: f64, pan: f64, frames: usize) {
+        let index = match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => index,
+            None => {
+                self.nb_frames_left.push(0);
+                self.velocity.push(0.0);
+                self.pan.push(0.0);
+                self.open.push(false);
+                self.envelope.push(0.0);
+                self.coefficient.push(0.0);
+                self.last_input.push(0.0);
+                self.last_output.push(0.0);
+                self.nb_frames_left.len() - 1
+            }
+        };
+        self.nb_frames_left[index] = frames;
+        self.velocity[index] = velocity;
+        self.pan[index] = pan;
+        self.open[index] = open;
+        self.envelope[index] = 1.0;
+        self.coefficient[index] = Voice::decay_coefficient(frames);
+        self.last_input[index] = 0.0;
+        self.last_output[index] = 0.0;
+    }
+}
+
+///The hi-hats being played
+pub(super) struct Voice {
+    /// The sample rate of the audio
+    rate: usize,
+    /// The hits being played
+    hits: Hits,
+    /// Choose the pan of the next hit
+    spread: Spread,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        Voice {
+            rate,
+            hits: Hits::new(128),
+            spread: Spread::new(),
+            conf: Configuration::new(rate),
+        }
+    }
+
+    ///The factor applied to the envelope at every frame, so that it reaches
+    /// [DECAY_END_LEVEL] after 'frames' frames
+    fn decay_coefficient(frames: usize) -> f64 {
+        if frames == 0 {
+            0.0
+        } else {
+            DECAY_END_LEVEL.powf(1.0 / frames as f64)
+        }
+    }
+
+    ///The alpha value of the high pass filter for a cutoff frequency
+    fn alpha(rate: usize, cutoff: f64) -> f64 {
+        let rc = 1.0 / (2.0 * std::f64::consts::PI * cutoff);
+        rc / (rc + 1.0 / rate as f64)
+    }
+
+    ///The next sample of the high-passed noise of a hit
+    fn noise(alpha: f64, last_input: &mut f64, last_output: &mut f64) -> f64 {
+        let x = 1.0 - (random::<f64>() * 2.0);
+        let y = alpha * (*last_output + x - *last_input);
+        *last_input = x;
+        *last_output = y;
+        y
+    }
+}
+
+impl DrumVoice for Voice {
+    ///Only the notes of the closed and of the open hat are played
+    fn start(&mut self, note_index: usize, velocity: f64) {
+        if let Some(open) = self.conf.hat(note_index) {
+            let velocity = super::velocity::response(
+                velocity,
+                self.conf.velocity_curve.get_value(),
+                self.conf.dynamics.get_value(),
+            );
+            let pan = self.spread.next_pan(
+                self.conf.spread.get_value(),
+                self.conf.spread_alternate.get_value(),
+            );
+            //a new hit closes the hi-hat
+            self.hits.choke((CHOKE_TIME * self.rate as f64) as usize);
+            self.hits.start(open, velocity, pan, self.conf.decay(open));
+        }
+    }
+
+    fn panic(&mut self) {
+        self.hits.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.hits.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let alpha = Self::alpha(self.rate, self.conf.tone.get_value());
+        let volume = self.conf.volume.get_value();
+        let mut frame = [0.0; 3];
+        let hits = &mut self.hits;
+        for index in 0..hits.nb_frames_left.len() {
+            if hits.nb_frames_left[index] == 0 {
+                continue;
+            }
+            let y = Self::noise(
+                alpha,
+                &mut hits.last_input[index],
+                &mut hits.last_output[index],
+            );
+            let value = y * hits.velocity[index] * hits.envelope[index] * volume;
+
+            let (gain_left, gain_right) = pan_gains(hits.pan[index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            hits.envelope[index] *= hits.coefficient[index];
+            hits.nb_frames_left[index] -= 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct HiHat {
+    /// The hi-hats being played
+    voice: Voice,
+    /// The midi input to listen to
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The audio output
+    audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToHiHat, spsc::Receiver<MessageToHiHat>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToHiHatUI>,
+    ///If set, the next control will be mapped to this key
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
+}
+
+impl HiHat {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToHiHat>,
+        mut messages_out: spsc::Sender<MessageToHiHatUI>,
+    ) -> Result<HiHat, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        let sample_rate = client.sample_rate();
+
+        let voice = Voice::new(sample_rate);
+
+        //initialize the configuration on the UI side
+        Self::send_message(
+            MessageToHiHatUI::NewConfig(Box::new(voice.conf.clone())),
+            &mut messages_out,
+        );
+
+        Ok(HiHat {
+            voice,
+            midi_in: m_in,
+            audio_out: a_out,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+        })
+    }
+
+    fn send_message(msg: MessageToHiHatUI, messages_out: &mut spsc::Sender<MessageToHiHatUI>) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for HiHat {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToHiHat::ChangeActivationMidiKey(key) => self.key_change = Some(key),
+                MessageToHiHat::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToHiHatUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToHiHat::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToHiHat::Channel(channel) => self.channel = channel,
+                MessageToHiHat::Panic => self.voice.panic(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+            match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
+                            Self::send_message(
+                                MessageToHiHatUI::NewConfig(Box::new(self.voice.conf.clone())),
+                                &mut self.messages_out,
+                            );
+                        }
+                    }
+
+                    if let Some(k) = self.key_change {
+                        self.keyboard.update_key(k, control);
+                        self.key_change = None;
+                        Self::send_message(
+                            MessageToHiHatUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
+                    }
+                }
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index,
+                    timing: _,
+                    velocity,
+                } => {
+                    crate::utils::statistics::note_played();
+                    self.voice.start(note_index, velocity);
+                }
+                _ => {}
+            }
+        }
+
+        let out = self.audio_out.as_mut_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToHiHat {
+    ChangeActivationMidiKey(KeyBoardKey),
+    ClearActiviationMidiKey(KeyBoardKey),
+    NewConfig(Box<Configuration>),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToHiHat {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToHiHat::ChangeActivationMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToHiHat::ClearActiviationMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToHiHatUI {
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
+}
+
+struct HiHatUI {
+    messages_in: spsc::Receiver<MessageToHiHatUI>,
+    message_out: spsc::Sender<MessageToHiHat>,
+    messages: Vec<String>,
+    current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl HiHatUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToHiHatUI>,
+        messages_out: spsc::Sender<MessageToHiHat>,
+    ) -> HiHatUI {
+        HiHatUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                if let Some(conf) = &mut self.current_config {
+                    configuration::Configuration::create_menu_keyboard_settings(
+                        conf,
+                        ui,
+                        &mut self.message_out,
+                        &mut self.messages,
+                    );
+                }
+                self.shortcuts
+                    .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                if crate::utils::create_channel_select(
+                    ui,
+                    TOOL_NAME,
+                    &mut self.channel,
+                    &mut self.messages,
+                ) {
+                    if let Err(e) = self.message_out.send(MessageToHiHat::Channel(self.channel)) {
+                        self.messages.push(format!("Internal error: {e}"));
+                    }
+                }
+            });
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        if let Some(current_config) = &self.current_config {
+            let mut conf = current_config.clone();
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
+
+            if !self.current_config.as_ref().eq(&Some(&conf)) {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToHiHat::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToHiHat::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToHiHat::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToHiHatUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToHiHatUI::KeyMapped(key, control) => self.mappings.set(key, control),
+            }
+        }
+    }
+}
+
+impl eframe::App for HiHatUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+///Render a single hit to a wav file, without starting the jack client: the
+/// open hat if the note is the one of the open hat, else the closed hat. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_hihat(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let open = conf.hat(MidiNote::raw_index(options.note)).unwrap_or(false);
+    let velocity = super::velocity::response(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let alpha = Voice::alpha(options.rate, conf.tone.get_value());
+    let coefficient = Voice::decay_coefficient(conf.decay(open));
+    let volume = conf.volume.get_value();
+    let mut envelope = 1.0;
+    let mut last_input = 0.0;
+    let mut last_output = 0.0;
+    let mut samples: Vec<f64> = (0..conf.decay(open))
+        .map(|_| {
+            let y = Voice::noise(alpha, &mut last_input, &mut last_output);
+            let value = y * velocity * envelope * volume;
+            envelope *= coefficient;
+            value
+        })
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
+pub fn hihat() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the synth and the UI
+    let (send_to_synth, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_synth) = spsc::channel(MESSAGES_CAPACITY);
+
+    let synth = HiHat::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Hi-hat",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(HiHatUI::new(
+                cc,
+                status,
+                rcv_from_synth,
+                send_to_synth,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Configuration, Hits, Voice, DECAY_END_LEVEL};
+
+    #[test]
+    fn closed_and_open_notes() {
+        let conf = Configuration::new(48000);
+        //the notes of the general midi drum map, as index of the note
+        assert_eq!(conf.hat(42 + 12), Some(false));
+        assert_eq!(conf.hat(46 + 12), Some(true));
+        assert_eq!(conf.hat(38 + 12), None);
+        assert!(conf.decay(true) > conf.decay(false));
+    }
+
+    #[test]
+    fn decay_reaches_its_end_level() {
+        let coefficient = Voice::decay_coefficient(1000);
+        let end = coefficient.powi(1000);
+        assert!((end - DECAY_END_LEVEL).abs() < 1e-9);
+        assert_eq!(Voice::decay_coefficient(0), 0.0);
+    }
+
+    #[test]
+    fn choke_stops_the_open_hats() {
+        let mut hits = Hits::new(2);
+        hits.start(true, 0.5, 0.0, 48000);
+        hits.start(false, 0.5, 0.0, 2400);
+        hits.choke(480);
+        assert_eq!(hits.nb_frames_left, vec![480, 2400]);
+        assert!(hits.coefficient[0] < hits.coefficient[1]);
+        //the closed hats are not choked
+        hits.choke(10);
+        assert_eq!(hits.nb_frames_left, vec![10, 2400]);
+    }
+}
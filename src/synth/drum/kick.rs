@@ -1,19 +1,40 @@
 use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     configuration::{
-        self, ConfigurationValue, FloatValueInRange, UsizeValueInRange, WaveTypeValue,
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange, WaveTypeValue,
     },
+    i18n::tr,
     midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
     synth::{
         hardware::{HardWare, KeyBoardKey},
         wavetype::WaveType,
     },
-    utils::{CommonError, ConnectionType, KeyBoardKeySetter},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    trigger::Trigger,
+    velocity::VelocityResponse,
+    voice::DrumVoice,
 };
 
-#[derive(Debug, Clone, PartialEq)]
-struct Configuration {
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "kick";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
     /// The number of frames needed to reach full volume
     attack: UsizeValueInRange,
     /// The number of frames to completely stop the sound
@@ -22,16 +43,44 @@ struct Configuration {
     duration: UsizeValueInRange,
     /// The volume of the kick
     volume: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes changes the volume
+    #[serde(default)]
+    pub(super) velocity_response: VelocityResponse,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+    /// The notes playing the drum
+    #[serde(default)]
+    trigger: Trigger,
     /// The start frequency
     start_freq: FloatValueInRange,
     /// The end frequency
     end_freq: FloatValueInRange,
+    /// The steepness of the sweep from the start to the end frequency: the
+    /// higher, the faster the punch turns into the boom
+    #[serde(default = "default_sweep_curve")]
+    sweep_curve: FloatValueInRange,
     /// The type of wave that we want to use
     wave_type: WaveTypeValue,
 }
 
 impl Configuration {
-    pub fn new(rate: usize) -> Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
         Self {
             wave_type: WaveTypeValue::new("base wave", KeyBoardKey::WaveSelection),
             attack: UsizeValueInRange::new(50, 0, 128, "attack", KeyBoardKey::FadeInDuration),
@@ -42,68 +91,233 @@ impl Configuration {
                 2 * rate,
                 "duration",
                 KeyBoardKey::Duration,
-            ),
-            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain),
+            )
+            .with_unit(Unit::Frames(rate)),
+            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_response: VelocityResponse::Linear,
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+            trigger: Trigger::Any,
             start_freq: FloatValueInRange::new(
                 350.0,
-                0.0,
+                10.0,
                 8.0 * 440.0,
                 "start freq",
                 KeyBoardKey::Parameter,
-            ),
+            )
+            .with_unit(Unit::Hertz)
+            .logarithmic(),
             end_freq: FloatValueInRange::new(
                 16.0,
-                0.0,
+                10.0,
                 8.0 * 440.0,
                 "end freq",
                 KeyBoardKey::Parameter,
-            ),
+            )
+            .with_unit(Unit::Hertz)
+            .logarithmic(),
+            sweep_curve: default_sweep_curve(),
         }
     }
 }
 
+///The steepness of the sweep of the kicks saved without it
+fn default_sweep_curve() -> FloatValueInRange {
+    FloatValueInRange::new(
+        5.0,
+        0.5,
+        20.0,
+        "sweep curve",
+        KeyBoardKey::PitchEnvelopeDecay,
+    )
+}
+
 impl<'c> configuration::Configuration<'c> for Configuration {
     fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
         vec![
             ConfigurationValue::WaveType(&mut self.wave_type),
             ConfigurationValue::USize(&mut self.duration),
             ConfigurationValue::Float(&mut self.volume),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
             ConfigurationValue::Float(&mut self.start_freq),
             ConfigurationValue::Float(&mut self.end_freq),
+            ConfigurationValue::Float(&mut self.sweep_curve),
             ConfigurationValue::USize(&mut self.attack),
             ConfigurationValue::USize(&mut self.decay),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
         ]
     }
 }
 
-struct Kicker {
+///The kicks being played
+pub(super) struct Voice {
     /// The duration of a single audio frame
     frame_t: f64,
     /// The number of frames that still needs to be played
     nb_frames_left: Vec<usize>,
     /// Velocity of the last kick
     velocity: Vec<f64>,
+    /// The pan of every kick, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// Choose the pan of the next kick
+    spread: Spread,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        Voice {
+            frame_t: 1.0 / rate as f64,
+            nb_frames_left: vec![0; 128],
+            velocity: vec![0.0; 128],
+            pan: vec![0.0; 128],
+            spread: Spread::new(),
+            conf: Configuration::new(rate),
+        }
+    }
+
+    ///The number of frames of a hit
+    fn total_frames(conf: &Configuration) -> usize {
+        conf.decay.get_value() + conf.duration.get_value() + conf.attack.get_value()
+    }
+
+    ///The position in the sweep from the start (1) to the end frequency (0),
+    /// once 'fraction_passed' of the hit is played
+    fn sweep(fraction_passed: f64, curve: f64) -> f64 {
+        f64::exp(-curve * fraction_passed)
+    }
+
+    ///The sample of a kick played at full velocity, 'frames_left' frames before its end
+    fn hit_sample(conf: &Configuration, frame_t: f64, frames_left: usize) -> f64 {
+        let total_frames = Self::total_frames(conf);
+        let volume = if frames_left > conf.decay.get_value() + conf.duration.get_value() {
+            let v = total_frames - frames_left;
+            (v as f64 / conf.attack.get_value() as f64) * conf.volume.get_value()
+        } else if frames_left > conf.decay.get_value() {
+            conf.volume.get_value()
+        } else {
+            (frames_left as f64 / conf.decay.get_value() as f64) * conf.volume.get_value()
+        };
+
+        let ellapsed_frames = total_frames - frames_left;
+        assert!(ellapsed_frames < total_frames);
+        let fraction_passed = ellapsed_frames as f64 / (total_frames) as f64;
+        let time = ellapsed_frames as f64 * frame_t;
+        let non_linear_param = Self::sweep(fraction_passed, conf.sweep_curve.get_value());
+        assert!(non_linear_param < 1.00001 && non_linear_param > 0.0);
+        let freq = conf.end_freq.get_value()
+            + non_linear_param * (conf.start_freq.get_value() - conf.end_freq.get_value());
+
+        let x = freq * time * 2.0 * std::f64::consts::PI;
+
+        WaveType::Sin.compute(x) * volume
+    }
+}
+
+impl DrumVoice for Voice {
+    fn start(&mut self, _note_index: usize, velocity: f64) {
+        let total_frames = Self::total_frames(&self.conf);
+        let velocity = self.conf.velocity_response.apply(
+            velocity,
+            self.conf.velocity_curve.get_value(),
+            self.conf.dynamics.get_value(),
+        );
+        let pan = self.spread.next_pan(
+            self.conf.spread.get_value(),
+            self.conf.spread_alternate.get_value(),
+        );
+        match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => {
+                self.nb_frames_left[index] = total_frames;
+                self.velocity[index] = velocity;
+                self.pan[index] = pan;
+            }
+            None => {
+                self.nb_frames_left.push(total_frames);
+                self.velocity.push(velocity);
+                self.pan.push(pan);
+            }
+        }
+    }
+
+    fn panic(&mut self) {
+        self.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let total_frames = Self::total_frames(&self.conf);
+        let mut frame = [0.0; 3];
+        for kick_index in 0..self.nb_frames_left.len() {
+            let frames_left = self.nb_frames_left[kick_index];
+            //a shorter kick may have been set while the kick was playing
+            if frames_left == 0 || frames_left > total_frames {
+                self.nb_frames_left[kick_index] = 0;
+                continue;
+            }
+            let value =
+                Self::hit_sample(&self.conf, self.frame_t, frames_left) * self.velocity[kick_index];
+
+            let (gain_left, gain_right) = pan_gains(self.pan[kick_index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            self.nb_frames_left[kick_index] = frames_left - 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct Kicker {
+    /// The kicks being played
+    voice: Voice,
     /// The midi input to listen to
     midi_in: jack::Port<jack::MidiIn>,
     /// The audio output
     audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
     //The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToKicker>,
+    messages_in: ChannelWatchdog<MessageToKicker, spsc::Receiver<MessageToKicker>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToKickerUI>,
+    messages_out: spsc::Sender<MessageToKickerUI>,
     ///If true, the next control will be used as key to start/stop the recording
     key_change: Option<KeyBoardKey>,
+    ///If true, the next note received becomes the trigger of the drum
+    learn_trigger: bool,
     ///The keyboard events we are listening to
     keyboard: HardWare,
-    /// The configuration
-    conf: Configuration,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
 }
 
 impl Kicker {
     pub fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToKicker>,
-        mut messages_out: std::sync::mpsc::Sender<MessageToKickerUI>,
+        messages_in: spsc::Receiver<MessageToKicker>,
+        mut messages_out: spsc::Sender<MessageToKickerUI>,
     ) -> Result<Kicker, CommonError> {
         let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
             Ok(v) => v,
@@ -113,41 +327,42 @@ impl Kicker {
             Ok(v) => v,
             Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
         };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
 
         let sample_rate = client.sample_rate();
 
-        let current_config = Configuration::new(sample_rate);
+        let voice = Voice::new(sample_rate);
 
         //initialize the configuration on the UI side
         Self::send_message(
-            MessageToKickerUI::NewConfig(current_config.clone()),
+            MessageToKickerUI::NewConfig(Box::new(voice.conf.clone())),
             &mut messages_out,
         );
 
-        let mut frames = Vec::with_capacity(128);
-        let mut vel = Vec::with_capacity(128);
-        for _index in 0..128 {
-            frames.push(0);
-            vel.push(0.0);
-        }
         Ok(Kicker {
-            frame_t: 1.0 / sample_rate as f64,
-            nb_frames_left: frames,
-            velocity: vel,
+            voice,
             midi_in: m_in,
             audio_out: a_out,
-            messages_in,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
             messages_out,
             key_change: None,
+            learn_trigger: false,
             keyboard: HardWare::new(),
-            conf: current_config,
+            channel: MidiChannel::load(TOOL_NAME),
         })
     }
 
-    fn send_message(
-        msg: MessageToKickerUI,
-        messages_out: &mut std::sync::mpsc::Sender<MessageToKickerUI>,
-    ) {
+    fn send_message(msg: MessageToKickerUI, messages_out: &mut spsc::Sender<MessageToKickerUI>) {
         if let Err(e) = messages_out.send(msg) {
             eprintln!("Internal error: {e}");
         }
@@ -156,31 +371,42 @@ impl Kicker {
 
 impl jack::ProcessHandler for Kicker {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToKicker::ChangeActivationMidiKey(key) => self.key_change = Some(key),
-                MessageToKicker::ClearActiviationMidiKey(key) => self.keyboard.clear_key(key),
-                MessageToKicker::NewConfig(configuration) => self.conf = configuration,
+                MessageToKicker::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToKickerUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToKicker::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToKicker::Channel(channel) => self.channel = channel,
+                MessageToKicker::LearnTrigger => self.learn_trigger = true,
+                MessageToKicker::Panic => self.voice.panic(),
             }
         }
 
-        let total_frames = self.conf.decay.get_value()
-            + self.conf.duration.get_value()
-            + self.conf.attack.get_value();
-
-        let show_p = self.midi_in.iter(ps);
-        for e in show_p {
+        for e in self.midi_in.iter(ps) {
             let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
             match midi {
                 MidiInput::Controller {
-                    channel: _,
+                    channel,
                     control,
                     value,
                 } => {
                     if let Some(key) = self.keyboard.get_keyboard_key(control) {
-                        if configuration::Configuration::apply_midi(&mut self.conf, key, value) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
                             Self::send_message(
-                                MessageToKickerUI::NewConfig(self.conf.clone()),
+                                MessageToKickerUI::NewConfig(Box::new(self.voice.conf.clone())),
                                 &mut self.messages_out,
                             );
                         }
@@ -189,26 +415,29 @@ impl jack::ProcessHandler for Kicker {
                     if let Some(k) = self.key_change {
                         self.keyboard.update_key(k, control);
                         self.key_change = None;
+                        Self::send_message(
+                            MessageToKickerUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
                     }
                 }
                 MidiInput::NoteStart {
                     channel: _,
-                    note_index: _,
+                    note_index,
                     timing: _,
                     velocity,
                 } => {
-                    let mut added = false;
-                    for index in 0..self.nb_frames_left.len() {
-                        if self.nb_frames_left[index] == 0 {
-                            self.nb_frames_left[index] = total_frames;
-                            self.velocity[index] = velocity;
-                            added = true;
-                            break;
-                        }
+                    if self.learn_trigger {
+                        self.learn_trigger = false;
+                        self.voice.conf.trigger = Trigger::learned(note_index);
+                        Self::send_message(
+                            MessageToKickerUI::NewConfig(Box::new(self.voice.conf.clone())),
+                            &mut self.messages_out,
+                        );
                     }
-                    if !added {
-                        self.nb_frames_left.push(total_frames);
-                        self.velocity.push(velocity);
+                    if self.voice.conf.trigger.accepts(note_index) {
+                        crate::utils::statistics::note_played();
+                        self.voice.start(note_index, velocity);
                     }
                 }
                 _ => {}
@@ -216,50 +445,15 @@ impl jack::ProcessHandler for Kicker {
         }
 
         let out = self.audio_out.as_mut_slice(ps);
-
-        assert_eq!(self.nb_frames_left.len(), self.velocity.len());
-        for output in out.iter_mut() {
-            let mut v: f64 = 0.0;
-            for kick_index in 0..self.nb_frames_left.len() {
-                assert!(kick_index < self.nb_frames_left.len());
-                assert!(kick_index < self.velocity.len());
-                if self.nb_frames_left[kick_index] == 0 {
-                    continue;
-                }
-                let volume = if self.nb_frames_left[kick_index]
-                    > self.conf.decay.get_value() + self.conf.duration.get_value()
-                {
-                    let v = total_frames - self.nb_frames_left[kick_index];
-                    (v as f64 / self.conf.attack.get_value() as f64) * self.conf.volume.get_value()
-                } else if self.nb_frames_left[kick_index] > self.conf.decay.get_value() {
-                    self.conf.volume.get_value()
-                } else {
-                    let v = self.nb_frames_left[kick_index];
-                    (v as f64 / self.conf.decay.get_value() as f64) * self.conf.volume.get_value()
-                };
-
-                assert!(total_frames >= self.nb_frames_left[kick_index]);
-                let ellapsed_frames = total_frames - self.nb_frames_left[kick_index];
-                assert!(ellapsed_frames < total_frames);
-                let fraction_passed = ellapsed_frames as f64 / (total_frames) as f64;
-                let time = ellapsed_frames as f64 * self.frame_t;
-                let non_linear_param = f64::exp(-5.0 * fraction_passed);
-                assert!(non_linear_param < 1.00001 && non_linear_param > 0.0);
-                let freq = self.conf.end_freq.get_value()
-                    + non_linear_param
-                        * (self.conf.start_freq.get_value() - self.conf.end_freq.get_value());
-
-                let x = freq * time * 2.0 * std::f64::consts::PI;
-
-                let y = WaveType::Sin.compute(x);
-
-                let value = y * self.velocity[kick_index] * volume;
-
-                v += value;
-
-                self.nb_frames_left[kick_index] = self.nb_frames_left[kick_index] - 1;
-            }
-            *output = v as f32;
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
         }
 
         jack::Control::Continue
@@ -270,7 +464,12 @@ impl jack::ProcessHandler for Kicker {
 enum MessageToKicker {
     ChangeActivationMidiKey(KeyBoardKey),
     ClearActiviationMidiKey(KeyBoardKey),
-    NewConfig(Configuration),
+    NewConfig(Box<Configuration>),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    ///Use the next note received as trigger
+    LearnTrigger,
+    Panic,
 }
 
 impl From<KeyBoardKeySetter> for MessageToKicker {
@@ -284,40 +483,75 @@ impl From<KeyBoardKeySetter> for MessageToKicker {
 
 #[derive(Debug)]
 enum MessageToKickerUI {
-    NewConfig(Configuration),
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
 }
 
 struct KickerUI {
-    messages_in: std::sync::mpsc::Receiver<MessageToKickerUI>,
-    message_out: std::sync::mpsc::Sender<MessageToKicker>,
+    messages_in: spsc::Receiver<MessageToKickerUI>,
+    message_out: spsc::Sender<MessageToKicker>,
     messages: Vec<String>,
     current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl KickerUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToKickerUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToKicker>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToKickerUI>,
+        messages_out: spsc::Sender<MessageToKicker>,
     ) -> KickerUI {
         KickerUI {
+            status,
             messages_in,
             message_out: messages_out,
             messages: Vec::new(),
             current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
         }
     }
 
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
             if let Some(config) = &mut self.current_config {
-                ui.menu_button("Settings", |ui| {
+                ui.menu_button(tr("Settings"), |ui| {
                     configuration::Configuration::create_menu_keyboard_settings(
                         config,
                         ui,
                         &mut self.message_out,
                         &mut self.messages,
                     );
+                    self.shortcuts
+                        .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                    if crate::utils::create_channel_select(
+                        ui,
+                        TOOL_NAME,
+                        &mut self.channel,
+                        &mut self.messages,
+                    ) {
+                        if let Err(e) = self
+                            .message_out
+                            .send(MessageToKicker::Channel(self.channel))
+                        {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
                 });
             }
             crate::utils::common_menu_luncher(ui, &mut self.messages);
@@ -325,15 +559,31 @@ impl KickerUI {
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
         if let Some(current_config) = &self.current_config {
             let mut conf = current_config.clone();
 
-            configuration::Configuration::draw(&mut conf, ui);
+            if conf.trigger.draw(ui) {
+                if let Err(e) = self.message_out.send(MessageToKicker::LearnTrigger) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+                self.messages
+                    .push(String::from(tr("Play the note of the drum")));
+            }
+            conf.velocity_response.draw(ui);
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
 
             if !self.current_config.as_ref().eq(&Some(&conf)) {
                 if let Err(e) = self
                     .message_out
-                    .send(MessageToKicker::NewConfig(conf.clone()))
+                    .send(MessageToKicker::NewConfig(Box::new(conf.clone())))
                 {
                     self.messages
                         .push(format!("Error while sending new conf: {e}"));
@@ -344,6 +594,38 @@ impl KickerUI {
         crate::utils::show_logs(ui, &mut self.messages);
     }
 
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToKicker::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToKicker::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
     fn read_input(&mut self) {
         //read message queue
         match self.messages_in.try_recv() {
@@ -354,7 +636,8 @@ impl KickerUI {
                 )),
             },
             Ok(v) => match v {
-                MessageToKickerUI::NewConfig(cfg) => self.current_config = Some(cfg),
+                MessageToKickerUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToKickerUI::KeyMapped(key, control) => self.mappings.set(key, control),
             },
         }
     }
@@ -368,6 +651,10 @@ impl eframe::App for KickerUI {
         ctx.request_repaint();
 
         self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -378,21 +665,43 @@ impl eframe::App for KickerUI {
     }
 }
 
+///Render a single kick to a wav file, without starting the jack client. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_kick(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let velocity = conf.velocity_response.apply(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let frame_t = 1.0 / options.rate as f64;
+    let mut samples: Vec<f64> = (1..=Voice::total_frames(&conf))
+        .rev()
+        .map(|frames_left| Voice::hit_sample(&conf, frame_t, frames_left) * velocity)
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
 pub fn kick() -> Result<(), CommonError> {
     // open client
-    let (client, _status) = match jack::Client::new("kick", jack::ClientOptions::NO_START_SERVER) {
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
         Ok(v) => v,
         Err(e) => {
             return Err(CommonError::UnableToStartClient(e));
         }
     };
+    crate::storage::set_instance(TOOL_NAME, client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
     let synth = Kicker::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -404,7 +713,14 @@ pub fn kick() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(KickerUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(KickerUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
@@ -417,3 +733,16 @@ pub fn kick() -> Result<(), CommonError> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Voice;
+
+    #[test]
+    fn steeper_sweep_is_faster() {
+        assert_eq!(Voice::sweep(0.0, 5.0), 1.0);
+        assert_eq!(Voice::sweep(0.5, 5.0), (-2.5f64).exp());
+        assert!(Voice::sweep(0.2, 10.0) < Voice::sweep(0.2, 2.0));
+        assert!(Voice::sweep(1.0, 20.0) < 1e-8);
+    }
+}
@@ -1,4 +1,21 @@
+mod clap;
+pub use clap::{clap, render_clap};
+mod cymbal;
+pub use cymbal::{cymbal, render_cymbal};
+mod drumkit;
+pub use drumkit::{drumkit, render_drumkit};
+mod hihat;
+pub use hihat::{hihat, render_hihat};
 mod kick;
-pub use kick::kick;
+pub use kick::{kick, render_kick};
+mod output;
+mod render;
+pub use render::{RenderError, RenderOptions};
 mod snare;
-pub use snare::snare;
+pub use snare::{render_snare, snare};
+mod spread;
+mod tom;
+pub use tom::{render_tom, tom};
+mod trigger;
+mod velocity;
+mod voice;
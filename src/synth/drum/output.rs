@@ -0,0 +1,93 @@
+use crate::{
+    configuration::{BoolValue, FloatValueInRange, Unit},
+    dsp::{db_to_gain, soft_clip, DcBlocker},
+    synth::hardware::KeyBoardKey,
+};
+
+///The last stage of the outputs of a drum (mono, left and right): the offset
+/// is removed and, if the soft clipper is on, the loud hits are bent below
+/// the ceiling instead of clipping harshly in the jack graph
+pub struct OutputStage {
+    ///Remove the offset of the outputs: mono, left and right
+    dc_blockers: [DcBlocker; 3],
+}
+
+impl OutputStage {
+    pub fn new(rate: usize) -> OutputStage {
+        OutputStage {
+            dc_blockers: std::array::from_fn(|_| DcBlocker::new(rate)),
+        }
+    }
+
+    ///Process a frame of the mono, left and right outputs, 'ceiling' being
+    /// the level of the soft clipper, if it is on
+    pub fn process(&mut self, frame: [f64; 3], ceiling: Option<f64>) -> [f32; 3] {
+        let mut output = [0.0; 3];
+        for (index, blocker) in self.dc_blockers.iter_mut().enumerate() {
+            output[index] = clip(blocker.process(frame[index]), ceiling) as f32;
+        }
+        output
+    }
+}
+
+///Bend a value below the ceiling, if any
+pub fn clip(value: f64, ceiling: Option<f64>) -> f64 {
+    match ceiling {
+        Some(ceiling) => soft_clip(value, ceiling),
+        None => value,
+    }
+}
+
+///Bend the samples of a rendered hit below the ceiling, if any
+pub fn clip_all(samples: &mut [f64], ceiling: Option<f64>) {
+    for sample in samples.iter_mut() {
+        *sample = clip(*sample, ceiling);
+    }
+}
+
+///The level of the soft clipper, if it is on
+pub fn ceiling(soft_clipper: &BoolValue, ceiling: &FloatValueInRange) -> Option<f64> {
+    if soft_clipper.get_value() {
+        Some(ceiling.get_value())
+    } else {
+        None
+    }
+}
+
+///The default soft clipper: off, the hits are left as they are
+pub fn default_soft_clipper() -> BoolValue {
+    BoolValue::new(false, "soft clipper", KeyBoardKey::SoftClipper)
+}
+
+///The default ceiling of the soft clipper: just below full scale
+pub fn default_ceiling() -> FloatValueInRange {
+    FloatValueInRange::new(
+        db_to_gain(-1.0),
+        db_to_gain(-24.0),
+        1.0,
+        "ceiling",
+        KeyBoardKey::Ceiling,
+    )
+    .with_unit(Unit::Decibels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::OutputStage;
+
+    #[test]
+    fn output_below_the_ceiling() {
+        let mut stage = OutputStage::new(48000);
+        let mut loudest: f32 = 0.0;
+        for _ in 0..100 {
+            let [mono, left, right] = stage.process([4.0, -4.0, 0.0], Some(0.5));
+            assert!(left < 0.0 && right == 0.0);
+            loudest = loudest.max(mono.abs()).max(left.abs());
+        }
+        assert!(loudest > 0.49 && loudest <= 0.5);
+        //without soft clipper, only the offset is removed
+        let mut stage = OutputStage::new(48000);
+        let [mono, _, _] = stage.process([4.0, 0.0, 0.0], None);
+        assert!(mono > 3.9);
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fmt::Display, path::PathBuf};
+
+use serde::de::DeserializeOwned;
+
+use crate::storage::{self, StorageError};
+
+///The sample rate used when none is given
+const DEFAULT_RATE: usize = 48000;
+///The note used when none is given (C1, the kick of the general midi drums)
+const DEFAULT_NOTE: u8 = 36;
+///The velocity used when none is given
+const DEFAULT_VELOCITY: u8 = 100;
+
+///The errors that can occur while rendering a hit
+#[derive(Debug)]
+pub enum RenderError {
+    InvalidArgument(String),
+    Storage(StorageError),
+    File(std::io::Error),
+}
+
+impl From<StorageError> for RenderError {
+    fn from(value: StorageError) -> Self {
+        RenderError::Storage(value)
+    }
+}
+
+impl From<std::io::Error> for RenderError {
+    fn from(value: std::io::Error) -> Self {
+        RenderError::File(value)
+    }
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::InvalidArgument(e) => write!(f, "{e}"),
+            RenderError::Storage(e) => write!(f, "Unable to load the configuration: {e}"),
+            RenderError::File(e) => write!(f, "Unable to write the wav file: {e}"),
+        }
+    }
+}
+
+///The rendering of a single hit to a wav file, without starting the jack
+/// client nor the user interface
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    ///The wav file to write
+    pub output: PathBuf,
+    ///The midi note of the hit. Every note plays the same hit.
+    pub note: u8,
+    ///The midi velocity of the hit
+    pub velocity: u8,
+    ///The sample rate of the wav file
+    pub rate: usize,
+    ///The configuration to render. If not given, the last session is used.
+    pub configuration: Option<PathBuf>,
+}
+
+impl RenderOptions {
+    ///Read the options from the command line: `--render out.wav [--note 36]
+    /// [--velocity 100] [--rate 48000] [--config kick.json]`. If `--render`
+    /// is not given, None is returned.
+    pub fn from_args() -> Result<Option<RenderOptions>, RenderError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    fn parse<I>(mut args: I) -> Result<Option<RenderOptions>, RenderError>
+    where
+        I: Iterator<Item = String>,
+    {
+        let mut output = None;
+        let mut options = RenderOptions {
+            output: PathBuf::new(),
+            note: DEFAULT_NOTE,
+            velocity: DEFAULT_VELOCITY,
+            rate: DEFAULT_RATE,
+            configuration: None,
+        };
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| RenderError::InvalidArgument(format!("Missing value for {arg}")))?;
+            match arg.as_str() {
+                "--render" => output = Some(PathBuf::from(value)),
+                "--note" => options.note = Self::midi_value(&arg, &value)?,
+                "--velocity" => options.velocity = Self::midi_value(&arg, &value)?,
+                "--rate" => {
+                    options.rate = value.parse().map_err(|_| {
+                        RenderError::InvalidArgument(format!("Invalid sample rate: {value}"))
+                    })?
+                }
+                "--config" => options.configuration = Some(PathBuf::from(value)),
+                _ => {
+                    return Err(RenderError::InvalidArgument(format!(
+                        "Unknown argument: {arg}"
+                    )))
+                }
+            }
+        }
+        Ok(output.map(|output| RenderOptions { output, ..options }))
+    }
+
+    fn midi_value(arg: &str, value: &str) -> Result<u8, RenderError> {
+        match value.parse::<u8>() {
+            Ok(v) if v < 128 => Ok(v),
+            _ => Err(RenderError::InvalidArgument(format!(
+                "Invalid value for {arg}: {value} (expected 0 to 127)"
+            ))),
+        }
+    }
+
+    ///The velocity of the hit, as given by [crate::midiinput::MidiInput]
+    pub fn note_velocity(&self) -> f64 {
+        crate::midiinput::velocity_from_midi(self.velocity)
+    }
+
+    ///Load the configuration to render: the given file, the last session of
+    /// the tool or else the default one
+    pub fn load_configuration<T>(&self, tool: &str, default: T) -> Result<T, RenderError>
+    where
+        T: DeserializeOwned,
+    {
+        match &self.configuration {
+            Some(path) => Ok(storage::load(path)?),
+            None => Ok(crate::session::last_session(tool)?.unwrap_or(default)),
+        }
+    }
+
+    ///Write the rendered hit to the output file
+    pub fn write(&self, samples: &[f64]) -> Result<(), RenderError> {
+        let samples: Vec<i16> = samples
+            .iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f64) as i16)
+            .collect();
+        crate::wavwriter::write_wav(&self.output, &samples, self.rate as u32)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::RenderOptions;
+
+    fn parse(args: &[&str]) -> Option<RenderOptions> {
+        RenderOptions::parse(args.iter().map(|a| String::from(*a))).unwrap()
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(parse(&[]), None);
+        let options = parse(&["--render", "out.wav", "--velocity", "64"]).unwrap();
+        assert_eq!(options.output, PathBuf::from("out.wav"));
+        assert_eq!(options.velocity, 64);
+        assert_eq!(options.note, 36);
+        assert!(RenderOptions::parse(["--note", "200"].iter().map(|a| String::from(*a))).is_err());
+        assert!(RenderOptions::parse(["--render"].iter().map(|a| String::from(*a))).is_err());
+    }
+}
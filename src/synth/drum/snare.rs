@@ -1,15 +1,39 @@
 use eframe::egui::{self, ViewportBuilder};
 use rand::random;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    configuration::{self, ConfigurationValue, FloatValueInRange, UsizeValueInRange},
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    dsp::{StateVariableFilter, SvfCoefficients},
+    i18n::tr,
     midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
     synth::hardware::{HardWare, KeyBoardKey},
-    utils::{CommonError, ConnectionType, KeyBoardKeySetter},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
 };
 
-#[derive(Debug, Clone, PartialEq)]
-struct Configuration {
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    trigger::Trigger,
+    velocity::VelocityResponse,
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "snare";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
     /// The number of frames needed to reach full volume
     attack: UsizeValueInRange,
     /// The number of frames to completely stop the sound
@@ -18,12 +42,59 @@ struct Configuration {
     duration: UsizeValueInRange,
     /// The volume of the snare
     volume: FloatValueInRange,
-    /// The alpha value for the high pass filter
-    alpha: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes changes the volume
+    #[serde(default)]
+    pub(super) velocity_response: VelocityResponse,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+    /// The notes playing the drum
+    #[serde(default)]
+    trigger: Trigger,
+    /// The center frequency of the band-pass filter applied to the noise
+    #[serde(default = "default_tone")]
+    tone: FloatValueInRange,
+    /// The quality factor of the band-pass filter: the higher, the narrower
+    /// the band of noise kept
+    #[serde(default = "default_q")]
+    q: FloatValueInRange,
+    /// How much the filter and the decay randomly change from one hit to another
+    #[serde(default = "default_variation")]
+    variation: FloatValueInRange,
+}
+
+fn default_tone() -> FloatValueInRange {
+    FloatValueInRange::new(2500.0, 200.0, 12000.0, "tone", KeyBoardKey::FilterCutoff)
+        .with_unit(Unit::Hertz)
+        .logarithmic()
+}
+
+fn default_q() -> FloatValueInRange {
+    FloatValueInRange::new(0.8, 0.3, 8.0, "Q", KeyBoardKey::FilterResonance)
+}
+
+fn default_variation() -> FloatValueInRange {
+    FloatValueInRange::new(0.0, 0.0, 1.0, "variation", KeyBoardKey::Variation)
+        .with_unit(Unit::Percent)
 }
 
 impl Configuration {
-    pub fn new(rate: usize) -> Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
         Self {
             attack: UsizeValueInRange::new(50, 0, 128, "attack", KeyBoardKey::FadeInDuration),
             decay: UsizeValueInRange::new(50, 0, 128, "decay", KeyBoardKey::FadeOutDuration),
@@ -33,9 +104,21 @@ impl Configuration {
                 rate * 5,
                 "duration",
                 KeyBoardKey::Duration,
-            ),
-            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain),
-            alpha: FloatValueInRange::new(0.2, 0.0, 1.0, "alpha", KeyBoardKey::Parameter),
+            )
+            .with_unit(Unit::Frames(rate)),
+            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_response: VelocityResponse::Linear,
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+            trigger: Trigger::Any,
+            tone: default_tone(),
+            q: default_q(),
+            variation: default_variation(),
         }
     }
 }
@@ -47,41 +130,206 @@ impl<'c> configuration::Configuration<'c> for Configuration {
             ConfigurationValue::USize(&mut self.decay),
             ConfigurationValue::USize(&mut self.duration),
             ConfigurationValue::Float(&mut self.volume),
-            ConfigurationValue::Float(&mut self.alpha),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
+            ConfigurationValue::Float(&mut self.tone),
+            ConfigurationValue::Float(&mut self.q),
+            ConfigurationValue::Float(&mut self.variation),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
         ]
     }
 }
 
-struct Snare {
+///The snares being played
+pub(super) struct Voice {
     /// The number of frames that still needs to be played
     nb_frames_left: Vec<usize>,
     /// Velocity of the last snare
     velocity: Vec<f64>,
+    /// The pan of every snare, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// Choose the pan of the next snare
+    spread: Spread,
+    /// The band-pass filter of the noise of every snare
+    filters: Vec<StateVariableFilter>,
+    /// The coefficients of the filter of every snare
+    coefs: Vec<SvfCoefficients>,
+    /// The number of frames of the decay of every snare
+    decay: Vec<usize>,
+    /// The total number of frames of every snare
+    total_frames: Vec<usize>,
+    /// The sample rate of the audio
+    rate: usize,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        let conf = Configuration::new(rate);
+        let coefs = SvfCoefficients::with_q(rate, conf.tone.get_value(), conf.q.get_value());
+        Voice {
+            nb_frames_left: vec![0; 128],
+            velocity: vec![0.0; 128],
+            pan: vec![0.0; 128],
+            spread: Spread::new(),
+            filters: vec![StateVariableFilter::new(); 128],
+            coefs: vec![coefs; 128],
+            decay: vec![0; 128],
+            total_frames: vec![0; 128],
+            rate,
+            conf,
+        }
+    }
+
+    ///Vary the filter and the decay of a new hit so that repeated hits don't
+    /// sound identical. Return the coefficients of the filter, the decay and
+    /// the total number of frames of the hit.
+    fn hit_shape(conf: &Configuration, rate: usize) -> (SvfCoefficients, usize, usize) {
+        let variation = conf.variation.get_value();
+        //up to half an octave above or below the tone
+        let tone =
+            conf.tone.get_value() * 2.0_f64.powf(0.5 * variation * (1.0 - random::<f64>() * 2.0));
+        let coefs = SvfCoefficients::with_q(rate, tone, conf.q.get_value());
+        let decay = (conf.decay.get_value() as f64
+            * (1.0 + variation * (1.0 - random::<f64>() * 2.0))) as usize;
+        let total_frames = decay + conf.duration.get_value() + conf.attack.get_value();
+        (coefs, decay, total_frames)
+    }
+
+    ///The volume of a hit, 'frames_left' frames before its end
+    fn hit_volume(
+        conf: &Configuration,
+        decay: usize,
+        total_frames: usize,
+        frames_left: usize,
+    ) -> f64 {
+        if frames_left > decay + conf.duration.get_value() {
+            let v = total_frames - frames_left;
+            (v as f64 / conf.attack.get_value() as f64) * conf.volume.get_value()
+        } else if frames_left > decay {
+            conf.volume.get_value()
+        } else {
+            (frames_left as f64 / decay as f64) * conf.volume.get_value()
+        }
+    }
+
+    ///The next sample of the band-passed noise of a hit
+    fn noise(filter: &mut StateVariableFilter, coefs: &SvfCoefficients) -> f64 {
+        filter.peak_band_pass(1.0 - (random::<f64>() * 2.0), coefs)
+    }
+}
+
+impl DrumVoice for Voice {
+    fn start(&mut self, _note_index: usize, velocity: f64) {
+        let velocity = self.conf.velocity_response.apply(
+            velocity,
+            self.conf.velocity_curve.get_value(),
+            self.conf.dynamics.get_value(),
+        );
+        let pan = self.spread.next_pan(
+            self.conf.spread.get_value(),
+            self.conf.spread_alternate.get_value(),
+        );
+        let (coefs, decay, total_frames) = Self::hit_shape(&self.conf, self.rate);
+        match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => {
+                self.nb_frames_left[index] = total_frames;
+                self.velocity[index] = velocity;
+                self.pan[index] = pan;
+                self.filters[index].reset();
+                self.coefs[index] = coefs;
+                self.decay[index] = decay;
+                self.total_frames[index] = total_frames;
+            }
+            None => {
+                self.nb_frames_left.push(total_frames);
+                self.velocity.push(velocity);
+                self.pan.push(pan);
+                self.filters.push(StateVariableFilter::new());
+                self.coefs.push(coefs);
+                self.decay.push(decay);
+                self.total_frames.push(total_frames);
+            }
+        }
+    }
+
+    fn panic(&mut self) {
+        self.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let mut frame = [0.0; 3];
+        for snare_index in 0..self.nb_frames_left.len() {
+            let frames_left = self.nb_frames_left[snare_index];
+            if frames_left == 0 {
+                continue;
+            }
+            let volume = Self::hit_volume(
+                &self.conf,
+                self.decay[snare_index],
+                self.total_frames[snare_index],
+                frames_left,
+            );
+
+            let y = Self::noise(&mut self.filters[snare_index], &self.coefs[snare_index]);
+
+            let value = y * self.velocity[snare_index] * volume;
+
+            let (gain_left, gain_right) = pan_gains(self.pan[snare_index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            self.nb_frames_left[snare_index] = frames_left - 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct Snare {
+    /// The snares being played
+    voice: Voice,
     /// The midi input to listen to
     midi_in: jack::Port<jack::MidiIn>,
     /// The audio output
     audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
     //The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToSnare>,
+    messages_in: ChannelWatchdog<MessageToSnare, spsc::Receiver<MessageToSnare>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToSnareUI>,
+    messages_out: spsc::Sender<MessageToSnareUI>,
     ///If true, the next control will be used as key to start/stop the recording
     key_change: Option<KeyBoardKey>,
+    ///If true, the next note received becomes the trigger of the drum
+    learn_trigger: bool,
     ///The keyboard events we are listening to
     keyboard: HardWare,
-    /// The configuration
-    conf: Configuration,
-    /// The last value pushed to the buffer
-    last_output: Vec<f64>,
-    /// The value before the last value pushed to the buffer
-    last_input: Vec<f64>,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
 }
 
 impl Snare {
     pub fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToSnare>,
-        mut messages_out: std::sync::mpsc::Sender<MessageToSnareUI>,
+        messages_in: spsc::Receiver<MessageToSnare>,
+        mut messages_out: spsc::Sender<MessageToSnareUI>,
     ) -> Result<Snare, CommonError> {
         let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
             Ok(v) => v,
@@ -91,44 +339,42 @@ impl Snare {
             Ok(v) => v,
             Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
         };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
 
         let sample_rate = client.sample_rate();
 
-        let current_config = Configuration::new(sample_rate);
+        let voice = Voice::new(sample_rate);
 
         //initialize the configuration on the UI side
         Self::send_message(
-            MessageToSnareUI::NewConfig(current_config.clone()),
+            MessageToSnareUI::NewConfig(Box::new(voice.conf.clone())),
             &mut messages_out,
         );
 
-        let mut frames = Vec::with_capacity(128);
-        let mut vel = Vec::with_capacity(128);
-        let mut last = Vec::with_capacity(128);
-        for _index in 0..128 {
-            frames.push(0);
-            vel.push(0.0);
-            last.push(0.0);
-        }
         Ok(Snare {
-            nb_frames_left: frames,
-            velocity: vel,
+            voice,
             midi_in: m_in,
             audio_out: a_out,
-            messages_in,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
             messages_out,
             key_change: None,
+            learn_trigger: false,
             keyboard: HardWare::new(),
-            conf: current_config,
-            last_output: last.clone(),
-            last_input: last,
+            channel: MidiChannel::load(TOOL_NAME),
         })
     }
 
-    fn send_message(
-        msg: MessageToSnareUI,
-        messages_out: &mut std::sync::mpsc::Sender<MessageToSnareUI>,
-    ) {
+    fn send_message(msg: MessageToSnareUI, messages_out: &mut spsc::Sender<MessageToSnareUI>) {
         if let Err(e) = messages_out.send(msg) {
             eprintln!("Internal error: {e}");
         }
@@ -137,31 +383,42 @@ impl Snare {
 
 impl jack::ProcessHandler for Snare {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToSnare::ChangeActivationMidiKey(key) => self.key_change = Some(key),
-                MessageToSnare::ClearActiviationMidiKey(key) => self.keyboard.clear_key(key),
-                MessageToSnare::NewConfig(configuration) => self.conf = configuration,
+                MessageToSnare::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToSnareUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToSnare::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToSnare::Channel(channel) => self.channel = channel,
+                MessageToSnare::LearnTrigger => self.learn_trigger = true,
+                MessageToSnare::Panic => self.voice.panic(),
             }
         }
 
-        let total_frames = self.conf.decay.get_value()
-            + self.conf.duration.get_value()
-            + self.conf.attack.get_value();
-
-        let show_p = self.midi_in.iter(ps);
-        for e in show_p {
+        for e in self.midi_in.iter(ps) {
             let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
             match midi {
                 MidiInput::Controller {
-                    channel: _,
+                    channel,
                     control,
                     value,
                 } => {
                     if let Some(key) = self.keyboard.get_keyboard_key(control) {
-                        if configuration::Configuration::apply_midi(&mut self.conf, key, value) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
                             Self::send_message(
-                                MessageToSnareUI::NewConfig(self.conf.clone()),
+                                MessageToSnareUI::NewConfig(Box::new(self.voice.conf.clone())),
                                 &mut self.messages_out,
                             );
                         }
@@ -170,26 +427,29 @@ impl jack::ProcessHandler for Snare {
                     if let Some(k) = self.key_change {
                         self.keyboard.update_key(k, control);
                         self.key_change = None;
+                        Self::send_message(
+                            MessageToSnareUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
                     }
                 }
                 MidiInput::NoteStart {
                     channel: _,
-                    note_index: _,
+                    note_index,
                     timing: _,
                     velocity,
                 } => {
-                    let mut added = false;
-                    for index in 0..self.nb_frames_left.len() {
-                        if self.nb_frames_left[index] == 0 {
-                            self.nb_frames_left[index] = total_frames;
-                            self.velocity[index] = velocity;
-                            added = true;
-                            break;
-                        }
+                    if self.learn_trigger {
+                        self.learn_trigger = false;
+                        self.voice.conf.trigger = Trigger::learned(note_index);
+                        Self::send_message(
+                            MessageToSnareUI::NewConfig(Box::new(self.voice.conf.clone())),
+                            &mut self.messages_out,
+                        );
                     }
-                    if !added {
-                        self.nb_frames_left.push(total_frames);
-                        self.velocity.push(velocity);
+                    if self.voice.conf.trigger.accepts(note_index) {
+                        crate::utils::statistics::note_played();
+                        self.voice.start(note_index, velocity);
                     }
                 }
                 _ => {}
@@ -197,45 +457,15 @@ impl jack::ProcessHandler for Snare {
         }
 
         let out = self.audio_out.as_mut_slice(ps);
-
-        assert_eq!(self.nb_frames_left.len(), self.velocity.len());
-        for output in out.iter_mut() {
-            let mut v: f64 = 0.0;
-            for snare_index in 0..self.nb_frames_left.len() {
-                assert!(snare_index < self.nb_frames_left.len());
-                assert!(snare_index < self.velocity.len());
-                if self.nb_frames_left[snare_index] == 0 {
-                    continue;
-                }
-                let volume = if self.nb_frames_left[snare_index]
-                    > self.conf.decay.get_value() + self.conf.duration.get_value()
-                {
-                    let v = total_frames - self.nb_frames_left[snare_index];
-                    (v as f64 / self.conf.attack.get_value() as f64) * self.conf.volume.get_value()
-                } else if self.nb_frames_left[snare_index] > self.conf.decay.get_value() {
-                    self.conf.volume.get_value()
-                } else {
-                    let v = self.nb_frames_left[snare_index];
-                    (v as f64 / self.conf.decay.get_value() as f64) * self.conf.volume.get_value()
-                };
-
-                assert!(total_frames >= self.nb_frames_left[snare_index]);
-
-                let x = 1.0 - (random::<f64>() * 2.0);
-
-                let y = self.conf.alpha.get_value()
-                    * (self.last_output[snare_index] + x - self.last_input[snare_index]);
-
-                self.last_input[snare_index] = x;
-                self.last_output[snare_index] = y;
-
-                let value = y * self.velocity[snare_index] * volume;
-
-                v += value;
-
-                self.nb_frames_left[snare_index] = self.nb_frames_left[snare_index] - 1;
-            }
-            *output = v as f32;
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
         }
 
         jack::Control::Continue
@@ -246,7 +476,12 @@ impl jack::ProcessHandler for Snare {
 enum MessageToSnare {
     ChangeActivationMidiKey(KeyBoardKey),
     ClearActiviationMidiKey(KeyBoardKey),
-    NewConfig(Configuration),
+    NewConfig(Box<Configuration>),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    ///Use the next note received as trigger
+    LearnTrigger,
+    Panic,
 }
 
 impl From<KeyBoardKeySetter> for MessageToSnare {
@@ -260,33 +495,53 @@ impl From<KeyBoardKeySetter> for MessageToSnare {
 
 #[derive(Debug)]
 enum MessageToSnareUI {
-    NewConfig(Configuration),
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
 }
 
 struct SnareUI {
-    messages_in: std::sync::mpsc::Receiver<MessageToSnareUI>,
-    message_out: std::sync::mpsc::Sender<MessageToSnare>,
+    messages_in: spsc::Receiver<MessageToSnareUI>,
+    message_out: spsc::Sender<MessageToSnare>,
     messages: Vec<String>,
     current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl SnareUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToSnareUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToSnare>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToSnareUI>,
+        messages_out: spsc::Sender<MessageToSnare>,
     ) -> SnareUI {
         SnareUI {
+            status,
             messages_in,
             message_out: messages_out,
             messages: Vec::new(),
             current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
         }
     }
 
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("Settings", |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
                 if let Some(conf) = &mut self.current_config {
                     configuration::Configuration::create_menu_keyboard_settings(
                         conf,
@@ -295,21 +550,49 @@ impl SnareUI {
                         &mut self.messages,
                     );
                 }
+                self.shortcuts
+                    .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                if crate::utils::create_channel_select(
+                    ui,
+                    TOOL_NAME,
+                    &mut self.channel,
+                    &mut self.messages,
+                ) {
+                    if let Err(e) = self.message_out.send(MessageToSnare::Channel(self.channel)) {
+                        self.messages.push(format!("Internal error: {e}"));
+                    }
+                }
             });
             crate::utils::common_menu_luncher(ui, &mut self.messages);
         });
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
         if let Some(current_config) = &self.current_config {
             let mut conf = current_config.clone();
 
-            configuration::Configuration::draw(&mut conf, ui);
+            if conf.trigger.draw(ui) {
+                if let Err(e) = self.message_out.send(MessageToSnare::LearnTrigger) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+                self.messages
+                    .push(String::from(tr("Play the note of the drum")));
+            }
+            conf.velocity_response.draw(ui);
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
 
             if !self.current_config.as_ref().eq(&Some(&conf)) {
                 if let Err(e) = self
                     .message_out
-                    .send(MessageToSnare::NewConfig(conf.clone()))
+                    .send(MessageToSnare::NewConfig(Box::new(conf.clone())))
                 {
                     self.messages
                         .push(format!("Error while sending new conf: {e}"));
@@ -320,6 +603,38 @@ impl SnareUI {
         crate::utils::show_logs(ui, &mut self.messages);
     }
 
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToSnare::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToSnare::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
     fn read_input(&mut self) {
         //read message queue
         match self.messages_in.try_recv() {
@@ -330,7 +645,8 @@ impl SnareUI {
                 )),
             },
             Ok(v) => match v {
-                MessageToSnareUI::NewConfig(cfg) => self.current_config = Some(cfg),
+                MessageToSnareUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToSnareUI::KeyMapped(key, control) => self.mappings.set(key, control),
             },
         }
     }
@@ -344,6 +660,10 @@ impl eframe::App for SnareUI {
         ctx.request_repaint();
 
         self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -354,21 +674,47 @@ impl eframe::App for SnareUI {
     }
 }
 
+///Render a single snare to a wav file, without starting the jack client. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_snare(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let velocity = conf.velocity_response.apply(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let (coefs, decay, total_frames) = Voice::hit_shape(&conf, options.rate);
+    let mut filter = StateVariableFilter::new();
+    let mut samples: Vec<f64> = (1..=total_frames)
+        .rev()
+        .map(|frames_left| {
+            let volume = Voice::hit_volume(&conf, decay, total_frames, frames_left);
+            Voice::noise(&mut filter, &coefs) * velocity * volume
+        })
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
 pub fn snare() -> Result<(), CommonError> {
     // open client
-    let (client, _status) = match jack::Client::new("snare", jack::ClientOptions::NO_START_SERVER) {
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
         Ok(v) => v,
         Err(e) => {
             return Err(CommonError::UnableToStartClient(e));
         }
     };
+    crate::storage::set_instance(TOOL_NAME, client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
     let synth = Snare::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -380,7 +726,14 @@ pub fn snare() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(SnareUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(SnareUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
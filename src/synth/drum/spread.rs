@@ -0,0 +1,50 @@
+use rand::random;
+
+use crate::{
+    configuration::{BoolValue, FloatValueInRange, Unit},
+    synth::hardware::KeyBoardKey,
+};
+
+///Distribute the hits of a drum over the stereo field
+pub struct Spread {
+    ///The side used by the last hit when alternating
+    left: bool,
+}
+
+impl Spread {
+    pub fn new() -> Spread {
+        Spread { left: false }
+    }
+
+    ///Compute the pan of the next hit, between -1 (left) and 1 (right).
+    /// 'amount' is the maximum distance from the center, between 0 and 1.
+    pub fn next_pan(&mut self, amount: f64, alternate: bool) -> f64 {
+        if alternate {
+            self.left = !self.left;
+            if self.left {
+                -amount
+            } else {
+                amount
+            }
+        } else {
+            amount * (1.0 - random::<f64>() * 2.0)
+        }
+    }
+}
+
+///Compute the gain of the left and right channels for a pan. The center
+/// keeps both channels at full volume, so that it sounds like the mono output.
+pub fn pan_gains(pan: f64) -> (f64, f64) {
+    (f64::min(1.0, 1.0 - pan), f64::min(1.0, 1.0 + pan))
+}
+
+///The default amount of spread: every hit in the center
+pub fn default_spread() -> FloatValueInRange {
+    FloatValueInRange::new(0.0, 0.0, 1.0, "stereo spread", KeyBoardKey::Spread)
+        .with_unit(Unit::Percent)
+}
+
+///The default spread mode: random pan
+pub fn default_alternate() -> BoolValue {
+    BoolValue::new(false, "alternate left/right", KeyBoardKey::SpreadAlternate)
+}
@@ -0,0 +1,782 @@
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{
+        self, BoolValue, ConfigurationValue, FloatValueInRange, MidiControl, MidiMappings, Unit,
+        UsizeValueInRange,
+    },
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+        ShortcutAction, Shortcuts,
+    },
+};
+
+use super::{
+    output::OutputStage,
+    render::{RenderError, RenderOptions},
+    spread::{pan_gains, Spread},
+    voice::DrumVoice,
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "tom";
+///The time the noise of the stick takes to fade, in seconds
+const NOISE_DECAY: f64 = 0.01;
+
+///The sizes of tom, each with its own pitch and decay
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TomSize {
+    Low,
+    Mid,
+    High,
+}
+
+impl TomSize {
+    const ALL: [TomSize; 3] = [TomSize::Low, TomSize::Mid, TomSize::High];
+}
+
+impl std::fmt::Display for TomSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TomSize::Low => write!(f, "Low"),
+            TomSize::Mid => write!(f, "Mid"),
+            TomSize::High => write!(f, "High"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Configuration {
+    /// The number of frames needed to reach full volume
+    attack: UsizeValueInRange,
+    /// The number of frames to completely stop the sound
+    decay: UsizeValueInRange,
+    /// The volume of the tom
+    volume: FloatValueInRange,
+    /// How far from the center the hits can be panned
+    #[serde(default = "super::spread::default_spread")]
+    spread: FloatValueInRange,
+    /// If true, the hits alternate between left and right instead of being randomly panned
+    #[serde(default = "super::spread::default_alternate")]
+    spread_alternate: BoolValue,
+    /// How the velocity of the notes is translated into volume
+    #[serde(default = "super::velocity::default_curve")]
+    velocity_curve: FloatValueInRange,
+    /// Compress or expand the differences of velocity between the notes
+    #[serde(default = "super::velocity::default_dynamics")]
+    dynamics: FloatValueInRange,
+    /// If true, the loud hits are bent below the ceiling instead of clipping
+    #[serde(default = "super::output::default_soft_clipper")]
+    soft_clipper: BoolValue,
+    /// The highest level of the outputs when the soft clipper is on
+    #[serde(default = "super::output::default_ceiling")]
+    ceiling: FloatValueInRange,
+    /// The frequency at the start of the hit
+    start_freq: FloatValueInRange,
+    /// The frequency the hit sweeps down to
+    end_freq: FloatValueInRange,
+    /// The number of frames for the sweep to get close to the end frequency
+    sweep: UsizeValueInRange,
+    /// The volume of the noise at the start of the hit, relative to the tone
+    noise: FloatValueInRange,
+}
+
+impl Configuration {
+    pub(super) fn new(rate: usize) -> Configuration {
+        Self::preset(TomSize::Mid, rate)
+    }
+
+    ///The configuration of a size of tom
+    fn preset(size: TomSize, rate: usize) -> Configuration {
+        //start and end frequencies, duration of the sweep and of the decay
+        let (start, end, sweep, decay) = match size {
+            TomSize::Low => (150.0, 75.0, 0.04, 0.6),
+            TomSize::Mid => (220.0, 110.0, 0.03, 0.45),
+            TomSize::High => (330.0, 165.0, 0.025, 0.35),
+        };
+        let frames = |seconds: f64| (seconds * rate as f64) as usize;
+        Self {
+            attack: UsizeValueInRange::new(50, 0, 128, "attack", KeyBoardKey::FadeInDuration),
+            decay: UsizeValueInRange::new(
+                frames(decay),
+                0,
+                2 * rate,
+                "decay",
+                KeyBoardKey::FadeOutDuration,
+            )
+            .with_unit(Unit::Frames(rate)),
+            volume: FloatValueInRange::new(0.5, 0.0, 10.0, "volume", KeyBoardKey::Gain)
+                .with_unit(Unit::Decibels),
+            spread: super::spread::default_spread(),
+            spread_alternate: super::spread::default_alternate(),
+            velocity_curve: super::velocity::default_curve(),
+            dynamics: super::velocity::default_dynamics(),
+            soft_clipper: super::output::default_soft_clipper(),
+            ceiling: super::output::default_ceiling(),
+            start_freq: FloatValueInRange::new(
+                start,
+                20.0,
+                2000.0,
+                "start freq",
+                KeyBoardKey::Parameter,
+            )
+            .with_unit(Unit::Hertz)
+            .logarithmic(),
+            end_freq: FloatValueInRange::new(
+                end,
+                20.0,
+                2000.0,
+                "end freq",
+                KeyBoardKey::ParameterB,
+            )
+            .with_unit(Unit::Hertz)
+            .logarithmic(),
+            sweep: UsizeValueInRange::new(
+                frames(sweep),
+                1,
+                rate / 2,
+                "sweep",
+                KeyBoardKey::PitchEnvelopeDecay,
+            )
+            .with_unit(Unit::Frames(rate)),
+            noise: FloatValueInRange::new(0.3, 0.0, 1.0, "noise", KeyBoardKey::Noise)
+                .with_unit(Unit::Percent),
+        }
+    }
+
+    ///Take the pitch and the decay of a size of tom, keeping the other values
+    fn apply_preset(&mut self, size: TomSize, rate: usize) {
+        let preset = Self::preset(size, rate);
+        self.start_freq = preset.start_freq;
+        self.end_freq = preset.end_freq;
+        self.sweep = preset.sweep;
+        self.decay = preset.decay;
+    }
+}
+
+impl<'c> configuration::Configuration<'c> for Configuration {
+    fn elements(&'c mut self) -> Vec<ConfigurationValue<'c>> {
+        vec![
+            ConfigurationValue::Float(&mut self.volume),
+            ConfigurationValue::Float(&mut self.spread),
+            ConfigurationValue::Bool(&mut self.spread_alternate),
+            ConfigurationValue::Float(&mut self.velocity_curve),
+            ConfigurationValue::Float(&mut self.dynamics),
+            ConfigurationValue::Float(&mut self.start_freq),
+            ConfigurationValue::Float(&mut self.end_freq),
+            ConfigurationValue::USize(&mut self.sweep),
+            ConfigurationValue::Float(&mut self.noise),
+            ConfigurationValue::USize(&mut self.attack),
+            ConfigurationValue::USize(&mut self.decay),
+            ConfigurationValue::Bool(&mut self.soft_clipper),
+            ConfigurationValue::Float(&mut self.ceiling),
+        ]
+    }
+}
+
+///The toms being played
+pub(super) struct Voice {
+    /// The sample rate of the audio
+    rate: usize,
+    /// The number of frames that still needs to be played
+    nb_frames_left: Vec<usize>,
+    /// Velocity of the last tom
+    velocity: Vec<f64>,
+    /// The pan of every tom, between -1 (left) and 1 (right)
+    pan: Vec<f64>,
+    /// Choose the pan of the next tom
+    spread: Spread,
+    /// The configuration
+    pub(super) conf: Configuration,
+}
+
+impl Voice {
+    pub(super) fn new(rate: usize) -> Voice {
+        Voice {
+            rate,
+            nb_frames_left: vec![0; 128],
+            velocity: vec![0.0; 128],
+            pan: vec![0.0; 128],
+            spread: Spread::new(),
+            conf: Configuration::new(rate),
+        }
+    }
+
+    ///The number of frames of a hit
+    fn total_frames(conf: &Configuration) -> usize {
+        conf.attack.get_value() + conf.decay.get_value()
+    }
+
+    ///The sample of a tom played at full velocity, 'frames_left' frames
+    /// before its end, 'noise' being a random value between -1 and 1
+    fn hit_sample(conf: &Configuration, rate: usize, frames_left: usize, noise: f64) -> f64 {
+        let total_frames = Self::total_frames(conf);
+        let elapsed_frames = total_frames - frames_left;
+        let attack = conf.attack.get_value();
+        let envelope = if elapsed_frames < attack {
+            elapsed_frames as f64 / attack as f64
+        } else {
+            //the skin rings longer at low volume
+            (frames_left as f64 / conf.decay.get_value() as f64).powi(2)
+        };
+
+        //the phase of a frequency sliding exponentially from the start to the end
+        let time = elapsed_frames as f64 / rate as f64;
+        let sweep = conf.sweep.get_value() as f64 / rate as f64;
+        let start = conf.start_freq.get_value();
+        let end = conf.end_freq.get_value();
+        let cycles = end * time + (start - end) * sweep * (1.0 - (-time / sweep).exp());
+        let tone = (cycles * 2.0 * std::f64::consts::PI).sin();
+
+        //the stick hitting the skin
+        let noise = noise * conf.noise.get_value() * (-time / NOISE_DECAY).exp();
+
+        (tone + noise) * envelope * conf.volume.get_value()
+    }
+}
+
+impl DrumVoice for Voice {
+    fn start(&mut self, _note_index: usize, velocity: f64) {
+        let total_frames = Self::total_frames(&self.conf);
+        let velocity = super::velocity::response(
+            velocity,
+            self.conf.velocity_curve.get_value(),
+            self.conf.dynamics.get_value(),
+        );
+        let pan = self.spread.next_pan(
+            self.conf.spread.get_value(),
+            self.conf.spread_alternate.get_value(),
+        );
+        match self.nb_frames_left.iter().position(|left| *left == 0) {
+            Some(index) => {
+                self.nb_frames_left[index] = total_frames;
+                self.velocity[index] = velocity;
+                self.pan[index] = pan;
+            }
+            None => {
+                self.nb_frames_left.push(total_frames);
+                self.velocity.push(velocity);
+                self.pan.push(pan);
+            }
+        }
+    }
+
+    fn panic(&mut self) {
+        self.nb_frames_left.fill(0);
+    }
+
+    fn playing(&self) -> bool {
+        self.nb_frames_left.iter().any(|left| *left > 0)
+    }
+
+    fn next_frame(&mut self) -> [f64; 3] {
+        let total_frames = Self::total_frames(&self.conf);
+        let mut frame = [0.0; 3];
+        for tom_index in 0..self.nb_frames_left.len() {
+            let frames_left = self.nb_frames_left[tom_index];
+            //a shorter decay may have been set while the tom was playing
+            if frames_left == 0 || frames_left > total_frames {
+                self.nb_frames_left[tom_index] = 0;
+                continue;
+            }
+            let noise = 1.0 - random::<f64>() * 2.0;
+            let value = Self::hit_sample(&self.conf, self.rate, frames_left, noise)
+                * self.velocity[tom_index];
+
+            let (gain_left, gain_right) = pan_gains(self.pan[tom_index]);
+            frame[0] += value;
+            frame[1] += value * gain_left;
+            frame[2] += value * gain_right;
+
+            self.nb_frames_left[tom_index] = frames_left - 1;
+        }
+        frame
+    }
+
+    fn ceiling(&self) -> Option<f64> {
+        super::output::ceiling(&self.conf.soft_clipper, &self.conf.ceiling)
+    }
+}
+
+struct Tom {
+    /// The toms being played
+    voice: Voice,
+    /// The midi input to listen to
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The audio output
+    audio_out: jack::Port<jack::AudioOut>,
+    /// The left audio output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right audio output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    /// The last stage of the outputs: mono, left and right
+    output: OutputStage,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToTom, spsc::Receiver<MessageToTom>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToTomUI>,
+    ///If true, the next control will be used as key to start/stop the recording
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
+}
+
+impl Tom {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToTom>,
+        mut messages_out: spsc::Sender<MessageToTomUI>,
+    ) -> Result<Tom, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_left = match client.register_port("audio_out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let a_out_right = match client.register_port("audio_out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        let sample_rate = client.sample_rate();
+
+        let voice = Voice::new(sample_rate);
+
+        //initialize the configuration on the UI side
+        Self::send_message(
+            MessageToTomUI::NewConfig(Box::new(voice.conf.clone())),
+            &mut messages_out,
+        );
+
+        Ok(Tom {
+            voice,
+            midi_in: m_in,
+            audio_out: a_out,
+            audio_out_left: a_out_left,
+            audio_out_right: a_out_right,
+            output: OutputStage::new(sample_rate),
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+        })
+    }
+
+    fn send_message(msg: MessageToTomUI, messages_out: &mut spsc::Sender<MessageToTomUI>) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Tom {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToTom::ChangeActivationMidiKey(key) => self.key_change = Some(key),
+                MessageToTom::ClearActiviationMidiKey(key) => {
+                    self.keyboard.clear_key(key);
+                    Self::send_message(
+                        MessageToTomUI::KeyMapped(key, None),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToTom::NewConfig(configuration) => self.voice.conf = *configuration,
+                MessageToTom::Preset(size) => {
+                    self.voice.conf.apply_preset(size, self.voice.rate);
+                    Self::send_message(
+                        MessageToTomUI::NewConfig(Box::new(self.voice.conf.clone())),
+                        &mut self.messages_out,
+                    );
+                }
+                MessageToTom::Channel(channel) => self.channel = channel,
+                MessageToTom::Panic => self.voice.panic(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+            match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                        if configuration::Configuration::apply_midi(
+                            &mut self.voice.conf,
+                            key,
+                            value,
+                        ) {
+                            Self::send_message(
+                                MessageToTomUI::NewConfig(Box::new(self.voice.conf.clone())),
+                                &mut self.messages_out,
+                            );
+                        }
+                    }
+
+                    if let Some(k) = self.key_change {
+                        self.keyboard.update_key(k, control);
+                        self.key_change = None;
+                        Self::send_message(
+                            MessageToTomUI::KeyMapped(k, Some(MidiControl { channel, control })),
+                            &mut self.messages_out,
+                        );
+                    }
+                }
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index,
+                    timing: _,
+                    velocity,
+                } => {
+                    crate::utils::statistics::note_played();
+                    self.voice.start(note_index, velocity);
+                }
+                _ => {}
+            }
+        }
+
+        let out = self.audio_out.as_mut_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+
+        let ceiling = self.voice.ceiling();
+        for (frame, output) in out.iter_mut().enumerate() {
+            let [mono, left, right] = self.output.process(self.voice.next_frame(), ceiling);
+            *output = mono;
+            out_left[frame] = left;
+            out_right[frame] = right;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToTom {
+    ChangeActivationMidiKey(KeyBoardKey),
+    ClearActiviationMidiKey(KeyBoardKey),
+    NewConfig(Box<Configuration>),
+    ///Take the pitch and the decay of a size of tom
+    Preset(TomSize),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToTom {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToTom::ChangeActivationMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToTom::ClearActiviationMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToTomUI {
+    NewConfig(Box<Configuration>),
+    KeyMapped(KeyBoardKey, Option<MidiControl>),
+}
+
+struct TomUI {
+    messages_in: spsc::Receiver<MessageToTomUI>,
+    message_out: spsc::Sender<MessageToTom>,
+    messages: Vec<String>,
+    current_config: Option<Configuration>,
+    ///The midi controls mapped to the values of the configuration
+    mappings: MidiMappings,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl TomUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToTomUI>,
+        messages_out: spsc::Sender<MessageToTom>,
+    ) -> TomUI {
+        TomUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            current_config: None,
+            mappings: MidiMappings::new(),
+            shortcuts: Shortcuts::load(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            if let Some(config) = &mut self.current_config {
+                ui.menu_button(tr("Settings"), |ui| {
+                    configuration::Configuration::create_menu_keyboard_settings(
+                        config,
+                        ui,
+                        &mut self.message_out,
+                        &mut self.messages,
+                    );
+                    self.shortcuts
+                        .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                    if crate::utils::create_channel_select(
+                        ui,
+                        TOOL_NAME,
+                        &mut self.channel,
+                        &mut self.messages,
+                    ) {
+                        if let Err(e) = self.message_out.send(MessageToTom::Channel(self.channel)) {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                });
+            }
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        if let Some(current_config) = &self.current_config {
+            ui.horizontal(|ui| {
+                ui.label(tr("Preset: "));
+                for size in TomSize::ALL {
+                    if ui.button(tr(&size.to_string())).clicked() {
+                        if let Err(e) = self.message_out.send(MessageToTom::Preset(size)) {
+                            self.messages.push(format!("Internal error: {e}"));
+                        }
+                    }
+                }
+            });
+
+            let mut conf = current_config.clone();
+
+            configuration::Configuration::draw_with_mappings(
+                &mut conf,
+                ui,
+                &self.mappings,
+                &mut self.message_out,
+                &mut self.messages,
+            );
+
+            if !self.current_config.as_ref().eq(&Some(&conf)) {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToTom::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                if let Err(e) = self.message_out.send(MessageToTom::Panic) {
+                    self.messages.push(format!("Internal error: {e}"));
+                }
+            }
+        }
+    }
+
+    fn read_scene(&mut self, ctx: &egui::Context) {
+        if let Some(current_config) = &self.current_config {
+            let session = self.session.update(current_config, &mut self.messages);
+            let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+            if let Some(conf) = self
+                .scenes
+                .update(TOOL_NAME, current_config, &mut self.messages)
+                .or(session)
+                .or(dropped)
+            {
+                if let Err(e) = self
+                    .message_out
+                    .send(MessageToTom::NewConfig(Box::new(conf.clone())))
+                {
+                    self.messages
+                        .push(format!("Error while sending new conf: {e}"));
+                }
+                self.current_config = Some(conf);
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        //read message queue
+        match self.messages_in.try_recv() {
+            Err(e) => match e {
+                std::sync::mpsc::TryRecvError::Empty => {}
+                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(String::from(
+                    "Internal error: lost connection between UI and logic",
+                )),
+            },
+            Ok(v) => match v {
+                MessageToTomUI::NewConfig(cfg) => self.current_config = Some(*cfg),
+                MessageToTomUI::KeyMapped(key, control) => self.mappings.set(key, control),
+            },
+        }
+    }
+}
+
+impl eframe::App for TomUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_scene(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+///Render a single tom to a wav file, without starting the jack client. The
+/// configuration of the last session is used, unless another one is given.
+pub fn render_tom(options: &RenderOptions) -> Result<(), RenderError> {
+    let conf = options.load_configuration(TOOL_NAME, Configuration::new(options.rate))?;
+    let velocity = super::velocity::response(
+        options.note_velocity(),
+        conf.velocity_curve.get_value(),
+        conf.dynamics.get_value(),
+    );
+    let mut samples: Vec<f64> = (1..=Voice::total_frames(&conf))
+        .rev()
+        .map(|frames_left| {
+            let noise = 1.0 - random::<f64>() * 2.0;
+            Voice::hit_sample(&conf, options.rate, frames_left, noise) * velocity
+        })
+        .collect();
+    let ceiling = super::output::ceiling(&conf.soft_clipper, &conf.ceiling);
+    super::output::clip_all(&mut samples, ceiling);
+    options.write(&samples)
+}
+
+pub fn tom() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the tom and the UI
+    let (send_to_tom, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_tom) = spsc::channel(MESSAGES_CAPACITY);
+
+    let synth = Tom::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Tom",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| Ok(Box::new(TomUI::new(cc, status, rcv_from_tom, send_to_tom)))),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Configuration, TomSize, Voice};
+
+    const RATE: usize = 48000;
+
+    #[test]
+    fn presets_from_low_to_high() {
+        let low = Configuration::preset(TomSize::Low, RATE);
+        let mid = Configuration::preset(TomSize::Mid, RATE);
+        let high = Configuration::preset(TomSize::High, RATE);
+        assert!(low.end_freq.get_value() < mid.end_freq.get_value());
+        assert!(mid.end_freq.get_value() < high.end_freq.get_value());
+        assert!(low.decay.get_value() > high.decay.get_value());
+
+        //a preset keeps the values it does not define
+        let mut conf = high.clone();
+        conf.noise.from_midi_value(0);
+        conf.apply_preset(TomSize::Low, RATE);
+        assert_eq!(conf.start_freq, low.start_freq);
+        assert_eq!(conf.noise.get_value(), 0.0);
+    }
+
+    #[test]
+    fn hit_starts_and_ends_silent() {
+        let conf = Configuration::new(RATE);
+        let total_frames = Voice::total_frames(&conf);
+        assert_eq!(Voice::hit_sample(&conf, RATE, total_frames, 1.0), 0.0);
+        assert!(Voice::hit_sample(&conf, RATE, 1, 1.0).abs() < 1e-6);
+        //the noise is only heard at the start of the hit
+        let attack = conf.attack.get_value();
+        let start = total_frames - attack;
+        let with_noise = Voice::hit_sample(&conf, RATE, start, 1.0);
+        let without_noise = Voice::hit_sample(&conf, RATE, start, 0.0);
+        assert!(with_noise - without_noise > 0.1);
+        let end = total_frames / 2;
+        let with_noise = Voice::hit_sample(&conf, RATE, end, 1.0);
+        let without_noise = Voice::hit_sample(&conf, RATE, end, 0.0);
+        assert!((with_noise - without_noise).abs() < 1e-6);
+    }
+}
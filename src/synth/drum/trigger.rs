@@ -0,0 +1,99 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::{note_name, MidiNote},
+};
+
+///The notes a drum is played by, so that several drums can share a midi
+/// channel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum Trigger {
+    ///Every note plays the drum
+    #[default]
+    Any,
+    ///The notes between 'lowest' and 'highest' (both included) play the drum
+    Notes { lowest: u8, highest: u8 },
+}
+
+impl Trigger {
+    ///The trigger of a single note, given by its index
+    pub fn learned(note_index: usize) -> Trigger {
+        let note = MidiNote::raw_byte(note_index).min(MidiNote::MAX);
+        Trigger::Notes {
+            lowest: note,
+            highest: note,
+        }
+    }
+
+    ///True if the note, given by its index, plays the drum
+    pub fn accepts(&self, note_index: usize) -> bool {
+        match self {
+            Trigger::Any => true,
+            Trigger::Notes { lowest, highest } => MidiNote::from_index(note_index)
+                .is_some_and(|note| (*lowest..=*highest).contains(&note.number())),
+        }
+    }
+
+    ///Draw the trigger on the user interface. True is returned if the user
+    /// asks to learn the next note received.
+    pub fn draw(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut learn = false;
+        ui.horizontal(|ui| {
+            ui.label(tr("Trigger: "));
+            let any = *self == Trigger::Any;
+            if ui.selectable_label(any, tr("Any note")).clicked() {
+                *self = Trigger::Any;
+            }
+            if ui.selectable_label(!any, tr("Notes")).clicked() && any {
+                //the kick of the general midi drums
+                *self = Trigger::learned(36 + 12);
+            }
+            learn = ui.button(tr("Learn")).clicked();
+        });
+        if let Trigger::Notes { lowest, highest } = self {
+            ui.horizontal(|ui| {
+                let note = |ui: &mut egui::Ui, value: &mut u8| {
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .range(0..=127)
+                            .custom_formatter(|n, _| note_name(MidiNote::raw_index(n as u8))),
+                    );
+                };
+                note(ui, lowest);
+                ui.label(tr("to"));
+                note(ui, highest);
+            });
+            *highest = (*highest).max(*lowest);
+        }
+        learn
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Trigger;
+
+    #[test]
+    fn notes_played() {
+        assert!(Trigger::Any.accepts(3));
+        let kick = Trigger::learned(36 + 12);
+        assert_eq!(
+            kick,
+            Trigger::Notes {
+                lowest: 36,
+                highest: 36
+            }
+        );
+        assert!(kick.accepts(36 + 12));
+        assert!(!kick.accepts(37 + 12));
+        assert!(!kick.accepts(0));
+        let toms = Trigger::Notes {
+            lowest: 41,
+            highest: 50,
+        };
+        assert!(toms.accepts(41 + 12) && toms.accepts(50 + 12));
+        assert!(!toms.accepts(51 + 12));
+    }
+}
@@ -0,0 +1,115 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{configuration::FloatValueInRange, i18n::tr, synth::hardware::KeyBoardKey};
+
+///The gain of a hit at full velocity, leaving headroom for the hits played
+/// at once
+const FULL_VELOCITY_GAIN: f64 = 127.0 / 256.0;
+///The difference of level, in dB, between the softest and the loudest hits
+/// of the exponential response
+const EXPONENTIAL_RANGE_DB: f64 = 40.0;
+
+///How the velocity of the notes changes the volume of the hits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum VelocityResponse {
+    ///The volume follows the velocity
+    #[default]
+    Linear,
+    ///The soft hits are much quieter than the loud ones, as the velocity is
+    /// spread over a range of decibels
+    Exponential,
+    ///Every hit has the same volume, e.g. for sequenced parts
+    Fixed,
+}
+
+impl VelocityResponse {
+    pub const ALL: [VelocityResponse; 3] = [
+        VelocityResponse::Linear,
+        VelocityResponse::Exponential,
+        VelocityResponse::Fixed,
+    ];
+
+    ///The velocity of a hit, see [response] for the curve and the dynamics.
+    /// They have no effect on the fixed response.
+    pub fn apply(&self, velocity: f64, curve: f64, dynamics: f64) -> f64 {
+        match self {
+            VelocityResponse::Linear => response(velocity, curve, dynamics),
+            VelocityResponse::Exponential => {
+                let velocity = velocity.clamp(0.0, 1.0);
+                let gain = if velocity > 0.0 {
+                    crate::dsp::db_to_gain(EXPONENTIAL_RANGE_DB * (velocity - 1.0))
+                } else {
+                    0.0
+                };
+                response(gain, curve, dynamics)
+            }
+            VelocityResponse::Fixed => FULL_VELOCITY_GAIN,
+        }
+    }
+
+    ///Draw the choice of the response on the user interface
+    pub fn draw(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr("Velocity: "));
+            for response in Self::ALL {
+                ui.selectable_value(self, response, tr(&response.to_string()));
+            }
+        });
+    }
+}
+
+impl std::fmt::Display for VelocityResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VelocityResponse::Linear => write!(f, "Linear"),
+            VelocityResponse::Exponential => write!(f, "Exponential"),
+            VelocityResponse::Fixed => write!(f, "Fixed"),
+        }
+    }
+}
+
+///Change the velocity of a hit according to the response of an instrument.
+/// 'curve' bends the response (negative: loud sooner, positive: loud later,
+/// 0: linear) and 'dynamics' compresses (below 1) or expands (above 1) the
+/// velocities around the middle one. A value of 0 for the dynamics plays
+/// every hit with the same velocity.
+pub fn response(velocity: f64, curve: f64, dynamics: f64) -> f64 {
+    let normalized = velocity.clamp(0.0, 1.0);
+    let curved = normalized.powf(4.0_f64.powf(curve));
+    let scaled = (0.5 + (curved - 0.5) * dynamics).clamp(0.0, 1.0);
+    scaled * FULL_VELOCITY_GAIN
+}
+
+///The default velocity curve: linear
+pub fn default_curve() -> FloatValueInRange {
+    FloatValueInRange::new(0.0, -1.0, 1.0, "velocity curve", KeyBoardKey::VelocityCurve)
+}
+
+///The default dynamics: velocities are not changed
+pub fn default_dynamics() -> FloatValueInRange {
+    FloatValueInRange::new(1.0, 0.0, 2.0, "dynamics", KeyBoardKey::Dynamics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{VelocityResponse, FULL_VELOCITY_GAIN};
+
+    #[test]
+    fn velocity_responses() {
+        let linear = |v| VelocityResponse::Linear.apply(v, 0.0, 1.0);
+        let exponential = |v| VelocityResponse::Exponential.apply(v, 0.0, 1.0);
+        assert!((linear(0.5) - 0.5 * FULL_VELOCITY_GAIN).abs() < 1e-9);
+        assert!((exponential(1.0) - FULL_VELOCITY_GAIN).abs() < 1e-9);
+        assert_eq!(exponential(0.0), 0.0);
+        //the soft hits are quieter, half the velocity is 20 dB below the full one
+        assert!((exponential(0.5) - 0.1 * FULL_VELOCITY_GAIN).abs() < 1e-9);
+        assert!(exponential(0.3) < linear(0.3));
+        for velocity in [0.1, 0.5, 1.0] {
+            assert_eq!(
+                VelocityResponse::Fixed.apply(velocity, 1.0, 0.5),
+                FULL_VELOCITY_GAIN
+            );
+        }
+    }
+}
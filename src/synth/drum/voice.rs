@@ -0,0 +1,19 @@
+///The sound of a drum without its jack client: the hits being played and
+/// the configuration they are played with. A drum can then be played on its
+/// own or as part of the drum kit.
+pub trait DrumVoice {
+    ///Start a hit of a note, at the velocity given by the midi input
+    fn start(&mut self, note_index: usize, velocity: f64);
+
+    ///Stop every hit at once
+    fn panic(&mut self);
+
+    ///True while a hit is being played
+    fn playing(&self) -> bool;
+
+    ///Compute the next frame of the hits being played: mono, left and right
+    fn next_frame(&mut self) -> [f64; 3];
+
+    ///The level of the soft clipper, if it is on
+    fn ceiling(&self) -> Option<f64>;
+}
@@ -1,7 +1,12 @@
 use std::{collections::HashMap, fmt::Display};
 
+use serde::{Deserialize, Serialize};
+
+///The midi control sent by the modulation wheel
+pub const MOD_WHEEL: u8 = 1;
+
 ///This enum represent all the dials/button from the midi device
-#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum KeyBoardKey {
     WaveSelection,
     Overtone(u8),
@@ -23,6 +28,43 @@ pub enum KeyBoardKey {
     ModulationIntensity,
     Activate,
     Tempo,
+    Mute,
+    Dim,
+    VoiceMode,
+    Spread,
+    SpreadAlternate,
+    VelocityCurve,
+    Dynamics,
+    Synthesis,
+    FmRatio,
+    FmIndex,
+    Variation,
+    FilterCutoff,
+    FilterResonance,
+    FilterEnvelope,
+    LfoWave,
+    LfoRate,
+    LfoDepth,
+    LfoDestination,
+    SubLevel,
+    SubWave,
+    AftertouchDestination,
+    AftertouchDepth,
+    PolyAftertouchDestination,
+    PolyAftertouchDepth,
+    PitchEnvelopeAmount,
+    PitchEnvelopeDecay,
+    RingFrequency,
+    RingMix,
+    Noise,
+    SoftClipper,
+    Ceiling,
+    SongNext,
+    SongPrevious,
+    ///Mute a midi channel (from 0 to 15)
+    ChannelMute(u8),
+    ///Solo a midi channel (from 0 to 15)
+    ChannelSolo(u8),
 }
 
 impl Display for KeyBoardKey {
@@ -48,11 +90,49 @@ impl Display for KeyBoardKey {
             KeyBoardKey::Activate => write!(f, "Activate"),
             KeyBoardKey::Tempo => write!(f, "Tempo"),
             KeyBoardKey::Duration => write!(f, "Duration"),
+            KeyBoardKey::Mute => write!(f, "Mute"),
+            KeyBoardKey::Dim => write!(f, "Dim"),
+            KeyBoardKey::VoiceMode => write!(f, "Voice Mode"),
+            KeyBoardKey::Spread => write!(f, "Stereo Spread"),
+            KeyBoardKey::SpreadAlternate => write!(f, "Alternate Spread"),
+            KeyBoardKey::VelocityCurve => write!(f, "Velocity Curve"),
+            KeyBoardKey::Dynamics => write!(f, "Dynamics"),
+            KeyBoardKey::Synthesis => write!(f, "Synthesis Type"),
+            KeyBoardKey::FmRatio => write!(f, "FM Ratio"),
+            KeyBoardKey::FmIndex => write!(f, "FM Index"),
+            KeyBoardKey::Variation => write!(f, "Variation"),
+            KeyBoardKey::FilterCutoff => write!(f, "Filter Cutoff"),
+            KeyBoardKey::FilterResonance => write!(f, "Filter Resonance"),
+            KeyBoardKey::FilterEnvelope => write!(f, "Filter Envelope"),
+            KeyBoardKey::LfoWave => write!(f, "LFO Wave"),
+            KeyBoardKey::LfoRate => write!(f, "LFO Rate"),
+            KeyBoardKey::LfoDepth => write!(f, "LFO Depth"),
+            KeyBoardKey::LfoDestination => write!(f, "LFO Destination"),
+            KeyBoardKey::SubLevel => write!(f, "Sub Oscillator Level"),
+            KeyBoardKey::SubWave => write!(f, "Sub Oscillator Wave"),
+            KeyBoardKey::AftertouchDestination => write!(f, "Aftertouch Destination"),
+            KeyBoardKey::AftertouchDepth => write!(f, "Aftertouch Depth"),
+            KeyBoardKey::PolyAftertouchDestination => write!(f, "Poly Aftertouch Destination"),
+            KeyBoardKey::PolyAftertouchDepth => write!(f, "Poly Aftertouch Depth"),
+            KeyBoardKey::PitchEnvelopeAmount => write!(f, "Pitch Envelope Amount"),
+            KeyBoardKey::PitchEnvelopeDecay => write!(f, "Pitch Envelope Decay"),
+            KeyBoardKey::RingFrequency => write!(f, "Ring Modulation Frequency"),
+            KeyBoardKey::RingMix => write!(f, "Ring Modulation Mix"),
+            KeyBoardKey::Noise => write!(f, "Noise"),
+            KeyBoardKey::SoftClipper => write!(f, "Soft Clipper"),
+            KeyBoardKey::Ceiling => write!(f, "Ceiling"),
+            KeyBoardKey::SongNext => write!(f, "Next Song"),
+            KeyBoardKey::SongPrevious => write!(f, "Previous Song"),
+            KeyBoardKey::ChannelMute(v) => write!(f, "Mute Channel {}", v + 1),
+            KeyBoardKey::ChannelSolo(v) => write!(f, "Solo Channel {}", v + 1),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+///The midi controls mapped to the keys. It is saved as the list of the
+/// mapped controls, so that it can be edited by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(u8, KeyBoardKey)>", into = "Vec<(u8, KeyBoardKey)>")]
 pub struct HardWare {
     midi_keys_mapping: HashMap<u8, KeyBoardKey>,
     kb_keys_mapping: HashMap<KeyBoardKey, u8>,
@@ -91,6 +171,24 @@ impl Default for HardWare {
     }
 }
 
+impl From<Vec<(u8, KeyBoardKey)>> for HardWare {
+    fn from(value: Vec<(u8, KeyBoardKey)>) -> Self {
+        let mut hardware = HardWare::new();
+        for (midi_key, key) in value {
+            hardware.update_key(key, midi_key);
+        }
+        hardware
+    }
+}
+
+impl From<HardWare> for Vec<(u8, KeyBoardKey)> {
+    fn from(value: HardWare) -> Self {
+        let mut mapping: Vec<(u8, KeyBoardKey)> = value.midi_keys_mapping.into_iter().collect();
+        mapping.sort();
+        mapping
+    }
+}
+
 impl HardWare {
     pub fn new() -> Self {
         Self {
@@ -99,9 +197,12 @@ impl HardWare {
         }
     }
 
+    ///The key mapped to a midi control. Unless it is mapped to another key,
+    /// the modulation wheel controls the intensity of the modulation.
     pub fn get_keyboard_key(&self, midi_control: u8) -> Option<KeyBoardKey> {
         match self.midi_keys_mapping.get(&midi_control) {
             Some(k) => Some(k.clone()),
+            None if midi_control == MOD_WHEEL => Some(KeyBoardKey::ModulationIntensity),
             None => None,
         }
     }
@@ -132,3 +233,38 @@ impl HardWare {
         self.kb_keys_mapping.insert(key, midi_key);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{HardWare, KeyBoardKey, MOD_WHEEL};
+
+    #[test]
+    fn mapping_round_trip() {
+        let hardware = HardWare::default();
+        let json = serde_json::to_string(&hardware).unwrap();
+        let loaded: HardWare = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, hardware);
+        assert_eq!(loaded.get_keyboard_key(95), Some(KeyBoardKey::Gain));
+    }
+
+    #[test]
+    fn mod_wheel_by_default() {
+        let mut hardware = HardWare::new();
+        assert_eq!(
+            hardware.get_keyboard_key(MOD_WHEEL),
+            Some(KeyBoardKey::ModulationIntensity)
+        );
+        hardware.update_key(KeyBoardKey::Gain, MOD_WHEEL);
+        assert_eq!(
+            hardware.get_keyboard_key(MOD_WHEEL),
+            Some(KeyBoardKey::Gain)
+        );
+        hardware.clear_key(KeyBoardKey::Gain);
+        assert_eq!(
+            hardware.get_keyboard_key(MOD_WHEEL),
+            Some(KeyBoardKey::ModulationIntensity)
+        );
+        //the default is not saved with the mapping
+        assert!(Vec::<(u8, KeyBoardKey)>::from(hardware).is_empty());
+    }
+}
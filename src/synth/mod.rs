@@ -1,8 +1,21 @@
 mod drum;
 
 pub mod hardware;
+mod pads;
+mod riser;
 pub mod rsynth;
 pub mod wavetype;
 
+pub use drum::clap;
+pub use drum::cymbal;
+pub use drum::drumkit;
+pub use drum::hihat;
 pub use drum::kick;
 pub use drum::snare;
+pub use drum::tom;
+pub use drum::{
+    render_clap, render_cymbal, render_drumkit, render_hihat, render_kick, render_snare,
+    render_tom, RenderError, RenderOptions,
+};
+pub use pads::pads;
+pub use riser::riser;
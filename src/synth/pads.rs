@@ -0,0 +1,593 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::{note_name, MidiInput, MidiNote},
+    scene::SceneListener,
+    session::Autosave,
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus, ShortcutAction, Shortcuts},
+};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "pads";
+///The number of pads
+const NB_PADS: usize = 16;
+///The number of pads on every row of the grid
+const NB_COLUMNS: usize = 4;
+///The midi note of the first pad (the kick of the general midi drums)
+const FIRST_NOTE: u8 = 36;
+///The number of choke groups, 0 meaning that the pad is not part of any
+const NB_CHOKE_GROUPS: u8 = 8;
+///The highest number of samples played at once
+const MAX_VOICES: usize = 32;
+///The duration of the fade out of a choked sample, in seconds
+const CHOKE_DURATION: f64 = 0.005;
+///The longest sample that can be loaded, in seconds
+const SAMPLE_MAX_DURATION: f64 = 10.0;
+
+///A pad, playing a sample when its note is received
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Pad {
+    ///The path of the wav file played by the pad
+    sample: String,
+    ///The midi note triggering the pad
+    note: u8,
+    ///The volume of the pad
+    gain: f64,
+    ///Triggering a pad silences the other pads of its choke group (e.g. an
+    /// open hi-hat is cut by the closed one). 0 for no group.
+    choke: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct PadsConfiguration {
+    pads: Vec<Pad>,
+}
+
+impl Default for PadsConfiguration {
+    fn default() -> Self {
+        Self {
+            pads: (0..NB_PADS)
+                .map(|i| Pad {
+                    sample: String::new(),
+                    note: FIRST_NOTE + i as u8,
+                    gain: 1.0,
+                    choke: 0,
+                })
+                .collect(),
+        }
+    }
+}
+
+///A sample being played
+#[derive(Debug)]
+struct Voice {
+    ///The pad playing the sample
+    pad: usize,
+    ///The position in the sample, in frames
+    position: usize,
+    ///The volume of the voice: the gain of the pad and the velocity
+    gain: f64,
+    ///The number of frames left before the end of the fade out, once choked
+    fade: Option<usize>,
+}
+
+impl Voice {
+    ///The next frame of the voice, None once it is over
+    fn next(&mut self, sample: Option<&[f32]>, fade_length: usize) -> Option<f64> {
+        let value = *sample?.get(self.position)? as f64;
+        self.position += 1;
+        let fade = match &mut self.fade {
+            Some(0) => return None,
+            Some(left) => {
+                *left -= 1;
+                *left as f64 / fade_length as f64
+            }
+            None => 1.0,
+        };
+        Some(value * self.gain * fade)
+    }
+}
+
+///Play the samples of the pads
+#[derive(Debug)]
+struct PadPlayer {
+    ///The samples being played, the oldest first
+    voices: Vec<Voice>,
+}
+
+impl PadPlayer {
+    fn new() -> PadPlayer {
+        PadPlayer {
+            voices: Vec::with_capacity(MAX_VOICES),
+        }
+    }
+
+    ///Start the sample of a pad, choking the pads of its group
+    fn trigger(&mut self, pad: usize, gain: f64, pads: &[Pad], fade_length: usize) {
+        let group = pads.get(pad).map(|p| p.choke).unwrap_or(0);
+        if group != 0 {
+            for voice in self.voices.iter_mut() {
+                if voice.fade.is_none() && pads.get(voice.pad).map(|p| p.choke) == Some(group) {
+                    voice.fade = Some(fade_length);
+                }
+            }
+        }
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            pad,
+            position: 0,
+            gain,
+            fade: None,
+        });
+    }
+
+    ///The next frame of every voice, mixed together
+    fn render(&mut self, samples: &[Option<Vec<f32>>], fade_length: usize) -> f64 {
+        let mut value = 0.0;
+        self.voices.retain_mut(|voice| {
+            let sample = samples.get(voice.pad).and_then(|s| s.as_deref());
+            match voice.next(sample, fade_length) {
+                Some(v) => {
+                    value += v;
+                    true
+                }
+                None => false,
+            }
+        });
+        value
+    }
+
+    fn stop(&mut self) {
+        self.voices.clear();
+    }
+}
+
+struct Pads {
+    configuration: PadsConfiguration,
+    ///The sample of every pad
+    samples: Vec<Option<Vec<f32>>>,
+    player: PadPlayer,
+    ///The pads triggered during the current cycle: position in the buffer,
+    /// pad and gain
+    events: Vec<(usize, usize, f64)>,
+    ///The pad learning the next note received
+    learn: Option<usize>,
+    ///The duration of the fade out of a choked sample, in frames
+    fade_length: usize,
+    /// The midi input triggering the pads
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The output audio port
+    audio_mono_out: jack::Port<jack::AudioOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToPads>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToPadsUI>,
+}
+
+impl Pads {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToPads>,
+        messages_out: std::sync::mpsc::Sender<MessageToPadsUI>,
+    ) -> Result<Pads, CommonError> {
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+
+        Ok(Pads {
+            configuration: PadsConfiguration::default(),
+            samples: vec![None; NB_PADS],
+            player: PadPlayer::new(),
+            events: Vec::with_capacity(MAX_VOICES),
+            learn: None,
+            fade_length: ((CHOKE_DURATION * client.sample_rate() as f64) as usize).max(1),
+            midi_in: m_in,
+            audio_mono_out: a_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+}
+
+impl jack::ProcessHandler for Pads {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        self.events.clear();
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToPads::NewConfiguration(conf) => self.configuration = conf,
+                MessageToPads::Sample(pad, sample) => {
+                    if let Some(s) = self.samples.get_mut(pad) {
+                        *s = sample;
+                    }
+                }
+                MessageToPads::Trigger(pad) => {
+                    if let Some(p) = self.configuration.pads.get(pad) {
+                        self.events.push((0, pad, p.gain));
+                    }
+                }
+                MessageToPads::Learn(pad) => self.learn = Some(pad),
+                MessageToPads::Panic => self.player.stop(),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::NoteStart {
+                note_index,
+                timing,
+                velocity,
+                ..
+            } = midi
+            {
+                let Some(note) = MidiNote::from_index(note_index) else {
+                    continue;
+                };
+                if let Some(pad) = self.learn.take() {
+                    if let Some(p) = self.configuration.pads.get_mut(pad) {
+                        p.note = note.number();
+                    }
+                    if let Err(e) = self.messages_out.send(MessageToPadsUI::NewConfiguration(
+                        self.configuration.clone(),
+                    )) {
+                        eprintln!("Internal error: {e}");
+                    }
+                }
+                crate::utils::statistics::note_played();
+                let velocity = velocity.clamp(0.0, 1.0);
+                for (pad, p) in self.configuration.pads.iter().enumerate() {
+                    if p.note == note.number() {
+                        self.events.push((timing as usize, pad, p.gain * velocity));
+                    }
+                }
+            }
+        }
+
+        let out = self.audio_mono_out.as_mut_slice(ps);
+        let mut next_event = 0;
+        for (i, v) in out.iter_mut().enumerate() {
+            while let Some((timing, pad, gain)) = self.events.get(next_event) {
+                if *timing > i {
+                    break;
+                }
+                self.player
+                    .trigger(*pad, *gain, &self.configuration.pads, self.fade_length);
+                next_event += 1;
+            }
+            *v = self.player.render(&self.samples, self.fade_length) as f32;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+enum MessageToPads {
+    NewConfiguration(PadsConfiguration),
+    ///The sample of a pad, None to silence it
+    Sample(usize, Option<Vec<f32>>),
+    ///Play a pad, as if its note was received
+    Trigger(usize),
+    ///The next note received triggers the given pad
+    Learn(usize),
+    Panic,
+}
+
+#[derive(Debug)]
+enum MessageToPadsUI {
+    NewConfiguration(PadsConfiguration),
+}
+
+struct PadsUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToPadsUI>,
+    messages_out: std::sync::mpsc::Sender<MessageToPads>,
+    messages: Vec<String>,
+    conf: PadsConfiguration,
+    ///The path of the sample sent for every pad
+    loaded: Vec<String>,
+    ///The pad being edited
+    selected: usize,
+    ///The path typed for the sample of the selected pad
+    sample_path: String,
+    ///The audio rate, used to convert the samples
+    rate: usize,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<PadsConfiguration>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl PadsUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToPadsUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToPads>,
+        rate: usize,
+    ) -> PadsUI {
+        PadsUI {
+            status,
+            messages_in,
+            messages_out,
+            messages: Vec::new(),
+            conf: PadsConfiguration::default(),
+            loaded: vec![String::new(); NB_PADS],
+            selected: 0,
+            sample_path: String::new(),
+            rate,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+            shortcuts: Shortcuts::load(),
+        }
+    }
+
+    ///Send the samples of the pads whose path changed
+    fn load_samples(&mut self) {
+        for pad in 0..self.conf.pads.len().min(NB_PADS) {
+            let path = self.conf.pads[pad].sample.clone();
+            if path == self.loaded[pad] {
+                continue;
+            }
+            self.loaded[pad] = path.clone();
+            if path.is_empty() {
+                self.send_message(MessageToPads::Sample(pad, None));
+                continue;
+            }
+            match crate::wavreader::load_mono(std::path::Path::new(&path), self.rate) {
+                Ok(mut samples) => {
+                    samples.truncate((self.rate as f64 * SAMPLE_MAX_DURATION) as usize);
+                    self.send_message(MessageToPads::Sample(pad, Some(samples)));
+                }
+                Err(e) => {
+                    self.messages.push(format!("Unable to load '{path}': {e}"));
+                    self.send_message(MessageToPads::Sample(pad, None));
+                }
+            }
+        }
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                self.send_message(MessageToPads::Panic);
+            }
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToPadsUI::NewConfiguration(c) => self.conf = c,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_message(MessageToPads::NewConfiguration(self.conf.clone()));
+        }
+        self.load_samples();
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        let mut triggered = None;
+        egui::Grid::new("pads").show(ui, |ui| {
+            for (index, pad) in self.conf.pads.iter().enumerate() {
+                let name = std::path::Path::new(&pad.sample)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let button = egui::Button::new(format!(
+                    "{} ({})\n{}",
+                    index + 1,
+                    note_name(MidiNote::raw_index(pad.note)),
+                    name
+                ))
+                .min_size(egui::vec2(72.0, 48.0))
+                .selected(index == self.selected);
+                if ui.add(button).clicked() {
+                    triggered = Some(index);
+                }
+                if (index + 1) % NB_COLUMNS == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+        if let Some(index) = triggered {
+            if index != self.selected {
+                self.selected = index;
+                self.sample_path = self.conf.pads[index].sample.clone();
+            }
+            self.send_message(MessageToPads::Trigger(index));
+        }
+
+        ui.separator();
+        let selected = self.selected;
+        let Some(pad) = self.conf.pads.get_mut(selected) else {
+            return;
+        };
+        ui.label(format!("{}{}", tr("Pad "), selected + 1));
+        ui.horizontal(|ui| {
+            ui.label(tr("Sample: "));
+            ui.text_edit_singleline(&mut self.sample_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button(tr("Load")).clicked() {
+                pad.sample = self.sample_path.trim().to_string();
+            }
+            if ui.button(tr("Clear")).clicked() {
+                pad.sample.clear();
+                self.sample_path.clear();
+            }
+        });
+        let mut learn = false;
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{}{}",
+                tr("Note: "),
+                note_name(MidiNote::raw_index(pad.note))
+            ));
+            learn = ui.button(tr("Learn")).clicked();
+        });
+        crate::utils::create_f64_slider(ui, "Gain: ", &mut pad.gain, RangeInclusive::new(0.0, 2.0));
+        ui.horizontal(|ui| {
+            ui.label(tr("Choke group (0: none): "));
+            ui.add(egui::Slider::new(
+                &mut pad.choke,
+                RangeInclusive::new(0, NB_CHOKE_GROUPS),
+            ));
+        });
+        if learn {
+            self.send_message(MessageToPads::Learn(selected));
+            self.messages
+                .push(String::from(tr("Play the note of the pad")));
+        }
+
+        if self.conf != current_conf {
+            self.send_message(MessageToPads::NewConfiguration(self.conf.clone()));
+            self.load_samples();
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                self.shortcuts
+                    .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+            });
+
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_message(&mut self, msg: MessageToPads) {
+        if let Err(e) = self.messages_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for PadsUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn pads() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the pads and the UI
+    let (send_to_pads, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_pads) = std::sync::mpsc::channel();
+
+    let rate = client.sample_rate();
+    let pads = Pads::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(pads)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Pads",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(360.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(PadsUI::new(
+                cc,
+                status,
+                rcv_from_pads,
+                send_to_pads,
+                rate,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PadPlayer, PadsConfiguration};
+
+    #[test]
+    fn choke_group() {
+        let mut conf = PadsConfiguration::default();
+        conf.pads[0].choke = 1;
+        conf.pads[1].choke = 1;
+        let samples = vec![
+            Some(vec![1.0; 100]),
+            Some(vec![0.5; 100]),
+            Some(vec![0.25; 3]),
+        ];
+        let mut player = PadPlayer::new();
+
+        player.trigger(0, 1.0, &conf.pads, 4);
+        player.trigger(2, 1.0, &conf.pads, 4);
+        assert_eq!(player.render(&samples, 4), 1.25);
+        //the second pad chokes the first one, which fades out
+        player.trigger(1, 2.0, &conf.pads, 4);
+        assert_eq!(player.render(&samples, 4), 0.75 + 0.25 + 1.0);
+        for _ in 0..4 {
+            player.render(&samples, 4);
+        }
+        //the first pad is silenced, the short sample is over
+        assert_eq!(player.voices.len(), 1);
+        assert_eq!(player.render(&samples, 4), 1.0);
+    }
+}
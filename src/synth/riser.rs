@@ -0,0 +1,516 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dsp::{StateVariableFilter, SvfCoefficients},
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{
+        ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter,
+        ShortcutAction, Shortcuts, TapTempo,
+    },
+};
+
+///The midi clock sends 24 ticks per quarter note
+const CLOCK_TICKS_PER_QUARTER: usize = 24;
+///The midi clock tick message
+const CLOCK_TICK: u8 = 0xF8;
+///The midi stop message, which cuts the swell
+const CLOCK_STOP: u8 = 0xFC;
+///The clock is considered lost if no tick was received for that long, in seconds
+const CLOCK_TIMEOUT: f64 = 1.0;
+///The cutoff of the noise filter at the start of the swell, in Hz
+const START_CUTOFF: f64 = 200.0;
+///The cutoff of the noise filter at the end of the swell, in Hz
+const END_CUTOFF: f64 = 18000.0;
+///The duration of the fade at the very end of the swell, in seconds, to avoid a click
+const END_RAMP_DURATION: f64 = 0.003;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct RiserConfiguration {
+    ///The number of bars of the swell
+    bars: usize,
+    ///The number of quarter notes per bar
+    nb_notes: usize,
+    ///The number of quarters per minutes, used when no midi clock is received
+    bpm: usize,
+    ///The volume at the end of the swell
+    volume: f64,
+    ///If true, the tempo of the incoming midi clock is used instead of 'bpm'
+    follow_clock: bool,
+}
+
+impl Default for RiserConfiguration {
+    fn default() -> Self {
+        Self {
+            bars: 1,
+            nb_notes: 4,
+            bpm: 120,
+            volume: 0.5,
+            follow_clock: true,
+        }
+    }
+}
+
+///Follow the tempo of an incoming midi clock
+struct MidiClock {
+    ///The time of the last clock tick
+    last_tick: Option<usize>,
+    ///The average number of frames between two clock ticks
+    frames_per_tick: Option<f64>,
+}
+
+impl MidiClock {
+    fn new() -> MidiClock {
+        MidiClock {
+            last_tick: None,
+            frames_per_tick: None,
+        }
+    }
+
+    ///Register a clock tick received at 'time' (in frames)
+    fn tick(&mut self, time: usize, rate: usize) {
+        if let Some(last_tick) = self.last_tick {
+            let interval = (time - last_tick) as f64;
+            if interval < CLOCK_TIMEOUT * rate as f64 {
+                self.frames_per_tick = Some(match self.frames_per_tick {
+                    Some(frames) => 0.9 * frames + 0.1 * interval,
+                    None => interval,
+                });
+            } else {
+                self.frames_per_tick = None;
+            }
+        }
+        self.last_tick = Some(time);
+    }
+
+    ///The tempo of the clock at 'time' (in frames), if it is still running
+    fn bpm(&self, time: usize, rate: usize) -> Option<f64> {
+        let last_tick = self.last_tick?;
+        if (time - last_tick) as f64 > CLOCK_TIMEOUT * rate as f64 {
+            return None;
+        }
+        self.frames_per_tick
+            .map(|frames| 60.0 * rate as f64 / (frames * CLOCK_TICKS_PER_QUARTER as f64))
+    }
+}
+
+struct Riser {
+    configuration: RiserConfiguration,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    /// The midi input triggering the swells and providing the clock
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The output audio port
+    audio_mono_out: jack::Port<jack::AudioOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToRiser>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToRiserUI>,
+    ///The audio rate (number of audio frames per seconds)
+    rate: usize,
+    ///The number of frames since the start of the client
+    time: usize,
+    ///The midi clock giving the tempo
+    clock: MidiClock,
+    ///The duration of the current swell, in frames
+    duration: usize,
+    ///The position in the current swell, None if nothing is playing
+    position: Option<usize>,
+    ///The filter giving its color to the noise
+    filter: StateVariableFilter,
+    /// The next key to map
+    next_key_map: Option<KeyBoardKey>,
+}
+
+impl Riser {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToRiser>,
+        messages_out: std::sync::mpsc::Sender<MessageToRiserUI>,
+    ) -> Result<Riser, CommonError> {
+        let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+
+        Ok(Riser {
+            configuration: RiserConfiguration::default(),
+            keyboard: HardWare::new(),
+            midi_in: m_in,
+            audio_mono_out: a_out,
+            messages_in: ChannelWatchdog::new("riser", messages_in),
+            messages_out,
+            rate: client.sample_rate(),
+            time: 0,
+            clock: MidiClock::new(),
+            duration: 0,
+            position: None,
+            filter: StateVariableFilter::new(),
+            next_key_map: None,
+        })
+    }
+
+    ///The duration of a swell with the current configuration, in frames
+    fn swell_duration(&self) -> usize {
+        let quarters = self.configuration.bars * self.configuration.nb_notes;
+        let bpm = match self.clock.bpm(self.time, self.rate) {
+            Some(bpm) if self.configuration.follow_clock => bpm,
+            _ => self.configuration.bpm as f64,
+        };
+        (quarters as f64 * 60.0 * self.rate as f64 / bpm) as usize
+    }
+
+    fn send_message(
+        msg: MessageToRiserUI,
+        messages_out: &mut std::sync::mpsc::Sender<MessageToRiserUI>,
+    ) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Riser {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToRiser::NewConfiguration(conf) => self.configuration = conf,
+                MessageToRiser::SetKey(key) => self.next_key_map = Some(key),
+                MessageToRiser::ClearKey(key) => self.keyboard.clear_key(key),
+                MessageToRiser::Panic => self.position = None,
+            }
+        }
+
+        let mut start = None;
+        let mut stop = false;
+        let mut conf_changed = false;
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            match midi {
+                MidiInput::NoteStart {
+                    channel: _,
+                    note_index: _,
+                    timing,
+                    velocity: _,
+                } => start = Some(timing as usize),
+                MidiInput::Controller {
+                    channel: _,
+                    control,
+                    value,
+                } => {
+                    if let Some(key) = self.next_key_map {
+                        self.keyboard.update_key(key, control);
+                        self.next_key_map = None;
+                    }
+                    match self.keyboard.get_keyboard_key(control) {
+                        Some(KeyBoardKey::Duration) => {
+                            self.configuration.bars = 1 + value as usize * 16 / 128;
+                        }
+                        Some(KeyBoardKey::Tempo) => {
+                            self.configuration.bpm = 60 + value as usize * (240 - 60) / 128;
+                        }
+                        Some(KeyBoardKey::Gain) => {
+                            self.configuration.volume = value as f64 / 127.0;
+                        }
+                        _ => continue,
+                    }
+                    conf_changed = true;
+                }
+                MidiInput::Unknown { d1, d2: _, d3: _ } if d1 == CLOCK_TICK => {
+                    self.clock.tick(self.time + e.time as usize, self.rate)
+                }
+                MidiInput::Unknown { d1, d2: _, d3: _ } if d1 == CLOCK_STOP => stop = true,
+                _ => {}
+            }
+        }
+        if conf_changed {
+            Self::send_message(
+                MessageToRiserUI::NewConfiguration(self.configuration.clone()),
+                &mut self.messages_out,
+            );
+        }
+        if stop {
+            self.position = None;
+        }
+
+        let duration = self.swell_duration();
+        let end_ramp = (END_RAMP_DURATION * self.rate as f64) as usize;
+        let out = self.audio_mono_out.as_mut_slice(ps);
+        for (i, v) in out.iter_mut().enumerate() {
+            if start == Some(i) {
+                self.duration = duration;
+                self.position = Some(0);
+                self.filter.reset();
+            }
+            *v = match self.position {
+                Some(position) if position < self.duration => {
+                    let progress = position as f64 / self.duration as f64;
+                    let cutoff = START_CUTOFF * (END_CUTOFF / START_CUTOFF).powf(progress);
+                    let coefs = SvfCoefficients::new(self.rate, cutoff, 0.3);
+                    let noise = self.filter.low_pass(1.0 - 2.0 * random::<f64>(), &coefs);
+                    let ramp = f64::min(1.0, (self.duration - position) as f64 / end_ramp as f64);
+                    self.position = Some(position + 1);
+                    (noise * progress.powi(3) * ramp * self.configuration.volume) as f32
+                }
+                _ => {
+                    self.position = None;
+                    0.0
+                }
+            };
+        }
+        self.time += out.len();
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToRiser {
+    NewConfiguration(RiserConfiguration),
+    SetKey(KeyBoardKey),
+    ClearKey(KeyBoardKey),
+    Panic,
+}
+
+impl From<KeyBoardKeySetter> for MessageToRiser {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToRiser::SetKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToRiser::ClearKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToRiserUI {
+    NewConfiguration(RiserConfiguration),
+}
+
+struct RiserUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToRiserUI>,
+    messages_out: std::sync::mpsc::Sender<MessageToRiser>,
+    messages: Vec<String>,
+    conf: RiserConfiguration,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<RiserConfiguration>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///Compute the tempo from the tap tempo shortcut
+    tap: TapTempo,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl RiserUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToRiserUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToRiser>,
+    ) -> RiserUI {
+        RiserUI {
+            status,
+            messages_in,
+            messages_out,
+            messages: Vec::new(),
+            conf: RiserConfiguration::default(),
+            scenes: SceneListener::new(),
+            session: Autosave::new("riser"),
+            shortcuts: Shortcuts::load(),
+            tap: TapTempo::new(),
+        }
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            match action {
+                ShortcutAction::TapTempo => {
+                    if let Some(bpm) = self.tap.tap() {
+                        self.conf.bpm = (bpm.round() as usize).clamp(60, 240);
+                        self.send_message(MessageToRiser::NewConfiguration(self.conf.clone()));
+                    }
+                }
+                ShortcutAction::Panic => self.send_message(MessageToRiser::Panic),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToRiserUI::NewConfiguration(c) => self.conf = c,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update("riser", &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_message(MessageToRiser::NewConfiguration(self.conf.clone()));
+        }
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        crate::utils::create_usize_slider(
+            ui,
+            "Nb bars",
+            &mut self.conf.bars,
+            RangeInclusive::new(1, 16),
+        );
+        crate::utils::create_usize_slider(
+            ui,
+            "Nb Quarter notes per bar",
+            &mut self.conf.nb_notes,
+            RangeInclusive::new(2, 10),
+        );
+        ui.checkbox(&mut self.conf.follow_clock, tr("Follow the midi clock"));
+        crate::utils::create_usize_slider(
+            ui,
+            "Beats per minutes",
+            &mut self.conf.bpm,
+            RangeInclusive::new(60, 240),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "Volume",
+            &mut self.conf.volume,
+            RangeInclusive::new(0.0, 1.0),
+        );
+
+        if self.conf != current_conf {
+            self.send_message(MessageToRiser::NewConfiguration(self.conf.clone()));
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Nb bars",
+                    KeyBoardKey::Duration,
+                    &mut self.messages_out,
+                    &mut self.messages,
+                );
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Tempo",
+                    KeyBoardKey::Tempo,
+                    &mut self.messages_out,
+                    &mut self.messages,
+                );
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Volume",
+                    KeyBoardKey::Gain,
+                    &mut self.messages_out,
+                    &mut self.messages,
+                );
+                self.shortcuts.create_menu(
+                    ui,
+                    &[ShortcutAction::TapTempo, ShortcutAction::Panic],
+                    &mut self.messages,
+                );
+            });
+
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_message(&mut self, msg: MessageToRiser) {
+        if let Err(e) = self.messages_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for RiserUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn riser() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new("riser", jack::ClientOptions::NO_START_SERVER) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance("riser", client.name());
+
+    //open a message channel for the riser and the UI
+    let (send_to_riser, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_riser) = std::sync::mpsc::channel();
+
+    let riser = Riser::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(riser)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Riser",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(RiserUI::new(
+                cc,
+                status,
+                rcv_from_riser,
+                send_to_riser,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
@@ -1,9 +1,350 @@
-use crate::synth::wavetype::WaveType;
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::synth::{
+    rsynth::modulation::{
+        ModDestination, ModRoute, ModSource, ModulationMatrix, FILTER_RANGE, PITCH_RANGE,
+    },
+    wavetype::WaveType,
+};
+
+///The longest fade in of a random configuration, in seconds
+const RANDOM_MAX_FADE_IN: f64 = 0.8;
+///The longest fade out of a random configuration, in seconds
+const RANDOM_MAX_FADE_OUT: f64 = 1.6;
+///The chance of every overtone (but the first one) to be heard in a random
+/// configuration
+const RANDOM_OVERTONE_CHANCE: f64 = 0.5;
+///The depth of the aftertouch vibrato at full pressure, before the modulation
+/// matrix, in semitones
+const AFTERTOUCH_PITCH_RANGE: f64 = 1.0;
+///The cutoff change at full pressure, before the modulation matrix, in octaves
+const AFTERTOUCH_FILTER_RANGE: f64 = 4.0;
+
+///How the notes are distributed over the voices
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum VoiceMode {
+    ///Every note is played
+    #[default]
+    Poly,
+    ///A single note is played (the last one), every new note restarts the fade in
+    MonoRetrigger,
+    ///A single note is played (the last one), a note played while another one
+    /// is held continues its fade in instead of restarting it
+    MonoLegato,
+}
+
+impl VoiceMode {
+    ///Cycle through the different voice modes
+    pub fn cycle(&self) -> VoiceMode {
+        match self {
+            VoiceMode::Poly => VoiceMode::MonoRetrigger,
+            VoiceMode::MonoRetrigger => VoiceMode::MonoLegato,
+            VoiceMode::MonoLegato => VoiceMode::Poly,
+        }
+    }
+}
+
+impl std::fmt::Display for VoiceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoiceMode::Poly => write!(f, "Poly"),
+            VoiceMode::MonoRetrigger => write!(f, "Mono"),
+            VoiceMode::MonoLegato => write!(f, "Legato"),
+        }
+    }
+}
+
+///The voice silenced when a note is played while every voice is used
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum VoiceStealing {
+    ///The note played first
+    #[default]
+    Oldest,
+    ///The note with the lowest volume
+    Quietest,
+}
+
+impl VoiceStealing {
+    ///Cycle through the different stealing modes
+    pub fn cycle(&self) -> VoiceStealing {
+        match self {
+            VoiceStealing::Oldest => VoiceStealing::Quietest,
+            VoiceStealing::Quietest => VoiceStealing::Oldest,
+        }
+    }
+}
+
+impl std::fmt::Display for VoiceStealing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoiceStealing::Oldest => write!(f, "Oldest"),
+            VoiceStealing::Quietest => write!(f, "Quietest"),
+        }
+    }
+}
+
+///Where the wave of a note starts
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum OscillatorPhase {
+    ///Every note starts at the beginning of its wave: the attacks are
+    /// punchy and always sound the same
+    #[default]
+    Retrigger,
+    ///Every note starts anywhere in its wave, as if its oscillator was always
+    /// running: the attacks vary from one note to the next
+    FreeRun,
+}
+
+impl OscillatorPhase {
+    ///Cycle through the different phase modes
+    pub fn cycle(&self) -> OscillatorPhase {
+        match self {
+            OscillatorPhase::Retrigger => OscillatorPhase::FreeRun,
+            OscillatorPhase::FreeRun => OscillatorPhase::Retrigger,
+        }
+    }
+}
+
+impl std::fmt::Display for OscillatorPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OscillatorPhase::Retrigger => write!(f, "Retrigger"),
+            OscillatorPhase::FreeRun => write!(f, "Free run"),
+        }
+    }
+}
+
+///How many times the sample rate the oscillators and the limiter run at:
+/// the higher, the less aliasing (harsh frequencies folding back below the
+/// Nyquist frequency), but the more CPU is used
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Oversampling {
+    ///The sample rate of jack
+    #[default]
+    Off,
+    ///Twice the sample rate of jack
+    Double,
+    ///Four times the sample rate of jack
+    Quadruple,
+}
+
+impl Oversampling {
+    ///The highest factor applied to the sample rate
+    pub const MAX_FACTOR: usize = 4;
+
+    ///Every quality, in the order of their values
+    pub const ALL: [Oversampling; 3] = [
+        Oversampling::Off,
+        Oversampling::Double,
+        Oversampling::Quadruple,
+    ];
+
+    ///The factor applied to the sample rate
+    pub fn factor(&self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::Double => 2,
+            Oversampling::Quadruple => 4,
+        }
+    }
+
+    ///Cycle through the different qualities
+    pub fn cycle(&self) -> Oversampling {
+        match self {
+            Oversampling::Off => Oversampling::Double,
+            Oversampling::Double => Oversampling::Quadruple,
+            Oversampling::Quadruple => Oversampling::Off,
+        }
+    }
+}
+
+impl std::fmt::Display for Oversampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Oversampling::Off => write!(f, "Normal"),
+            Oversampling::Double => write!(f, "High (2x)"),
+            Oversampling::Quadruple => write!(f, "Best (4x)"),
+        }
+    }
+}
+
+///How the sound of a note is generated
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SynthesisType {
+    ///The wave is played at several frequencies (the overtones)
+    #[default]
+    Overtones,
+    ///Two operators: a sinus modulates the phase of the wave
+    Fm,
+}
+
+impl SynthesisType {
+    ///Cycle through the different synthesis types
+    pub fn cycle(&self) -> SynthesisType {
+        match self {
+            SynthesisType::Overtones => SynthesisType::Fm,
+            SynthesisType::Fm => SynthesisType::Overtones,
+        }
+    }
+}
+
+impl std::fmt::Display for SynthesisType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynthesisType::Overtones => write!(f, "Overtones"),
+            SynthesisType::Fm => write!(f, "FM"),
+        }
+    }
+}
+
+///The wave of the sub-oscillator
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SubWave {
+    #[default]
+    Sin,
+    Square,
+}
+
+impl SubWave {
+    ///Cycle through the different waves
+    pub fn cycle(&self) -> SubWave {
+        match self {
+            SubWave::Sin => SubWave::Square,
+            SubWave::Square => SubWave::Sin,
+        }
+    }
+
+    ///Compute the value of the wave, 'x' being the phase in radians
+    pub fn compute(&self, x: f64) -> f64 {
+        match self {
+            SubWave::Sin => x.sin(),
+            SubWave::Square => WaveType::Square.compute(x),
+        }
+    }
+}
+
+impl std::fmt::Display for SubWave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubWave::Sin => write!(f, "Sinus"),
+            SubWave::Square => write!(f, "Square"),
+        }
+    }
+}
+
+///The parameter moved by the LFO, before the modulation matrix. Only read
+/// to migrate the configurations saved with it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum LfoDestination {
+    ///The LFO is not used
+    #[default]
+    Off,
+    ///The pitch of every note (vibrato)
+    Pitch,
+    ///The volume of every note (tremolo)
+    Amplitude,
+    ///The cutoff of the low-pass filter
+    Filter,
+}
+
+///The parameter moved by the pressure on the keys (channel aftertouch), before
+/// the modulation matrix. Only read to migrate the configurations saved with it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum AftertouchDestination {
+    ///The aftertouch is ignored
+    #[default]
+    Off,
+    ///The depth of a vibrato following the wave and the rate of the LFO
+    Vibrato,
+    ///The cutoff of the low-pass filter
+    Filter,
+}
+
+///What the pressure on a single key (polyphonic aftertouch) changes on its note
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum PolyAftertouchDestination {
+    ///The pressure is ignored
+    #[default]
+    Off,
+    ///The volume of the note
+    Amplitude,
+    ///The cutoff of the low-pass filter of the note
+    Brightness,
+}
+
+impl PolyAftertouchDestination {
+    ///Cycle through the different destinations
+    pub fn cycle(&self) -> PolyAftertouchDestination {
+        match self {
+            PolyAftertouchDestination::Off => PolyAftertouchDestination::Amplitude,
+            PolyAftertouchDestination::Amplitude => PolyAftertouchDestination::Brightness,
+            PolyAftertouchDestination::Brightness => PolyAftertouchDestination::Off,
+        }
+    }
+}
+
+impl std::fmt::Display for PolyAftertouchDestination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolyAftertouchDestination::Off => write!(f, "Off"),
+            PolyAftertouchDestination::Amplitude => write!(f, "Amplitude"),
+            PolyAftertouchDestination::Brightness => write!(f, "Brightness"),
+        }
+    }
+}
+
+///A low frequency oscillator, a source of the modulation matrix
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Lfo {
+    /// The shape of the oscillation
+    pub wave: WaveType,
+    /// The frequency of the oscillation, in Hz
+    pub rate: f64,
+    /// The amount of modulation, from 0 to 1, before the modulation matrix
+    #[serde(default, skip_serializing)]
+    pub depth: f64,
+    /// The parameter that was modulated, before the modulation matrix
+    #[serde(default, skip_serializing)]
+    pub destination: LfoDestination,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self {
+            wave: WaveType::Sin,
+            rate: 4.0,
+            depth: 0.0,
+            destination: LfoDestination::Off,
+        }
+    }
+}
+
+///The lower part of a split keyboard, played with its own configuration
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Split {
+    /// The lowest note of the upper part, as index of the note
+    pub point: usize,
+    /// The configuration of the notes below the split point
+    pub configuration: Configuration,
+}
+
+impl Split {
+    ///The default split point: the middle C
+    pub const DEFAULT_POINT: usize = 72;
+}
+
+///One end of the range of the keys played by the synth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyLimit {
+    Lowest,
+    Highest,
+}
 
 ///A configuration is user-input defined: it specify
 /// the wave type, the amount of overtone, the speed of the attack
 /// and the speed of the release
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Configuration {
     /// The wave type used by this configuration
     pub wave: WaveType,
@@ -11,6 +352,9 @@ pub struct Configuration {
     pub overtone: Vec<f64>,
     /// The frequency mutliplier for to obtain the overtone
     pub overtone_freq: Vec<f64>,
+    /// The wave type of every overtone, None to use the wave type of the configuration
+    #[serde(default)]
+    pub overtone_wave: Vec<Option<WaveType>>,
     /// The speed of the attack
     pub fade_in_duration: f64,
     pub fade_in_shape: u8,
@@ -25,13 +369,178 @@ pub struct Configuration {
     pub mod_intensity: f64,
     /// The speed of the modulation
     pub mod_speed: f64,
+    /// How the notes are distributed over the voices
+    #[serde(default)]
+    pub voice_mode: VoiceMode,
+    /// The number of notes that can sound at once, fade-outs included
+    #[serde(default = "default_max_polyphony")]
+    pub max_polyphony: usize,
+    /// The note silenced when more notes are played
+    #[serde(default)]
+    pub voice_stealing: VoiceStealing,
+    /// Where the wave of a note starts
+    #[serde(default)]
+    pub phase: OscillatorPhase,
+    /// How the sound is generated
+    #[serde(default)]
+    pub synthesis: SynthesisType,
+    /// The frequency of the FM modulator, relative to the frequency of the note
+    #[serde(default = "default_fm_ratio")]
+    pub fm_ratio: f64,
+    /// The intensity of the FM modulation
+    #[serde(default = "default_fm_index")]
+    pub fm_index: f64,
+    /// The cutoff frequency of the low-pass filter, in Hz
+    #[serde(default = "default_filter_cutoff")]
+    pub filter_cutoff: f64,
+    /// The resonance of the low-pass filter, from 0 to 1
+    #[serde(default)]
+    pub filter_resonance: f64,
+    /// How much the fade in/out moves the cutoff, in octaves
+    #[serde(default)]
+    pub filter_envelope: f64,
+    /// The first low frequency oscillator
+    #[serde(default)]
+    pub lfo: Lfo,
+    /// The second low frequency oscillator
+    #[serde(default)]
+    pub lfo2: Lfo,
+    /// The routes from the modulation sources to the parameters
+    #[serde(default)]
+    pub matrix: ModulationMatrix,
+    /// The volume of the sub-oscillator, one octave below every note
+    #[serde(default)]
+    pub sub_level: f64,
+    /// The wave of the sub-oscillator
+    #[serde(default)]
+    pub sub_wave: SubWave,
+    /// The parameter moved by the channel aftertouch, before the modulation
+    /// matrix
+    #[serde(default, skip_serializing)]
+    pub aftertouch: AftertouchDestination,
+    /// The amount of modulation at full pressure, before the modulation matrix
+    #[serde(default, skip_serializing)]
+    pub aftertouch_depth: f64,
+    /// The parameter of a note moved by the pressure on its key
+    #[serde(default)]
+    pub poly_aftertouch: PolyAftertouchDestination,
+    /// The amount of modulation of a note at full pressure, from 0 to 1
+    #[serde(default)]
+    pub poly_aftertouch_depth: f64,
+    /// The pitch offset of every note when it starts, in semitones
+    #[serde(default)]
+    pub pitch_env_amount: f64,
+    /// The time the pitch offset takes to decay, in seconds
+    #[serde(default = "default_pitch_env_decay")]
+    pub pitch_env_decay: f64,
+    /// The frequency of the carrier multiplied with every note, in Hz
+    #[serde(default = "default_ring_frequency")]
+    pub ring_frequency: f64,
+    /// The part of the ring modulated sound in the output, from 0 to 1
+    #[serde(default)]
+    pub ring_mix: f64,
+    /// How much every note slowly drifts away from its pitch, from 0 to 1
+    #[serde(default)]
+    pub drift: f64,
+    /// How much the velocity brightens the overtones above the note, from 0
+    /// (every velocity has the same tone) to 1
+    #[serde(default)]
+    pub velocity_brightness: f64,
+    /// The frequency of the sweep of the chorus, in Hz
+    #[serde(default = "default_chorus_rate")]
+    pub chorus_rate: f64,
+    /// The amount of sweep of the chorus, from 0 to 1
+    #[serde(default = "default_chorus_depth")]
+    pub chorus_depth: f64,
+    /// The part of the chorus in the output, from 0 to 1 (0 to disable it).
+    /// Only the chorus of the upper part is used, on the whole output.
+    #[serde(default)]
+    pub chorus_mix: f64,
+    /// If true, the output goes through a soft limiter instead of clipping.
+    /// Only the limiter of the upper part is used, on the whole output.
+    #[serde(default)]
+    pub limiter: bool,
+    /// The gain applied before the limiter, in dB
+    #[serde(default)]
+    pub limiter_drive: f64,
+    /// The highest level of the output of the limiter, in dB
+    #[serde(default = "default_limiter_ceiling")]
+    pub limiter_ceiling: f64,
+    /// How many times the sample rate the oscillators and the limiter run at.
+    /// Only the oversampling of the upper part is used, on the whole output.
+    #[serde(default)]
+    pub oversampling: Oversampling,
+    /// The lower part of the keyboard, if it plays another configuration
+    #[serde(default)]
+    pub split: Option<Box<Split>>,
+    /// The lowest key played, as index of the note. The keys outside of the
+    /// range are left to the other programs sharing the keyboard.
+    /// Only the range of the upper part is used, on the whole keyboard.
+    #[serde(default = "default_lowest_key")]
+    pub lowest_key: u8,
+    /// The highest key played, as index of the note
+    #[serde(default = "default_highest_key")]
+    pub highest_key: u8,
+}
+
+fn default_max_polyphony() -> usize {
+    16
+}
+
+fn default_fm_ratio() -> f64 {
+    2.0
+}
+
+fn default_fm_index() -> f64 {
+    1.0
+}
+
+fn default_filter_cutoff() -> f64 {
+    20000.0
+}
+
+fn default_pitch_env_decay() -> f64 {
+    0.1
+}
+
+fn default_ring_frequency() -> f64 {
+    440.0
+}
+
+fn default_chorus_rate() -> f64 {
+    0.8
+}
+
+fn default_chorus_depth() -> f64 {
+    0.5
+}
+
+fn default_limiter_ceiling() -> f64 {
+    -1.0
+}
+
+fn default_lowest_key() -> u8 {
+    Configuration::LOWEST_KEY
+}
+
+fn default_highest_key() -> u8 {
+    Configuration::HIGHEST_KEY
 }
 
 impl Configuration {
+    ///The lowest key of the midi notes, as index of the note
+    pub const LOWEST_KEY: u8 = 12;
+    ///The highest key of the midi notes, as index of the note
+    pub const HIGHEST_KEY: u8 = 139;
+    ///The number of keys whose state is kept by the player: up to the highest
+    /// key, the lower ones being there to keep the note index as position
+    pub const NB_KEYS: usize = Self::HIGHEST_KEY as usize + 1;
+
     pub fn new() -> Configuration {
         Self {
             overtone: vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
             overtone_freq: vec![1.0, 1.0 / 2.0, 1.0 / 3.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0],
+            overtone_wave: vec![None; 9],
             wave: WaveType::default(),
             fade_in_duration: 0.1,
             fade_in_shape: 64,
@@ -41,6 +550,232 @@ impl Configuration {
             modulation: 0,
             mod_intensity: 0.1,
             mod_speed: 8.0,
+            voice_mode: VoiceMode::Poly,
+            max_polyphony: default_max_polyphony(),
+            voice_stealing: VoiceStealing::Oldest,
+            phase: OscillatorPhase::Retrigger,
+            synthesis: SynthesisType::Overtones,
+            fm_ratio: default_fm_ratio(),
+            fm_index: default_fm_index(),
+            filter_cutoff: default_filter_cutoff(),
+            filter_resonance: 0.0,
+            filter_envelope: 0.0,
+            lfo: Lfo::default(),
+            lfo2: Lfo::default(),
+            matrix: ModulationMatrix::default(),
+            sub_level: 0.0,
+            sub_wave: SubWave::Sin,
+            aftertouch: AftertouchDestination::Off,
+            aftertouch_depth: 0.0,
+            poly_aftertouch: PolyAftertouchDestination::Off,
+            poly_aftertouch_depth: 0.0,
+            pitch_env_amount: 0.0,
+            pitch_env_decay: default_pitch_env_decay(),
+            ring_frequency: default_ring_frequency(),
+            ring_mix: 0.0,
+            drift: 0.0,
+            velocity_brightness: 0.0,
+            chorus_rate: default_chorus_rate(),
+            chorus_depth: default_chorus_depth(),
+            chorus_mix: 0.0,
+            limiter: false,
+            limiter_drive: 0.0,
+            limiter_ceiling: default_limiter_ceiling(),
+            oversampling: Oversampling::Off,
+            split: None,
+            lowest_key: default_lowest_key(),
+            highest_key: default_highest_key(),
+        }
+    }
+
+    ///True if the key is within the range played by the synth
+    pub fn plays(&self, note_index: usize) -> bool {
+        (self.lowest_key as usize..=self.highest_key as usize).contains(&note_index)
+    }
+
+    ///Move one end of the range of the keys played. The other end follows
+    /// if needed, so that the range is never empty.
+    pub fn set_key_limit(&mut self, limit: KeyLimit, note_index: usize) {
+        let note_index =
+            note_index.clamp(Self::LOWEST_KEY as usize, Self::HIGHEST_KEY as usize) as u8;
+        match limit {
+            KeyLimit::Lowest => {
+                self.lowest_key = note_index;
+                self.highest_key = self.highest_key.max(note_index);
+            }
+            KeyLimit::Highest => {
+                self.highest_key = note_index;
+                self.lowest_key = self.lowest_key.min(note_index);
+            }
+        }
+    }
+
+    ///Replace the LFO and aftertouch settings of the configurations saved
+    /// before the modulation matrix by the routes doing the same
+    pub fn migrate_modulation(&mut self) {
+        if let Some(split) = &mut self.split {
+            split.configuration.migrate_modulation();
+        }
+        let lfo_route = |destination, amount| ModRoute {
+            source: ModSource::Lfo1,
+            via: None,
+            destination,
+            amount,
+        };
+        //the LFO had the ranges of the matrix
+        let depth = self.lfo.depth;
+        match self.lfo.destination {
+            LfoDestination::Off => {}
+            LfoDestination::Pitch => self
+                .matrix
+                .routes
+                .push(lfo_route(ModDestination::Pitch, depth)),
+            //the tremolo swings around the volume instead of below it
+            LfoDestination::Amplitude => self
+                .matrix
+                .routes
+                .push(lfo_route(ModDestination::Amplitude, depth / 2.0)),
+            LfoDestination::Filter => self
+                .matrix
+                .routes
+                .push(lfo_route(ModDestination::Filter, depth)),
+        }
+        let depth = self.aftertouch_depth;
+        match self.aftertouch {
+            AftertouchDestination::Off => {}
+            //the pressure scales a vibrato following the LFO
+            AftertouchDestination::Vibrato => self.matrix.routes.push(ModRoute {
+                source: ModSource::Lfo1,
+                via: Some(ModSource::Aftertouch),
+                destination: ModDestination::Pitch,
+                amount: depth * AFTERTOUCH_PITCH_RANGE / PITCH_RANGE,
+            }),
+            AftertouchDestination::Filter => self.matrix.routes.push(ModRoute {
+                source: ModSource::Aftertouch,
+                via: None,
+                destination: ModDestination::Filter,
+                amount: depth * AFTERTOUCH_FILTER_RANGE / FILTER_RANGE,
+            }),
+        }
+        self.lfo.depth = 0.0;
+        self.lfo.destination = LfoDestination::Off;
+        self.aftertouch = AftertouchDestination::Off;
+        self.aftertouch_depth = 0.0;
+    }
+
+    ///Retrieve the wave type used by an overtone
+    pub fn overtone_wave(&self, overtone_index: usize) -> &WaveType {
+        match self.overtone_wave.get(overtone_index) {
+            Some(Some(wave)) => wave,
+            _ => &self.wave,
+        }
+    }
+
+    ///Draw a new sound: the wave, the overtones and the fades are chosen at
+    /// random, within bounds that keep the sound playable. The other
+    /// parameters (gain, voices, effects, split) are kept.
+    pub fn randomize(&mut self) {
+        self.wave = match random::<u8>() % 4 {
+            0 => WaveType::Sin,
+            1 => WaveType::Square,
+            2 => WaveType::SawTooth,
+            _ => WaveType::Triangle,
+        };
+        for (index, overtone) in self.overtone.iter_mut().enumerate() {
+            *overtone = if index == 0 {
+                //the note itself is always heard
+                0.5 + random::<f64>() / 2.0
+            } else if random::<f64>() < RANDOM_OVERTONE_CHANCE {
+                random::<f64>()
+            } else {
+                0.0
+            };
         }
+        self.overtone_wave.iter_mut().for_each(|wave| *wave = None);
+        self.fade_in_duration = random::<f64>() * RANDOM_MAX_FADE_IN;
+        self.fade_in_shape = random::<u8>() % 128;
+        self.fade_out_duration = random::<f64>() * RANDOM_MAX_FADE_OUT;
+        self.fade_out_shape = random::<u8>() % 128;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AftertouchDestination, Configuration, KeyLimit, LfoDestination, Split, RANDOM_MAX_FADE_IN,
+        RANDOM_MAX_FADE_OUT,
+    };
+    use crate::synth::rsynth::modulation::{ModDestination, ModSource};
+
+    #[test]
+    fn randomize_within_bounds() {
+        let mut configuration = Configuration::new();
+        configuration.gain = 0.5;
+        for _ in 0..100 {
+            configuration.randomize();
+            assert!(configuration.overtone[0] >= 0.5);
+            assert!(configuration
+                .overtone
+                .iter()
+                .all(|v| (0.0..=1.0).contains(v)));
+            assert!(configuration.fade_in_duration <= RANDOM_MAX_FADE_IN);
+            assert!(configuration.fade_out_duration <= RANDOM_MAX_FADE_OUT);
+            assert!(configuration.fade_in_shape < 128 && configuration.fade_out_shape < 128);
+        }
+        assert_eq!(configuration.gain, 0.5);
+        assert_eq!(
+            configuration.overtone_freq,
+            Configuration::new().overtone_freq
+        );
+    }
+
+    #[test]
+    fn key_range() {
+        let mut configuration = Configuration::new();
+        assert!(configuration.plays(Configuration::LOWEST_KEY as usize));
+        assert!(configuration.plays(Configuration::HIGHEST_KEY as usize));
+        configuration.set_key_limit(KeyLimit::Lowest, 60);
+        configuration.set_key_limit(KeyLimit::Highest, 84);
+        assert!(!configuration.plays(59));
+        assert!(configuration.plays(60) && configuration.plays(84));
+        assert!(!configuration.plays(85));
+        //the range is never empty
+        configuration.set_key_limit(KeyLimit::Highest, 48);
+        assert_eq!(
+            (configuration.lowest_key, configuration.highest_key),
+            (48, 48)
+        );
+        configuration.set_key_limit(KeyLimit::Lowest, 200);
+        assert_eq!(configuration.lowest_key, Configuration::HIGHEST_KEY);
+        assert_eq!(configuration.highest_key, Configuration::HIGHEST_KEY);
+    }
+
+    #[test]
+    fn migrate_modulation() {
+        let mut configuration = Configuration::new();
+        configuration.lfo.destination = LfoDestination::Filter;
+        configuration.lfo.depth = 0.5;
+        configuration.aftertouch = AftertouchDestination::Vibrato;
+        configuration.aftertouch_depth = 1.0;
+        let mut lower = configuration.clone();
+        lower.lfo.destination = LfoDestination::Off;
+        configuration.split = Some(Box::new(Split {
+            point: Split::DEFAULT_POINT,
+            configuration: lower,
+        }));
+        configuration.migrate_modulation();
+        let routes = &configuration.matrix.routes;
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].source, ModSource::Lfo1);
+        assert_eq!(routes[0].destination, ModDestination::Filter);
+        assert_eq!(routes[0].amount, 0.5);
+        assert_eq!(routes[1].via, Some(ModSource::Aftertouch));
+        assert_eq!(routes[1].destination, ModDestination::Pitch);
+        let lower = &configuration.split.as_ref().unwrap().configuration;
+        assert_eq!(lower.matrix.routes.len(), 1);
+        //migrating again changes nothing
+        let migrated = configuration.clone();
+        configuration.migrate_modulation();
+        assert_eq!(configuration, migrated);
     }
 }
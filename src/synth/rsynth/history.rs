@@ -0,0 +1,109 @@
+use std::{collections::VecDeque, time::Instant};
+
+///The number of steps that can be undone
+const HISTORY_LENGTH: usize = 100;
+///Changes closer than this, in seconds, are undone at once: a slider being
+/// dragged or a knob being turned is a single step
+const MERGE_DELAY: f64 = 1.0;
+
+///The history of the edits of a state, to undo and redo them
+pub struct History<T> {
+    ///The state as it was the last time it was followed
+    current: T,
+    ///The states to go back to, the most recent last
+    undo: VecDeque<T>,
+    ///The states undone, the most recent last
+    redo: Vec<T>,
+    ///When the last change happened
+    last_change: Option<Instant>,
+}
+
+impl<T> History<T>
+where
+    T: Clone + PartialEq,
+{
+    pub fn new(initial: T) -> History<T> {
+        History {
+            current: initial,
+            undo: VecDeque::with_capacity(HISTORY_LENGTH),
+            redo: Vec::new(),
+            last_change: None,
+        }
+    }
+
+    ///Follow the state: every change becomes a step that can be undone,
+    /// unless it comes right after the previous one
+    pub fn update(&mut self, state: &T, now: Instant) {
+        if *state == self.current {
+            return;
+        }
+        let merged = self
+            .last_change
+            .is_some_and(|last| now.duration_since(last).as_secs_f64() < MERGE_DELAY);
+        let previous = std::mem::replace(&mut self.current, state.clone());
+        if !merged {
+            if self.undo.len() == HISTORY_LENGTH {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(previous);
+        }
+        self.redo.clear();
+        self.last_change = Some(now);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    ///Go back to the state before the last step, if any
+    pub fn undo(&mut self) -> Option<T> {
+        let previous = self.undo.pop_back()?;
+        let current = std::mem::replace(&mut self.current, previous);
+        self.redo.push(current);
+        self.last_change = None;
+        Some(self.current.clone())
+    }
+
+    ///Apply again the last step undone, if any
+    pub fn redo(&mut self) -> Option<T> {
+        let next = self.redo.pop()?;
+        let current = std::mem::replace(&mut self.current, next);
+        self.undo.push_back(current);
+        self.last_change = None;
+        Some(self.current.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::History;
+
+    #[test]
+    fn undo_redo() {
+        let start = Instant::now();
+        let mut history = History::new(0);
+        assert!(!history.can_undo());
+        history.update(&1, start);
+        //a continuous change is a single step
+        history.update(&2, start + Duration::from_millis(100));
+        history.update(&3, start + Duration::from_millis(200));
+        history.update(&10, start + Duration::from_secs(5));
+        assert_eq!(history.undo(), Some(3));
+        assert_eq!(history.undo(), Some(0));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(3));
+
+        //undoing does not create a step, a new change forgets what was undone
+        history.update(&3, start + Duration::from_secs(6));
+        assert!(history.can_redo());
+        history.update(&4, start + Duration::from_secs(7));
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(), Some(3));
+    }
+}
@@ -1,31 +1,39 @@
+use crate::spsc::{channel, MESSAGES_CAPACITY};
 use crate::synth::rsynth::ui::RustySynth;
-use crate::utils::CommonError;
+use crate::utils::{CommonError, JackStatus};
 use eframe::egui::{self, ViewportBuilder};
-use std::sync::mpsc::channel;
 
 mod configuration;
+mod history;
+mod modulation;
 mod player;
+mod presets;
 mod ui;
 
+///The name of the jack client, under which the settings are also stored
+const TOOL_NAME: &str = "rsynth";
+
 pub fn rsynth() -> Result<(), CommonError> {
     // open client
-    let (client, _status) = match jack::Client::new("RSynth", jack::ClientOptions::NO_START_SERVER)
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
     {
         Ok(v) => v,
         Err(e) => {
             return Err(CommonError::UnableToStartClient(e));
         }
     };
+    crate::storage::set_instance(TOOL_NAME, client.name());
 
     let client_name = String::from(client.name());
 
-    //create a sync channel to send back copies of midi messages we get
-    let (player_change_sender, player_change_receiver) = channel();
-    //create a sync channel to send non midi commands to the player
-    let (external_command_send, external_command_receive) = channel();
+    //create a channel to send back copies of midi messages we get
+    let (player_change_sender, player_change_receiver) = channel(MESSAGES_CAPACITY);
+    //create a channel to send non midi commands to the player
+    let (external_command_send, external_command_receive) = channel(MESSAGES_CAPACITY);
     let synth =
         player::Player::new(&client, external_command_receive, player_change_sender).unwrap();
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => {
             return Err(CommonError::UnableToStartClient(e));
@@ -42,6 +50,7 @@ pub fn rsynth() -> Result<(), CommonError> {
         Box::new(|cc| {
             Ok(Box::new(RustySynth::new(
                 cc,
+                status,
                 player_change_receiver,
                 external_command_send,
                 &active_client.as_client(),
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+///The pitch change of a route at full amount, in semitones
+pub const PITCH_RANGE: f64 = 12.0;
+///The cutoff change of a route at full amount, in octaves
+pub const FILTER_RANGE: f64 = 4.0;
+///The highest number of routes of a matrix
+pub const MAX_ROUTES: usize = 8;
+
+///A signal that can move the parameters of the synth
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ModSource {
+    ///The first low frequency oscillator, from -1 to 1
+    Lfo1,
+    ///The second low frequency oscillator, from -1 to 1
+    Lfo2,
+    ///The fade in/out of the note, from 0 to 1
+    Envelope,
+    ///The velocity of the note, from 0 to 1
+    Velocity,
+    ///The pressure on the keys (channel aftertouch), from 0 to 1
+    Aftertouch,
+    ///The position of the modulation wheel, from 0 to 1
+    ModWheel,
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 6] = [
+        ModSource::Lfo1,
+        ModSource::Lfo2,
+        ModSource::Envelope,
+        ModSource::Velocity,
+        ModSource::Aftertouch,
+        ModSource::ModWheel,
+    ];
+}
+
+impl std::fmt::Display for ModSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModSource::Lfo1 => write!(f, "LFO 1"),
+            ModSource::Lfo2 => write!(f, "LFO 2"),
+            ModSource::Envelope => write!(f, "Envelope"),
+            ModSource::Velocity => write!(f, "Velocity"),
+            ModSource::Aftertouch => write!(f, "Aftertouch"),
+            ModSource::ModWheel => write!(f, "Mod wheel"),
+        }
+    }
+}
+
+///A parameter of the synth that can be moved by a source
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ModDestination {
+    ///The pitch of the notes, up to [PITCH_RANGE] semitones
+    Pitch,
+    ///The volume of the notes, up to twice as loud
+    Amplitude,
+    ///The cutoff of the low-pass filter, up to [FILTER_RANGE] octaves
+    Filter,
+    ///The balance of the overtones: positive values bring out the overtones
+    /// above the note, negative ones the overtones below it
+    OvertoneTilt,
+}
+
+impl ModDestination {
+    pub const ALL: [ModDestination; 4] = [
+        ModDestination::Pitch,
+        ModDestination::Amplitude,
+        ModDestination::Filter,
+        ModDestination::OvertoneTilt,
+    ];
+
+    ///Cycle through the different destinations
+    pub fn cycle(&self) -> ModDestination {
+        match self {
+            ModDestination::Pitch => ModDestination::Amplitude,
+            ModDestination::Amplitude => ModDestination::Filter,
+            ModDestination::Filter => ModDestination::OvertoneTilt,
+            ModDestination::OvertoneTilt => ModDestination::Pitch,
+        }
+    }
+}
+
+impl std::fmt::Display for ModDestination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModDestination::Pitch => write!(f, "Pitch"),
+            ModDestination::Amplitude => write!(f, "Amplitude"),
+            ModDestination::Filter => write!(f, "Filter"),
+            ModDestination::OvertoneTilt => write!(f, "Overtone tilt"),
+        }
+    }
+}
+
+///A connection of the matrix: the source, optionally scaled by a second
+/// source (e.g. the LFO through the mod wheel for a vibrato that follows the
+/// wheel), moves the destination
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct ModRoute {
+    pub source: ModSource,
+    #[serde(default)]
+    pub via: Option<ModSource>,
+    pub destination: ModDestination,
+    ///The amount of modulation, from -1 to 1
+    pub amount: f64,
+}
+
+///The values of the sources, in the order of [ModSource::ALL]
+pub type SourceValues = [f64; 6];
+
+///The routes from the sources to the destinations
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct ModulationMatrix {
+    pub routes: Vec<ModRoute>,
+}
+
+///The effect of the matrix on a note
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Modulation {
+    ///The frequency ratio applied to the note
+    pub pitch: f64,
+    ///The factor applied to the volume of the note
+    pub amplitude: f64,
+    ///The factor applied to the cutoff of the filter
+    pub cutoff: f64,
+    ///The tilt of the overtones: an overtone 'ratio' times the frequency of
+    /// the note is multiplied by ratio^tilt
+    pub tilt: f64,
+}
+
+impl Modulation {
+    ///Nothing is modulated
+    pub const NONE: Modulation = Modulation {
+        pitch: 1.0,
+        amplitude: 1.0,
+        cutoff: 1.0,
+        tilt: 0.0,
+    };
+}
+
+impl ModulationMatrix {
+    fn value(sources: &SourceValues, source: ModSource) -> f64 {
+        sources[source as usize]
+    }
+
+    ///Compute the effect of the routes for the given values of the sources
+    pub fn modulate(&self, sources: &SourceValues) -> Modulation {
+        if self.routes.is_empty() {
+            return Modulation::NONE;
+        }
+        let mut amounts = [0.0; 4];
+        for route in &self.routes {
+            let mut value = Self::value(sources, route.source) * route.amount;
+            if let Some(via) = route.via {
+                value *= Self::value(sources, via);
+            }
+            amounts[route.destination as usize] += value;
+        }
+        let [pitch, amplitude, filter, tilt] = amounts;
+        Modulation {
+            pitch: 2.0_f64.powf(pitch * PITCH_RANGE / 12.0),
+            amplitude: (1.0 + amplitude).max(0.0),
+            cutoff: 2.0_f64.powf(filter * FILTER_RANGE),
+            tilt,
+        }
+    }
+
+    ///The first route from a source alone (without 'via'), created if there
+    /// is none
+    pub fn route_from(&mut self, source: ModSource) -> &mut ModRoute {
+        let position = self
+            .routes
+            .iter()
+            .position(|r| r.source == source && r.via.is_none());
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.routes.push(ModRoute {
+                    source,
+                    via: None,
+                    destination: ModDestination::Pitch,
+                    amount: 0.0,
+                });
+                self.routes.len() - 1
+            }
+        };
+        &mut self.routes[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ModDestination, ModRoute, ModSource, Modulation, ModulationMatrix};
+
+    fn route(source: ModSource, via: Option<ModSource>, destination: ModDestination) -> ModRoute {
+        ModRoute {
+            source,
+            via,
+            destination,
+            amount: 0.5,
+        }
+    }
+
+    #[test]
+    fn routes_add_up() {
+        let mut sources = [0.0; 6];
+        let mut matrix = ModulationMatrix::default();
+        assert_eq!(matrix.modulate(&sources), Modulation::NONE);
+        matrix.routes = vec![
+            route(
+                ModSource::Lfo1,
+                Some(ModSource::ModWheel),
+                ModDestination::Pitch,
+            ),
+            route(ModSource::Velocity, None, ModDestination::Filter),
+            route(ModSource::Envelope, None, ModDestination::Filter),
+        ];
+        //the vibrato follows the wheel
+        sources[ModSource::Lfo1 as usize] = 1.0;
+        assert_eq!(matrix.modulate(&sources).pitch, 1.0);
+        sources[ModSource::ModWheel as usize] = 1.0;
+        assert!((matrix.modulate(&sources).pitch - 2.0_f64.sqrt()).abs() < 1e-9);
+        //half an octave for the velocity, half an octave for the envelope
+        sources[ModSource::Velocity as usize] = 0.25;
+        sources[ModSource::Envelope as usize] = 0.25;
+        assert!((matrix.modulate(&sources).cutoff - 2.0).abs() < 1e-9);
+        assert_eq!(matrix.modulate(&sources).amplitude, 1.0);
+    }
+
+    #[test]
+    fn route_from_a_source() {
+        let mut matrix = ModulationMatrix::default();
+        matrix.route_from(ModSource::Aftertouch).amount = 1.0;
+        matrix.route_from(ModSource::Aftertouch).destination = ModDestination::Filter;
+        assert_eq!(matrix.routes.len(), 1);
+        assert_eq!(matrix.routes[0].destination, ModDestination::Filter);
+        assert_eq!(matrix.routes[0].amount, 1.0);
+    }
+}
@@ -1,19 +1,103 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf, sync::Arc};
+
+use rand::random;
 
 use crate::synth::{
-    hardware::{HardWare, KeyBoardKey},
-    rsynth::configuration::Configuration,
+    hardware::{HardWare, KeyBoardKey, MOD_WHEEL},
+    rsynth::{
+        configuration::{
+            Configuration, KeyLimit, OscillatorPhase, Oversampling, PolyAftertouchDestination,
+            SynthesisType, VoiceMode, VoiceStealing,
+        },
+        modulation::{ModSource, Modulation, SourceValues},
+        TOOL_NAME,
+    },
+    wavetype::{WaveType, Wavetable},
+};
+use crate::{
+    dsp::{
+        db_to_gain, flush_denormal, soft_clip, Chorus, DcBlocker, Oversampler, StateVariableFilter,
+        SvfCoefficients,
+    },
+    midiinput::{frequency, MidiInput, MidiNote, ALL_NOTES_OFF, ALL_SOUND_OFF},
+    mmc::ALL_DEVICES,
+    mts::{MtsEvent, Tuning},
+    spsc,
+    storage::{self, StorageError},
+    utils::{ChannelWatchdog, KeyBoardKeySetter, MidiChannel},
 };
-use crate::{midiinput::MidiInput, utils::KeyBoardKeySetter};
 
 pub const FADE_DURATION_STEP: f64 = 0.025;
 pub const GAIN_STEP: f64 = 8.0 / 127.0;
 pub const OVERTONE_STEP: f64 = 1.0 / 128.0;
+pub const OVERTONE_MIN_RATIO: f64 = 1.0 / 16.0;
+pub const OVERTONE_MAX_RATIO: f64 = 16.0;
+pub const FM_RATIO_STEP: f64 = 1.0 / 16.0;
+pub const FM_INDEX_STEP: f64 = 10.0 / 128.0;
+pub const FILTER_MIN_CUTOFF: f64 = 20.0;
+pub const FILTER_MAX_CUTOFF: f64 = 20000.0;
+pub const FILTER_MAX_ENVELOPE: f64 = 8.0;
+pub const LFO_MAX_RATE: f64 = 20.0;
+///The cutoff change at full pressure on a key, in octaves
+pub const AFTERTOUCH_FILTER_RANGE: f64 = 4.0;
+///The highest number of notes that can sound at once
+pub const MAX_POLYPHONY: usize = 64;
+///The largest pitch offset of the pitch envelope, in semitones
+pub const PITCH_ENVELOPE_RANGE: f64 = 24.0;
+///The longest decay of the pitch envelope, in seconds
+pub const PITCH_ENVELOPE_MAX_DECAY: f64 = 2.0;
+///The lowest frequency of the ring modulation carrier, in Hz
+pub const RING_MIN_FREQUENCY: f64 = 20.0;
+///The highest frequency of the ring modulation carrier, in Hz
+pub const RING_MAX_FREQUENCY: f64 = 5000.0;
+///The fastest sweep of the chorus, in Hz
+pub const CHORUS_MAX_RATE: f64 = 5.0;
+///The highest drive of the limiter, in dB
+pub const LIMITER_MAX_DRIVE: f64 = 24.0;
+///The lowest ceiling of the limiter, in dB
+pub const LIMITER_MIN_CEILING: f64 = -24.0;
+///The largest detune of a note at full drift, in semitones
+const DRIFT_RANGE: f64 = 0.2;
+///The time a drifting note takes to come back toward its pitch, in seconds
+const DRIFT_TIME: f64 = 0.5;
+///The pitch change at full pitch bend, in semitones
+const PITCH_BEND_RANGE: f64 = 2.0;
+///The time the pitch takes to follow the bend wheel, in seconds
+const PITCH_BEND_SMOOTHING: f64 = 0.01;
+///The gain of a note at full velocity, leaving headroom for the chords
+const FULL_VELOCITY_GAIN: f64 = 127.0 / 256.0;
+///The octaves between the midi pitch of the notes and the frequency played
+const OCTAVE_SHIFT: i32 = 2;
+///The end of the name of the file containing the keyboard mapping, after
+/// the name of the instance
+const MAPPING_FILE: &str = "_mapping.json";
+///The number of midi events of a cycle that can be stored; the events
+/// beyond are dropped, so that nothing is allocated on the jack thread
+const MAX_EVENTS: usize = 256;
 
 ///This enum represent the different elements that can change for the player
 #[derive(Debug)]
 pub enum MessageToUI {
-    NewConfiguration(Configuration),
+    ///A mapped midi control changed the configuration: the user interface
+    /// applies it to its own copy, see [Player::apply_control]
+    Control(KeyBoardKey, u8),
+    ///The next note played set this end of the range of the keys
+    KeyLimitLearnt(KeyLimit, usize),
+    ///A program change recalled the preset at the given position. The user
+    /// interface recalls it as well, and sends a copy of it back to the bank.
+    PresetRecalled(usize),
+    ///A configuration replaced, to be freed outside of the audio thread
+    ConfigurationReleased(Box<Configuration>),
+    ///A preset bank replaced, to be freed outside of the audio thread
+    BankReleased(Vec<Option<Box<Configuration>>>),
+    ///A keyboard mapping replaced, to be freed outside of the audio thread
+    KeyboardMappingReleased(Box<HardWare>),
+    ///A wave replaced by a wavetable, to be freed outside of the audio thread
+    WaveReleased(WaveType),
+    ///The tuning changed: the number of keys that are retuned
+    Retuned(usize),
+    ///The keyboard mapping to save, as asked by [MessageToPlayer::SaveConf]
+    KeyboardMapping(Box<HardWare>),
     Error(PlayerError),
 }
 
@@ -21,10 +105,29 @@ pub enum MessageToUI {
 pub enum MessageToPlayer {
     NewKeyboardKey(KeyBoardKey),
     ClearKeybaordKey(KeyBoardKey),
-    NewConfiguration(Configuration),
+    NewConfiguration(Box<Configuration>),
+    NewWavetable(Arc<Wavetable>),
+    ///The configurations recalled by the program changes
+    NewBank(Vec<Option<Box<Configuration>>>),
+    ///A copy of a recalled preset, put back in its slot of the bank
+    Preset(usize, Box<Configuration>),
     ClearAllKeyboardKeys,
+    ///Listen to another midi channel
+    Channel(MidiChannel),
+    ///Send the keyboard mapping to the user interface, which saves it
     SaveConf,
-    LoadConf,
+    ///Use a keyboard mapping read by the user interface
+    NewKeyboardMapping(Box<HardWare>),
+    ///Go back to the equal temperament
+    ResetTuning,
+    ///Send the current tuning as a bulk dump
+    SendTuning,
+    ///Stop every note at once, for instance when a note is stuck
+    AllNotesOff,
+    ///A note played on the keyboard of the user interface
+    Note(MidiInput),
+    ///Use the next note played as one end of the range of the keys played
+    LearnKeyLimit(KeyLimit),
 }
 
 impl From<KeyBoardKeySetter> for MessageToPlayer {
@@ -40,7 +143,9 @@ impl From<KeyBoardKeySetter> for MessageToPlayer {
 pub enum PlayerError {
     JackError(jack::Error),
     FileError(std::io::Error),
-    CommunicationError(std::sync::mpsc::TryRecvError),
+    Storage(StorageError),
+    ///A note outside of the keys of the synth was received and ignored
+    NoteOutOfRange(usize),
 }
 
 impl From<jack::Error> for PlayerError {
@@ -55,105 +160,328 @@ impl From<std::io::Error> for PlayerError {
     }
 }
 
+impl From<StorageError> for PlayerError {
+    fn from(value: StorageError) -> Self {
+        PlayerError::Storage(value)
+    }
+}
+
 impl Display for PlayerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PlayerError::JackError(e) => write!(f, "{e}"),
             PlayerError::FileError(e) => write!(f, "{e}"),
-            PlayerError::CommunicationError(e) => write!(f, "{e}"),
+            PlayerError::Storage(e) => write!(f, "{e}"),
+            PlayerError::NoteOutOfRange(note_index) => write!(
+                f,
+                "The note {} is out of the range of the synth, it is ignored",
+                MidiNote::raw_byte(*note_index)
+            ),
         }
     }
 }
 
+///The state of every note that can be played
+struct Notes {
+    /// The velocity that was used to activate a note
+    velocity: Vec<f64>,
+    /// Specify for each note if it should be played or not
+    play: Vec<bool>,
+    fade_in: Vec<f64>,
+    fade_out: Vec<f64>,
+    ///The notes currently held, the last one being the most recent
+    held: Vec<usize>,
+    ///The note played when a mono voice mode is used
+    mono_note: Option<usize>,
+    ///The low-pass filter of every note
+    filters: Vec<StateVariableFilter>,
+    ///The pressure on the key of every note (polyphonic aftertouch), from 0 to 1
+    pressure: Vec<f64>,
+    ///The notes sounding (played or fading out), the oldest first. Only these
+    /// notes are computed.
+    voices: Vec<usize>,
+    ///The time since every note started, in seconds, for the pitch envelope
+    pitch_age: Vec<f64>,
+    ///The phase of every note, in cycles of its frequency. Every note has its
+    /// own phase, so that it starts at the beginning of its wave, or anywhere
+    /// in it for free running oscillators.
+    phase: Vec<f64>,
+    ///The random walk of every note around its pitch, from -1 to 1
+    drift: Vec<f64>,
+}
+
+impl Notes {
+    ///The state of the notes up to 'nb_notes' (excluded)
+    fn new(nb_notes: usize) -> Notes {
+        Notes {
+            velocity: vec![0.0; nb_notes],
+            play: vec![false; nb_notes],
+            fade_in: vec![1.0; nb_notes],
+            fade_out: vec![0.0; nb_notes],
+            held: Vec::with_capacity(nb_notes),
+            mono_note: None,
+            filters: vec![StateVariableFilter::new(); nb_notes],
+            pressure: vec![0.0; nb_notes],
+            voices: Vec::with_capacity(MAX_POLYPHONY),
+            pitch_age: vec![0.0; nb_notes],
+            phase: vec![0.0; nb_notes],
+            drift: vec![0.0; nb_notes],
+        }
+    }
+
+    ///True if the state of the note is kept. Other notes can not be played.
+    fn holds(&self, note_index: usize) -> bool {
+        note_index < self.play.len()
+    }
+
+    ///Start the fade out of a note
+    fn release(&mut self, note_index: usize) {
+        self.play[note_index] = false;
+        //start the fade out not higher than the value of the fade_in. Otherwise,
+        //it would create a click
+        self.fade_out[note_index] = 1.0;
+    }
+
+    ///Stop every note at once, without fade out. Only the notes sounding are
+    /// visited, the others are already silent.
+    fn silence(&mut self) {
+        //the mono note may have lost its voice, when taken back from the held
+        //notes after being stolen
+        if let Some(note_index) = self.mono_note {
+            self.cut(note_index);
+        }
+        while let Some(note_index) = self.voices.pop() {
+            self.cut(note_index);
+            self.pressure[note_index] = 0.0;
+            self.phase[note_index] = 0.0;
+        }
+        self.held.clear();
+    }
+
+    ///Stop a single note at once, without fade out
+    fn cut(&mut self, note_index: usize) {
+        self.play[note_index] = false;
+        self.fade_in[note_index] = 1.0;
+        self.fade_out[note_index] = 0.0;
+        self.filters[note_index].reset();
+        if self.mono_note == Some(note_index) {
+            self.mono_note = None;
+        }
+    }
+
+    ///The current volume of a note
+    fn level(&self, note_index: usize) -> f64 {
+        let fade = if self.play[note_index] {
+            self.fade_in[note_index]
+        } else {
+            self.fade_out[note_index]
+        };
+        self.velocity[note_index] * fade.clamp(0.0, 1.0)
+    }
+
+    ///Give a voice to a note about to be played. If every voice is used, one
+    /// of them is stolen: the released notes are stolen first.
+    fn allocate(
+        &mut self,
+        note_index: usize,
+        max_polyphony: usize,
+        stealing: VoiceStealing,
+        phase: OscillatorPhase,
+    ) {
+        if let Some(position) = self.voices.iter().position(|n| *n == note_index) {
+            //the note is played again: it becomes the most recent one, its
+            //wave goes on to avoid a click
+            self.voices.remove(position);
+        } else {
+            self.phase[note_index] = match phase {
+                OscillatorPhase::Retrigger => 0.0,
+                //two cycles, for the sub-oscillator one octave down
+                OscillatorPhase::FreeRun => 2.0 * random::<f64>(),
+            };
+        }
+        self.pitch_age[note_index] = 0.0;
+        while self.voices.len() >= max_polyphony.max(1) {
+            let released = self.voices.iter().any(|n| !self.play[*n]);
+            let mut candidates = self
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| !released || !self.play[**n]);
+            let stolen = match stealing {
+                VoiceStealing::Oldest => candidates.next(),
+                VoiceStealing::Quietest => {
+                    candidates.min_by(|(_, a), (_, b)| self.level(**a).total_cmp(&self.level(**b)))
+                }
+            };
+            match stolen.map(|(position, _)| position) {
+                Some(position) => {
+                    let stolen = self.voices.remove(position);
+                    self.cut(stolen);
+                }
+                None => break,
+            }
+        }
+        self.voices.push(note_index);
+    }
+
+    ///Play a note as the single note of a mono voice mode. The previous one
+    /// is released, or replaced without restarting the fade in if 'legato'
+    fn play_mono(&mut self, note_index: usize, legato: bool) {
+        let previous = self.mono_note.replace(note_index);
+        let mut level = self.fade_out[note_index].max(0.0);
+        if let Some(previous) = previous {
+            if previous != note_index && self.play[previous] {
+                if legato {
+                    level = self.fade_in[previous].clamp(0.0, 1.0);
+                    //the wave goes on from the previous note
+                    self.phase[note_index] = self.phase[previous];
+                    //silence the previous note at once, the new one takes over
+                    self.play[previous] = false;
+                    self.fade_in[previous] = 1.0;
+                    self.fade_out[previous] = 0.0;
+                } else {
+                    self.release(previous);
+                }
+            }
+        }
+        if !self.play[note_index] {
+            self.play[note_index] = true;
+            self.fade_in[note_index] = level;
+        }
+    }
+}
+
+///A part of the keyboard, played with its own configuration
+struct Zone {
+    /// The state of every note
+    notes: Notes,
+    /// The real time that has passed since the zone is playing
+    real_time: f64,
+    /// The phase of the first LFO
+    lfo_phase: f64,
+    /// The phase of the second LFO
+    lfo2_phase: f64,
+    /// The phase of the ring modulation carrier
+    ring_phase: f64,
+    /// The frequency ratio applied by the pitch bend, moving toward the
+    /// position of the bend wheel
+    bend: f64,
+}
+
 pub struct Player {
     rate: usize,
-    /// The duration of a single audio frame
-    frame_t: f64,
-    /// Time dilation
-    time_dilation_factor: f64,
-    /// The dilated time that has passed since the beginning
-    time: f64,
-    /// The real time that has passed since the synth is playing
-    real_time: f64,
+    /// The frequency ratio given by the position of the bend wheel
+    bend: f64,
+    /// The pressure on the keys (channel aftertouch), from 0 to 1
+    pressure: f64,
+    /// The position of the modulation wheel, from 0 to 1
+    mod_wheel: f64,
     /// The input midi port
     midi_in: jack::Port<jack::MidiIn>,
+    /// The midi events of the current cycle, with their position in the buffer
+    events: Vec<(jack::Frames, MidiInput)>,
     /// The output audio port
     audio_mono_out: jack::Port<jack::AudioOut>,
+    /// The chorus applied on the output
+    chorus: Chorus,
+    /// Remove the offset of the output
+    dc_blocker: DcBlocker,
+    /// The sound of the zones, at the oversampled rate
+    oversampled: Vec<f32>,
+    /// Bring the sound of the zones back to the sample rate, for every
+    /// oversampling quality, so that none is built on the jack thread
+    oscillators: [Oversampler; 3],
+    /// Run the limiter at the oversampled rate, for every quality
+    limiter: [Oversampler; 3],
+    /// The output midi port, sending the tuning
+    midi_out: jack::Port<jack::MidiOut>,
+    /// The pitch of every key, changed by the midi tuning standard messages
+    tuning: Tuning,
+    /// The program of the tuning to send at the end of the cycle, if any
+    tuning_request: Option<u8>,
     /// Listener to changes in the configuration
-    change_listener: std::sync::mpsc::Sender<MessageToUI>,
+    change_listener: spsc::Sender<MessageToUI>,
     /// The keyboard configuration
     keyboard: HardWare,
-    /// The velocity that was used to activate a note
-    velocity: Vec<f64>,
-    /// Specify for each note if it should be played or not
-    play: Vec<bool>,
-    fade_in: Vec<f64>,
-    fade_out: Vec<f64>,
+    /// The midi channel we are listening to
+    channel: MidiChannel,
+    /// The notes above the split point, or every note without split
+    upper: Zone,
+    /// The notes below the split point
+    lower: Zone,
     config: Configuration,
     ///The channel allowing to receive external commands
-    external_commands: std::sync::mpsc::Receiver<MessageToPlayer>,
+    external_commands: ChannelWatchdog<MessageToPlayer, spsc::Receiver<MessageToPlayer>>,
     ///If true, the next control input should be used for mapping
     map_next_contrl: Option<KeyBoardKey>,
+    ///If set, the next note played becomes this end of the range of the keys
+    learn_key_limit: Option<KeyLimit>,
+    ///The configurations recalled by the program changes. A recalled preset
+    /// leaves its slot empty until the user interface sends a copy back.
+    bank: Vec<Option<Box<Configuration>>>,
 }
 
 impl Player {
     pub fn new(
         client: &jack::Client,
-        extra_input: std::sync::mpsc::Receiver<MessageToPlayer>,
-        channel_input: std::sync::mpsc::Sender<MessageToUI>,
+        extra_input: spsc::Receiver<MessageToPlayer>,
+        mut channel_input: spsc::Sender<MessageToUI>,
     ) -> Result<Player, PlayerError> {
         let sample_rate = client.sample_rate();
 
-        let nb_notes = 12 * 12;
-
-        let mut velocity_array = Vec::<f64>::with_capacity(nb_notes);
-        let mut play_array = Vec::<bool>::with_capacity(nb_notes);
-        let mut fin = Vec::<f64>::with_capacity(nb_notes);
-        let mut fout = Vec::<f64>::with_capacity(nb_notes);
-
-        for _f in 0..nb_notes {
-            velocity_array.push(0.0);
-            play_array.push(false);
-            fin.push(1.0);
-            fout.push(0.0);
-        }
-
+        //without saved mapping, the default one is used silently
         let midi_keyboard = match Self::load_keyboard_conf() {
             Ok(v) => v,
-            Err(_e) => HardWare::default(),
+            Err(PlayerError::Storage(StorageError::FileError(e)))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                HardWare::default()
+            }
+            Err(e) => {
+                Self::send(&mut channel_input, MessageToUI::Error(e));
+                HardWare::default()
+            }
         };
 
         Ok(Player {
             rate: sample_rate,
-            frame_t: 1.0 / sample_rate as f64,
-            time_dilation_factor: 1.0,
-            time: 0.0,
-            real_time: 0.0,
+            bend: 1.0,
+            pressure: 0.0,
+            mod_wheel: 0.0,
             midi_in: client.register_port("midi_input", jack::MidiIn::default())?,
+            events: Vec::with_capacity(MAX_EVENTS),
             audio_mono_out: client.register_port("music_out", jack::AudioOut::default())?,
+            chorus: Chorus::new(sample_rate),
+            dc_blocker: DcBlocker::new(sample_rate),
+            oversampled: vec![0.0; client.buffer_size() as usize * Oversampling::MAX_FACTOR],
+            oscillators: Oversampling::ALL
+                .map(|quality| Oversampler::new(sample_rate, quality.factor())),
+            limiter: Oversampling::ALL
+                .map(|quality| Oversampler::new(sample_rate, quality.factor())),
+            midi_out: client.register_port("midi_out", jack::MidiOut::default())?,
+            tuning: Tuning::default(),
+            tuning_request: None,
             change_listener: channel_input,
             keyboard: midi_keyboard,
-            velocity: velocity_array,
-            play: play_array,
-            fade_in: fin,
-            fade_out: fout,
+            channel: MidiChannel::load(TOOL_NAME),
+            upper: Zone::new(),
+            lower: Zone::new(),
             config: Configuration::new(),
-            external_commands: extra_input,
+            external_commands: ChannelWatchdog::new(TOOL_NAME, extra_input)
+                .mute_when_disconnected(),
             map_next_contrl: None,
+            learn_key_limit: None,
+            bank: Vec::new(),
         })
     }
 
-    fn get_frequency(note_index: f64) -> f64 {
-        let index = note_index + 1.0;
-        let mid_a_freq = 440.0;
-        let a5_index: f64 = 4.0 * 12.0 + 10.0;
-        let b: f64 = (2.0 as f64).powf(1.0 / 12.0);
-        let a: f64 = mid_a_freq / ((2.0 as f64).powf(a5_index / 12.0));
-        a * b.powf(index)
+    ///The frequency played for a pitch given as index of note
+    fn get_frequency(pitch: f64) -> f64 {
+        frequency(pitch) * 2.0_f64.powi(OCTAVE_SHIFT)
     }
 
     /// Send a notification to the change listener
-    fn send(change_listener: &mut std::sync::mpsc::Sender<MessageToUI>, to_send: MessageToUI) {
+    fn send(change_listener: &mut spsc::Sender<MessageToUI>, to_send: MessageToUI) {
         if let Err(e) = change_listener.send(to_send) {
             eprintln!("Internal error: {e}");
         }
@@ -167,228 +495,914 @@ impl Player {
         }
     }
 
-    fn save_keyboard_conf(_keyboard: &HardWare) -> Result<(), PlayerError> {
-        todo!("implement the saving of the keyboard configuration");
+    ///The file in which the keyboard mapping is saved
+    pub fn mapping_path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(format!(
+            "{}{MAPPING_FILE}",
+            storage::instance_name(TOOL_NAME)
+        ));
+        Ok(path)
+    }
+
+    ///Save the keyboard mapping. The file is written by the user interface,
+    /// never by the jack thread.
+    pub fn save_keyboard_conf(keyboard: &HardWare) -> Result<(), PlayerError> {
+        storage::save(&Self::mapping_path()?, keyboard)?;
+        Ok(())
+    }
+
+    ///Read the saved keyboard mapping, see [Player::save_keyboard_conf]
+    pub fn load_keyboard_conf() -> Result<HardWare, PlayerError> {
+        Ok(storage::load(&Self::mapping_path()?)?)
+    }
+
+    ///The state and the configuration of the zone playing a note, if the
+    /// state of the note is kept
+    fn zone(&mut self, note_index: usize) -> Option<(&mut Notes, &Configuration)> {
+        let (notes, config) = match &self.config.split {
+            Some(split) if note_index < split.point => {
+                (&mut self.lower.notes, &split.configuration)
+            }
+            _ => (&mut self.upper.notes, &self.config),
+        };
+        notes.holds(note_index).then_some((notes, config))
+    }
+
+    ///Stop every note at once
+    fn silence(&mut self) {
+        self.upper.notes.silence();
+        self.lower.notes.silence();
+        self.chorus.reset();
+        self.pressure = 0.0;
     }
 
-    fn load_keyboard_conf() -> Result<HardWare, PlayerError> {
-        todo!("implement the loading of the keyboard configuration");
+    ///Use a new configuration. If the split point moves, the notes are
+    /// stopped, as they could be released in another zone. The replaced
+    /// configuration is sent back to be freed by the user interface.
+    fn set_configuration(&mut self, mut config: Box<Configuration>) {
+        config.migrate_modulation();
+        let point = |c: &Configuration| c.split.as_ref().map(|split| split.point);
+        if point(&config) != point(&self.config) {
+            self.silence();
+        }
+        std::mem::swap(&mut self.config, &mut config);
+        Self::send(
+            &mut self.change_listener,
+            MessageToUI::ConfigurationReleased(config),
+        );
     }
 
-    fn read_input(&mut self, ps: &jack::ProcessScope) {
-        match self.external_commands.try_recv() {
-            Ok(v) => match v {
+    ///Apply the messages received from the user interface
+    fn read_commands(&mut self) {
+        if let Some(v) = self.external_commands.try_recv() {
+            match v {
                 MessageToPlayer::NewKeyboardKey(k) => self.map_next_contrl = Some(k),
                 MessageToPlayer::ClearAllKeyboardKeys => self.keyboard.clear_all(),
-                MessageToPlayer::SaveConf => match Self::save_keyboard_conf(&mut self.keyboard) {
-                    Ok(()) => {}
-                    Err(e) => {
-                        Self::send(&mut self.change_listener, MessageToUI::Error(e));
-                    }
-                },
-                MessageToPlayer::LoadConf => match Self::load_keyboard_conf() {
-                    Ok(kb) => self.keyboard = kb,
-                    Err(e) => Self::send(&mut self.change_listener, MessageToUI::Error(e)),
-                },
-                MessageToPlayer::ClearKeybaordKey(k) => self.keyboard.clear_key(k),
-                MessageToPlayer::NewConfiguration(conf) => self.config = conf,
-            },
-            Err(e) => match e {
-                std::sync::mpsc::TryRecvError::Empty => {}
-                std::sync::mpsc::TryRecvError::Disconnected => Self::send(
+                MessageToPlayer::SaveConf => Self::send(
                     &mut self.change_listener,
-                    MessageToUI::Error(PlayerError::CommunicationError(e)),
+                    MessageToUI::KeyboardMapping(Box::new(self.keyboard.clone())),
                 ),
-            },
-        };
+                MessageToPlayer::NewKeyboardMapping(mut keyboard) => {
+                    std::mem::swap(&mut self.keyboard, &mut keyboard);
+                    Self::send(
+                        &mut self.change_listener,
+                        MessageToUI::KeyboardMappingReleased(keyboard),
+                    );
+                }
+                MessageToPlayer::ClearKeybaordKey(k) => self.keyboard.clear_key(k),
+                MessageToPlayer::NewConfiguration(conf) => self.set_configuration(conf),
+                MessageToPlayer::AllNotesOff => self.silence(),
+                MessageToPlayer::Note(midi) => self.apply_midi(midi),
+                MessageToPlayer::LearnKeyLimit(limit) => self.learn_key_limit = Some(limit),
+                MessageToPlayer::ResetTuning => {
+                    self.tuning = Tuning::default();
+                    Self::send(&mut self.change_listener, MessageToUI::Retuned(0));
+                }
+                MessageToPlayer::SendTuning => self.tuning_request = Some(0),
+                MessageToPlayer::NewBank(bank) => {
+                    let bank = std::mem::replace(&mut self.bank, bank);
+                    Self::send(&mut self.change_listener, MessageToUI::BankReleased(bank));
+                }
+                MessageToPlayer::Preset(index, preset) => match self.bank.get_mut(index) {
+                    Some(slot) if slot.is_none() => *slot = Some(preset),
+                    //the bank changed since the recall
+                    _ => Self::send(
+                        &mut self.change_listener,
+                        MessageToUI::ConfigurationReleased(preset),
+                    ),
+                },
+                MessageToPlayer::Channel(channel) => self.channel = channel,
+                MessageToPlayer::NewWavetable(table) => {
+                    let wave = std::mem::replace(&mut self.config.wave, WaveType::Table(table));
+                    Self::send(&mut self.change_listener, MessageToUI::WaveReleased(wave));
+                }
+            }
+        }
+    }
 
-        let show_p = self.midi_in.iter(ps);
-        for e in show_p {
-            let midi = e.into();
+    ///Retrieve the midi events of the cycle, with their position in the buffer
+    fn read_midi(&mut self, ps: &jack::ProcessScope) {
+        self.events.clear();
+        for e in self.midi_in.iter(ps) {
+            match self.tuning.read(e.bytes) {
+                Some(MtsEvent::Retuned) => {
+                    Self::send(
+                        &mut self.change_listener,
+                        MessageToUI::Retuned(self.tuning.retuned()),
+                    );
+                    continue;
+                }
+                Some(MtsEvent::DumpRequested(program)) => {
+                    self.tuning_request = Some(program);
+                    continue;
+                }
+                None => {}
+            }
+            let midi: MidiInput = e.into();
+            if self.channel.accepts(&midi) && self.events.len() < MAX_EVENTS {
+                self.events.push((e.time, midi));
+            }
+        }
+    }
 
-            match midi {
-                MidiInput::NoteStart {
-                    note_index,
-                    timing: _,
-                    velocity,
-                    channel: _,
-                } => {
-                    if !self.play[note_index] {
-                        self.velocity[note_index] = velocity;
-                        self.play[note_index] = true;
-                        //if we play before the fade_out was completed, continue from where we were
-                        self.fade_in[note_index] = self.fade_out[note_index];
-                    }
+    ///Apply a midi event to the state of the notes and to the configuration
+    fn apply_midi(&mut self, midi: MidiInput) {
+        if let Some(note_index) = Self::note_out_of_range(&midi) {
+            Self::send(
+                &mut self.change_listener,
+                MessageToUI::Error(PlayerError::NoteOutOfRange(note_index)),
+            );
+            return;
+        }
+        match midi {
+            MidiInput::NoteStart {
+                note_index,
+                timing: _,
+                velocity,
+                channel: _,
+            } => {
+                if let Some(limit) = self.learn_key_limit.take() {
+                    //the note only sets the range, it is not played
+                    self.config.set_key_limit(limit, note_index);
+                    Self::send(
+                        &mut self.change_listener,
+                        MessageToUI::KeyLimitLearnt(limit, note_index),
+                    );
+                    return;
                 }
-                MidiInput::NoteEnd {
-                    note_index,
-                    channel: _,
-                    timing: _,
-                    velocity: _,
-                } => {
-                    self.play[note_index] = false;
-                    //start the fade out not higher than the value of the fade_in. Otherwise,
-                    //it would create a click
-                    self.fade_out[note_index] = 1.0;
+                if !self.config.plays(note_index) {
+                    return;
                 }
-                MidiInput::Controller {
-                    control,
-                    value,
-                    channel: _,
-                } => {
-                    if self.map_next_contrl.is_some() {
-                        let k = self.map_next_contrl.take().unwrap();
-                        self.keyboard.update_key(k, control);
-                    }
-                    let current_conf = self.config.clone();
-                    match self.keyboard.get_keyboard_key(control) {
-                        None => {}
-                        Some(v) => match v {
-                            KeyBoardKey::WaveSelection => {
-                                if value > 0 {
-                                    self.config.wave = self.config.wave.cycle();
-                                }
-                            }
-                            KeyBoardKey::Overtone(overtone_index) => {
-                                let new_value = (value as f64) * OVERTONE_STEP;
-                                self.config.overtone[overtone_index as usize] = new_value;
-                            }
-                            KeyBoardKey::FadeInDuration => {
-                                self.config.fade_in_duration =
-                                    FADE_DURATION_STEP * (1.0 + value as f64)
-                            }
-                            KeyBoardKey::FadeInShape => {
-                                self.config.fade_in_shape = value;
-                            }
-                            KeyBoardKey::FadeOutDuration => {
-                                let new_duration = FADE_DURATION_STEP * (1.0 + value as f64);
-                                self.config.fade_out_duration = new_duration;
-                            }
-                            KeyBoardKey::FadeOutShape => {
-                                self.config.fade_out_shape = value;
-                            }
-                            KeyBoardKey::Gain => {
-                                let new_gain = (1 + value) as f64 * GAIN_STEP;
-                                self.config.gain = new_gain;
-                            }
-                            KeyBoardKey::Modulation => {
-                                self.config.modulation = value;
-                            }
-                            KeyBoardKey::ModulationSpeed => {
-                                self.config.mod_speed = (value as f64) / 4.0;
-                            }
-                            KeyBoardKey::ModulationIntensity => {
-                                self.config.mod_intensity = (value as f64) / 128.0;
-                            }
-                            _ => {}
-                        },
+                let Some((notes, config)) = self.zone(note_index) else {
+                    return;
+                };
+                crate::utils::statistics::note_played();
+                notes.held.retain(|n| *n != note_index);
+                notes.held.push(note_index);
+                notes.pressure[note_index] = 0.0;
+                notes.allocate(
+                    note_index,
+                    config.max_polyphony,
+                    config.voice_stealing,
+                    config.phase,
+                );
+                match config.voice_mode {
+                    VoiceMode::Poly => {
+                        if !notes.play[note_index] {
+                            notes.velocity[note_index] = velocity * FULL_VELOCITY_GAIN;
+                            notes.play[note_index] = true;
+                            //if we play before the fade_out was completed, continue from where we were
+                            notes.fade_in[note_index] = notes.fade_out[note_index];
+                        }
                     }
-                    if self.config != current_conf {
-                        Player::send(
-                            &mut self.change_listener,
-                            MessageToUI::NewConfiguration(self.config.clone()),
-                        )
+                    VoiceMode::MonoRetrigger | VoiceMode::MonoLegato => {
+                        notes.velocity[note_index] = velocity * FULL_VELOCITY_GAIN;
+                        let legato = config.voice_mode == VoiceMode::MonoLegato;
+                        notes.play_mono(note_index, legato);
                     }
                 }
-                MidiInput::PitchBend { value } => {
-                    if value < 64 {
-                        self.time_dilation_factor = (value as f64) / 64.0
-                    } else {
-                        self.time_dilation_factor = 1.0 + (value - 64) as f64 / 64.0
+            }
+            MidiInput::NoteEnd {
+                note_index,
+                channel: _,
+                timing: _,
+                velocity: _,
+            } => {
+                let Some((notes, config)) = self.zone(note_index) else {
+                    return;
+                };
+                notes.held.retain(|n| *n != note_index);
+                let last = notes.held.last().copied();
+                let mono = config.voice_mode != VoiceMode::Poly;
+                match last {
+                    //last note priority: go back to the most recent note still held
+                    Some(last) if mono && notes.mono_note == Some(note_index) => {
+                        let legato = config.voice_mode == VoiceMode::MonoLegato;
+                        notes.play_mono(last, legato);
+                    }
+                    _ => {
+                        if notes.play[note_index] {
+                            notes.release(note_index);
+                        }
+                        if notes.mono_note == Some(note_index) {
+                            notes.mono_note = None;
+                        }
                     }
                 }
-                MidiInput::Unknown {
-                    d1: _,
-                    d2: _,
-                    d3: _,
-                } => {}
             }
+            MidiInput::Controller {
+                control: ALL_SOUND_OFF | ALL_NOTES_OFF,
+                ..
+            } => self.silence(),
+            MidiInput::Controller {
+                control,
+                value,
+                channel: _,
+            } => {
+                if control == MOD_WHEEL {
+                    self.mod_wheel = value as f64 / 127.0;
+                }
+                if self.map_next_contrl.is_some() {
+                    let k = self.map_next_contrl.take().unwrap();
+                    self.keyboard.update_key(k, control);
+                }
+                if let Some(key) = self.keyboard.get_keyboard_key(control) {
+                    Self::apply_control(&mut self.config, key, value);
+                    Self::send(&mut self.change_listener, MessageToUI::Control(key, value));
+                }
+            }
+            MidiInput::PitchBend { value } => self.bend = Self::bend_ratio(value),
+            MidiInput::ProgramChange {
+                channel: _,
+                program,
+            } => {
+                //the preset is moved out of the bank, the user interface sends
+                //a copy back
+                let preset = self.bank.get_mut(program as usize).and_then(Option::take);
+                if let Some(preset) = preset {
+                    self.set_configuration(preset);
+                    Player::send(
+                        &mut self.change_listener,
+                        MessageToUI::PresetRecalled(program as usize),
+                    );
+                }
+            }
+            MidiInput::KeyPressure {
+                channel: _,
+                note_index,
+                value,
+            } => {
+                if let Some((notes, _)) = self.zone(note_index) {
+                    notes.pressure[note_index] = value as f64 / 127.0;
+                }
+            }
+            MidiInput::ChannelPressure { channel: _, value } => {
+                self.pressure = value as f64 / 127.0;
+            }
+            MidiInput::Unknown {
+                d1: _,
+                d2: _,
+                d3: _,
+            } => {}
+        }
+    }
+
+    ///Apply a mapped midi control to a configuration. The user interface
+    /// applies the same controls to its own copy, so that the configuration
+    /// is never copied on the jack thread.
+    pub fn apply_control(config: &mut Configuration, key: KeyBoardKey, value: u8) {
+        match key {
+            KeyBoardKey::WaveSelection if value > 0 => {
+                config.wave = config.wave.cycle();
+            }
+            KeyBoardKey::Overtone(overtone_index) => {
+                let new_value = (value as f64) * OVERTONE_STEP;
+                config.overtone[overtone_index as usize] = new_value;
+            }
+            KeyBoardKey::FadeInDuration => {
+                config.fade_in_duration = FADE_DURATION_STEP * (1.0 + value as f64)
+            }
+            KeyBoardKey::FadeInShape => {
+                config.fade_in_shape = value;
+            }
+            KeyBoardKey::FadeOutDuration => {
+                let new_duration = FADE_DURATION_STEP * (1.0 + value as f64);
+                config.fade_out_duration = new_duration;
+            }
+            KeyBoardKey::FadeOutShape => {
+                config.fade_out_shape = value;
+            }
+            KeyBoardKey::Gain => {
+                let new_gain = (1 + value) as f64 * GAIN_STEP;
+                config.gain = new_gain;
+            }
+            KeyBoardKey::Modulation => {
+                config.modulation = value;
+            }
+            KeyBoardKey::ModulationSpeed => {
+                config.mod_speed = (value as f64) / 4.0;
+            }
+            KeyBoardKey::ModulationIntensity => {
+                config.mod_intensity = (value as f64) / 128.0;
+            }
+            KeyBoardKey::VoiceMode if value > 0 => {
+                config.voice_mode = config.voice_mode.cycle();
+            }
+            KeyBoardKey::Synthesis if value > 0 => {
+                config.synthesis = config.synthesis.cycle();
+            }
+            KeyBoardKey::FmRatio => {
+                config.fm_ratio = (1 + value) as f64 * FM_RATIO_STEP;
+            }
+            KeyBoardKey::FmIndex => {
+                config.fm_index = value as f64 * FM_INDEX_STEP;
+            }
+            KeyBoardKey::FilterCutoff => {
+                //the cutoff is exponential, to have the same precision on every octave
+                let ratio = FILTER_MAX_CUTOFF / FILTER_MIN_CUTOFF;
+                config.filter_cutoff = FILTER_MIN_CUTOFF * ratio.powf(value as f64 / 127.0);
+            }
+            KeyBoardKey::FilterResonance => {
+                config.filter_resonance = value as f64 / 127.0;
+            }
+            KeyBoardKey::FilterEnvelope => {
+                config.filter_envelope = (value as f64 - 64.0) / 64.0 * FILTER_MAX_ENVELOPE;
+            }
+            KeyBoardKey::LfoWave if value > 0 => {
+                config.lfo.wave = config.lfo.wave.cycle();
+            }
+            KeyBoardKey::LfoRate => {
+                config.lfo.rate = value as f64 / 127.0 * LFO_MAX_RATE;
+            }
+            KeyBoardKey::LfoDepth => {
+                let route = config.matrix.route_from(ModSource::Lfo1);
+                route.amount = value as f64 / 127.0;
+            }
+            KeyBoardKey::PitchEnvelopeAmount => {
+                config.pitch_env_amount = (value as f64 - 64.0) / 64.0 * PITCH_ENVELOPE_RANGE;
+            }
+            KeyBoardKey::PitchEnvelopeDecay => {
+                config.pitch_env_decay = value as f64 / 127.0 * PITCH_ENVELOPE_MAX_DECAY;
+            }
+            KeyBoardKey::RingFrequency => {
+                let ratio = RING_MAX_FREQUENCY / RING_MIN_FREQUENCY;
+                config.ring_frequency = RING_MIN_FREQUENCY * ratio.powf(value as f64 / 127.0);
+            }
+            KeyBoardKey::RingMix => {
+                config.ring_mix = value as f64 / 127.0;
+            }
+            KeyBoardKey::SubLevel => {
+                config.sub_level = value as f64 / 127.0;
+            }
+            KeyBoardKey::SubWave if value > 0 => {
+                config.sub_wave = config.sub_wave.cycle();
+            }
+            KeyBoardKey::AftertouchDestination if value > 0 => {
+                let route = config.matrix.route_from(ModSource::Aftertouch);
+                route.destination = route.destination.cycle();
+            }
+            KeyBoardKey::AftertouchDepth => {
+                let route = config.matrix.route_from(ModSource::Aftertouch);
+                route.amount = value as f64 / 127.0;
+            }
+            KeyBoardKey::PolyAftertouchDestination if value > 0 => {
+                config.poly_aftertouch = config.poly_aftertouch.cycle();
+            }
+            KeyBoardKey::PolyAftertouchDepth => {
+                config.poly_aftertouch_depth = value as f64 / 127.0;
+            }
+            KeyBoardKey::LfoDestination if value > 0 => {
+                let route = config.matrix.route_from(ModSource::Lfo1);
+                route.destination = route.destination.cycle();
+            }
+            _ => {}
+        }
+    }
+
+    ///The index of the note of an event, if the synth keeps no state for it.
+    /// A malformed midi message can give a note above the highest key.
+    fn note_out_of_range(midi: &MidiInput) -> Option<usize> {
+        match midi {
+            MidiInput::NoteStart { note_index, .. }
+            | MidiInput::NoteEnd { note_index, .. }
+            | MidiInput::KeyPressure { note_index, .. }
+                if *note_index >= Configuration::NB_KEYS =>
+            {
+                Some(*note_index)
+            }
+            _ => None,
         }
     }
 
+    ///The frequency ratio of a position of the bend wheel (64 in the middle)
+    fn bend_ratio(value: u8) -> f64 {
+        let position = if value < 64 {
+            (value as f64 - 64.0) / 64.0
+        } else {
+            (value as f64 - 64.0) / 63.0
+        };
+        2.0_f64.powf(position.min(1.0) * PITCH_BEND_RANGE / 12.0)
+    }
+
+    ///The next step of the random walk of a drifting note: it moves by a
+    /// random amount ('random' being between 0 and 1) and is pulled back
+    /// toward its pitch
+    fn drift_step(drift: f64, random: f64, frame_t: f64) -> f64 {
+        let noise = (random - 0.5) * 0.7 * (12.0 * frame_t / DRIFT_TIME).sqrt();
+        (drift - drift * frame_t / DRIFT_TIME + noise).clamp(-1.0, 1.0)
+    }
+
+    ///The frequency ratio of the pitch envelope, 'age' seconds after the
+    /// start of a note: 'amount' semitones at first, decaying toward the note
+    fn pitch_envelope(amount: f64, decay: f64, age: f64) -> f64 {
+        let offset = if decay > 0.0 {
+            amount * (-age / decay).exp()
+        } else {
+            0.0
+        };
+        2.0_f64.powf(offset / 12.0)
+    }
+
+    ///The gain of an overtone 'ratio' times higher than the note: the softer
+    /// the note, the quieter the overtones above it, an octave higher fading
+    /// 'amount' times as much
+    fn velocity_tilt(amount: f64, velocity: f64, ratio: f64) -> f64 {
+        if amount <= 0.0 || ratio <= 1.0 {
+            return 1.0;
+        }
+        let velocity = (velocity / FULL_VELOCITY_GAIN).clamp(0.0, 1.0);
+        velocity.powf(amount * ratio.log2())
+    }
+
     fn compute_increment(rate: usize, duration: f64) -> f64 {
         1.0 / ((rate as f64) * duration)
     }
 
-    ///Generate the sound buffer according to the current state
-    fn generate_sound(&mut self, ps: &jack::ProcessScope) -> jack::Control {
+    ///Generate the given frames of the sound buffer according to the current state
+    fn generate_sound(&mut self, ps: &jack::ProcessScope, frames: std::ops::Range<usize>) {
         // Get output buffer
-        let out = self.audio_mono_out.as_mut_slice(ps);
+        let out = &mut self.audio_mono_out.as_mut_slice(ps)[frames];
+
+        let factor = self.config.oversampling.factor();
+        let quality = self.config.oversampling as usize;
+        //only grows if jack asks for longer buffers
+        let oversampled_len = out.len() * factor;
+        if self.oversampled.len() < oversampled_len {
+            self.oversampled.resize(oversampled_len, 0.0);
+        }
+        let oversampled = &mut self.oversampled[..oversampled_len];
+        oversampled.fill(0.0);
+
+        //the sources of the modulation matrix shared by every note
+        let mut sources = [0.0; 6];
+        sources[ModSource::Aftertouch as usize] = self.pressure;
+        sources[ModSource::ModWheel as usize] = self.mod_wheel;
+        self.upper.render(
+            &self.config,
+            &self.tuning,
+            self.rate * factor,
+            self.bend,
+            sources,
+            oversampled,
+        );
+        if let Some(split) = &self.config.split {
+            self.lower.render(
+                &split.configuration,
+                &self.tuning,
+                self.rate * factor,
+                self.bend,
+                sources,
+                oversampled,
+            );
+        }
+        for (v, samples) in out.iter_mut().zip(oversampled.chunks(factor)) {
+            *v = self.oscillators[quality].decimate(samples) as f32;
+        }
+
+        if self.config.chorus_mix > 0.0 {
+            for v in out.iter_mut() {
+                *v = self.chorus.process(
+                    *v as f64,
+                    self.config.chorus_rate,
+                    self.config.chorus_depth,
+                    self.config.chorus_mix,
+                ) as f32;
+            }
+        }
+        for v in out.iter_mut() {
+            *v = self.dc_blocker.process(*v as f64) as f32;
+        }
+        if self.config.limiter {
+            let drive = db_to_gain(self.config.limiter_drive);
+            let ceiling = db_to_gain(self.config.limiter_ceiling);
+            for v in out.iter_mut() {
+                *v = self.limiter[quality].process(*v as f64, |x| soft_clip(x * drive, ceiling))
+                    as f32;
+            }
+        }
+    }
+}
+
+impl Zone {
+    fn new() -> Zone {
+        Zone {
+            notes: Notes::new(Configuration::NB_KEYS),
+            real_time: 0.0,
+            lfo_phase: 0.0,
+            lfo2_phase: 0.0,
+            ring_phase: 0.0,
+            bend: 1.0,
+        }
+    }
+
+    ///Add the sound of the zone to the output buffer, 'bend' being the
+    /// frequency ratio given by the bend wheel and 'sources' the values of the
+    /// modulation sources that do not depend on the zone (the controllers)
+    fn render(
+        &mut self,
+        config: &Configuration,
+        tuning: &Tuning,
+        rate: usize,
+        bend: f64,
+        mut sources: SourceValues,
+        out: &mut [f32],
+    ) {
+        let frame_t = 1.0 / rate as f64;
+        //the pitch glides toward the bend, without zipper noise
+        let bend_step = 1.0 - (-frame_t / PITCH_BEND_SMOOTHING).exp();
+
+        //without envelope, every voice shares the same filter
+        let filter = SvfCoefficients::new(rate, config.filter_cutoff, config.filter_resonance);
+
+        let lfo_step = 2.0 * std::f64::consts::PI * config.lfo.rate * frame_t;
+        let lfo2_step = 2.0 * std::f64::consts::PI * config.lfo2.rate * frame_t;
+        let ring_step = 2.0 * std::f64::consts::PI * config.ring_frequency * frame_t;
 
         // Write output
         for v in out.iter_mut() {
             let mut value: f64 = 0.0;
             let mut mute = true;
 
-            let nb_notes = self.velocity.len();
+            sources[ModSource::Lfo1 as usize] = config.lfo.wave.compute(self.lfo_phase);
+            self.lfo_phase = (self.lfo_phase + lfo_step) % (2.0 * std::f64::consts::PI);
+            sources[ModSource::Lfo2 as usize] = config.lfo2.wave.compute(self.lfo2_phase);
+            self.lfo2_phase = (self.lfo2_phase + lfo2_step) % (2.0 * std::f64::consts::PI);
+            let modulation_aux = (config.modulation as f64) * self.real_time * std::f64::consts::PI
+                / config.mod_speed;
+            let modulation = config.mod_intensity * modulation_aux.sin() + 1.0;
+            self.bend += (bend - self.bend) * bend_step;
+            //the time of the notes, stretched by the bend and the modulations
+            let step = frame_t * self.bend * modulation;
+            //the notes are multiplied by the carrier, then mixed with the dry notes
+            let ring = 1.0 - config.ring_mix + config.ring_mix * self.ring_phase.sin();
+            self.ring_phase = (self.ring_phase + ring_step) % (2.0 * std::f64::consts::PI);
 
-            for note_index in 0..nb_notes {
-                let fade = if self.play[note_index] || self.fade_in[note_index] < 1.0 {
-                    if self.fade_in[note_index] > 1.0 {
+            let mut voice_index = 0;
+            while voice_index < self.notes.voices.len() {
+                let note_index = self.notes.voices[voice_index];
+                let fade = if self.notes.play[note_index] || self.notes.fade_in[note_index] < 1.0 {
+                    if self.notes.fade_in[note_index] > 1.0 {
                         1.0
                     } else {
-                        let prev = self.fade_in[note_index];
-                        self.fade_in[note_index] +=
-                            Self::compute_increment(self.rate, self.config.fade_in_duration);
-                        let factor = Player::get_shape_factor(self.config.fade_in_shape);
+                        let prev = self.notes.fade_in[note_index];
+                        self.notes.fade_in[note_index] +=
+                            Player::compute_increment(rate, config.fade_in_duration);
+                        let factor = Player::get_shape_factor(config.fade_in_shape);
                         prev.powf(factor)
                     }
                 } else {
-                    if self.fade_out[note_index] < 0.0 {
+                    if self.notes.fade_out[note_index] < 0.0 {
                         0.0
                     } else {
-                        let prev = self.fade_out[note_index];
-                        self.fade_out[note_index] -=
-                            Self::compute_increment(self.rate, self.config.fade_out_duration);
-                        let factor = Player::get_shape_factor(self.config.fade_out_shape);
-                        prev.powf(factor)
+                        let prev = self.notes.fade_out[note_index];
+                        self.notes.fade_out[note_index] -=
+                            Player::compute_increment(rate, config.fade_out_duration);
+                        let factor = Player::get_shape_factor(config.fade_out_shape);
+                        //the end of a steep fade out gets extremely small
+                        flush_denormal(prev.powf(factor))
                     }
                 };
 
-                if fade > 0.0 {
-                    let overtones_freq = &self.config.overtone_freq;
-                    let overtones_impact = &self.config.overtone;
+                if fade <= 0.0 {
+                    if !self.notes.play[note_index] && self.notes.fade_in[note_index] >= 1.0 {
+                        //the fade out is over: the voice is free
+                        self.notes.voices.remove(voice_index);
+                    } else {
+                        voice_index += 1;
+                    }
+                    continue;
+                }
+                voice_index += 1;
+                let matrix = if config.matrix.routes.is_empty() {
+                    Modulation::NONE
+                } else {
+                    sources[ModSource::Envelope as usize] = fade;
+                    sources[ModSource::Velocity as usize] =
+                        self.notes.velocity[note_index] / FULL_VELOCITY_GAIN;
+                    config.matrix.modulate(&sources)
+                };
+                let mut ratio = matrix.pitch;
+                if config.pitch_env_amount != 0.0 {
+                    ratio *= Player::pitch_envelope(
+                        config.pitch_env_amount,
+                        config.pitch_env_decay,
+                        self.notes.pitch_age[note_index],
+                    );
+                }
+                if config.drift > 0.0 {
+                    let drift =
+                        Player::drift_step(self.notes.drift[note_index], random::<f64>(), frame_t);
+                    self.notes.drift[note_index] = drift;
+                    ratio *= 2.0_f64.powf(drift * config.drift * DRIFT_RANGE / 12.0);
+                }
+                self.notes.pitch_age[note_index] += frame_t;
+                let frequency = Player::get_frequency(tuning.pitch(note_index));
+                let cycles = self.notes.phase[note_index];
+                self.notes.phase[note_index] += frequency * step * ratio;
+                let mut voice: f64 = 0.0;
+                if config.sub_level > 0.0 {
+                    //one octave down: half the frequency of the note
+                    let phase = cycles * std::f64::consts::PI;
+                    voice += config.sub_wave.compute(phase)
+                        * config.sub_level
+                        * self.notes.velocity[note_index]
+                        * fade;
+                }
+                if config.synthesis == SynthesisType::Fm {
+                    let phase = cycles * 2.0 * std::f64::consts::PI;
+                    let modulator = (phase * config.fm_ratio).sin();
+                    let y = config.wave.compute(phase + config.fm_index * modulator);
+                    voice += y * self.notes.velocity[note_index] * fade;
+                } else {
+                    let overtones_freq = &config.overtone_freq;
+                    let overtones_impact = &config.overtone;
                     for overtone_index in
                         0..std::cmp::min(overtones_freq.len(), overtones_impact.len())
                     {
-                        let x = Self::get_frequency(note_index as f64)
-                            * overtones_freq[overtone_index]
-                            * self.time
-                            * 2.0
-                            * std::f64::consts::PI;
-
-                        let y = self.config.wave.compute(x);
-                        value +=
-                            y * self.velocity[note_index] * overtones_impact[overtone_index] * fade;
+                        let x =
+                            overtones_freq[overtone_index] * cycles * 2.0 * std::f64::consts::PI;
+
+                        let y = config.overtone_wave(overtone_index).compute(x);
+                        let mut tilt = Player::velocity_tilt(
+                            config.velocity_brightness,
+                            self.notes.velocity[note_index],
+                            overtones_freq[overtone_index],
+                        );
+                        if matrix.tilt != 0.0 {
+                            tilt *= overtones_freq[overtone_index].powf(matrix.tilt);
+                        }
+                        voice += y
+                            * self.notes.velocity[note_index]
+                            * overtones_impact[overtone_index]
+                            * tilt
+                            * fade;
                     }
-                    mute = false;
                 }
+                let voice = voice * ring * matrix.amplitude;
+                let note_pressure = self.notes.pressure[note_index] * config.poly_aftertouch_depth;
+                let (voice, brightness) = match config.poly_aftertouch {
+                    PolyAftertouchDestination::Off => (voice, 1.0),
+                    PolyAftertouchDestination::Amplitude => (voice * (1.0 + note_pressure), 1.0),
+                    PolyAftertouchDestination::Brightness => {
+                        (voice, 2.0_f64.powf(note_pressure * AFTERTOUCH_FILTER_RANGE))
+                    }
+                };
+                value +=
+                    if config.filter_envelope != 0.0 || brightness != 1.0 || matrix.cutoff != 1.0 {
+                        //the cutoff follows the fade in/out, the pressure of the
+                        //note and the modulations
+                        let cutoff = config.filter_cutoff
+                            * matrix.cutoff
+                            * brightness
+                            * 2.0_f64.powf(config.filter_envelope * fade);
+                        let coefs = SvfCoefficients::new(rate, cutoff, config.filter_resonance);
+                        self.notes.filters[note_index].low_pass(voice, &coefs)
+                    } else {
+                        self.notes.filters[note_index].low_pass(voice, &filter)
+                    };
+                mute = false;
             }
-            value *= self.config.gain;
-            *v = value as f32;
+            value *= config.gain;
+            *v += value as f32;
 
-            let modulation_aux =
-                (self.config.modulation as f64) * self.real_time * std::f64::consts::PI
-                    / self.config.mod_speed;
-            let modulation = self.config.mod_intensity * modulation_aux.sin() + 1.0;
-            self.time += self.frame_t * self.time_dilation_factor * modulation;
-            self.real_time += self.frame_t;
+            self.real_time += frame_t;
             if mute {
-                self.time = 0.0;
                 self.real_time = 0.0;
             }
         }
-
-        // Continue as normal
-        jack::Control::Continue
     }
 }
 
 impl jack::ProcessHandler for Player {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
         //update according to the input received
-        self.read_input(ps);
+        self.read_commands();
+        self.read_midi(ps);
+
+        //without user interface, nobody can stop the sound
+        if self.external_commands.muted() {
+            self.audio_mono_out.as_mut_slice(ps).fill(0.0);
+            return jack::Control::Continue;
+        }
+
+        //every event is applied at its own frame, so that the notes do not
+        //start on the boundaries of the buffer
+        let nb_frames = ps.n_frames() as usize;
+        let mut start = 0;
+        let mut events = std::mem::take(&mut self.events);
+        for (time, midi) in events.drain(..) {
+            let time = (time as usize).min(nb_frames);
+            if time > start {
+                self.generate_sound(ps, start..time);
+                start = time;
+            }
+            self.apply_midi(midi);
+        }
+        self.events = events;
+        self.generate_sound(ps, start..nb_frames);
+
+        if let Some(program) = self.tuning_request.take() {
+            let dump = self.tuning.bulk_dump(ALL_DEVICES, program);
+            let raw = jack::RawMidi {
+                time: 0,
+                bytes: &dump,
+            };
+            if let Err(e) = self.midi_out.writer(ps).write(&raw) {
+                eprintln!("Unable to send the tuning: {e}");
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        midiinput::MidiInput,
+        synth::{
+            hardware::KeyBoardKey,
+            rsynth::configuration::{Configuration, OscillatorPhase, VoiceStealing},
+        },
+    };
+
+    use super::{Notes, Player, FULL_VELOCITY_GAIN, PITCH_BEND_RANGE};
+
+    fn notes(nb_notes: usize) -> Notes {
+        let mut notes = Notes::new(nb_notes);
+        notes.velocity.fill(0.5);
+        notes
+    }
+
+    #[test]
+    fn voice_stealing() {
+        let mut notes = notes(8);
+        for note_index in 0..3 {
+            notes.allocate(
+                note_index,
+                2,
+                VoiceStealing::Oldest,
+                OscillatorPhase::Retrigger,
+            );
+            notes.play[note_index] = true;
+        }
+        assert_eq!(notes.voices, vec![1, 2]);
+        assert!(!notes.play[0]);
+
+        //the released notes are stolen first
+        notes.release(2);
+        notes.allocate(3, 2, VoiceStealing::Oldest, OscillatorPhase::Retrigger);
+        assert_eq!(notes.voices, vec![1, 3]);
+
+        notes.play[3] = true;
+        notes.velocity[1] = 0.1;
+        notes.allocate(4, 2, VoiceStealing::Quietest, OscillatorPhase::Retrigger);
+        assert_eq!(notes.voices, vec![3, 4]);
+    }
+
+    #[test]
+    fn phase_per_voice() {
+        let mut notes = notes(8);
+        notes.allocate(2, 4, VoiceStealing::Oldest, OscillatorPhase::Retrigger);
+        notes.phase[2] = 3.25;
+        //a note played again while it sounds goes on with its wave
+        notes.allocate(2, 4, VoiceStealing::Oldest, OscillatorPhase::Retrigger);
+        assert_eq!(notes.phase[2], 3.25);
+        //a silent note starts at the beginning of its wave
+        notes.voices.clear();
+        notes.allocate(2, 4, VoiceStealing::Oldest, OscillatorPhase::Retrigger);
+        assert_eq!(notes.phase[2], 0.0);
+        //a free running note starts anywhere in its wave
+        notes.voices.clear();
+        notes.phase[2] = 3.25;
+        notes.allocate(2, 4, VoiceStealing::Oldest, OscillatorPhase::FreeRun);
+        assert!((0.0..2.0).contains(&notes.phase[2]));
+    }
+
+    #[test]
+    fn silence_the_voices() {
+        let mut notes = notes(8);
+        for note_index in [1, 5] {
+            notes.allocate(
+                note_index,
+                4,
+                VoiceStealing::Oldest,
+                OscillatorPhase::Retrigger,
+            );
+            notes.play[note_index] = true;
+            notes.held.push(note_index);
+        }
+        notes.release(5);
+        notes.silence();
+        assert!(notes.voices.is_empty() && notes.held.is_empty());
+        assert!(notes.play.iter().all(|play| !play));
+        assert_eq!(notes.fade_out[5], 0.0);
+        //the state is only kept for the notes below 'nb_notes'
+        assert!(notes.holds(7) && !notes.holds(8));
+    }
+
+    #[test]
+    fn notes_out_of_range() {
+        let note = |bytes: &[u8]| {
+            Player::note_out_of_range(&MidiInput::from(jack::RawMidi { time: 0, bytes }))
+        };
+        //the highest midi note is the highest key of the synth
+        assert_eq!(note(&[0x90, 127, 100]), None);
+        assert_eq!(note(&[0x80, 127, 0]), None);
+        assert_eq!(note(&[0xA0, 60, 10]), None);
+        //a data byte above 127 is not a valid note
+        assert_eq!(note(&[0x90, 200, 100]), Some(212));
+        assert_eq!(note(&[0x80, 128, 0]), Some(140));
+        assert_eq!(note(&[0xA0, 255, 10]), Some(267));
+        assert_eq!(note(&[0xB0, 200, 10]), None);
+    }
+
+    #[test]
+    fn octave_shift() {
+        //the A of the midi note 69 is played two octaves above 440 Hz
+        assert!((Player::get_frequency(12.0 + 69.0) - 1760.0).abs() < 1e-9);
+        assert!((Player::get_frequency(12.0 + 57.0) - 880.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pitch_bend() {
+        let semitones = |value| 12.0 * Player::bend_ratio(value).log2();
+        assert_eq!(Player::bend_ratio(64), 1.0);
+        assert!((semitones(127) - PITCH_BEND_RANGE).abs() < 1e-9);
+        assert!((semitones(0) + PITCH_BEND_RANGE).abs() < 1e-9);
+        assert!((semitones(32) + PITCH_BEND_RANGE / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pitch_envelope() {
+        assert_eq!(Player::pitch_envelope(12.0, 0.1, 0.0), 2.0);
+        assert_eq!(Player::pitch_envelope(-12.0, 0.1, 0.0), 0.5);
+        assert_eq!(Player::pitch_envelope(12.0, 0.0, 0.0), 1.0);
+        let late = Player::pitch_envelope(12.0, 0.1, 1.0);
+        assert!(late > 1.0 && late < 1.001);
+    }
+
+    #[test]
+    fn velocity_tilt() {
+        //the note itself and the lower overtones are not changed
+        assert_eq!(Player::velocity_tilt(1.0, 0.1, 1.0), 1.0);
+        assert_eq!(Player::velocity_tilt(1.0, 0.1, 0.5), 1.0);
+        assert_eq!(Player::velocity_tilt(0.0, 0.1, 4.0), 1.0);
+        //the hardest hit keeps every overtone
+        assert_eq!(Player::velocity_tilt(1.0, FULL_VELOCITY_GAIN, 4.0), 1.0);
+        let half = FULL_VELOCITY_GAIN / 2.0;
+        assert!((Player::velocity_tilt(1.0, half, 2.0) - 0.5).abs() < 1e-9);
+        assert!((Player::velocity_tilt(1.0, half, 4.0) - 0.25).abs() < 1e-9);
+        assert!((Player::velocity_tilt(0.5, half, 4.0) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn drift() {
+        let frame_t = 1.0 / 48000.0;
+        //without noise, the note goes back to its pitch
+        let mut drift = 1.0;
+        for _ in 0..48000 {
+            drift = Player::drift_step(drift, 0.5, frame_t);
+        }
+        assert!(drift > 0.0 && drift < 0.2);
+        //the drift never goes beyond its range
+        for _ in 0..48000 {
+            drift = Player::drift_step(drift, 1.0, frame_t);
+        }
+        assert!(drift <= 1.0);
+    }
 
-        self.generate_sound(ps)
+    #[test]
+    fn apply_control() {
+        let mut config = Configuration::new();
+        Player::apply_control(&mut config, KeyBoardKey::FilterResonance, 127);
+        assert_eq!(config.filter_resonance, 1.0);
+        //a switch only reacts when it is pressed
+        let voice_mode = config.voice_mode;
+        Player::apply_control(&mut config, KeyBoardKey::VoiceMode, 0);
+        assert_eq!(config.voice_mode, voice_mode);
+        Player::apply_control(&mut config, KeyBoardKey::VoiceMode, 127);
+        assert_eq!(config.voice_mode, voice_mode.cycle());
     }
 }
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, StorageError};
+
+use super::{configuration::Configuration, TOOL_NAME};
+
+///The end of the name of the file containing the presets, after the name of
+/// the instance
+const PRESETS_FILE: &str = "_presets.json";
+
+///A named configuration of the synthesizer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub configuration: Configuration,
+}
+
+///The presets of the synthesizer. A midi program change recalls the preset
+/// at the same position: program 0 recalls the first preset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PresetBank {
+    presets: Vec<Preset>,
+}
+
+impl PresetBank {
+    ///The file in which the presets are saved
+    pub fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(format!(
+            "{}{PRESETS_FILE}",
+            storage::instance_name(TOOL_NAME)
+        ));
+        Ok(path)
+    }
+
+    ///Load the presets saved by the user. The bank is empty if nothing was saved yet.
+    pub fn load() -> Result<PresetBank, StorageError> {
+        let path = Self::path()?;
+        if path.exists() {
+            storage::load(&path)
+        } else {
+            Ok(PresetBank::default())
+        }
+    }
+
+    pub fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path()?, self)
+    }
+
+    pub fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    ///Add a preset at the end of the bank, or replace the one with the same name.
+    /// Return the position of the preset.
+    pub fn store(&mut self, name: &str, configuration: &Configuration) -> usize {
+        let preset = Preset {
+            name: String::from(name),
+            configuration: configuration.clone(),
+        };
+        match self.presets.iter().position(|p| p.name == name) {
+            Some(index) => {
+                self.presets[index] = preset;
+                index
+            }
+            None => {
+                self.presets.push(preset);
+                self.presets.len() - 1
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.presets.len() {
+            self.presets.remove(index);
+        }
+    }
+
+    ///The configurations of the presets, in the order of the program changes
+    pub fn configurations(&self) -> Vec<Configuration> {
+        self.presets
+            .iter()
+            .map(|p| p.configuration.clone())
+            .collect()
+    }
+}
@@ -1,41 +1,96 @@
 use eframe::egui::{self};
 use egui_plot::{Line, PlotPoints};
-use std::{
-    ops::RangeInclusive,
-    sync::mpsc::{Receiver, Sender},
-};
+use std::{ops::RangeInclusive, sync::Arc, time::Instant};
 
+use crate::dsp::SvfCoefficients;
+use crate::i18n::tr;
+use crate::scene::SceneListener;
+use crate::session::Autosave;
+use crate::spsc::{Receiver, Sender};
+use crate::storage::{self, FileWatcher};
 use crate::synth::{
-    hardware::KeyBoardKey,
+    hardware::{HardWare, KeyBoardKey},
     rsynth::{
-        configuration::Configuration,
+        configuration::{Configuration, KeyLimit, PolyAftertouchDestination, Split, SynthesisType},
+        history::History,
+        modulation::{ModDestination, ModRoute, ModSource, MAX_ROUTES},
         player::{
-            MessageToPlayer, MessageToUI, Player, FADE_DURATION_STEP, GAIN_STEP, OVERTONE_STEP,
+            MessageToPlayer, MessageToUI, Player, CHORUS_MAX_RATE, FADE_DURATION_STEP,
+            FILTER_MAX_CUTOFF, FILTER_MAX_ENVELOPE, FILTER_MIN_CUTOFF, FM_INDEX_STEP,
+            FM_RATIO_STEP, GAIN_STEP, LFO_MAX_RATE, LIMITER_MAX_DRIVE, LIMITER_MIN_CEILING,
+            MAX_POLYPHONY, OVERTONE_MAX_RATIO, OVERTONE_MIN_RATIO, OVERTONE_STEP,
+            PITCH_ENVELOPE_MAX_DECAY, PITCH_ENVELOPE_RANGE, RING_MAX_FREQUENCY, RING_MIN_FREQUENCY,
         },
+        presets::PresetBank,
+        TOOL_NAME,
     },
+    wavetype::{WaveType, Wavetable},
 };
+use crate::utils::{piano::Piano, JackStatus, MidiChannel, ShortcutAction, Shortcuts};
+
+///The lowest split point, as index of the note
+const MIN_SPLIT_POINT: usize = 13;
+///The highest split point, as index of the note
+const MAX_SPLIT_POINT: usize = 139;
 
 pub struct RustySynth<'c> {
     receiver: Receiver<MessageToUI>,
     commands: Sender<MessageToPlayer>,
     configuration: Configuration,
+    ///The edits of the configuration, to undo them
+    history: History<Configuration>,
     messages: Vec<String>,
     used_keys: Vec<KeyBoardKey>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the synth listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<Configuration>,
+    ///The path of the wav file to load as wavetable
+    wavetable_path: String,
+    ///The path of the json file in which the configuration is saved
+    patch_path: String,
+    ///The presets recalled by the program changes
+    bank: PresetBank,
+    ///The name under which the configuration is stored in the bank
+    preset_name: String,
+    ///The last preset recalled
+    current_preset: Option<usize>,
+    ///If true, the parameters of the lower part of the split keyboard are shown
+    lower_page: bool,
+    ///The end of the range of the keys waiting for the next note played
+    learning_key_limit: Option<KeyLimit>,
+    ///The number of keys retuned by the midi tuning standard messages
+    retuned: usize,
+    ///Detect the changes of the presets file made outside of the synth
+    presets_watcher: Option<FileWatcher>,
+    ///Detect the changes of the keyboard mapping file made outside of the synth
+    mapping_watcher: Option<FileWatcher>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The keyboard to play the synth with the mouse
+    piano: Piano,
     //the jack client to make sure that we update the name of the window
     client: &'c jack::Client,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl<'c> RustySynth<'c> {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
         rcv: Receiver<MessageToUI>,
         send: Sender<MessageToPlayer>,
         client: &'c jack::Client,
     ) -> Self {
-        return Self {
+        let mut synth = Self {
+            status,
             receiver: rcv,
             commands: send,
             configuration: Configuration::new(),
+            history: History::new(Configuration::new()),
             messages: Vec::new(),
             used_keys: vec![
                 KeyBoardKey::WaveSelection,
@@ -56,9 +111,207 @@ impl<'c> RustySynth<'c> {
                 KeyBoardKey::Modulation,
                 KeyBoardKey::ModulationSpeed,
                 KeyBoardKey::ModulationIntensity,
+                KeyBoardKey::VoiceMode,
+                KeyBoardKey::Synthesis,
+                KeyBoardKey::FmRatio,
+                KeyBoardKey::FmIndex,
+                KeyBoardKey::FilterCutoff,
+                KeyBoardKey::FilterResonance,
+                KeyBoardKey::FilterEnvelope,
+                KeyBoardKey::SubLevel,
+                KeyBoardKey::SubWave,
+                KeyBoardKey::LfoWave,
+                KeyBoardKey::LfoRate,
+                KeyBoardKey::LfoDepth,
+                KeyBoardKey::LfoDestination,
+                KeyBoardKey::AftertouchDestination,
+                KeyBoardKey::AftertouchDepth,
+                KeyBoardKey::PolyAftertouchDestination,
+                KeyBoardKey::PolyAftertouchDepth,
+                KeyBoardKey::PitchEnvelopeAmount,
+                KeyBoardKey::PitchEnvelopeDecay,
+                KeyBoardKey::RingFrequency,
+                KeyBoardKey::RingMix,
             ],
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+            wavetable_path: String::new(),
+            patch_path: String::new(),
+            bank: PresetBank::default(),
+            preset_name: String::new(),
+            current_preset: None,
+            lower_page: false,
+            learning_key_limit: None,
+            retuned: 0,
+            presets_watcher: PresetBank::path().ok().map(FileWatcher::new),
+            mapping_watcher: Player::mapping_path().ok().map(FileWatcher::new),
+            shortcuts: Shortcuts::load(),
+            piano: Piano::default(),
             client: client,
         };
+        synth.load_presets();
+        synth
+    }
+
+    ///Load the preset bank and send it to the player
+    fn load_presets(&mut self) {
+        match PresetBank::load() {
+            Ok(bank) => {
+                self.bank = bank;
+                self.send_bank();
+            }
+            Err(e) => self
+                .messages
+                .push(format!("Unable to load the presets: {e}")),
+        }
+    }
+
+    ///Send the configurations of the preset bank to the player
+    fn send_bank(&mut self) {
+        let bank = self
+            .bank
+            .configurations()
+            .into_iter()
+            .map(|configuration| Some(Box::new(configuration)))
+            .collect();
+        if let Err(e) = self.commands.send(MessageToPlayer::NewBank(bank)) {
+            self.messages
+                .push(format!("Unable to send the presets to player: {e}"));
+        }
+    }
+
+    ///Read the saved keyboard mapping and send it to the player
+    fn load_mapping(&mut self) {
+        match Player::load_keyboard_conf() {
+            Ok(mapping) => {
+                let mapping = MessageToPlayer::NewKeyboardMapping(Box::new(mapping));
+                if let Err(e) = self.commands.send(mapping) {
+                    self.messages.push(format!("[UI] {e}"));
+                }
+            }
+            Err(e) => self
+                .messages
+                .push(format!("Unable to load the keyboard mapping: {e}")),
+        }
+    }
+
+    ///Reload the presets and the keyboard mapping when their files are
+    /// changed, for instance by hand or by a synchronisation tool
+    fn watch_files(&mut self) {
+        if self.presets_watcher.as_mut().is_some_and(|w| w.changed()) {
+            match PresetBank::load() {
+                Ok(bank) => {
+                    if bank != self.bank {
+                        self.bank = bank;
+                        if self
+                            .current_preset
+                            .is_some_and(|index| index >= self.bank.presets().len())
+                        {
+                            self.current_preset = None;
+                        }
+                        self.send_bank();
+                    }
+                }
+                Err(e) => self
+                    .messages
+                    .push(format!("Unable to reload the presets: {e}")),
+            }
+        }
+        if self.mapping_watcher.as_mut().is_some_and(|w| w.changed()) {
+            self.load_mapping();
+        }
+    }
+
+    ///Load the files dropped on the window: a keyboard mapping replaces the
+    /// current one, a preset bank replaces the presets and a patch becomes
+    /// the current configuration. Other files are reported as errors.
+    fn load_dropped_files(&mut self, ctx: &egui::Context) {
+        for path in crate::utils::dropped_files(ctx) {
+            if let Ok(mapping) = storage::load::<HardWare>(&path) {
+                let saved = Player::mapping_path().and_then(|p| storage::save(&p, &mapping));
+                match saved {
+                    Ok(()) => {
+                        let mapping = MessageToPlayer::NewKeyboardMapping(Box::new(mapping));
+                        if let Err(e) = self.commands.send(mapping) {
+                            self.messages.push(format!("[UI] {e}"));
+                        }
+                    }
+                    Err(e) => self
+                        .messages
+                        .push(format!("Unable to save the keyboard mapping: {e}")),
+                }
+            } else if let Ok(bank) = storage::load::<PresetBank>(&path) {
+                self.bank = bank;
+                self.current_preset = None;
+                self.update_bank();
+            } else {
+                match storage::load::<Configuration>(&path) {
+                    Ok(configuration) => {
+                        self.configuration = configuration;
+                        self.current_preset = None;
+                        if let Err(e) =
+                            self.commands
+                                .send(MessageToPlayer::NewConfiguration(Box::new(
+                                    self.configuration.clone(),
+                                )))
+                        {
+                            self.messages
+                                .push(format!("Unable to send configuration to player: {e}"));
+                        }
+                    }
+                    Err(e) => self.messages.push(format!(
+                        "'{}' is neither a patch, a preset bank nor a keyboard mapping: {e}",
+                        path.display()
+                    )),
+                }
+            }
+        }
+    }
+
+    ///Save the preset bank and send it to the player
+    fn update_bank(&mut self) {
+        if let Err(e) = self.bank.save() {
+            self.messages
+                .push(format!("Unable to save the presets: {e}"));
+        }
+        self.send_bank();
+    }
+
+    ///Show the presets. Clicking on a preset recalls it; its position is the
+    /// midi program recalling it.
+    fn create_presets(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(tr("Presets")).show(ui, |ui| {
+            let mut removed = None;
+            for (index, preset) in self.bank.presets().iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let selected = self.current_preset == Some(index);
+                    if ui
+                        .selectable_label(selected, format!("{index}: {}", preset.name))
+                        .clicked()
+                    {
+                        self.configuration = preset.configuration.clone();
+                        self.current_preset = Some(index);
+                    }
+                    if ui.small_button(tr("Delete")).clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                self.bank.remove(index);
+                self.current_preset = None;
+                self.update_bank();
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.preset_name);
+                if ui.button(tr("Save as preset")).clicked() && !self.preset_name.is_empty() {
+                    let index = self.bank.store(&self.preset_name, &self.configuration);
+                    self.current_preset = Some(index);
+                    self.update_bank();
+                }
+            });
+        });
     }
 
     fn create_fade_shape(fade_in: bool, shape: u8, duration: f64) -> Line {
@@ -77,18 +330,84 @@ impl<'c> RustySynth<'c> {
         Line::new(points)
     }
 
+    ///A short name for the wave type of an overtone, "-" if it uses the wave type of the configuration
+    fn overtone_wave_name(wave: &Option<WaveType>) -> &'static str {
+        match wave {
+            None => "-",
+            Some(WaveType::Sin) => "Sin",
+            Some(WaveType::Square) => "Sqr",
+            Some(WaveType::SawTooth) => "Saw",
+            Some(WaveType::Triangle) => "Tri",
+            Some(WaveType::Table(_)) => "Tbl",
+        }
+    }
+
+    ///Cycle through the wave types of an overtone, going back to the wave
+    /// type of the configuration after the last one
+    fn cycle_overtone_wave(wave: &Option<WaveType>) -> Option<WaveType> {
+        match wave {
+            None => Some(WaveType::Sin),
+            Some(WaveType::Triangle) => None,
+            Some(wave) => Some(wave.cycle()),
+        }
+    }
+
+    ///Plot the gain (in dB) of the filter against the frequency (log scale)
+    fn create_filter_response(cutoff: f64, resonance: f64) -> Line {
+        let mut points = Vec::with_capacity(300);
+        let ratio = FILTER_MAX_CUTOFF / FILTER_MIN_CUTOFF;
+        for i in 0..300 {
+            let frequency = FILTER_MIN_CUTOFF * ratio.powf(i as f64 / 299.0);
+            let gain = SvfCoefficients::low_pass_magnitude(frequency, cutoff, resonance);
+            points.push([frequency.log10(), crate::dsp::gain_to_db(gain)]);
+        }
+        Line::new(PlotPoints::new(points))
+    }
+
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("File", |ui| {
-                if ui.button("Save Configuration").clicked() {
-                    todo!()
+            ui.menu_button(tr("File"), |ui| {
+                ui.label(tr("Patch (json file):"));
+                ui.text_edit_singleline(&mut self.patch_path);
+                if ui.button(tr("Save Configuration")).clicked() {
+                    self.save_configuration();
+                    ui.close_menu();
+                }
+                if ui.button(tr("Load Configuration")).clicked() {
+                    self.load_configuration();
+                    ui.close_menu();
                 }
-                if ui.button("Load Configuration").clicked() {
-                    todo!()
+                ui.separator();
+                ui.label(tr("Wavetable (wav file):"));
+                ui.text_edit_singleline(&mut self.wavetable_path);
+                if ui.button(tr("Load Wavetable")).clicked() {
+                    self.load_wavetable();
+                    ui.close_menu();
                 }
             });
-            ui.menu_button("Settings", |ui| {
-                ui.menu_button("KeyBoard", |ui| {
+            ui.menu_button(tr("Edit"), |ui| {
+                if ui
+                    .add_enabled(self.history.can_undo(), egui::Button::new(tr("Undo")))
+                    .clicked()
+                {
+                    self.undo();
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(self.history.can_redo(), egui::Button::new(tr("Redo")))
+                    .clicked()
+                {
+                    self.redo();
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button(tr("Randomize")).clicked() {
+                    self.randomize();
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button(tr("Settings"), |ui| {
+                ui.menu_button(tr("KeyBoard"), |ui| {
                     for k in &self.used_keys {
                         crate::utils::create_keyboard_select(
                             ui,
@@ -99,7 +418,7 @@ impl<'c> RustySynth<'c> {
                         );
                     }
                 });
-                if ui.button("Clear keyboard mapping").clicked() {
+                if ui.button(tr("Clear keyboard mapping")).clicked() {
                     match self.commands.send(MessageToPlayer::ClearAllKeyboardKeys) {
                         Ok(()) => {
                             ui.close_menu();
@@ -107,7 +426,7 @@ impl<'c> RustySynth<'c> {
                         Err(e) => self.messages.push(format!("[UI] {e}")),
                     }
                 }
-                if ui.button("Save keyboard mapping").clicked() {
+                if ui.button(tr("Save keyboard mapping")).clicked() {
                     match self.commands.send(MessageToPlayer::SaveConf) {
                         Ok(()) => {
                             ui.close_menu();
@@ -115,19 +434,169 @@ impl<'c> RustySynth<'c> {
                         Err(e) => self.messages.push(format!("[UI] {e}")),
                     }
                 }
-                if ui.button("Load keyboard mapping").clicked() {
-                    match self.commands.send(MessageToPlayer::LoadConf) {
-                        Ok(()) => {
-                            ui.close_menu();
+                if ui.button(tr("Load keyboard mapping")).clicked() {
+                    self.load_mapping();
+                    ui.close_menu();
+                }
+                ui.menu_button(tr("Tuning"), |ui| {
+                    ui.label(format!("{}{}", tr("Retuned keys: "), self.retuned));
+                    if ui.button(tr("Reset tuning")).clicked() {
+                        if let Err(e) = self.commands.send(MessageToPlayer::ResetTuning) {
+                            self.messages.push(format!("[UI] {e}"));
                         }
-                        Err(e) => self.messages.push(format!("[UI] {e}")),
+                        ui.close_menu();
+                    }
+                    if ui.button(tr("Send tuning")).clicked() {
+                        if let Err(e) = self.commands.send(MessageToPlayer::SendTuning) {
+                            self.messages.push(format!("[UI] {e}"));
+                        }
+                        ui.close_menu();
+                    }
+                });
+                self.shortcuts
+                    .create_menu(ui, &[ShortcutAction::Panic], &mut self.messages);
+                if crate::utils::create_channel_select(
+                    ui,
+                    TOOL_NAME,
+                    &mut self.channel,
+                    &mut self.messages,
+                ) {
+                    if let Err(e) = self.commands.send(MessageToPlayer::Channel(self.channel)) {
+                        self.messages.push(format!("[UI] {e}"));
                     }
                 }
             });
+            if ui.button(tr("Panic")).clicked() {
+                self.panic();
+            }
             crate::utils::common_menu_luncher(ui, &mut self.messages);
         });
     }
 
+    ///Replace the whole configuration and send it to the player
+    fn restore(&mut self, configuration: Configuration) {
+        self.configuration = configuration;
+        if let Err(e) = self
+            .commands
+            .send(MessageToPlayer::NewConfiguration(Box::new(
+                self.configuration.clone(),
+            )))
+        {
+            self.messages
+                .push(format!("Unable to send configuration to player: {e}"));
+        }
+    }
+
+    ///Draw a new sound for the part shown, and send it to the player
+    fn randomize(&mut self) {
+        match self.configuration.split.as_mut() {
+            Some(split) if self.lower_page => split.configuration.randomize(),
+            _ => self.configuration.randomize(),
+        }
+        self.restore(self.configuration.clone());
+    }
+
+    ///Go back to the configuration before the last edit
+    fn undo(&mut self) {
+        if let Some(configuration) = self.history.undo() {
+            self.restore(configuration);
+        }
+    }
+
+    ///Apply again the last edit undone
+    fn redo(&mut self) {
+        if let Some(configuration) = self.history.redo() {
+            self.restore(configuration);
+        }
+    }
+
+    ///Undo and redo with Ctrl+Z and Ctrl+Y, unless a text field is edited
+    fn read_history_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (undo, redo) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z),
+                i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y),
+            )
+        });
+        if undo {
+            self.undo();
+        }
+        if redo {
+            self.redo();
+        }
+    }
+
+    ///Stop every note, when a note is stuck
+    fn panic(&mut self) {
+        if let Err(e) = self.commands.send(MessageToPlayer::AllNotesOff) {
+            self.messages.push(format!("[UI] {e}"));
+        }
+    }
+
+    ///Save the current configuration in the patch file
+    fn save_configuration(&mut self) {
+        let path = std::path::Path::new(&self.patch_path);
+        if let Err(e) = crate::storage::save(path, &self.configuration) {
+            self.messages
+                .push(format!("Unable to save '{}': {e}", path.display()));
+        }
+    }
+
+    ///Load the configuration of the patch file and send it to the player
+    fn load_configuration(&mut self) {
+        let path = std::path::Path::new(&self.patch_path);
+        match crate::storage::load::<Configuration>(path) {
+            Ok(configuration) => {
+                self.configuration = configuration;
+                if let Err(e) = self
+                    .commands
+                    .send(MessageToPlayer::NewConfiguration(Box::new(
+                        self.configuration.clone(),
+                    )))
+                {
+                    self.messages
+                        .push(format!("Unable to send configuration to player: {e}"));
+                }
+            }
+            Err(e) => self
+                .messages
+                .push(format!("Unable to load '{}': {e}", path.display())),
+        }
+    }
+
+    ///Load a single cycle waveform from a wav file and send it to the player
+    fn load_wavetable(&mut self) {
+        let path = std::path::Path::new(&self.wavetable_path);
+        let samples = match crate::wavreader::load_wav(path) {
+            Ok(audio) => audio.to_mono(),
+            Err(e) => {
+                self.messages
+                    .push(format!("Unable to load '{}': {e}", path.display()));
+                return;
+            }
+        };
+        let name = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => String::from("Wavetable"),
+        };
+        match Wavetable::new(&name, &samples) {
+            Some(table) => {
+                let table = Arc::new(table);
+                self.configuration.wave = WaveType::Table(table.clone());
+                if let Err(e) = self.commands.send(MessageToPlayer::NewWavetable(table)) {
+                    self.messages
+                        .push(format!("Unable to send wavetable to player: {e}"));
+                }
+            }
+            None => self
+                .messages
+                .push(format!("'{}' contains no sound", path.display())),
+        }
+    }
+
     fn create_f64_slider(ui: &mut egui::Ui, label: &str, value: &mut f64, step: f64) {
         let range = std::ops::RangeInclusive::new(0.0, 128.0 * step);
         crate::utils::create_f64_slider(ui, label, value, range);
@@ -136,46 +605,426 @@ impl<'c> RustySynth<'c> {
     fn create_content(&mut self, ui: &mut egui::Ui) {
         let current_config = self.configuration.clone();
 
+        self.session.show(ui);
+        self.create_split(ui);
+        self.create_key_range(ui);
+        match self.configuration.split.take() {
+            Some(mut split) if self.lower_page => {
+                //the lower part is edited as if it was the current configuration
+                std::mem::swap(&mut self.configuration, &mut split.configuration);
+                self.create_parameters(ui);
+                std::mem::swap(&mut self.configuration, &mut split.configuration);
+                split.configuration.split = None;
+                self.configuration.split = Some(split);
+            }
+            split => {
+                self.configuration.split = split;
+                self.create_parameters(ui);
+            }
+        }
+        self.create_effects(ui);
+
+        if current_config != self.configuration {
+            if let Err(e) = self
+                .commands
+                .send(MessageToPlayer::NewConfiguration(Box::new(
+                    self.configuration.clone(),
+                )))
+            {
+                self.messages
+                    .push(format!("Unable to send configuration to player: {e}"));
+            }
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    ///Show the chorus and the soft limiter, applied on the whole output
+    fn create_effects(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.label(tr("Chorus: "));
+        crate::utils::create_f64_slider(
+            ui,
+            "rate (Hz): ",
+            &mut self.configuration.chorus_rate,
+            RangeInclusive::new(0.0, CHORUS_MAX_RATE),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "depth: ",
+            &mut self.configuration.chorus_depth,
+            RangeInclusive::new(0.0, 1.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "mix: ",
+            &mut self.configuration.chorus_mix,
+            RangeInclusive::new(0.0, 1.0),
+        );
+        ui.checkbox(&mut self.configuration.limiter, tr("Soft limiter"));
+        if self.configuration.limiter {
+            crate::utils::create_f64_slider(
+                ui,
+                "drive (dB): ",
+                &mut self.configuration.limiter_drive,
+                RangeInclusive::new(0.0, LIMITER_MAX_DRIVE),
+            );
+            crate::utils::create_f64_slider(
+                ui,
+                "ceiling (dB): ",
+                &mut self.configuration.limiter_ceiling,
+                RangeInclusive::new(LIMITER_MIN_CEILING, 0.0),
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.label(tr("Quality:"));
+            if ui
+                .button(tr(&self.configuration.oversampling.to_string()))
+                .on_hover_text(tr(
+                    "Oversampling of the oscillators and of the limiter: less aliasing, more CPU",
+                ))
+                .clicked()
+            {
+                self.configuration.oversampling = self.configuration.oversampling.cycle();
+            }
+        });
+    }
+
+    ///Show the split of the keyboard and the part whose parameters are shown
+    fn create_split(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut split = self.configuration.split.is_some();
+            if ui.checkbox(&mut split, tr("Split keyboard")).changed() {
+                self.configuration.split = if split {
+                    //the lower part starts with the current sound
+                    Some(Box::new(Split {
+                        point: Split::DEFAULT_POINT,
+                        configuration: Configuration {
+                            split: None,
+                            ..self.configuration.clone()
+                        },
+                    }))
+                } else {
+                    self.lower_page = false;
+                    None
+                };
+            }
+            if let Some(split) = self.configuration.split.as_mut() {
+                ui.label(tr("Split point:"));
+                ui.add(
+                    egui::Slider::new(&mut split.point, MIN_SPLIT_POINT..=MAX_SPLIT_POINT)
+                        .custom_formatter(|n, _| crate::midiinput::note_name(n as usize)),
+                );
+                ui.selectable_value(&mut self.lower_page, false, tr("Upper part"));
+                ui.selectable_value(&mut self.lower_page, true, tr("Lower part"));
+            }
+        });
+    }
+
+    ///Show the range of the keys played, each end can be learned from the
+    /// next note played
+    fn create_key_range(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr("Key range:"));
+            for limit in [KeyLimit::Lowest, KeyLimit::Highest] {
+                let mut key = match limit {
+                    KeyLimit::Lowest => self.configuration.lowest_key,
+                    KeyLimit::Highest => self.configuration.highest_key,
+                };
+                let range = Configuration::LOWEST_KEY..=Configuration::HIGHEST_KEY;
+                if ui
+                    .add(
+                        egui::Slider::new(&mut key, range)
+                            .custom_formatter(|n, _| crate::midiinput::note_name(n as usize)),
+                    )
+                    .changed()
+                {
+                    self.configuration.set_key_limit(limit, key as usize);
+                }
+                if self.learning_key_limit == Some(limit) {
+                    ui.label(tr("Waiting"));
+                } else if ui.button(tr("Learn")).clicked() {
+                    match self.commands.send(MessageToPlayer::LearnKeyLimit(limit)) {
+                        Ok(()) => self.learning_key_limit = Some(limit),
+                        Err(e) => self.messages.push(format!("[UI] {e}")),
+                    }
+                }
+            }
+        });
+    }
+
+    ///Show the parameters of the current configuration
+    ///Show the routes of the modulation matrix, one per row
+    fn create_matrix(&mut self, ui: &mut egui::Ui) {
+        ui.label(tr("Modulation matrix: "));
+        let matrix = &mut self.configuration.matrix;
+        let mut remove = None;
+        egui::Grid::new("modulation_matrix")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr("Source"));
+                ui.label(tr("Via"));
+                ui.label(tr("Destination"));
+                ui.label(tr("Amount"));
+                ui.end_row();
+                for (index, route) in matrix.routes.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_salt(("modulation_source", index))
+                        .selected_text(format!("{}", route.source))
+                        .show_ui(ui, |ui| {
+                            for source in ModSource::ALL {
+                                ui.selectable_value(&mut route.source, source, format!("{source}"));
+                            }
+                        });
+                    let via = match route.via {
+                        Some(via) => format!("{via}"),
+                        None => tr("None").to_owned(),
+                    };
+                    egui::ComboBox::from_id_salt(("modulation_via", index))
+                        .selected_text(via)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut route.via, None, tr("None"));
+                            for source in ModSource::ALL {
+                                ui.selectable_value(
+                                    &mut route.via,
+                                    Some(source),
+                                    format!("{source}"),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_salt(("modulation_destination", index))
+                        .selected_text(format!("{}", route.destination))
+                        .show_ui(ui, |ui| {
+                            for destination in ModDestination::ALL {
+                                ui.selectable_value(
+                                    &mut route.destination,
+                                    destination,
+                                    format!("{destination}"),
+                                );
+                            }
+                        });
+                    ui.add(egui::Slider::new(
+                        &mut route.amount,
+                        RangeInclusive::new(-1.0, 1.0),
+                    ));
+                    if ui.button(tr("Remove")).clicked() {
+                        remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = remove {
+            matrix.routes.remove(index);
+        }
+        if ui
+            .add_enabled(
+                matrix.routes.len() < MAX_ROUTES,
+                egui::Button::new(tr("Add route")),
+            )
+            .clicked()
+        {
+            matrix.routes.push(ModRoute {
+                source: ModSource::Lfo1,
+                via: None,
+                destination: ModDestination::Pitch,
+                amount: 0.0,
+            });
+        }
+    }
+
+    fn create_parameters(&mut self, ui: &mut egui::Ui) {
+        self.create_presets(ui);
+
         //
         // Wave Type
         //
         ui.horizontal(|ui| {
-            ui.label("Wave type:");
+            ui.label(tr("Wave type:"));
             if ui.button(format!("{}", self.configuration.wave)).clicked() {
                 self.configuration.wave = self.configuration.wave.cycle();
             }
         });
+        ui.horizontal(|ui| {
+            ui.label(tr("Voice mode:"));
+            if ui
+                .button(format!("{}", self.configuration.voice_mode))
+                .clicked()
+            {
+                self.configuration.voice_mode = self.configuration.voice_mode.cycle();
+            }
+        });
+        crate::utils::create_usize_slider(
+            ui,
+            "Polyphony:",
+            &mut self.configuration.max_polyphony,
+            RangeInclusive::new(1, MAX_POLYPHONY),
+        );
+        ui.horizontal(|ui| {
+            ui.label(tr("Voice stealing:"));
+            if ui
+                .button(format!("{}", self.configuration.voice_stealing))
+                .clicked()
+            {
+                self.configuration.voice_stealing = self.configuration.voice_stealing.cycle();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("Oscillator phase:"));
+            if ui.button(format!("{}", self.configuration.phase)).clicked() {
+                self.configuration.phase = self.configuration.phase.cycle();
+            }
+        });
         let line = crate::utils::create_plot_line(&self.configuration.wave);
         egui_plot::Plot::new(format!("Wave type: {}", &self.configuration.wave))
             .view_aspect(21.0 / 9.0)
             .show(ui, |plot_ui| plot_ui.line(line));
 
+        ui.horizontal(|ui| {
+            ui.label(tr("Synthesis:"));
+            if ui
+                .button(format!("{}", self.configuration.synthesis))
+                .clicked()
+            {
+                self.configuration.synthesis = self.configuration.synthesis.cycle();
+            }
+        });
+
+        //
+        // FM
+        //
+        if self.configuration.synthesis == SynthesisType::Fm {
+            crate::utils::create_f64_slider(
+                ui,
+                "FM ratio: ",
+                &mut self.configuration.fm_ratio,
+                RangeInclusive::new(FM_RATIO_STEP, 129.0 * FM_RATIO_STEP),
+            );
+            Self::create_f64_slider(
+                ui,
+                "FM index: ",
+                &mut self.configuration.fm_index,
+                FM_INDEX_STEP,
+            );
+        }
+
         //
         // Overtones
         //
-        ui.label("Overtones:");
+        if self.configuration.synthesis == SynthesisType::Overtones {
+            ui.label(tr("Overtones:"));
+            let nb_overtones = self.configuration.overtone.len();
+            self.configuration.overtone_wave.resize(nb_overtones, None);
+            ui.horizontal(|ui| {
+                for overtone_index in 0..nb_overtones {
+                    ui.vertical(|ui| {
+                        let wave = &mut self.configuration.overtone_wave[overtone_index];
+                        if ui
+                            .small_button(Self::overtone_wave_name(wave))
+                            .on_hover_text(tr("Wave type of the overtone"))
+                            .clicked()
+                        {
+                            *wave = Self::cycle_overtone_wave(wave);
+                        }
+                        let range = std::ops::RangeInclusive::new(0.0, 128.0 * OVERTONE_STEP);
+                        ui.add_enabled(
+                            true,
+                            egui::Slider::new(
+                                &mut self.configuration.overtone[overtone_index],
+                                range,
+                            )
+                            .show_value(false)
+                            .vertical(),
+                        );
+                        ui.add(
+                            egui::DragValue::new(
+                                &mut self.configuration.overtone_freq[overtone_index],
+                            )
+                            .range(RangeInclusive::new(OVERTONE_MIN_RATIO, OVERTONE_MAX_RATIO))
+                            .speed(0.01)
+                            .max_decimals(3),
+                        )
+                        .on_hover_text(tr("Frequency of the overtone, relative to the note"));
+                    });
+                }
+            });
+            crate::utils::create_f64_slider(
+                ui,
+                "velocity → brightness: ",
+                &mut self.configuration.velocity_brightness,
+                RangeInclusive::new(0.0, 1.0),
+            );
+        }
+
+        //
+        // Sub-oscillator
+        //
         ui.horizontal(|ui| {
-            for overtone_index in 0..self.configuration.overtone.len() {
-                self.configuration.overtone[overtone_index] =
-                    self.configuration.overtone[overtone_index];
-                let range = std::ops::RangeInclusive::new(0.0, 128.0 * OVERTONE_STEP);
-                ui.add_enabled(
-                    true,
-                    egui::Slider::new(&mut self.configuration.overtone[overtone_index], range)
-                        .show_value(false)
-                        .text(format!(
-                            "{:.2}",
-                            self.configuration.overtone_freq[overtone_index]
-                        ))
-                        .vertical(),
-                );
+            ui.label(tr("Sub-oscillator:"));
+            if ui
+                .button(format!("{}", self.configuration.sub_wave))
+                .clicked()
+            {
+                self.configuration.sub_wave = self.configuration.sub_wave.cycle();
             }
         });
+        crate::utils::create_f64_slider(
+            ui,
+            "sub level: ",
+            &mut self.configuration.sub_level,
+            RangeInclusive::new(0.0, 1.0),
+        );
+
+        //
+        // Drift
+        //
+        crate::utils::create_f64_slider(
+            ui,
+            "Drift: ",
+            &mut self.configuration.drift,
+            RangeInclusive::new(0.0, 1.0),
+        );
+
+        //
+        // Pitch envelope
+        //
+        ui.label(tr("Pitch envelope: "));
+        crate::utils::create_f64_slider(
+            ui,
+            "amount (semitones): ",
+            &mut self.configuration.pitch_env_amount,
+            RangeInclusive::new(-PITCH_ENVELOPE_RANGE, PITCH_ENVELOPE_RANGE),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "decay (s): ",
+            &mut self.configuration.pitch_env_decay,
+            RangeInclusive::new(0.0, PITCH_ENVELOPE_MAX_DECAY),
+        );
+
+        //
+        // Ring modulation
+        //
+        ui.label(tr("Ring modulation: "));
+        ui.horizontal(|ui| {
+            ui.label(tr("carrier (Hz): "));
+            ui.add(
+                egui::Slider::new(
+                    &mut self.configuration.ring_frequency,
+                    RangeInclusive::new(RING_MIN_FREQUENCY, RING_MAX_FREQUENCY),
+                )
+                .logarithmic(true),
+            );
+        });
+        crate::utils::create_f64_slider(
+            ui,
+            "mix: ",
+            &mut self.configuration.ring_mix,
+            RangeInclusive::new(0.0, 1.0),
+        );
 
         //
         // Fade in
         //
-        ui.label("Fade in: ");
+        ui.label(tr("Fade in: "));
         Self::create_f64_slider(
             ui,
             "duration: ",
@@ -197,7 +1046,7 @@ impl<'c> RustySynth<'c> {
         //
         // Fade out
         //
-        ui.label("Fade out: ");
+        ui.label(tr("Fade out: "));
 
         Self::create_f64_slider(
             ui,
@@ -216,6 +1065,89 @@ impl<'c> RustySynth<'c> {
                 ))
             });
 
+        //
+        // Filter
+        //
+        ui.label(tr("Low-pass filter: "));
+        ui.horizontal(|ui| {
+            ui.label(tr("cutoff (Hz): "));
+            ui.add(
+                egui::Slider::new(
+                    &mut self.configuration.filter_cutoff,
+                    RangeInclusive::new(FILTER_MIN_CUTOFF, FILTER_MAX_CUTOFF),
+                )
+                .logarithmic(true),
+            );
+        });
+        crate::utils::create_f64_slider(
+            ui,
+            "resonance: ",
+            &mut self.configuration.filter_resonance,
+            RangeInclusive::new(0.0, 1.0),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "envelope (octaves): ",
+            &mut self.configuration.filter_envelope,
+            RangeInclusive::new(-FILTER_MAX_ENVELOPE, FILTER_MAX_ENVELOPE),
+        );
+        egui_plot::Plot::new("Low-pass filter")
+            .view_aspect(42.0 / 9.0)
+            .x_axis_formatter(|mark, _range| format!("{:.0}", 10.0_f64.powf(mark.value)))
+            .show(ui, |plot_ui| {
+                plot_ui.line(Self::create_filter_response(
+                    self.configuration.filter_cutoff,
+                    self.configuration.filter_resonance,
+                ))
+            });
+
+        //
+        // LFOs
+        //
+        for (label, lfo) in [
+            ("LFO 1: ", &mut self.configuration.lfo),
+            ("LFO 2: ", &mut self.configuration.lfo2),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(tr(label));
+                if ui.button(format!("{}", lfo.wave)).clicked() {
+                    lfo.wave = lfo.wave.cycle();
+                }
+            });
+            crate::utils::create_f64_slider(
+                ui,
+                "rate (Hz): ",
+                &mut lfo.rate,
+                RangeInclusive::new(0.0, LFO_MAX_RATE),
+            );
+        }
+
+        //
+        // Modulation matrix
+        //
+        self.create_matrix(ui);
+
+        //
+        // Aftertouch
+        //
+        ui.horizontal(|ui| {
+            ui.label(tr("Poly aftertouch: "));
+            if ui
+                .button(format!("{}", self.configuration.poly_aftertouch))
+                .clicked()
+            {
+                self.configuration.poly_aftertouch = self.configuration.poly_aftertouch.cycle();
+            }
+        });
+        if self.configuration.poly_aftertouch != PolyAftertouchDestination::Off {
+            crate::utils::create_f64_slider(
+                ui,
+                "depth: ",
+                &mut self.configuration.poly_aftertouch_depth,
+                RangeInclusive::new(0.0, 1.0),
+            );
+        }
+
         //
         // Gain
         //
@@ -234,17 +1166,6 @@ impl<'c> RustySynth<'c> {
             &mut self.configuration.mod_intensity,
             RangeInclusive::new(0.0, 1.0),
         );
-
-        if current_config != self.configuration {
-            if let Err(e) = self.commands.send(MessageToPlayer::NewConfiguration(
-                self.configuration.clone(),
-            )) {
-                self.messages
-                    .push(format!("Unable to send configuration to player: {e}"));
-            }
-        }
-
-        crate::utils::show_logs(ui, &mut self.messages);
     }
 }
 
@@ -261,15 +1182,88 @@ impl<'c> eframe::App for RustySynth<'c> {
         while let Ok(m) = self.receiver.try_recv() {
             match m {
                 MessageToUI::Error(e) => self.messages.push(format!("Error: {e}")),
-                MessageToUI::NewConfiguration(configuration) => self.configuration = configuration,
+                MessageToUI::Control(key, value) => {
+                    Player::apply_control(&mut self.configuration, key, value)
+                }
+                MessageToUI::KeyLimitLearnt(limit, note_index) => {
+                    self.configuration.set_key_limit(limit, note_index);
+                    self.learning_key_limit = None;
+                }
+                MessageToUI::PresetRecalled(index) => {
+                    if let Some(preset) = self.bank.presets().get(index) {
+                        self.configuration = preset.configuration.clone();
+                        //the player keeps a copy for the next recall
+                        let copy = Box::new(preset.configuration.clone());
+                        if let Err(e) = self.commands.send(MessageToPlayer::Preset(index, copy)) {
+                            self.messages.push(format!("[UI] {e}"));
+                        }
+                    }
+                    self.current_preset = Some(index);
+                }
+                MessageToUI::ConfigurationReleased(configuration) => drop(configuration),
+                MessageToUI::BankReleased(bank) => drop(bank),
+                MessageToUI::KeyboardMappingReleased(mapping) => drop(mapping),
+                MessageToUI::WaveReleased(wave) => drop(wave),
+                MessageToUI::Retuned(retuned) => self.retuned = retuned,
+                MessageToUI::KeyboardMapping(mapping) => {
+                    if let Err(e) = Player::save_keyboard_conf(&mapping) {
+                        self.messages
+                            .push(format!("Unable to save the keyboard mapping: {e}"));
+                    }
+                }
             }
         }
 
+        let session = self.session.update(&self.configuration, &mut self.messages);
+        if let Some(configuration) = self
+            .scenes
+            .update(TOOL_NAME, &self.configuration, &mut self.messages)
+            .or(session)
+        {
+            self.configuration = configuration;
+            if let Err(e) = self
+                .commands
+                .send(MessageToPlayer::NewConfiguration(Box::new(
+                    self.configuration.clone(),
+                )))
+            {
+                self.messages
+                    .push(format!("Unable to send configuration to player: {e}"));
+            }
+        }
+
+        self.watch_files();
+        self.load_dropped_files(ctx);
+        //the configurations saved before the modulation matrix
+        self.configuration.migrate_modulation();
+
+        self.read_history_keys(ctx);
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::Panic {
+                self.panic();
+            }
+        }
+
+        self.status.show(ctx);
+
+        egui::TopBottomPanel::bottom("piano").show(ctx, |ui| {
+            let commands = &self.commands;
+            let messages = &mut self.messages;
+            self.piano.show(ui, |midi| {
+                if let Err(e) = commands.send(MessageToPlayer::Note(midi)) {
+                    messages.push(format!("Unable to send the note to the player: {e}"));
+                }
+            });
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.create_menu(ui);
                 self.create_content(ui);
             });
         });
+
+        //the edits from the user interface and from the midi controls
+        self.history.update(&self.configuration, Instant::now());
     }
 }
@@ -1,5 +1,73 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+///A single cycle of a waveform, defined by the user
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "StoredWavetable")]
+pub struct Wavetable {
+    ///The name of the table, typically the name of the file it comes from
+    name: String,
+    ///The samples of a single cycle, normalized in the range [-1, 1]
+    samples: Vec<f64>,
+}
+
+///A wavetable as read from a preset, checked before being used
+#[derive(Deserialize)]
+struct StoredWavetable {
+    name: String,
+    samples: Vec<f64>,
+}
+
+impl TryFrom<StoredWavetable> for Wavetable {
+    type Error = String;
+
+    fn try_from(stored: StoredWavetable) -> Result<Self, Self::Error> {
+        if stored.samples.is_empty() {
+            return Err(format!("The wavetable {} has no samples", stored.name));
+        }
+        Ok(Wavetable {
+            name: stored.name,
+            samples: stored.samples,
+        })
+    }
+}
+
+impl Wavetable {
+    ///Create a wavetable from the samples of a single cycle. The samples are
+    /// normalized. If there are no samples, None is returned.
+    pub fn new(name: &str, samples: &[f32]) -> Option<Wavetable> {
+        let peak = samples
+            .iter()
+            .fold(0.0_f64, |p, v| p.max((*v as f64).abs()));
+        if samples.is_empty() || peak == 0.0 {
+            return None;
+        }
+        Some(Wavetable {
+            name: String::from(name),
+            samples: samples.iter().map(|v| *v as f64 / peak).collect(),
+        })
+    }
+
+    ///The name of the table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    ///Compute the amplitude for a given phase, interpolating between samples
+    pub fn compute(&self, x: f64) -> f64 {
+        let len = self.samples.len();
+        let position = (x / (std::f64::consts::PI * 2.0)).rem_euclid(1.0) * len as f64;
+        let index = (position.floor() as usize) % len;
+        let fraction = position - position.floor();
+        let a = self.samples[index];
+        let b = self.samples[(index + 1) % len];
+        a + (b - a) * fraction
+    }
+}
+
 ///The different type of known wave types
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum WaveType {
     ///A smooth sinusoidal wave
     Sin,
@@ -9,6 +77,8 @@ pub enum WaveType {
     SawTooth,
     ///A triangle shaped wave
     Triangle,
+    ///A wave loaded by the user
+    Table(Arc<Wavetable>),
 }
 impl WaveType {
     ///Compute the amplitude of the sound after a given time, frequency independent
@@ -32,6 +102,7 @@ impl WaveType {
                 let pi2 = std::f64::consts::PI * 2.0;
                 2.0 * (((shift / pi2) - (shift / pi2).floor()) * 2.0 - 1.0).abs() - 1.0
             }
+            WaveType::Table(table) => table.compute(x),
         }
     }
 
@@ -42,6 +113,7 @@ impl WaveType {
             WaveType::Square => WaveType::SawTooth,
             WaveType::SawTooth => WaveType::Triangle,
             WaveType::Triangle => WaveType::Sin,
+            WaveType::Table(_) => WaveType::Sin,
         }
     }
 }
@@ -59,6 +131,34 @@ impl std::fmt::Display for WaveType {
             WaveType::Square => write!(f, "Square"),
             WaveType::SawTooth => write!(f, "SawTooth"),
             WaveType::Triangle => write!(f, "Triangle"),
+            WaveType::Table(table) => write!(f, "{}", table.name()),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Wavetable;
+
+    #[test]
+    fn wavetable_interpolation() {
+        assert!(Wavetable::new("empty", &[]).is_none());
+        let table = Wavetable::new("ramp", &[0.0, 0.5, 0.0, -0.5]).unwrap();
+        let quarter = std::f64::consts::PI / 2.0;
+        assert_eq!(table.compute(0.0), 0.0);
+        assert_eq!(table.compute(quarter), 1.0);
+        assert_eq!(table.compute(quarter / 2.0), 0.5);
+        assert_eq!(table.compute(-quarter), -1.0);
+        assert_eq!(table.compute(3.5 * quarter), -0.5);
+    }
+
+    #[test]
+    fn stored_wavetables() {
+        let table = Wavetable::new("ramp", &[0.0, 0.5, 0.0, -0.5]).unwrap();
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(serde_json::from_str::<Wavetable>(&json).unwrap(), table);
+        //an empty table would divide by zero when computed
+        let empty = r#"{"name":"empty","samples":[]}"#;
+        assert!(serde_json::from_str::<Wavetable>(empty).is_err());
+    }
+}
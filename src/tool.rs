@@ -0,0 +1,342 @@
+use std::sync::Mutex;
+
+use eframe::egui::{self, ViewportBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    utils::{ChannelWatchdog, CommonError, JackStatus},
+};
+
+///The argument choosing the tool to run, see [run_from_args]
+pub const TOOL_ARGUMENT: &str = "--tool";
+
+///The tools registered by the program, see [register]
+static REGISTRY: Mutex<Vec<ToolEntry>> = Mutex::new(Vec::new());
+
+///A tool of the suite, possibly written in another crate and run by
+/// [run_tool], which handles the jack client, the user interface, the
+/// sessions and the scenes. The tool itself lives in the jack thread, while
+/// its configuration is edited by the user interface and sent to it whenever
+/// it changes. Once [register]ed, the launcher menu of every tool of the
+/// program can start it.
+pub trait RsuiteTool: Send + Sized + 'static {
+    ///The name of the tool, used as name of the jack client and of the
+    /// stored configuration
+    const NAME: &'static str;
+
+    ///The settings of the tool, saved in the sessions and the scenes
+    type Configuration: Serialize + DeserializeOwned + PartialEq + Clone + Send + 'static;
+
+    ///The configuration of a tool used for the first time
+    fn default_configuration(rate: usize) -> Self::Configuration;
+
+    ///Register the jack ports of the tool and create it
+    fn new(client: &jack::Client, configuration: &Self::Configuration)
+        -> Result<Self, CommonError>;
+
+    ///Use a new configuration, in the jack thread
+    fn configure(&mut self, configuration: Self::Configuration);
+
+    ///Process a cycle of jack. This is called in the realtime thread: it must
+    /// neither allocate nor wait.
+    fn process(&mut self, client: &jack::Client, ps: &jack::ProcessScope) -> jack::Control;
+
+    ///Draw the panel of the tool, changing its configuration. The messages
+    /// are shown to the user below the panel.
+    fn draw(configuration: &mut Self::Configuration, ui: &mut egui::Ui, messages: &mut Vec<String>);
+}
+
+///The menu of the launcher showing a tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Synths,
+    Effects,
+    Utils,
+}
+
+///A tool known to the launcher
+#[derive(Debug, Clone, Copy)]
+pub struct ToolEntry {
+    pub name: &'static str,
+    pub category: Category,
+    ///Run the tool until its window is closed
+    pub run: fn() -> Result<(), CommonError>,
+}
+
+impl ToolEntry {
+    pub fn new<T: RsuiteTool>(category: Category) -> ToolEntry {
+        ToolEntry {
+            name: T::NAME,
+            category,
+            run: run_tool::<T>,
+        }
+    }
+}
+
+///Make a tool known to the launcher. A tool registered twice replaces the
+/// first one.
+pub fn register(entry: ToolEntry) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.retain(|registered| registered.name != entry.name);
+    registry.push(entry);
+}
+
+///The tools registered in a category of the launcher
+pub fn registered(category: Category) -> Vec<ToolEntry> {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .filter(|entry| entry.category == category)
+        .copied()
+        .collect()
+}
+
+///The registered tool of the given name
+pub fn find(name: &str) -> Option<ToolEntry> {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.iter().find(|entry| entry.name == name).copied()
+}
+
+///The errors of the programs hosting the registered tools
+#[derive(Debug)]
+pub enum ToolError {
+    ///No tool of this name was registered
+    UnknownTool(String),
+    ///The program was started without choosing a tool
+    NoTool,
+    Tool(CommonError),
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::UnknownTool(name) => write!(f, "Unknown tool: {name}"),
+            ToolError::NoTool => write!(f, "No tool given, use {TOOL_ARGUMENT} <name>"),
+            ToolError::Tool(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+///Run the registered tool chosen by the arguments of the program
+/// (`--tool <name>`). The launcher starts the registered tools this way. If
+/// a single tool is registered, it is run without argument.
+pub fn run_from_args() -> Result<(), ToolError> {
+    let args: Vec<String> = std::env::args().collect();
+    let entry = match args.iter().position(|arg| arg == TOOL_ARGUMENT) {
+        Some(index) => {
+            let name = args.get(index + 1).ok_or(ToolError::NoTool)?;
+            find(name).ok_or_else(|| ToolError::UnknownTool(name.clone()))?
+        }
+        None => match REGISTRY
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_slice()
+        {
+            [entry] => *entry,
+            _ => return Err(ToolError::NoTool),
+        },
+    };
+    (entry.run)().map_err(ToolError::Tool)
+}
+
+///Start a registered tool in a new process, running the current program
+/// with `--tool <name>`. If anything goes wrong, details will be added to
+/// the vector 'messages'.
+pub fn start_registered(name: &str, messages: &mut Vec<String>) {
+    match std::env::current_exe() {
+        Ok(exe) => {
+            if let Err(e) = std::process::Command::new(exe)
+                .args([TOOL_ARGUMENT, name])
+                .spawn()
+            {
+                messages.push(format!("Unable to start process: {e}"));
+            }
+        }
+        Err(e) => messages.push(format!("Unable to find the current executable: {e}")),
+    }
+}
+
+///The jack side of a tool: the new configurations are applied before the
+/// processing of the cycle
+struct Host<T: RsuiteTool> {
+    tool: T,
+    messages_in: ChannelWatchdog<T::Configuration, spsc::Receiver<T::Configuration>>,
+}
+
+impl<T: RsuiteTool> jack::ProcessHandler for Host<T> {
+    fn process(&mut self, client: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(configuration) = self.messages_in.try_recv() {
+            self.tool.configure(configuration);
+        }
+        self.tool.process(client, ps)
+    }
+}
+
+///The user interface of a tool: its panel, the common menu and the messages
+struct HostUI<T: RsuiteTool> {
+    configuration: T::Configuration,
+    message_out: spsc::Sender<T::Configuration>,
+    messages: Vec<String>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<T::Configuration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl<T: RsuiteTool> HostUI<T> {
+    fn send_configuration(&mut self) {
+        if let Err(e) = self.message_out.send(self.configuration.clone()) {
+            self.messages
+                .push(format!("Error while sending new conf: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        let session = self.session.update(&self.configuration, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(configuration) = self
+            .scenes
+            .update(T::NAME, &self.configuration, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.configuration = configuration;
+            self.send_configuration();
+        }
+    }
+}
+
+impl<T: RsuiteTool> eframe::App for HostUI<T> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                crate::utils::common_menu_luncher(ui, &mut self.messages);
+            });
+            self.session.show(ui);
+            let mut configuration = self.configuration.clone();
+            T::draw(&mut configuration, ui, &mut self.messages);
+            if configuration != self.configuration {
+                self.configuration = configuration;
+                self.send_configuration();
+            }
+            crate::utils::show_logs(ui, &mut self.messages);
+        });
+    }
+}
+
+///Run a tool: open its jack client, process the cycles with it and show its
+/// user interface until its window is closed
+pub fn run_tool<T: RsuiteTool>() -> Result<(), CommonError> {
+    let (client, _status) = match jack::Client::new(T::NAME, jack::ClientOptions::NO_START_SERVER) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(T::NAME, client.name());
+
+    let (send_to_tool, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let configuration = T::default_configuration(client.sample_rate());
+    let host = Host {
+        tool: T::new(&client, &configuration)?,
+        messages_in: ChannelWatchdog::new(T::NAME, rcv_from_ui),
+    };
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(host)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        T::NAME,
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|_cc| {
+            Ok(Box::new(HostUI::<T> {
+                configuration,
+                message_out: send_to_tool,
+                messages: Vec::new(),
+                scenes: SceneListener::new(),
+                session: Autosave::new(T::NAME),
+                status,
+            }))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eframe::egui;
+
+    use crate::utils::CommonError;
+
+    use super::{find, register, registered, Category, RsuiteTool, ToolEntry};
+
+    ///A tool multiplying its input by a gain
+    struct Gain {
+        gain: f32,
+    }
+
+    impl RsuiteTool for Gain {
+        const NAME: &'static str = "gain";
+        type Configuration = f32;
+
+        fn default_configuration(_rate: usize) -> f32 {
+            1.0
+        }
+
+        fn new(_client: &jack::Client, configuration: &f32) -> Result<Gain, CommonError> {
+            Ok(Gain {
+                gain: *configuration,
+            })
+        }
+
+        fn configure(&mut self, configuration: f32) {
+            self.gain = configuration;
+        }
+
+        fn process(&mut self, _: &jack::Client, _: &jack::ProcessScope) -> jack::Control {
+            jack::Control::Continue
+        }
+
+        fn draw(configuration: &mut f32, ui: &mut egui::Ui, _messages: &mut Vec<String>) {
+            ui.add(egui::Slider::new(configuration, 0.0..=2.0));
+        }
+    }
+
+    #[test]
+    fn registered_tools() {
+        register(ToolEntry::new::<Gain>(Category::Effects));
+        register(ToolEntry::new::<Gain>(Category::Effects));
+        let effects = registered(Category::Effects);
+        assert_eq!(effects.len(), 1);
+        assert_eq!(effects[0].name, "gain");
+        assert!(registered(Category::Synths).is_empty());
+        assert!(find("gain").is_some());
+        assert!(find("kick").is_none());
+    }
+}
@@ -1,9 +1,12 @@
 use eframe::egui::{self, ViewportBuilder};
 
 use crate::{
+    i18n::tr,
     midiinput::MidiInput,
+    scene::SceneListener,
+    spsc::{self, MESSAGES_CAPACITY},
     synth::hardware::{HardWare, KeyBoardKey},
-    utils::{CommonError, ConnectionType},
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus},
 };
 
 struct Activator {
@@ -14,9 +17,9 @@ struct Activator {
     /// The midi output
     midi_out: jack::Port<jack::MidiOut>,
     //The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToActivator>,
+    messages_in: ChannelWatchdog<MessageToActivator, spsc::Receiver<MessageToActivator>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToActivatorUI>,
+    messages_out: spsc::Sender<MessageToActivatorUI>,
     ///If true, the next control will be used as key to start/stop the recording
     key_change: bool,
     ///The keyboard events we are listening to
@@ -26,8 +29,8 @@ struct Activator {
 impl Activator {
     pub fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToActivator>,
-        messages_out: std::sync::mpsc::Sender<MessageToActivatorUI>,
+        messages_in: spsc::Receiver<MessageToActivator>,
+        messages_out: spsc::Sender<MessageToActivatorUI>,
     ) -> Result<Activator, CommonError> {
         let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
             Ok(v) => v,
@@ -42,7 +45,7 @@ impl Activator {
             active: true,
             midi_in: m_in,
             midi_out: m_out,
-            messages_in,
+            messages_in: ChannelWatchdog::new("activator", messages_in),
             messages_out,
             key_change: false,
             keyboard: HardWare::new(),
@@ -51,7 +54,7 @@ impl Activator {
 
     fn send_message(
         msg: MessageToActivatorUI,
-        messages_out: &mut std::sync::mpsc::Sender<MessageToActivatorUI>,
+        messages_out: &mut spsc::Sender<MessageToActivatorUI>,
     ) {
         if let Err(e) = messages_out.send(msg) {
             eprintln!("Internal error: {e}");
@@ -61,7 +64,7 @@ impl Activator {
 
 impl jack::ProcessHandler for Activator {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToActivator::LetMidiThrough => {
                     self.active = true;
@@ -137,30 +140,37 @@ enum MessageToActivatorUI {
 }
 
 struct RecorderUI {
-    messages_in: std::sync::mpsc::Receiver<MessageToActivatorUI>,
-    message_out: std::sync::mpsc::Sender<MessageToActivator>,
+    messages_in: spsc::Receiver<MessageToActivatorUI>,
+    message_out: spsc::Sender<MessageToActivator>,
     messages: Vec<String>,
     active_pressed: bool,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl RecorderUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToActivatorUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToActivator>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToActivatorUI>,
+        messages_out: spsc::Sender<MessageToActivator>,
     ) -> RecorderUI {
         RecorderUI {
+            status,
             messages_in,
             message_out: messages_out,
             messages: Vec::new(),
             active_pressed: true,
+            scenes: SceneListener::new(),
         }
     }
 
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("Settings", |ui| {
-                if ui.button("Set Activation action").clicked() {
+            ui.menu_button(tr("Settings"), |ui| {
+                if ui.button(tr("Set Activation action")).clicked() {
                     self.send_message(MessageToActivator::ChangeActivationMidiKey);
                     ui.close_menu();
                 };
@@ -170,21 +180,18 @@ impl RecorderUI {
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
-        let rich_text = egui::RichText::new(format!(
-            "{}",
-            if self.active_pressed { "ACTIVE" } else { "" }
-        ))
-        .color(egui::Color32::from_rgb(180, 19, 60));
+        let rich_text = egui::RichText::new(tr(if self.active_pressed { "ACTIVE" } else { "" }))
+            .color(egui::Color32::from_rgb(180, 19, 60));
         let _recording = ui.label(rich_text);
         ui.horizontal(|ui| {
-            ui.label("Status: ");
+            ui.label(tr("Status: "));
             if self.active_pressed {
-                if ui.button("Pass-through").clicked() {
+                if ui.button(tr("Pass-through")).clicked() {
                     self.active_pressed = false;
                     self.send_message(MessageToActivator::BlockMidi);
                 }
             } else {
-                if ui.button("Blocked").clicked() {
+                if ui.button(tr("Blocked")).clicked() {
                     self.active_pressed = true;
                     self.send_message(MessageToActivator::LetMidiThrough);
                 }
@@ -213,6 +220,17 @@ impl RecorderUI {
                 MessageToActivatorUI::ShowMidiBlocked => self.active_pressed = false,
             },
         }
+        if let Some(active) =
+            self.scenes
+                .update("activator", &self.active_pressed, &mut self.messages)
+        {
+            self.active_pressed = active;
+            if active {
+                self.send_message(MessageToActivator::LetMidiThrough);
+            } else {
+                self.send_message(MessageToActivator::BlockMidi);
+            }
+        }
     }
 }
 
@@ -225,6 +243,8 @@ impl eframe::App for RecorderUI {
 
         self.read_input();
 
+        self.status.show(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.create_menu(ui);
@@ -243,13 +263,15 @@ pub fn activator() -> Result<(), CommonError> {
                 return Err(CommonError::UnableToStartClient(e));
             }
         };
+    crate::storage::set_instance("activator", client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
     let synth = Activator::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -261,7 +283,14 @@ pub fn activator() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(RecorderUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(RecorderUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use eframe::egui::{self, Color32, Stroke};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::{self, tr, Language},
+    storage::{self, StorageError},
+};
+
+///The name of the file containing the appearance
+const APPEARANCE_FILE: &str = "appearance.json";
+///The change of scale of the larger/smaller buttons
+const SCALE_STEP: f32 = 0.25;
+///The smallest scale of the user interface
+const MIN_SCALE: f32 = 0.75;
+///The largest scale of the user interface
+const MAX_SCALE: f32 = 3.0;
+
+///How the user interfaces look. The appearance is shared by every tool of the suite.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Appearance {
+    ///The zoom factor of the user interface
+    scale: f32,
+    ///If true, bright colors on black and thicker widgets are used
+    high_contrast: bool,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            high_contrast: false,
+        }
+    }
+}
+
+impl Appearance {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(APPEARANCE_FILE);
+        Ok(path)
+    }
+
+    ///Load the appearance saved by the user, or the default one
+    fn load() -> Appearance {
+        match Self::path() {
+            Ok(path) if path.exists() => storage::load(&path).unwrap_or_default(),
+            _ => Appearance::default(),
+        }
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path()?, self)
+    }
+
+    ///The colors used in high contrast mode
+    fn high_contrast_visuals() -> egui::Visuals {
+        let mut visuals = egui::Visuals::dark();
+        visuals.override_text_color = Some(Color32::WHITE);
+        visuals.panel_fill = Color32::BLACK;
+        visuals.window_fill = Color32::BLACK;
+        visuals.extreme_bg_color = Color32::BLACK;
+        visuals.selection.bg_fill = Color32::from_rgb(255, 200, 0);
+        visuals.selection.stroke = Stroke::new(2.0, Color32::BLACK);
+        visuals.widgets.noninteractive.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+        visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.5, Color32::WHITE);
+        visuals.widgets.inactive.bg_fill = Color32::from_gray(70);
+        visuals.widgets.inactive.weak_bg_fill = Color32::from_gray(70);
+        visuals.widgets.inactive.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+        visuals.widgets.hovered.bg_fill = Color32::from_rgb(0, 90, 200);
+        visuals.widgets.hovered.weak_bg_fill = Color32::from_rgb(0, 90, 200);
+        visuals.widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+        visuals.widgets.active.bg_fill = Color32::from_rgb(255, 200, 0);
+        visuals.widgets.active.weak_bg_fill = Color32::from_rgb(255, 200, 0);
+        visuals.widgets.active.fg_stroke = Stroke::new(2.0, Color32::BLACK);
+        visuals.slider_trailing_fill = true;
+        visuals
+    }
+
+    ///Apply the appearance to a user interface
+    fn apply(&self, ctx: &egui::Context) {
+        let mut style = egui::Style::default();
+        if self.high_contrast {
+            style.visuals = Self::high_contrast_visuals();
+            style.spacing.slider_rail_height *= 2.0;
+            style.spacing.interact_size.y *= 1.5;
+        }
+        ctx.set_style(style);
+        ctx.set_zoom_factor(self.scale);
+    }
+
+    ///The appearance currently used by a user interface. It is loaded and
+    /// applied the first time it is needed.
+    fn current(ctx: &egui::Context) -> Appearance {
+        let id = egui::Id::new(APPEARANCE_FILE);
+        match ctx.data(|d| d.get_temp::<Appearance>(id)) {
+            Some(appearance) => appearance,
+            None => {
+                let appearance = Appearance::load();
+                appearance.apply(ctx);
+                ctx.data_mut(|d| d.insert_temp(id, appearance));
+                appearance
+            }
+        }
+    }
+
+    fn set_current(&self, ctx: &egui::Context) {
+        self.apply(ctx);
+        ctx.data_mut(|d| d.insert_temp(egui::Id::new(APPEARANCE_FILE), *self));
+    }
+}
+
+///Generate the menu to change the appearance of the user interface
+pub fn create_appearance_menu(ui: &mut egui::Ui, messages: &mut Vec<String>) {
+    let current = Appearance::current(ui.ctx());
+    ui.menu_button(tr("View"), |ui| {
+        let mut appearance = current;
+        ui.label(format!(
+            "{}{:.0} %",
+            tr("Scale: "),
+            appearance.scale * 100.0
+        ));
+        ui.horizontal(|ui| {
+            if ui.button(tr("Smaller")).clicked() {
+                appearance.scale = (appearance.scale - SCALE_STEP).max(MIN_SCALE);
+            }
+            if ui.button(tr("Larger")).clicked() {
+                appearance.scale = (appearance.scale + SCALE_STEP).min(MAX_SCALE);
+            }
+            if ui.button(tr("Reset")).clicked() {
+                appearance.scale = 1.0;
+            }
+        });
+        ui.checkbox(&mut appearance.high_contrast, tr("High contrast"));
+        if appearance != current {
+            appearance.set_current(ui.ctx());
+            if let Err(e) = appearance.save() {
+                messages.push(format!("Unable to save the appearance: {e}"));
+            }
+        }
+        ui.separator();
+        ui.label(tr("Language:"));
+        let current_language = i18n::language();
+        for language in Language::ALL {
+            if ui
+                .radio(current_language == language, language.to_string())
+                .clicked()
+                && current_language != language
+            {
+                if let Err(e) = i18n::set_language(language) {
+                    messages.push(format!("Unable to save the language: {e}"));
+                }
+            }
+        }
+    });
+}
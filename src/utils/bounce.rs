@@ -0,0 +1,374 @@
+use eframe::egui::{self, ViewportBuilder};
+use jack::PortSpec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    utils::{
+        meters::{self, Level},
+        CommonError, ConnectionType, JackStatus,
+    },
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "bounce";
+///The number of level measures sent to the user interface every second
+const LEVELS_PER_SECOND: usize = 20;
+///The input recording the bounce, unless another one is chosen
+const DEFAULT_DESTINATION: &str = "recorder:music_in";
+
+///The outputs to record and where to record them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BounceConfiguration {
+    ///The full names of the outputs mixed in the bounce
+    sources: Vec<String>,
+    ///The full name of the input receiving the bounce
+    destination: String,
+}
+
+impl Default for BounceConfiguration {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            destination: String::from(DEFAULT_DESTINATION),
+        }
+    }
+}
+
+///Pass the mix of the sources to the destination, measuring its level
+struct Bounce {
+    ///The mix of the sources, summed by jack
+    mix_in: jack::Port<jack::AudioIn>,
+    ///The mix sent to the destination
+    mix_out: jack::Port<jack::AudioOut>,
+    ///The sample rate of the audio
+    rate: usize,
+    ///The level of the mix since the last one sent to the UI
+    level: Level,
+    ///The number of frames measured in the level
+    level_frames: usize,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToBounceUI>,
+}
+
+impl Bounce {
+    pub fn new(
+        client: &jack::Client,
+        messages_out: spsc::Sender<MessageToBounceUI>,
+    ) -> Result<Bounce, CommonError> {
+        let mix_in = match client.register_port("mix_in", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let mix_out = match client.register_port("mix_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        Ok(Bounce {
+            mix_in,
+            mix_out,
+            rate: client.sample_rate(),
+            level: Level::default(),
+            level_frames: 0,
+            messages_out,
+        })
+    }
+}
+
+impl jack::ProcessHandler for Bounce {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        let audio_in = self.mix_in.as_slice(ps);
+        self.mix_out.as_mut_slice(ps).copy_from_slice(audio_in);
+
+        let level = Level::measure(audio_in);
+        self.level = if self.level_frames == 0 {
+            level
+        } else {
+            self.level.merge(&level)
+        };
+        self.level_frames += audio_in.len();
+        if self.level_frames >= self.rate / LEVELS_PER_SECOND {
+            if let Err(e) = self.messages_out.send(MessageToBounceUI::Level(self.level)) {
+                println!("Error: {e}");
+            }
+            self.level_frames = 0;
+        }
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToBounceUI {
+    Level(Level),
+}
+
+struct BounceUI<'c> {
+    messages_in: spsc::Receiver<MessageToBounceUI>,
+    messages: Vec<String>,
+    conf: BounceConfiguration,
+    ///The configuration whose ports are connected, if any
+    connected: Option<BounceConfiguration>,
+    ///The audio outputs of the other clients
+    outputs: Vec<String>,
+    ///The audio inputs of the other clients
+    inputs: Vec<String>,
+    ///The last level of the mix
+    level: Level,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<BounceConfiguration>,
+    ///The jack client, to list and connect the ports
+    client: &'c jack::Client,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl<'c> BounceUI<'c> {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToBounceUI>,
+        client: &'c jack::Client,
+    ) -> BounceUI<'c> {
+        let mut ui = BounceUI {
+            status,
+            messages_in,
+            messages: Vec::new(),
+            conf: BounceConfiguration::default(),
+            connected: None,
+            outputs: Vec::new(),
+            inputs: Vec::new(),
+            level: Level::default(),
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+            client,
+        };
+        ui.refresh_ports();
+        ui
+    }
+
+    ///The full name of one of our ports
+    fn own_port(&self, name: &str) -> String {
+        format!("{}:{name}", self.client.name())
+    }
+
+    ///List the audio ports of the other clients
+    fn refresh_ports(&mut self) {
+        let own = self.own_port("");
+        let audio = jack::AudioIn::default().jack_port_type();
+        let list = |flags| {
+            self.client
+                .ports(None, Some(audio), flags)
+                .into_iter()
+                .filter(|name| !name.starts_with(&own))
+                .collect::<Vec<String>>()
+        };
+        self.outputs = list(jack::PortFlags::IS_OUTPUT);
+        self.inputs = list(jack::PortFlags::IS_INPUT);
+    }
+
+    ///Connect the sources to the bounce and the bounce to the destination
+    fn connect(&mut self) {
+        self.disconnect();
+        let mix_in = self.own_port("mix_in");
+        for source in &self.conf.sources {
+            if let Err(e) = self.client.connect_ports_by_name(source, &mix_in) {
+                self.messages
+                    .push(format!("Unable to connect '{source}': {e}"));
+            }
+        }
+        let mix_out = self.own_port("mix_out");
+        if let Err(e) = self
+            .client
+            .connect_ports_by_name(&mix_out, &self.conf.destination)
+        {
+            self.messages.push(format!(
+                "Unable to connect '{}': {e}",
+                self.conf.destination
+            ));
+        }
+        self.connected = Some(self.conf.clone());
+    }
+
+    ///Undo the connections made by [BounceUI::connect]
+    fn disconnect(&mut self) {
+        let Some(connected) = self.connected.take() else {
+            return;
+        };
+        let mix_in = self.own_port("mix_in");
+        for source in &connected.sources {
+            //the source may be gone with its client
+            let _ = self.client.disconnect_ports_by_name(source, &mix_in);
+        }
+        let mix_out = self.own_port("mix_out");
+        let _ = self
+            .client
+            .disconnect_ports_by_name(&mix_out, &connected.destination);
+    }
+
+    ///The latency of the mix since the capture, in frames. The recorder
+    /// skips it at the start of a take.
+    fn latency(&self) -> jack::Frames {
+        self.client
+            .port_by_name(&self.own_port("mix_out"))
+            .map(|port| port.get_latency_range(jack::LatencyType::Capture).1)
+            .unwrap_or(0)
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        ui.horizontal(|ui| {
+            ui.label(tr("Sources: "));
+            if ui.button(tr("Refresh")).clicked() {
+                self.refresh_ports();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() / 2.0)
+            .show(ui, |ui| {
+                for output in &self.outputs {
+                    let mut selected = self.conf.sources.contains(output);
+                    if ui.checkbox(&mut selected, output).changed() {
+                        if selected {
+                            self.conf.sources.push(output.clone());
+                        } else {
+                            self.conf.sources.retain(|s| s != output);
+                        }
+                    }
+                }
+            });
+        egui::ComboBox::from_label(tr("Destination"))
+            .selected_text(self.conf.destination.as_str())
+            .show_ui(ui, |ui| {
+                for input in &self.inputs {
+                    ui.selectable_value(&mut self.conf.destination, input.clone(), input);
+                }
+            });
+        ui.horizontal(|ui| {
+            if ui.button(tr("Connect")).clicked() {
+                self.connect();
+            }
+            if ui
+                .add_enabled(
+                    self.connected.is_some(),
+                    egui::Button::new(tr("Disconnect")),
+                )
+                .clicked()
+            {
+                self.disconnect();
+            }
+        });
+        if self.connected.is_some() {
+            if self.connected.as_ref() != Some(&self.conf) {
+                ui.label(tr("The connections differ from the selection"));
+            }
+            ui.label(format!(
+                "{}{} frames",
+                tr("Latency compensated by the recorder: "),
+                self.latency()
+            ));
+        }
+        ui.label(tr("Mix level: "));
+        meters::vu_meter(ui, &self.level);
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToBounceUI::Level(level) => self.level = level,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            if self.connected.is_some() {
+                self.connect();
+            }
+        }
+    }
+}
+
+impl eframe::App for BounceUI<'_> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn bounce() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the bounce and the UI
+    let (send_to_ui, rcv_from_bounce) = spsc::channel(MESSAGES_CAPACITY);
+
+    let bounce = Bounce::new(&client, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(bounce))
+    {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Bounce",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(BounceUI::new(
+                cc,
+                status,
+                rcv_from_bounce,
+                active_client.as_client(),
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
@@ -0,0 +1,355 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    synth::hardware::{HardWare, KeyBoardKey},
+};
+
+use super::{ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "channels";
+///The number of midi channels
+const NB_CHANNELS: usize = 16;
+
+///The channels muted and soloed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ChannelMatrix {
+    mute: [bool; NB_CHANNELS],
+    solo: [bool; NB_CHANNELS],
+}
+
+impl ChannelMatrix {
+    ///True if the messages of the channel go through: when a channel is
+    /// soloed, only the soloed channels are heard
+    fn passes(&self, channel: u8) -> bool {
+        let channel = channel as usize % NB_CHANNELS;
+        if self.solo.iter().any(|s| *s) {
+            self.solo[channel]
+        } else {
+            !self.mute[channel]
+        }
+    }
+}
+
+struct Channels {
+    matrix: ChannelMatrix,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    /// The next key to map
+    next_key_map: Option<KeyBoardKey>,
+    /// The midi input
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The midi output, without the silenced channels
+    midi_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToChannels>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<ChannelMatrix>,
+}
+
+impl Channels {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToChannels>,
+        messages_out: std::sync::mpsc::Sender<ChannelMatrix>,
+    ) -> Result<Channels, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let m_out = match client.register_port("midi_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        Ok(Channels {
+            matrix: ChannelMatrix::default(),
+            keyboard: HardWare::new(),
+            next_key_map: None,
+            midi_in: m_in,
+            midi_out: m_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+}
+
+impl jack::ProcessHandler for Channels {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToChannels::NewMatrix(matrix) => self.matrix = matrix,
+                MessageToChannels::SetKey(key) => self.next_key_map = Some(key),
+                MessageToChannels::ClearKey(key) => self.keyboard.clear_key(key),
+            }
+        }
+
+        let current_matrix = self.matrix.clone();
+        let mut writer = self.midi_out.writer(ps);
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::Controller { control, value, .. } = midi {
+                if let Some(key) = self.next_key_map.take() {
+                    self.keyboard.update_key(key, control);
+                }
+                let toggled = match self.keyboard.get_keyboard_key(control) {
+                    Some(KeyBoardKey::ChannelMute(c)) => self.matrix.mute.get_mut(c as usize),
+                    Some(KeyBoardKey::ChannelSolo(c)) => self.matrix.solo.get_mut(c as usize),
+                    _ => None,
+                };
+                if let Some(toggled) = toggled {
+                    //the buttons of the matrix are not forwarded
+                    if value > 0 {
+                        *toggled = !*toggled;
+                    }
+                    continue;
+                }
+            }
+            //the note-offs always go through, so that no note is left hanging
+            let passes = match e.bytes.first() {
+                Some(status) if (0x80..0xF0).contains(status) => {
+                    matches!(midi, MidiInput::NoteEnd { .. }) || self.matrix.passes(status & 0x0F)
+                }
+                _ => true,
+            };
+            if passes {
+                if let Err(err) = writer.write(&e) {
+                    eprintln!("Unable to forward {:?}: {err}", midi);
+                }
+            }
+        }
+
+        if self.matrix != current_matrix {
+            if let Err(e) = self.messages_out.send(self.matrix.clone()) {
+                eprintln!("Internal error: {e}");
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToChannels {
+    NewMatrix(ChannelMatrix),
+    SetKey(KeyBoardKey),
+    ClearKey(KeyBoardKey),
+}
+
+impl From<KeyBoardKeySetter> for MessageToChannels {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToChannels::SetKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToChannels::ClearKey(k),
+        }
+    }
+}
+
+struct ChannelsUI {
+    messages: Vec<String>,
+    matrix: ChannelMatrix,
+    messages_in: std::sync::mpsc::Receiver<ChannelMatrix>,
+    messages_out: std::sync::mpsc::Sender<MessageToChannels>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The matrix saved for crash recovery
+    session: Autosave<ChannelMatrix>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl ChannelsUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<ChannelMatrix>,
+        messages_out: std::sync::mpsc::Sender<MessageToChannels>,
+    ) -> ChannelsUI {
+        ChannelsUI {
+            status,
+            messages: Vec::with_capacity(16),
+            matrix: ChannelMatrix::default(),
+            messages_in,
+            messages_out,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(matrix) = self.messages_in.try_recv() {
+            self.matrix = matrix;
+        }
+        let session = self.session.update(&self.matrix, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(matrix) = self
+            .scenes
+            .update(TOOL_NAME, &self.matrix, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.matrix = matrix;
+            self.send_message(MessageToChannels::NewMatrix(self.matrix.clone()));
+        }
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_matrix = self.matrix.clone();
+
+        egui::Grid::new("channel_matrix").show(ui, |ui| {
+            for channel in 0..NB_CHANNELS {
+                ui.label(format!("{}{}", tr("Channel "), channel + 1));
+                ui.toggle_value(&mut self.matrix.mute[channel], tr("Mute"));
+                ui.toggle_value(&mut self.matrix.solo[channel], tr("Solo"));
+                let color = if self.matrix.passes(channel as u8) {
+                    ui.visuals().text_color()
+                } else {
+                    ui.visuals().weak_text_color()
+                };
+                ui.colored_label(color, "●");
+                ui.end_row();
+            }
+        });
+        if ui.button(tr("Clear")).clicked() {
+            self.matrix = ChannelMatrix::default();
+        }
+
+        if self.matrix != current_matrix {
+            self.send_message(MessageToChannels::NewMatrix(self.matrix.clone()));
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                ui.menu_button(tr("Mute keys"), |ui| {
+                    for channel in 0..NB_CHANNELS as u8 {
+                        let key = KeyBoardKey::ChannelMute(channel);
+                        crate::utils::create_keyboard_select(
+                            ui,
+                            &format!("{key}"),
+                            key,
+                            &mut self.messages_out,
+                            &mut self.messages,
+                        );
+                    }
+                });
+                ui.menu_button(tr("Solo keys"), |ui| {
+                    for channel in 0..NB_CHANNELS as u8 {
+                        let key = KeyBoardKey::ChannelSolo(channel);
+                        crate::utils::create_keyboard_select(
+                            ui,
+                            &format!("{key}"),
+                            key,
+                            &mut self.messages_out,
+                            &mut self.messages,
+                        );
+                    }
+                });
+            });
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_message(&mut self, msg: MessageToChannels) {
+        if let Err(e) = self.messages_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for ChannelsUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn channels() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the matrix and the UI
+    let (send_to_channels, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_channels) = std::sync::mpsc::channel();
+
+    let util = Channels::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Channels",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(ChannelsUI::new(
+                cc,
+                status,
+                rcv_from_channels,
+                send_to_channels,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChannelMatrix;
+
+    #[test]
+    fn mute_and_solo() {
+        let mut matrix = ChannelMatrix::default();
+        assert!(matrix.passes(0));
+        matrix.mute[0] = true;
+        assert!(!matrix.passes(0));
+        assert!(matrix.passes(1));
+        matrix.solo[2] = true;
+        assert!(!matrix.passes(1));
+        assert!(matrix.passes(2));
+        //solo wins over mute
+        matrix.mute[2] = true;
+        assert!(matrix.passes(2));
+    }
+}
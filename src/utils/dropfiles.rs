@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+use serde::de::DeserializeOwned;
+
+use crate::storage;
+
+///Retrieve the files dropped on the window during the last frame
+pub fn dropped_files(ctx: &egui::Context) -> Vec<PathBuf> {
+    ctx.input(|i| {
+        i.raw
+            .dropped_files
+            .iter()
+            .filter_map(|file| file.path.clone())
+            .collect()
+    })
+}
+
+///Load the files dropped on the window as a configuration of a tool, for
+/// instance a file of a scene or of a session. If several files are dropped,
+/// the last valid one is returned. The files that are not configurations of
+/// the tool are reported in 'messages'.
+pub fn load_dropped<T>(ctx: &egui::Context, messages: &mut Vec<String>) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    let mut loaded = None;
+    for path in dropped_files(ctx) {
+        match storage::load(&path) {
+            Ok(conf) => loaded = Some(conf),
+            Err(e) => messages.push(format!("Unable to load '{}': {e}", path.display())),
+        }
+    }
+    loaded
+}
@@ -0,0 +1,498 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::{MidiInput, MidiNote},
+    scene::SceneListener,
+    session::Autosave,
+};
+
+use super::{ChannelWatchdog, CommonError, ConnectionType, JackStatus};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "generator";
+///The midi clock sends 24 ticks per quarter note
+const CLOCK_TICKS_PER_QUARTER: usize = 24;
+///The midi clock tick message
+const CLOCK_TICK: u8 = 0xF8;
+///The midi start message, which restarts the steps on the beat
+const CLOCK_START: u8 = 0xFA;
+///The midi stop message, which ends the note being played
+const CLOCK_STOP: u8 = 0xFC;
+///The most clock ticks handled in a single cycle
+const MAX_TICKS: usize = 256;
+///The velocity of the generated notes
+const VELOCITY: f64 = 100.0 / 127.0;
+///The lowest note that can be generated (midi note 12)
+const MIN_NOTE: usize = 24;
+///The highest note that can be generated (midi note 127)
+const MAX_NOTE: usize = 139;
+
+///The notes the melodies are made of
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+enum Scale {
+    #[default]
+    Major,
+    Minor,
+    Pentatonic,
+    Blues,
+    Chromatic,
+}
+
+impl Scale {
+    ///Cycle through the different scales
+    fn cycle(&self) -> Scale {
+        match self {
+            Scale::Major => Scale::Minor,
+            Scale::Minor => Scale::Pentatonic,
+            Scale::Pentatonic => Scale::Blues,
+            Scale::Blues => Scale::Chromatic,
+            Scale::Chromatic => Scale::Major,
+        }
+    }
+
+    ///The half-steps of the scale, from its root
+    fn intervals(&self) -> &'static [usize] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+}
+
+impl std::fmt::Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scale::Major => write!(f, "Major"),
+            Scale::Minor => write!(f, "Minor"),
+            Scale::Pentatonic => write!(f, "Pentatonic"),
+            Scale::Blues => write!(f, "Blues"),
+            Scale::Chromatic => write!(f, "Chromatic"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct GeneratorConfiguration {
+    ///Specify if notes are generated
+    active: bool,
+    ///If true, the steps follow the incoming midi clock instead of 'bpm'
+    follow_clock: bool,
+    ///The number of quarters per minutes, used when the clock is not followed
+    bpm: usize,
+    ///The number of notes (or rests) per quarter
+    steps_per_quarter: usize,
+    ///The probability of a step to be a rest, from 0 to 1
+    rest_probability: f64,
+    scale: Scale,
+    ///The root of the scale, in half-steps from C
+    root: usize,
+    ///The lowest note generated, as index of the note
+    lowest: usize,
+    ///The highest note generated, as index of the note
+    highest: usize,
+}
+
+impl Default for GeneratorConfiguration {
+    fn default() -> Self {
+        Self {
+            active: false,
+            follow_clock: true,
+            bpm: 120,
+            steps_per_quarter: 4,
+            rest_probability: 0.25,
+            scale: Scale::default(),
+            root: 0,
+            lowest: 12 + 60,
+            highest: 12 + 84,
+        }
+    }
+}
+
+impl GeneratorConfiguration {
+    ///True if the note belongs to the scale
+    fn in_scale(&self, note_index: usize) -> bool {
+        self.scale
+            .intervals()
+            .contains(&((note_index + 12 - self.root % 12) % 12))
+    }
+
+    ///Pick a note of the scale in the range, 'random' being between 0 and 1
+    fn pick(&self, random: f64) -> Option<usize> {
+        let notes = (self.lowest..=self.highest).filter(|n| self.in_scale(*n));
+        let count = notes.clone().count();
+        let chosen = ((random * count as f64) as usize).min(count.saturating_sub(1));
+        notes.clone().nth(chosen)
+    }
+
+    ///The number of clock ticks between two steps
+    fn ticks_per_step(&self) -> usize {
+        (CLOCK_TICKS_PER_QUARTER / self.steps_per_quarter.max(1)).max(1)
+    }
+}
+
+struct Generator {
+    configuration: GeneratorConfiguration,
+    /// The midi input providing the clock
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The generated notes
+    midi_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<GeneratorConfiguration>,
+    ///The audio rate (number of audio frames per seconds)
+    rate: usize,
+    ///The position of the next tick of the internal clock, in frames from the
+    /// start of the cycle
+    next_tick: f64,
+    ///The number of ticks since the start of the clock
+    tick_count: usize,
+    ///The time of the ticks of the current cycle
+    ticks: Vec<jack::Frames>,
+    ///The note being played
+    playing: Option<usize>,
+}
+
+impl Generator {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<GeneratorConfiguration>,
+    ) -> Result<Generator, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let m_out = match client.register_port("midi_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        Ok(Generator {
+            configuration: GeneratorConfiguration::default(),
+            midi_in: m_in,
+            midi_out: m_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            rate: client.sample_rate(),
+            next_tick: 0.0,
+            tick_count: 0,
+            ticks: Vec::with_capacity(MAX_TICKS),
+            playing: None,
+        })
+    }
+
+    fn write(writer: &mut jack::MidiWriter<'_>, midi: MidiInput) {
+        let mut bytes = [0; 3];
+        if let Err(e) = writer.write(&midi.to_raw(&mut bytes)) {
+            eprintln!("Unable to send the generated note: {e}");
+        }
+    }
+
+    ///End the note being played, if any
+    fn release(
+        playing: &mut Option<usize>,
+        writer: &mut jack::MidiWriter<'_>,
+        timing: jack::Frames,
+    ) {
+        if let Some(note_index) = playing.take() {
+            Self::write(
+                writer,
+                MidiInput::NoteEnd {
+                    channel: 0,
+                    note_index,
+                    timing,
+                    velocity: 0.0,
+                },
+            );
+        }
+    }
+
+    ///Play the next step: a new note or a rest
+    fn step(
+        configuration: &GeneratorConfiguration,
+        playing: &mut Option<usize>,
+        writer: &mut jack::MidiWriter<'_>,
+        timing: jack::Frames,
+    ) {
+        Self::release(playing, writer, timing);
+        if random::<f64>() < configuration.rest_probability {
+            return;
+        }
+        if let Some(note_index) = configuration.pick(random::<f64>()) {
+            Self::write(
+                writer,
+                MidiInput::NoteStart {
+                    channel: 0,
+                    note_index,
+                    timing,
+                    velocity: VELOCITY,
+                },
+            );
+            *playing = Some(note_index);
+        }
+    }
+}
+
+impl jack::ProcessHandler for Generator {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(conf) = self.messages_in.try_recv() {
+            self.configuration = conf;
+        }
+
+        let mut writer = self.midi_out.writer(ps);
+        if !self.configuration.active {
+            Self::release(&mut self.playing, &mut writer, 0);
+            self.tick_count = 0;
+            return jack::Control::Continue;
+        }
+
+        self.ticks.clear();
+        if self.configuration.follow_clock {
+            for e in self.midi_in.iter(ps) {
+                match e.bytes {
+                    [CLOCK_TICK] if self.ticks.len() < MAX_TICKS => self.ticks.push(e.time),
+                    [CLOCK_START] => self.tick_count = 0,
+                    [CLOCK_STOP] => Self::release(&mut self.playing, &mut writer, e.time),
+                    _ => {}
+                }
+            }
+        } else {
+            let frames_per_tick = 60.0 * self.rate as f64
+                / (self.configuration.bpm.max(1) * CLOCK_TICKS_PER_QUARTER) as f64;
+            while self.next_tick < ps.n_frames() as f64 && self.ticks.len() < MAX_TICKS {
+                self.ticks.push(self.next_tick as jack::Frames);
+                self.next_tick += frames_per_tick;
+            }
+            self.next_tick = (self.next_tick - ps.n_frames() as f64).max(0.0);
+        }
+
+        let ticks_per_step = self.configuration.ticks_per_step();
+        for timing in &self.ticks {
+            if self.tick_count.is_multiple_of(ticks_per_step) {
+                Self::step(&self.configuration, &mut self.playing, &mut writer, *timing);
+            }
+            self.tick_count += 1;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+struct GeneratorUI {
+    messages: Vec<String>,
+    conf: GeneratorConfiguration,
+    messages_out: std::sync::mpsc::Sender<GeneratorConfiguration>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<GeneratorConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl GeneratorUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_out: std::sync::mpsc::Sender<GeneratorConfiguration>,
+    ) -> GeneratorUI {
+        GeneratorUI {
+            status,
+            messages: Vec::with_capacity(16),
+            conf: GeneratorConfiguration::default(),
+            messages_out,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_configuration();
+        }
+    }
+
+    ///Create a slider choosing a note, shown with its name
+    fn create_note_slider(ui: &mut egui::Ui, label: &str, note_index: &mut usize) {
+        ui.horizontal(|ui| {
+            ui.label(tr(label));
+            ui.add(
+                egui::Slider::new(note_index, MIN_NOTE..=MAX_NOTE)
+                    .custom_formatter(|n, _| crate::midiinput::note_name(n as usize)),
+            );
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        if ui
+            .button(tr(if self.conf.active {
+                "De-activate"
+            } else {
+                "Activate"
+            }))
+            .clicked()
+        {
+            self.conf.active = !self.conf.active;
+        }
+
+        ui.checkbox(&mut self.conf.follow_clock, tr("Follow the midi clock"));
+        if !self.conf.follow_clock {
+            crate::utils::create_usize_slider(
+                ui,
+                "Beats per minutes",
+                &mut self.conf.bpm,
+                RangeInclusive::new(60, 240),
+            );
+        }
+        crate::utils::create_usize_slider(
+            ui,
+            "Notes per quarter: ",
+            &mut self.conf.steps_per_quarter,
+            RangeInclusive::new(1, 8),
+        );
+        crate::utils::create_f64_slider(
+            ui,
+            "Rest probability: ",
+            &mut self.conf.rest_probability,
+            RangeInclusive::new(0.0, 1.0),
+        );
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Scale: "));
+            if ui.button(format!("{}", self.conf.scale)).clicked() {
+                self.conf.scale = self.conf.scale.cycle();
+            }
+            ui.add(
+                egui::Slider::new(&mut self.conf.root, 0..=11).custom_formatter(|n, _| {
+                    let name = crate::midiinput::note_name(MidiNote::raw_index(n as u8));
+                    name.trim_end_matches(char::is_numeric).to_string()
+                }),
+            );
+        });
+        Self::create_note_slider(ui, "Lowest note: ", &mut self.conf.lowest);
+        Self::create_note_slider(ui, "Highest note: ", &mut self.conf.highest);
+        self.conf.highest = self.conf.highest.max(self.conf.lowest);
+
+        if self.conf != current_conf {
+            self.send_configuration();
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_configuration(&mut self) {
+        if let Err(e) = self.messages_out.send(self.conf.clone()) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for GeneratorUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn generator() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the generator and the UI
+    let (send_to_generator, rcv_from_ui) = std::sync::mpsc::channel();
+
+    let util = Generator::new(&client, rcv_from_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Generator",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 480.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| Ok(Box::new(GeneratorUI::new(cc, status, send_to_generator)))),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GeneratorConfiguration, Scale};
+
+    #[test]
+    fn notes_in_scale() {
+        let mut conf = GeneratorConfiguration {
+            lowest: 12 + 60,
+            highest: 12 + 72,
+            ..Default::default()
+        };
+        assert_eq!(conf.pick(0.0), Some(12 + 60));
+        assert_eq!(conf.pick(0.99), Some(12 + 72));
+        for i in 0..100 {
+            let note = conf.pick(i as f64 / 100.0).unwrap();
+            assert!(conf.in_scale(note));
+        }
+        assert!(!conf.in_scale(12 + 61));
+
+        //A pentatonic: A B C# E F#
+        conf.scale = Scale::Pentatonic;
+        conf.root = 9;
+        conf.lowest = 12 + 62;
+        assert_eq!(conf.pick(0.0), Some(12 + 64));
+        assert_eq!(conf.ticks_per_step(), 6);
+    }
+}
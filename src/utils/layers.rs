@@ -0,0 +1,327 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use jack::MidiWriter;
+use serde::{Deserialize, Serialize};
+
+use crate::{midiinput::MidiInput, scene::SceneListener, session::Autosave};
+
+use super::{ChannelWatchdog, CommonError, ConnectionType, JackStatus};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "layers";
+///The highest velocity of a midi note
+const MAX_VELOCITY: u8 = 127;
+
+///Where the notes are sent according to their velocity
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LayersConfiguration {
+    ///The velocity from which the notes go to the hard layer
+    threshold: u8,
+    ///The width of the velocities around the threshold played by both layers
+    crossfade: u8,
+}
+
+impl Default for LayersConfiguration {
+    fn default() -> Self {
+        Self {
+            threshold: 64,
+            crossfade: 0,
+        }
+    }
+}
+
+impl LayersConfiguration {
+    ///The gain of the soft and of the hard layer for a note of the given
+    /// velocity. In the crossfade zone, the note is played by both layers.
+    fn gains(&self, velocity: u8) -> (f64, f64) {
+        if self.crossfade == 0 {
+            return if velocity < self.threshold {
+                (1.0, 0.0)
+            } else {
+                (0.0, 1.0)
+            };
+        }
+        let start = self.threshold as f64 - self.crossfade as f64 / 2.0;
+        let hard = ((velocity as f64 - start) / self.crossfade as f64).clamp(0.0, 1.0);
+        (1.0 - hard, hard)
+    }
+}
+
+struct Layers {
+    configuration: LayersConfiguration,
+    /// The midi input, split between the outputs
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The notes played softly
+    soft_out: jack::Port<jack::MidiOut>,
+    /// The notes played hard
+    hard_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<LayersConfiguration>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<String>,
+}
+
+impl Layers {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<LayersConfiguration>,
+        messages_out: std::sync::mpsc::Sender<String>,
+    ) -> Result<Layers, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let soft_out = match client.register_port("soft_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+        let hard_out = match client.register_port("hard_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        Ok(Layers {
+            configuration: LayersConfiguration::default(),
+            midi_in: m_in,
+            soft_out,
+            hard_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+
+    fn write(
+        writer: &mut MidiWriter<'_>,
+        raw: &jack::RawMidi<'_>,
+        messages_out: &mut std::sync::mpsc::Sender<String>,
+    ) {
+        if let Err(e) = writer.write(raw) {
+            if let Err(e) = messages_out.send(format!("Unable to write message {:?}: {e}", raw)) {
+                eprintln!("Internal error: {e}");
+            }
+        }
+    }
+
+    ///Write a note-on with its velocity scaled by the gain of the layer. A
+    /// note scaled down to nothing is not sent, as it would be a note-off.
+    fn write_note(
+        writer: &mut MidiWriter<'_>,
+        raw: &jack::RawMidi<'_>,
+        gain: f64,
+        messages_out: &mut std::sync::mpsc::Sender<String>,
+    ) {
+        let velocity = (raw.bytes[2] as f64 * gain).round() as u8;
+        if velocity > 0 {
+            let bytes = [raw.bytes[0], raw.bytes[1], velocity.min(MAX_VELOCITY)];
+            let scaled = jack::RawMidi {
+                time: raw.time,
+                bytes: &bytes,
+            };
+            Self::write(writer, &scaled, messages_out);
+        }
+    }
+}
+
+impl jack::ProcessHandler for Layers {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(conf) = self.messages_in.try_recv() {
+            self.configuration = conf;
+        }
+
+        let mut soft = self.soft_out.writer(ps);
+        let mut hard = self.hard_out.writer(ps);
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::NoteStart { .. } = midi {
+                let (soft_gain, hard_gain) = self.configuration.gains(e.bytes[2]);
+                Self::write_note(&mut soft, &e, soft_gain, &mut self.messages_out);
+                Self::write_note(&mut hard, &e, hard_gain, &mut self.messages_out);
+            } else {
+                //the note-offs and the controllers go to both layers, so
+                // that no note is left hanging when the settings change
+                Self::write(&mut soft, &e, &mut self.messages_out);
+                Self::write(&mut hard, &e, &mut self.messages_out);
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+struct LayersUI {
+    messages: Vec<String>,
+    conf: LayersConfiguration,
+    messages_in: std::sync::mpsc::Receiver<String>,
+    messages_out: std::sync::mpsc::Sender<LayersConfiguration>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<LayersConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl LayersUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<String>,
+        messages_out: std::sync::mpsc::Sender<LayersConfiguration>,
+    ) -> LayersUI {
+        LayersUI {
+            status,
+            messages: Vec::with_capacity(16),
+            conf: LayersConfiguration::default(),
+            messages_in,
+            messages_out,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(msg) = self.messages_in.try_recv() {
+            self.messages.push(msg);
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_configuration();
+        }
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        let mut threshold = self.conf.threshold as usize;
+        crate::utils::create_usize_slider(
+            ui,
+            "Velocity threshold: ",
+            &mut threshold,
+            RangeInclusive::new(1, MAX_VELOCITY as usize),
+        );
+        self.conf.threshold = threshold as u8;
+        let mut crossfade = self.conf.crossfade as usize;
+        crate::utils::create_usize_slider(
+            ui,
+            "Crossfade: ",
+            &mut crossfade,
+            RangeInclusive::new(0, MAX_VELOCITY as usize),
+        );
+        self.conf.crossfade = crossfade as u8;
+
+        if self.conf != current_conf {
+            self.send_configuration();
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_configuration(&mut self) {
+        if let Err(e) = self.messages_out.send(self.conf.clone()) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for LayersUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn layers() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the layers and the UI
+    let (send_to_layers, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_layers) = std::sync::mpsc::channel();
+
+    let util = Layers::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Layers",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 400.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(LayersUI::new(
+                cc,
+                status,
+                rcv_from_layers,
+                send_to_layers,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LayersConfiguration;
+
+    #[test]
+    fn velocity_split() {
+        let mut conf = LayersConfiguration {
+            threshold: 64,
+            crossfade: 0,
+        };
+        assert_eq!(conf.gains(63), (1.0, 0.0));
+        assert_eq!(conf.gains(64), (0.0, 1.0));
+        conf.crossfade = 20;
+        assert_eq!(conf.gains(54), (1.0, 0.0));
+        assert_eq!(conf.gains(64), (0.5, 0.5));
+        assert_eq!(conf.gains(74), (0.0, 1.0));
+        assert_eq!(conf.gains(127), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,371 @@
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::SceneListener,
+    session::Autosave,
+    synth::hardware::{HardWare, KeyBoardKey},
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus},
+};
+
+use super::KeyBoardKeySetter;
+
+///The gain applied when the output is dimmed (-20 dB)
+const DIM_GAIN: f32 = 0.1;
+///The duration of the gain changes, in seconds, to avoid clicks
+const RAMP_DURATION: f32 = 0.005;
+
+///The state of the master output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct MasterConfiguration {
+    ///If true, nothing goes out
+    mute: bool,
+    ///If true, the output is lowered by 20 dB
+    dim: bool,
+}
+
+impl MasterConfiguration {
+    fn gain(&self) -> f32 {
+        if self.mute {
+            0.0
+        } else if self.dim {
+            DIM_GAIN
+        } else {
+            1.0
+        }
+    }
+}
+
+struct Master {
+    ///The current state of the output
+    conf: MasterConfiguration,
+    ///The gain currently applied, moving toward the gain of the configuration
+    gain: f32,
+    ///The maximum change of the gain for a single frame
+    gain_step: f32,
+    /// The midi input used to mute/dim the output
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The left input
+    audio_in_left: jack::Port<jack::AudioIn>,
+    /// The right input
+    audio_in_right: jack::Port<jack::AudioIn>,
+    /// The left output
+    audio_out_left: jack::Port<jack::AudioOut>,
+    /// The right output
+    audio_out_right: jack::Port<jack::AudioOut>,
+    //The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToMaster>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToMasterUI>,
+    ///If set, the next control will be used for the given key
+    key_change: Option<KeyBoardKey>,
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+}
+
+impl Master {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToMaster>,
+        messages_out: std::sync::mpsc::Sender<MessageToMasterUI>,
+    ) -> Result<Master, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let in_left = match client.register_port("in_left", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let in_right = match client.register_port("in_right", jack::AudioIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioIn, e)),
+        };
+        let out_left = match client.register_port("out_left", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+        let out_right = match client.register_port("out_right", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
+
+        Ok(Master {
+            conf: MasterConfiguration {
+                mute: false,
+                dim: false,
+            },
+            gain: 1.0,
+            gain_step: 1.0 / (RAMP_DURATION * client.sample_rate() as f32),
+            midi_in: m_in,
+            audio_in_left: in_left,
+            audio_in_right: in_right,
+            audio_out_left: out_left,
+            audio_out_right: out_right,
+            messages_in: ChannelWatchdog::new("master", messages_in),
+            messages_out,
+            key_change: None,
+            keyboard: HardWare::new(),
+        })
+    }
+
+    fn send_message(
+        msg: MessageToMasterUI,
+        messages_out: &mut std::sync::mpsc::Sender<MessageToMasterUI>,
+    ) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Master {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        while let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToMaster::NewConfiguration(conf) => self.conf = conf,
+                MessageToMaster::ChangeMidiKey(key) => self.key_change = Some(key),
+                MessageToMaster::ClearMidiKey(key) => self.keyboard.clear_key(key),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::Controller {
+                channel: _,
+                control,
+                value,
+            } = midi
+            {
+                if let Some(key) = self.key_change {
+                    self.keyboard.update_key(key, control);
+                    self.key_change = None;
+                }
+                if value == 0 {
+                    continue;
+                }
+                match self.keyboard.get_keyboard_key(control) {
+                    Some(KeyBoardKey::Mute) => self.conf.mute = !self.conf.mute,
+                    Some(KeyBoardKey::Dim) => self.conf.dim = !self.conf.dim,
+                    _ => continue,
+                }
+                Self::send_message(
+                    MessageToMasterUI::NewConfiguration(self.conf),
+                    &mut self.messages_out,
+                );
+            }
+        }
+
+        let target = self.conf.gain();
+        let in_left = self.audio_in_left.as_slice(ps);
+        let in_right = self.audio_in_right.as_slice(ps);
+        let out_left = self.audio_out_left.as_mut_slice(ps);
+        let out_right = self.audio_out_right.as_mut_slice(ps);
+        for i in 0..out_left.len() {
+            if self.gain < target {
+                self.gain = (self.gain + self.gain_step).min(target);
+            } else if self.gain > target {
+                self.gain = (self.gain - self.gain_step).max(target);
+            }
+            out_left[i] = in_left[i] * self.gain;
+            out_right[i] = in_right[i] * self.gain;
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToMaster {
+    NewConfiguration(MasterConfiguration),
+    ChangeMidiKey(KeyBoardKey),
+    ClearMidiKey(KeyBoardKey),
+}
+
+impl From<KeyBoardKeySetter> for MessageToMaster {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToMaster::ChangeMidiKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToMaster::ClearMidiKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToMasterUI {
+    NewConfiguration(MasterConfiguration),
+}
+
+struct MasterUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToMasterUI>,
+    message_out: std::sync::mpsc::Sender<MessageToMaster>,
+    messages: Vec<String>,
+    conf: MasterConfiguration,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<MasterConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl MasterUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToMasterUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToMaster>,
+    ) -> MasterUI {
+        MasterUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            messages: Vec::new(),
+            conf: MasterConfiguration {
+                mute: false,
+                dim: false,
+            },
+            scenes: SceneListener::new(),
+            session: Autosave::new("master"),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Mute",
+                    KeyBoardKey::Mute,
+                    &mut self.message_out,
+                    &mut self.messages,
+                );
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Dim",
+                    KeyBoardKey::Dim,
+                    &mut self.message_out,
+                    &mut self.messages,
+                );
+            });
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let status = if self.conf.mute {
+            "MUTED"
+        } else if self.conf.dim {
+            "DIMMED (-20 dB)"
+        } else {
+            ""
+        };
+        ui.label(egui::RichText::new(tr(status)).color(egui::Color32::from_rgb(180, 19, 60)));
+        let initial = self.conf;
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.conf.mute, tr("Mute"));
+            ui.toggle_value(&mut self.conf.dim, tr("Dim"));
+        });
+        if initial != self.conf {
+            self.send_message(MessageToMaster::NewConfiguration(self.conf));
+        }
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send_message(&mut self, msg: MessageToMaster) {
+        if let Err(e) = self.message_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToMasterUI::NewConfiguration(c) => self.conf = c,
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update("master", &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_message(MessageToMaster::NewConfiguration(self.conf));
+        }
+    }
+}
+
+impl eframe::App for MasterUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn master() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new("master", jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance("master", client.name());
+
+    //open a message channel for the master and the UI
+    let (send_to_master, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_master) = std::sync::mpsc::channel();
+
+    let master = Master::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(master))
+    {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Master",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(MasterUI::new(
+                cc,
+                status,
+                rcv_from_master,
+                send_to_master,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
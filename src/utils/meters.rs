@@ -0,0 +1,259 @@
+use eframe::egui::{self, Color32, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::dsp::gain_to_db;
+
+///The lowest level shown by the meters, in decibels
+const METER_FLOOR_DB: f64 = -60.0;
+///The largest gain reduction shown by the gain-reduction meter, in decibels
+const MAX_REDUCTION_DB: f64 = 24.0;
+///Above this level (in decibels), the meter turns yellow
+const WARNING_DB: f64 = -12.0;
+///Above this level (in decibels), the meter turns red
+const DANGER_DB: f64 = -3.0;
+///The height of the meters
+const METER_HEIGHT: f32 = 14.0;
+///The height of the waveform strip
+const STRIP_HEIGHT: f32 = 40.0;
+///The height of the overview of a file
+const OVERVIEW_HEIGHT: f32 = 24.0;
+///The level from which a sample of a file is considered clipped: the samples
+/// of a 16 bits file do not reach 1
+const FILE_CLIP_LEVEL: f32 = 0.999;
+
+const GREEN: Color32 = Color32::from_rgb(40, 180, 70);
+const YELLOW: Color32 = Color32::from_rgb(230, 200, 30);
+const RED: Color32 = Color32::from_rgb(180, 19, 60);
+
+///The level of a block of audio
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Level {
+    ///The highest absolute value of the samples
+    pub peak: f32,
+    ///The root mean square of the samples
+    pub rms: f32,
+}
+
+impl Level {
+    ///Measure the level of a block of samples
+    pub fn measure(samples: &[f32]) -> Level {
+        if samples.is_empty() {
+            return Level::default();
+        }
+        let mut peak = 0.0_f32;
+        let mut sum = 0.0_f32;
+        for sample in samples {
+            peak = peak.max(sample.abs());
+            sum += sample * sample;
+        }
+        Level {
+            peak,
+            rms: (sum / samples.len() as f32).sqrt(),
+        }
+    }
+
+    ///Combine the levels of two consecutive blocks of the same size
+    pub fn merge(&self, other: &Level) -> Level {
+        Level {
+            peak: self.peak.max(other.peak),
+            rms: ((self.rms * self.rms + other.rms * other.rms) / 2.0).sqrt(),
+        }
+    }
+
+    ///True if the signal reached the full scale
+    pub fn clipped(&self) -> bool {
+        self.peak >= 1.0
+    }
+}
+
+///Compute the correlation between the two channels of a stereo signal: 1 if
+/// both channels are identical, 0 if they are unrelated and -1 if they are
+/// out of phase.
+pub fn correlation(left: &[f32], right: &[f32]) -> f32 {
+    let mut lr = 0.0_f32;
+    let mut ll = 0.0_f32;
+    let mut rr = 0.0_f32;
+    for (l, r) in left.iter().zip(right) {
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    if ll <= 0.0 || rr <= 0.0 {
+        return 0.0;
+    }
+    (lr / (ll * rr).sqrt()).clamp(-1.0, 1.0)
+}
+
+///Summarize a signal in 'columns' pairs of the lowest and highest samples of
+/// consecutive blocks, to draw it at once
+pub fn overview(samples: &[f32], columns: usize) -> Vec<(f32, f32)> {
+    if samples.is_empty() || columns == 0 {
+        return Vec::new();
+    }
+    let block = samples.len().div_ceil(columns);
+    samples
+        .chunks(block)
+        .map(|chunk| {
+            chunk.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            })
+        })
+        .collect()
+}
+
+///The position of a level in decibels on a meter, from 0 (floor) to 1 (full scale)
+fn meter_position(db: f64) -> f32 {
+    ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0) as f32
+}
+
+fn level_color(db: f64) -> Color32 {
+    if db >= DANGER_DB {
+        RED
+    } else if db >= WARNING_DB {
+        YELLOW
+    } else {
+        GREEN
+    }
+}
+
+///Allocate the space of a meter, as wide as possible
+fn allocate(ui: &mut egui::Ui, height: f32) -> (Rect, egui::Painter) {
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(ui.available_width(), height), Sense::hover());
+    painter.rect_filled(response.rect, 2.0, ui.visuals().extreme_bg_color);
+    (response.rect, painter)
+}
+
+///Show a VU meter: the bar is the rms level, the line the peak level. The
+/// right end turns red when the signal clipped.
+pub fn vu_meter(ui: &mut egui::Ui, level: &Level) {
+    let (rect, painter) = allocate(ui, METER_HEIGHT);
+    let rms_db = gain_to_db(level.rms as f64);
+    let peak_db = gain_to_db(level.peak as f64);
+    let mut bar = rect;
+    bar.set_width(rect.width() * meter_position(rms_db));
+    painter.rect_filled(bar, 2.0, level_color(rms_db));
+    let x = rect.left() + rect.width() * meter_position(peak_db);
+    painter.line_segment(
+        [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+        Stroke::new(2.0, level_color(peak_db)),
+    );
+    if level.clipped() {
+        let clip = Rect::from_min_max(Pos2::new(rect.right() - 6.0, rect.top()), rect.max);
+        painter.rect_filled(clip, 0.0, RED);
+    }
+}
+
+///Show a gain-reduction meter: the bar grows from the right with the reduction (in decibels)
+pub fn gain_reduction_meter(ui: &mut egui::Ui, reduction_db: f64) {
+    let (rect, painter) = allocate(ui, METER_HEIGHT);
+    let ratio = (reduction_db.abs() / MAX_REDUCTION_DB).clamp(0.0, 1.0) as f32;
+    let bar = Rect::from_min_max(
+        Pos2::new(rect.right() - rect.width() * ratio, rect.top()),
+        rect.max,
+    );
+    painter.rect_filled(bar, 2.0, YELLOW);
+}
+
+///Show a stereo correlation meter, from -1 (left) to 1 (right)
+pub fn correlation_meter(ui: &mut egui::Ui, correlation: f32) {
+    let (rect, painter) = allocate(ui, METER_HEIGHT);
+    let center = rect.center().x;
+    painter.line_segment(
+        [
+            Pos2::new(center, rect.top()),
+            Pos2::new(center, rect.bottom()),
+        ],
+        Stroke::new(1.0, ui.visuals().weak_text_color()),
+    );
+    let x = center + rect.width() / 2.0 * correlation.clamp(-1.0, 1.0);
+    let color = if correlation < 0.0 { RED } else { GREEN };
+    let marker = Rect::from_min_max(
+        Pos2::new(x.min(center), rect.top()),
+        Pos2::new(x.max(center), rect.bottom()),
+    );
+    painter.rect_filled(marker, 0.0, color);
+}
+
+///Show the envelope of a signal: every value is the peak of a block, the oldest first
+pub fn waveform_strip(ui: &mut egui::Ui, peaks: &[f32]) {
+    let (rect, painter) = allocate(ui, STRIP_HEIGHT);
+    if peaks.is_empty() {
+        return;
+    }
+    let step = rect.width() / peaks.len() as f32;
+    let center = rect.center().y;
+    for (i, peak) in peaks.iter().enumerate() {
+        let x = rect.left() + step * (i as f32 + 0.5);
+        let half = rect.height() / 2.0 * peak.clamp(0.0, 1.0);
+        let color = if *peak >= 1.0 {
+            RED
+        } else {
+            ui.visuals().text_color()
+        };
+        painter.line_segment(
+            [Pos2::new(x, center - half), Pos2::new(x, center + half)],
+            Stroke::new(step.max(1.0), color),
+        );
+    }
+}
+
+///Show the overview of a file computed by [overview]. The blocks that
+/// clipped are red.
+pub fn waveform_overview(ui: &mut egui::Ui, overview: &[(f32, f32)]) {
+    let (rect, painter) = allocate(ui, OVERVIEW_HEIGHT);
+    if overview.is_empty() {
+        return;
+    }
+    let step = rect.width() / overview.len() as f32;
+    let center = rect.center().y;
+    let half = rect.height() / 2.0;
+    for (i, (min, max)) in overview.iter().enumerate() {
+        let x = rect.left() + step * (i as f32 + 0.5);
+        let color = if *max >= FILE_CLIP_LEVEL || *min <= -FILE_CLIP_LEVEL {
+            RED
+        } else {
+            ui.visuals().text_color()
+        };
+        painter.line_segment(
+            [
+                Pos2::new(x, center - half * max.clamp(-1.0, 1.0)),
+                Pos2::new(x, center - half * min.clamp(-1.0, 1.0)),
+            ],
+            Stroke::new(step.max(1.0), color),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{correlation, overview, Level};
+
+    #[test]
+    fn level() {
+        let level = Level::measure(&[0.5, -1.0, 0.5, -0.5]);
+        assert_eq!(level.peak, 1.0);
+        assert!((level.rms - (1.75_f32 / 4.0).sqrt()).abs() < 1e-6);
+        assert!(level.clipped());
+        assert_eq!(Level::measure(&[]), Level::default());
+    }
+
+    #[test]
+    fn stereo_correlation() {
+        let left = [0.1, 0.5, -0.3, 0.8];
+        let opposite: Vec<f32> = left.iter().map(|v| -v).collect();
+        assert!((correlation(&left, &left) - 1.0).abs() < 1e-6);
+        assert!((correlation(&left, &opposite) + 1.0).abs() < 1e-6);
+        assert_eq!(correlation(&left, &[0.0; 4]), 0.0);
+    }
+
+    #[test]
+    fn file_overview() {
+        let samples = [0.1, -0.2, 0.3, 0.5, -1.0, 0.0, 0.2];
+        assert_eq!(
+            overview(&samples, 3),
+            vec![(-0.2, 0.3), (-1.0, 0.5), (0.2, 0.2)]
+        );
+        assert_eq!(overview(&samples, 10).len(), samples.len());
+        assert!(overview(&[], 10).is_empty());
+    }
+}
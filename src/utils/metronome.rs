@@ -1,18 +1,41 @@
-use std::ops::RangeInclusive;
+use std::{collections::VecDeque, ops::RangeInclusive};
 
 use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    i18n::tr,
     midiinput::MidiInput,
+    mtc::MtcGenerator,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
     synth::{
         hardware::{HardWare, KeyBoardKey},
         wavetype::WaveType,
     },
 };
 
-use super::{CommonError, ConnectionType, KeyBoardKeySetter};
+use super::{
+    ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, MidiChannel,
+    ShortcutAction, Shortcuts, TapTempo,
+};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+///The name under which the settings are stored
+const TOOL_NAME: &str = "metronome";
+
+///The gain applied to the click sample on every beat but the first one of the bar
+const CLICK_WEAK_BEAT_GAIN: f64 = 0.5;
+///The longest click sample that can be used, in seconds
+const CLICK_MAX_DURATION: f64 = 0.25;
+///The number of notes scored by the practice analyzer
+const PRACTICE_WINDOW: usize = 64;
+///The width of a bar of the timing histogram, in milliseconds
+const PRACTICE_BIN_WIDTH: f64 = 10.0;
+///The number of bars of the timing histogram on each side of the beat
+const PRACTICE_NB_BINS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 struct MetronomeConfiguration {
     ///The number of quarters per minutes
     bpm: usize,
@@ -32,18 +55,74 @@ impl Default for MetronomeConfiguration {
     }
 }
 
+///Score how far the last played notes landed from the beat
+#[derive(Debug, Default)]
+struct Practice {
+    ///The offset of every recent note, in milliseconds: negative when the note
+    /// is ahead of the beat, positive when it is behind
+    offsets: VecDeque<f64>,
+}
+
+impl Practice {
+    fn push(&mut self, offset: f64) {
+        if self.offsets.len() >= PRACTICE_WINDOW {
+            self.offsets.pop_front();
+        }
+        self.offsets.push_back(offset);
+    }
+
+    fn clear(&mut self) {
+        self.offsets.clear();
+    }
+
+    ///The average offset of the notes, in milliseconds
+    fn mean(&self) -> Option<f64> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        Some(self.offsets.iter().sum::<f64>() / self.offsets.len() as f64)
+    }
+
+    ///The average distance between the notes and the beat, in milliseconds
+    fn accuracy(&self) -> Option<f64> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        Some(self.offsets.iter().map(|o| o.abs()).sum::<f64>() / self.offsets.len() as f64)
+    }
+
+    ///The number of notes in every bar of the histogram, from the furthest
+    /// ahead to the furthest behind. The outer bars also count the notes
+    /// further away.
+    fn histogram(&self) -> [usize; 2 * PRACTICE_NB_BINS + 1] {
+        let mut bins = [0; 2 * PRACTICE_NB_BINS + 1];
+        let limit = PRACTICE_NB_BINS as f64;
+        for offset in &self.offsets {
+            let bin = (offset / PRACTICE_BIN_WIDTH).round().clamp(-limit, limit);
+            bins[(bin + limit) as usize] += 1;
+        }
+        bins
+    }
+}
+
 struct Metronome {
     configuration: MetronomeConfiguration,
     ///The keyboard events we are listening to
     keyboard: HardWare,
+    ///The midi channel we are listening to
+    channel: MidiChannel,
     /// The midi input to activate the metronome
     midi_in: jack::Port<jack::MidiIn>,
     /// The output audio port
     audio_mono_out: jack::Port<jack::AudioOut>,
+    /// The midi time code, sent while the metronome is active
+    mtc_out: jack::Port<jack::MidiOut>,
+    ///The generator of the midi time code
+    mtc: MtcGenerator,
     ///The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToMetronome>,
+    messages_in: ChannelWatchdog<MessageToMetronome, spsc::Receiver<MessageToMetronome>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToMetronomeUI>,
+    messages_out: spsc::Sender<MessageToMetronomeUI>,
     ///The audio rate (number of audio frames per seconds)
     rate: usize,
     ///The number of frames passed since the start of the first beat
@@ -56,13 +135,15 @@ struct Metronome {
     beat_nb: usize,
     /// The next key to map
     next_key_map: Option<KeyBoardKey>,
+    /// The sample played on every beat instead of the generated sound
+    click: Option<Vec<f32>>,
 }
 
 impl Metronome {
     fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToMetronome>,
-        messages_out: std::sync::mpsc::Sender<MessageToMetronomeUI>,
+        messages_in: spsc::Receiver<MessageToMetronome>,
+        messages_out: spsc::Sender<MessageToMetronomeUI>,
     ) -> Result<Metronome, CommonError> {
         let a_out = match client.register_port("audio_out", jack::AudioOut::default()) {
             Ok(v) => v,
@@ -72,13 +153,20 @@ impl Metronome {
             Ok(v) => v,
             Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
         };
+        let mtc_out = match client.register_port("mtc_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
 
         Ok(Metronome {
             configuration: MetronomeConfiguration::default(),
             keyboard: HardWare::new(),
+            channel: MidiChannel::load(TOOL_NAME),
             midi_in: m_in,
             audio_mono_out: a_out,
-            messages_in,
+            mtc_out,
+            mtc: MtcGenerator::new(client.sample_rate()),
+            messages_in: ChannelWatchdog::new("metronome", messages_in),
             messages_out,
             rate: client.sample_rate(),
             time: 0,
@@ -86,22 +174,45 @@ impl Metronome {
             sound_duration: client.sample_rate() / 10,
             beat_nb: 0,
             next_key_map: None,
+            click: None,
         })
     }
+
+    ///How far a note played at 'time' (in frames since the start of the bar)
+    /// is from the closest beat, in milliseconds
+    fn beat_offset(time: usize, rate: usize, bpm: usize) -> f64 {
+        let period = rate * 60 / bpm.max(1);
+        let phase = (time % period) as f64;
+        let offset = if phase > period as f64 / 2.0 {
+            phase - period as f64
+        } else {
+            phase
+        };
+        offset * 1000.0 / rate as f64
+    }
 }
 
 impl jack::ProcessHandler for Metronome {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToMetronome::NewConfiguration(conf) => self.configuration = conf,
                 MessageToMetronome::Active(active) => self.configuration.active = active,
+                MessageToMetronome::Channel(channel) => self.channel = channel,
                 MessageToMetronome::SetKey(key_board_key) => {
                     self.next_key_map = Some(key_board_key)
                 }
                 MessageToMetronome::ClearKey(key_board_key) => {
                     self.keyboard.clear_key(key_board_key)
                 }
+                MessageToMetronome::ClickSample(click) => {
+                    self.sound_duration = match &click {
+                        Some(samples) => samples.len(),
+                        None => self.rate / 10,
+                    };
+                    self.sound_left = 0;
+                    self.click = click;
+                }
             }
         }
 
@@ -109,7 +220,23 @@ impl jack::ProcessHandler for Metronome {
 
         let show_p = self.midi_in.iter(ps);
         for e in show_p {
-            let midi = e.into();
+            let midi: MidiInput = e.into();
+            if !self.channel.accepts(&midi) {
+                continue;
+            }
+
+            if let MidiInput::NoteStart { timing, .. } = midi {
+                if self.configuration.active {
+                    let offset = Self::beat_offset(
+                        self.time + timing as usize,
+                        self.rate,
+                        self.configuration.bpm,
+                    );
+                    if let Err(e) = self.messages_out.send(MessageToMetronomeUI::Timing(offset)) {
+                        eprintln!("Internal error: {e}");
+                    }
+                }
+            }
 
             if let MidiInput::Controller {
                 control,
@@ -146,7 +273,15 @@ impl jack::ProcessHandler for Metronome {
                 } else {
                     self.sound_left as f64
                 };
-                let result = if self.time + self.sound_left <= self.sound_duration {
+                let result = if let Some(click) = &self.click {
+                    let sample = click[self.sound_duration - self.sound_left] as f64;
+                    if self.time + self.sound_left <= self.sound_duration {
+                        //first beat
+                        sample
+                    } else {
+                        sample * CLICK_WEAK_BEAT_GAIN
+                    }
+                } else if self.time + self.sound_left <= self.sound_duration {
                     //first beat
                     let t = (self.time as f64) / (self.rate as f64);
                     fade * WaveType::Sin.compute(880.0 * t * 2.0 * std::f64::consts::PI)
@@ -182,6 +317,23 @@ impl jack::ProcessHandler for Metronome {
             }
         }
 
+        //the time code starts again every time the metronome is activated
+        if self.configuration.active {
+            let mut writer = self.mtc_out.writer(ps);
+            self.mtc
+                .generate(ps.n_frames() as usize, |offset, message| {
+                    let raw = jack::RawMidi {
+                        time: offset as jack::Frames,
+                        bytes: &message,
+                    };
+                    if let Err(e) = writer.write(&raw) {
+                        eprintln!("Unable to send the midi time code: {e}");
+                    }
+                });
+        } else {
+            self.mtc.reset();
+        }
+
         if self.configuration != current_conf {
             if let Err(e) = self
                 .messages_out
@@ -202,6 +354,9 @@ enum MessageToMetronome {
     NewConfiguration(MetronomeConfiguration),
     SetKey(KeyBoardKey),
     ClearKey(KeyBoardKey),
+    ClickSample(Option<Vec<f32>>),
+    ///Listen to another midi channel
+    Channel(MidiChannel),
 }
 
 impl From<KeyBoardKeySetter> for MessageToMetronome {
@@ -215,46 +370,119 @@ impl From<KeyBoardKeySetter> for MessageToMetronome {
 
 enum MessageToMetronomeUI {
     NewConfiguration(MetronomeConfiguration),
+    ///A note was played, at the given offset from the beat (in milliseconds)
+    Timing(f64),
 }
 
 struct MetronomeUI {
     messages: Vec<String>,
     conf: MetronomeConfiguration,
-    messages_in: std::sync::mpsc::Receiver<MessageToMetronomeUI>,
-    messages_out: std::sync::mpsc::Sender<MessageToMetronome>,
+    messages_in: spsc::Receiver<MessageToMetronomeUI>,
+    messages_out: spsc::Sender<MessageToMetronome>,
+    ///The audio rate, used to convert the click samples
+    rate: usize,
+    ///The path of the wav file to use as click
+    click_path: String,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The midi channel the metronome listens to
+    channel: MidiChannel,
+    ///The configuration saved for crash recovery
+    session: Autosave<MetronomeConfiguration>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///Compute the tempo from the tap tempo shortcut
+    tap: TapTempo,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+    ///The timing of the notes played along the metronome
+    practice: Practice,
 }
 
 impl MetronomeUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToMetronomeUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToMetronome>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToMetronomeUI>,
+        messages_out: spsc::Sender<MessageToMetronome>,
+        rate: usize,
     ) -> MetronomeUI {
         MetronomeUI {
+            status,
             messages: Vec::with_capacity(16),
             conf: MetronomeConfiguration::default(),
             messages_in,
             messages_out,
+            rate,
+            click_path: String::new(),
+            scenes: SceneListener::new(),
+            channel: MidiChannel::load(TOOL_NAME),
+            session: Autosave::new(TOOL_NAME),
+            shortcuts: Shortcuts::load(),
+            tap: TapTempo::new(),
+            practice: Practice::default(),
+        }
+    }
+
+    ///Load the click sample and send it to the metronome
+    fn load_click(&mut self) {
+        match crate::wavreader::load_mono(std::path::Path::new(&self.click_path), self.rate) {
+            Ok(mut samples) => {
+                samples.truncate((self.rate as f64 * CLICK_MAX_DURATION) as usize);
+                if samples.is_empty() {
+                    self.messages
+                        .push(format!("'{}' does not contain any sample", self.click_path));
+                } else {
+                    self.send_message(MessageToMetronome::ClickSample(Some(samples)));
+                }
+            }
+            Err(e) => self
+                .messages
+                .push(format!("Unable to load '{}': {e}", self.click_path)),
+        }
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if action == ShortcutAction::TapTempo {
+                if let Some(bpm) = self.tap.tap() {
+                    self.conf.bpm = (bpm.round() as usize).clamp(60, 240);
+                    self.send_message(MessageToMetronome::NewConfiguration(self.conf.clone()));
+                }
+            }
         }
     }
 
-    fn read_input(&mut self) {
+    fn read_input(&mut self, ctx: &egui::Context) {
         while let Ok(m) = self.messages_in.try_recv() {
             match m {
                 MessageToMetronomeUI::NewConfiguration(c) => self.conf = c,
+                MessageToMetronomeUI::Timing(offset) => self.practice.push(offset),
             }
         }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_message(MessageToMetronome::NewConfiguration(self.conf.clone()));
+        }
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
         let current_conf = self.conf.clone();
 
         if ui
-            .button(if self.conf.active {
+            .button(tr(if self.conf.active {
                 "De-activate"
             } else {
                 "Activate"
-            })
+            }))
             .clicked()
         {
             self.conf.active = !self.conf.active;
@@ -274,16 +502,80 @@ impl MetronomeUI {
             RangeInclusive::new(2, 10),
         );
 
+        ui.horizontal(|ui| {
+            ui.label(tr("Click sample: "));
+            ui.text_edit_singleline(&mut self.click_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button(tr("Load click")).clicked() {
+                self.load_click();
+            }
+            if ui.button(tr("Default click")).clicked() {
+                self.send_message(MessageToMetronome::ClickSample(None));
+            }
+        });
+
         if self.conf != current_conf {
             self.send_message(MessageToMetronome::NewConfiguration(self.conf.clone()));
         }
 
+        self.show_practice(ui);
+
         crate::utils::show_logs(ui, &mut self.messages);
     }
 
+    ///Show how far the notes played on the midi input land from the beat
+    fn show_practice(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(tr("Practice: "));
+            if ui.button(tr("Reset")).clicked() {
+                self.practice.clear();
+            }
+        });
+        match (self.practice.mean(), self.practice.accuracy()) {
+            (Some(mean), Some(accuracy)) => {
+                let tendency = if mean < 0.0 {
+                    tr("ahead of the beat")
+                } else {
+                    tr("behind the beat")
+                };
+                ui.label(format!(
+                    "{}{:.1} ms {}",
+                    tr("On average: "),
+                    mean.abs(),
+                    tendency
+                ));
+                ui.label(format!("{}{:.1} ms", tr("Accuracy: "), accuracy));
+            }
+            _ => {
+                ui.label(tr("Play along the metronome to score your timing"));
+            }
+        }
+        let limit = PRACTICE_NB_BINS as f64;
+        let bars = self
+            .practice
+            .histogram()
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| {
+                egui_plot::Bar::new((bin as f64 - limit) * PRACTICE_BIN_WIDTH, *count as f64)
+                    .width(PRACTICE_BIN_WIDTH * 0.9)
+            })
+            .collect();
+        egui_plot::Plot::new("Practice")
+            .view_aspect(2.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(egui_plot::BarChart::new(bars))
+            });
+    }
+
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("Settings", |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
                 if ui
                     .toggle_value(&mut self.conf.active, String::from("Active"))
                     .clicked()
@@ -306,6 +598,16 @@ impl MetronomeUI {
                     &mut self.messages_out,
                     &mut self.messages,
                 );
+                self.shortcuts
+                    .create_menu(ui, &[ShortcutAction::TapTempo], &mut self.messages);
+                if crate::utils::create_channel_select(
+                    ui,
+                    TOOL_NAME,
+                    &mut self.channel,
+                    &mut self.messages,
+                ) {
+                    self.send_message(MessageToMetronome::Channel(self.channel));
+                }
             });
 
             crate::utils::common_menu_luncher(ui, &mut self.messages);
@@ -326,7 +628,10 @@ impl eframe::App for MetronomeUI {
         //can be called whenever there is someting in the queue
         ctx.request_repaint();
 
-        self.read_input();
+        self.read_input(ctx);
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -346,13 +651,16 @@ pub fn metronome() -> Result<(), CommonError> {
                 return Err(CommonError::UnableToStartClient(e));
             }
         };
+    crate::storage::set_instance("metronome", client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
+    let rate = client.sample_rate();
     let util = Metronome::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), util) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -364,7 +672,15 @@ pub fn metronome() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(MetronomeUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(MetronomeUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+                rate,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
@@ -377,3 +693,31 @@ pub fn metronome() -> Result<(), CommonError> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Metronome, Practice};
+
+    #[test]
+    fn timing() {
+        //120 beats per minute: a beat every 24000 frames
+        assert_eq!(Metronome::beat_offset(0, 48000, 120), 0.0);
+        assert_eq!(Metronome::beat_offset(480, 48000, 120), 10.0);
+        assert_eq!(Metronome::beat_offset(23520, 48000, 120), -10.0);
+        assert_eq!(Metronome::beat_offset(24000 * 3 + 960, 48000, 120), 20.0);
+
+        let mut practice = Practice::default();
+        for offset in [-10.0, 10.0, 30.0, 500.0] {
+            practice.push(offset);
+        }
+        assert_eq!(practice.mean(), Some(132.5));
+        assert_eq!(practice.accuracy(), Some(137.5));
+        let histogram = practice.histogram();
+        assert_eq!(histogram[9], 1);
+        assert_eq!(histogram[11], 1);
+        assert_eq!(histogram[13], 1);
+        assert_eq!(histogram[20], 1);
+        practice.clear();
+        assert_eq!(practice.mean(), None);
+    }
+}
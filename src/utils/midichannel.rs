@@ -0,0 +1,128 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::MidiInput,
+    storage::{self, StorageError},
+};
+
+///The name of the file containing the channel of every tool
+const CHANNELS_FILE: &str = "midi_channels.json";
+///The number of midi channels
+const NB_CHANNELS: u8 = 16;
+
+///The midi channel a tool listens to, so that several tools can share the
+/// same midi cable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MidiChannel {
+    ///Every channel is accepted
+    #[default]
+    Omni,
+    ///Only the given channel (from 0 to 15) is accepted
+    Channel(u8),
+}
+
+impl MidiChannel {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(CHANNELS_FILE);
+        Ok(path)
+    }
+
+    fn load_all() -> Result<BTreeMap<String, MidiChannel>, StorageError> {
+        let path = Self::path()?;
+        if path.exists() {
+            storage::load(&path)
+        } else {
+            Ok(BTreeMap::new())
+        }
+    }
+
+    ///The channel chosen the last time the tool was used, omni by default
+    pub fn load(tool: &str) -> MidiChannel {
+        Self::load_all()
+            .ok()
+            .and_then(|channels| channels.get(&storage::instance_name(tool)).copied())
+            .unwrap_or_default()
+    }
+
+    ///Save the channel of a tool. Each instance of a tool has its own channel,
+    /// and the channels of the other tools saved meanwhile are kept.
+    pub fn save(&self, tool: &str) -> Result<(), StorageError> {
+        storage::update(&Self::path()?, BTreeMap::new(), |channels| {
+            channels.insert(storage::instance_name(tool), *self);
+        })
+    }
+
+    ///True if the message should be handled. Messages without channel, like
+    /// the pitch bend or the clock, are always accepted.
+    pub fn accepts(&self, midi: &MidiInput) -> bool {
+        match (self, midi.channel()) {
+            (MidiChannel::Channel(expected), Some(channel)) => *expected == channel,
+            _ => true,
+        }
+    }
+
+    ///The name shown to the user: channels are numbered from 1 to 16
+    fn label(&self) -> String {
+        match self {
+            MidiChannel::Omni => String::from(tr("Omni")),
+            MidiChannel::Channel(c) => format!("{}{}", tr("Channel "), c + 1),
+        }
+    }
+}
+
+///Generate the menu to choose the midi channel of a tool. The choice is
+/// saved; return true if the channel changed.
+pub fn create_channel_select(
+    ui: &mut egui::Ui,
+    tool: &str,
+    channel: &mut MidiChannel,
+    messages: &mut Vec<String>,
+) -> bool {
+    let mut changed = false;
+    ui.menu_button(
+        format!("{}{}", tr("Midi channel: "), channel.label()),
+        |ui| {
+            let choices = std::iter::once(MidiChannel::Omni)
+                .chain((0..NB_CHANNELS).map(MidiChannel::Channel));
+            for choice in choices {
+                if ui.radio_value(channel, choice, choice.label()).clicked() {
+                    changed = true;
+                    ui.close_menu();
+                }
+            }
+        },
+    );
+    if changed {
+        if let Err(e) = channel.save(tool) {
+            messages.push(format!("Unable to save the midi channel: {e}"));
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midiinput::MidiInput;
+
+    use super::MidiChannel;
+
+    #[test]
+    fn channel_filter() {
+        let note = MidiInput::NoteStart {
+            channel: 9,
+            note_index: 48,
+            timing: 0,
+            velocity: 0.5,
+        };
+        let bend = MidiInput::PitchBend { value: 64 };
+        assert!(MidiChannel::Omni.accepts(&note));
+        assert!(MidiChannel::Channel(9).accepts(&note));
+        assert!(!MidiChannel::Channel(0).accepts(&note));
+        assert!(MidiChannel::Channel(0).accepts(&bend));
+    }
+}
@@ -0,0 +1,605 @@
+use std::sync::mpsc;
+
+use eframe::egui::{self, ViewportBuilder};
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+
+use crate::{
+    i18n::tr,
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus},
+};
+
+///The name under which the configuration is stored
+const TOOL_NAME: &str = "midiscript";
+///The number of midi messages waiting on each side of the bridge between
+/// the jack thread and the script
+const BRIDGE_CAPACITY: usize = 256;
+///The delay between two polls of the script thread, in milliseconds, when
+/// no message is waiting
+const SCRIPT_POLL_MS: u64 = 1;
+///The function of the script called for every midi message
+const HANDLER: &str = "on_midi";
+///The script used until the user writes another one
+const DEFAULT_SCRIPT: &str = r#"// Called for every midi message received, with its status and data bytes.
+// Return the messages to send as arrays [status, data1, data2], or [] to
+// drop the message.
+fn on_midi(status, data1, data2) {
+    let kind = status & 0xF0;
+    // every note becomes a major chord
+    if kind == 0x90 || kind == 0x80 {
+        return [
+            [status, data1, data2],
+            [status, data1 + 4, data2],
+            [status, data1 + 7, data2],
+        ];
+    }
+    [[status, data1, data2]]
+}
+"#;
+
+///A midi message of at most three bytes, crossing the bridge between the
+/// jack thread and the script
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ShortMessage {
+    bytes: [u8; 3],
+    len: usize,
+}
+
+impl ShortMessage {
+    ///The message made of 'bytes', if they hold a valid message of at most
+    /// three bytes
+    fn new(bytes: &[u8]) -> Option<ShortMessage> {
+        let (status, data) = bytes.split_first()?;
+        if bytes.len() > 3 || *status < 0x80 || data.iter().any(|b| *b > 0x7F) {
+            return None;
+        }
+        let mut message = ShortMessage {
+            bytes: [0; 3],
+            len: bytes.len(),
+        };
+        message.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(message)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+///Call the handler of the script for a midi message, and return the
+/// messages to send instead
+fn transform(
+    engine: &Engine,
+    script: &AST,
+    message: ShortMessage,
+) -> Result<Vec<ShortMessage>, String> {
+    let [status, data1, data2] = message.bytes.map(i64::from);
+    let result: Dynamic = engine
+        .call_fn_with_options(
+            CallFnOptions::new().eval_ast(false),
+            &mut Scope::new(),
+            script,
+            HANDLER,
+            (status, data1, data2),
+        )
+        .map_err(|e| e.to_string())?;
+    messages_of(result)
+}
+
+///The messages returned by the handler: an array of messages, a single
+/// message or nothing. The messages out of the midi range (e.g. a note
+/// transposed above 127) are dropped.
+fn messages_of(result: Dynamic) -> Result<Vec<ShortMessage>, String> {
+    if result.is_unit() {
+        return Ok(Vec::new());
+    }
+    let type_name = result.type_name();
+    let array = result
+        .into_array()
+        .map_err(|_| format!("{HANDLER} returned {type_name} instead of an array"))?;
+    if !array.is_empty() && array.iter().all(|value| value.is_int()) {
+        return Ok(message_of(array)?.into_iter().collect());
+    }
+    let mut messages = Vec::with_capacity(array.len());
+    for value in array {
+        let type_name = value.type_name();
+        let message = value
+            .into_array()
+            .map_err(|_| format!("{HANDLER} returned {type_name} instead of a message"))?;
+        messages.extend(message_of(message)?);
+    }
+    Ok(messages)
+}
+
+///The midi message made of the numbers of an array, None if they are out of
+/// the midi range
+fn message_of(numbers: rhai::Array) -> Result<Option<ShortMessage>, String> {
+    let mut bytes = Vec::with_capacity(numbers.len());
+    for number in numbers {
+        let number = number
+            .as_int()
+            .map_err(|t| format!("a message contains {t} instead of a number"))?;
+        match u8::try_from(number) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => return Ok(None),
+        }
+    }
+    Ok(ShortMessage::new(&bytes))
+}
+
+///The result of the changes of the script, sent to the user interface
+#[derive(Debug)]
+enum ScriptReport {
+    ///The script was compiled and now handles the midi messages
+    Compiled,
+    Error(String),
+}
+
+///Run the script on the midi messages received by the jack thread, and send
+/// back the messages it returns. The script runs in a thread of its own, so
+/// that a slow script never blocks the audio: the messages it is too late for
+/// are forwarded unchanged by the jack thread.
+fn run_script(
+    events: spsc::Receiver<ShortMessage>,
+    results: spsc::Sender<ShortMessage>,
+    sources: mpsc::Receiver<String>,
+    reports: mpsc::Sender<ScriptReport>,
+) {
+    let report = |message: ScriptReport| {
+        if let Err(e) = reports.send(message) {
+            println!("Error: {e}");
+        }
+    };
+    let engine = Engine::new();
+    let mut script: Option<AST> = None;
+    //only the first error of a script is reported, not one per message
+    let mut failed = false;
+    loop {
+        match sources.try_recv() {
+            Ok(source) => match engine.compile(&source) {
+                Ok(ast) => {
+                    if ast
+                        .iter_functions()
+                        .any(|f| f.name == HANDLER && f.params.len() == 3)
+                    {
+                        script = Some(ast);
+                        failed = false;
+                        report(ScriptReport::Compiled);
+                    } else {
+                        report(ScriptReport::Error(format!(
+                            "The script has no function {HANDLER}(status, data1, data2)"
+                        )));
+                    }
+                }
+                Err(e) => report(ScriptReport::Error(format!(
+                    "Unable to compile the script: {e}"
+                ))),
+            },
+            Err(mpsc::TryRecvError::Empty) => {}
+            //the user interface was closed
+            Err(mpsc::TryRecvError::Disconnected) => break,
+        }
+        let event = match events.try_recv() {
+            Ok(event) => event,
+            Err(mpsc::TryRecvError::Empty) => {
+                std::thread::sleep(std::time::Duration::from_millis(SCRIPT_POLL_MS));
+                continue;
+            }
+            //the jack client was stopped
+            Err(mpsc::TryRecvError::Disconnected) => break,
+        };
+        let messages = match &script {
+            Some(script) => transform(&engine, script, event).unwrap_or_else(|e| {
+                if !failed {
+                    failed = true;
+                    report(ScriptReport::Error(format!("Error in the script: {e}")));
+                }
+                vec![event]
+            }),
+            None => vec![event],
+        };
+        for message in messages {
+            if results.send(message).is_err() {
+                report(ScriptReport::Error(String::from(
+                    "Too many messages sent by the script, some of them were dropped",
+                )));
+            }
+        }
+    }
+}
+
+///Pass the midi messages to the script, and write the messages it returns
+struct MidiScript {
+    /// The midi input
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The midi output
+    midi_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToMidiScript, spsc::Receiver<MessageToMidiScript>>,
+    ///The outgoing messages to the UI
+    messages_out: spsc::Sender<MessageToMidiScriptUI>,
+    ///The midi messages sent to the script
+    to_script: spsc::Sender<ShortMessage>,
+    ///The midi messages returned by the script
+    from_script: spsc::Receiver<ShortMessage>,
+    ///If false, the midi messages are forwarded unchanged
+    enabled: bool,
+    ///Set once the user was told that the script could not keep up
+    overflow_reported: bool,
+}
+
+impl MidiScript {
+    pub fn new(
+        client: &jack::Client,
+        messages_in: spsc::Receiver<MessageToMidiScript>,
+        messages_out: spsc::Sender<MessageToMidiScriptUI>,
+        to_script: spsc::Sender<ShortMessage>,
+        from_script: spsc::Receiver<ShortMessage>,
+    ) -> Result<MidiScript, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let m_out = match client.register_port("midi_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        Ok(MidiScript {
+            midi_in: m_in,
+            midi_out: m_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+            to_script,
+            from_script,
+            enabled: false,
+            overflow_reported: false,
+        })
+    }
+
+    fn send_message(
+        msg: MessageToMidiScriptUI,
+        messages_out: &mut spsc::Sender<MessageToMidiScriptUI>,
+    ) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+
+    fn write(
+        writer: &mut jack::MidiWriter<'_>,
+        raw: &jack::RawMidi<'_>,
+        messages_out: &mut spsc::Sender<MessageToMidiScriptUI>,
+    ) {
+        if let Err(e) = writer.write(raw) {
+            Self::send_message(
+                MessageToMidiScriptUI::Message(format!("Unable to write message {:?}: {e}", raw)),
+                messages_out,
+            );
+        }
+    }
+}
+
+impl jack::ProcessHandler for MidiScript {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToMidiScript::Enable(enabled) => {
+                    self.enabled = enabled;
+                    self.overflow_reported = false;
+                }
+            }
+        }
+
+        let mut writer = self.midi_out.writer(ps);
+        //the messages returned by the script since the last cycle
+        while let Ok(message) = self.from_script.try_recv() {
+            let raw = jack::RawMidi {
+                time: 0,
+                bytes: message.bytes(),
+            };
+            Self::write(&mut writer, &raw, &mut self.messages_out);
+        }
+        for e in self.midi_in.iter(ps) {
+            let scripted = match ShortMessage::new(e.bytes) {
+                Some(message) if self.enabled => match self.to_script.send(message) {
+                    Ok(()) => true,
+                    Err(_) => {
+                        if !self.overflow_reported {
+                            self.overflow_reported = true;
+                            Self::send_message(
+                                MessageToMidiScriptUI::Message(String::from(
+                                    "The script is too slow, some messages were forwarded unchanged",
+                                )),
+                                &mut self.messages_out,
+                            );
+                        }
+                        false
+                    }
+                },
+                //the system exclusive messages are never scripted
+                _ => false,
+            };
+            if !scripted {
+                Self::write(&mut writer, &e, &mut self.messages_out);
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToMidiScript {
+    ///Pass the midi messages to the script, or forward them unchanged
+    Enable(bool),
+}
+
+#[derive(Debug)]
+enum MessageToMidiScriptUI {
+    Message(String),
+}
+
+struct MidiScriptUI {
+    messages_in: spsc::Receiver<MessageToMidiScriptUI>,
+    message_out: spsc::Sender<MessageToMidiScript>,
+    ///The scripts sent to the script thread
+    sources: mpsc::Sender<String>,
+    ///The results of the compilation and execution of the scripts
+    reports: mpsc::Receiver<ScriptReport>,
+    messages: Vec<String>,
+    ///The script being edited
+    script: String,
+    ///The last script applied, saved in the session and the scenes
+    applied: String,
+    ///True once the applied script was compiled
+    compiled: bool,
+    ///If false, the midi messages are forwarded unchanged
+    enabled: bool,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The script saved for crash recovery
+    session: Autosave<String>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl MidiScriptUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToMidiScriptUI>,
+        messages_out: spsc::Sender<MessageToMidiScript>,
+        sources: mpsc::Sender<String>,
+        reports: mpsc::Receiver<ScriptReport>,
+    ) -> MidiScriptUI {
+        let mut ui = MidiScriptUI {
+            status,
+            messages_in,
+            message_out: messages_out,
+            sources,
+            reports,
+            messages: Vec::new(),
+            script: String::from(DEFAULT_SCRIPT),
+            applied: String::new(),
+            compiled: false,
+            enabled: true,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        };
+        ui.apply(String::from(DEFAULT_SCRIPT));
+        ui
+    }
+
+    ///Send a script to the script thread, which replaces the current one once compiled
+    fn apply(&mut self, script: String) {
+        self.script = script.clone();
+        self.applied = script.clone();
+        if let Err(e) = self.sources.send(script) {
+            self.messages.push(format!(
+                "Unable to send the script to the script thread: {e}"
+            ));
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut self.enabled, tr("Enabled")).changed() {
+                self.send_message(MessageToMidiScript::Enable(self.enabled && self.compiled));
+            }
+            if ui.button(tr("Apply")).clicked() {
+                self.apply(self.script.clone());
+            }
+            if ui.button(tr("Revert")).clicked() {
+                self.script = self.applied.clone();
+            }
+            if self.script != self.applied {
+                ui.label(tr("modified"));
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("script")
+            .max_height(ui.available_height() * 0.7)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.script)
+                        .code_editor()
+                        .desired_rows(20)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn send_message(&mut self, msg: MessageToMidiScript) {
+        if let Err(e) = self.message_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        //read message queue
+        match self.messages_in.try_recv() {
+            Err(e) => match e {
+                std::sync::mpsc::TryRecvError::Empty => {}
+                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(String::from(
+                    "Internal error: lost connection between UI and logic",
+                )),
+            },
+            Ok(MessageToMidiScriptUI::Message(msg)) => self.messages.push(msg),
+        }
+        while let Ok(report) = self.reports.try_recv() {
+            match report {
+                ScriptReport::Compiled => {
+                    self.compiled = true;
+                    self.send_message(MessageToMidiScript::Enable(self.enabled));
+                }
+                ScriptReport::Error(e) => self.messages.push(e),
+            }
+        }
+        let session = self.session.update(&self.applied, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(script) = self
+            .scenes
+            .update(TOOL_NAME, &self.applied, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.apply(script);
+        }
+    }
+}
+
+impl eframe::App for MidiScriptUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn midi_script() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the midi script and the UI
+    let (send_to_script, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_script) = spsc::channel(MESSAGES_CAPACITY);
+
+    //the bridge between the jack thread and the script thread
+    let (send_events, rcv_events) = spsc::channel(BRIDGE_CAPACITY);
+    let (send_results, rcv_results) = spsc::channel(BRIDGE_CAPACITY);
+    let (send_sources, rcv_sources) = mpsc::channel();
+    let (send_reports, rcv_reports) = mpsc::channel();
+    let runner =
+        std::thread::spawn(move || run_script(rcv_events, send_results, rcv_sources, send_reports));
+
+    let synth = MidiScript::new(&client, rcv_from_ui, send_to_ui, send_events, rcv_results)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "MIDI script",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(480.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(MidiScriptUI::new(
+                cc,
+                status,
+                rcv_from_script,
+                send_to_script,
+                send_sources,
+                rcv_reports,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => {
+            if runner.join().is_err() {
+                println!("Error: the thread running the script stopped unexpectedly");
+            }
+            Ok(())
+        }
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rhai::Engine;
+
+    use super::{transform, ShortMessage, DEFAULT_SCRIPT};
+
+    #[test]
+    fn scripted_messages() {
+        let engine = Engine::new();
+        let chords = engine.compile(DEFAULT_SCRIPT).unwrap();
+        let note = ShortMessage::new(&[0x90, 60, 100]).unwrap();
+        let chord: Vec<_> = transform(&engine, &chords, note)
+            .unwrap()
+            .iter()
+            .map(|m| m.bytes().to_vec())
+            .collect();
+        assert_eq!(chord, [[0x90, 60, 100], [0x90, 64, 100], [0x90, 67, 100]]);
+        //the notes of the chord above the midi range are dropped
+        let high = ShortMessage::new(&[0x80, 122, 0]).unwrap();
+        assert_eq!(transform(&engine, &chords, high).unwrap().len(), 2);
+        let control = ShortMessage::new(&[0xB0, 7, 90]).unwrap();
+        assert_eq!(transform(&engine, &chords, control).unwrap(), [control]);
+
+        //a single message, or nothing
+        let single = engine
+            .compile("fn on_midi(s, d1, d2) { if d1 == 7 { return (); } [s, d1, 127 - d2] }")
+            .unwrap();
+        assert_eq!(
+            transform(&engine, &single, ShortMessage::new(&[0xB0, 1, 27]).unwrap()).unwrap(),
+            [ShortMessage::new(&[0xB0, 1, 100]).unwrap()]
+        );
+        assert!(transform(&engine, &single, control).unwrap().is_empty());
+
+        let wrong = engine
+            .compile("fn on_midi(s, d1, d2) { \"note\" }")
+            .unwrap();
+        assert!(transform(&engine, &wrong, control).is_err());
+        assert!(ShortMessage::new(&[60, 100]).is_none());
+        assert!(ShortMessage::new(&[0xF0, 1, 2, 0xF7]).is_none());
+    }
+}
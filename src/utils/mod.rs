@@ -1,15 +1,55 @@
 mod activate;
+mod appearance;
+mod bounce;
+mod channels;
+mod dropfiles;
+mod generator;
+mod layers;
+mod master;
+pub mod meters;
 mod metronome;
+mod midichannel;
+mod midiscript;
+mod pedal;
+pub mod piano;
 mod recorder;
+mod scenemanager;
+mod setlist;
+mod shortcuts;
+pub mod statistics;
+mod status;
+mod strum;
 mod transposer;
+mod updates;
+mod watchdog;
 
 pub use activate::activator;
+pub use bounce::bounce;
+pub use channels::channels;
+pub use dropfiles::{dropped_files, load_dropped};
 use egui_plot::{Line, PlotPoints};
+pub use generator::generator;
+pub use layers::layers;
+pub use master::master;
 pub use metronome::metronome;
+pub use midichannel::{create_channel_select, MidiChannel};
+pub use midiscript::midi_script;
+pub use pedal::pedal;
 pub use recorder::record;
+pub use scenemanager::scene_manager;
+pub use setlist::setlist;
+pub use shortcuts::{ShortcutAction, Shortcuts, TapTempo};
+pub use status::JackStatus;
+pub use strum::strum;
 pub use transposer::transposer;
+pub use watchdog::{ChannelState, ChannelWatchdog};
 
-use crate::synth::{hardware::KeyBoardKey, wavetype::WaveType};
+use crate::{
+    i18n::tr,
+    spsc::MessageSender,
+    synth::{hardware::KeyBoardKey, wavetype::WaveType},
+    tool::Category,
+};
 
 #[derive(Debug)]
 pub enum ConnectionType {
@@ -103,24 +143,25 @@ pub enum KeyBoardKeySetter {
     Clear(KeyBoardKey),
 }
 
-pub fn create_keyboard_select<T>(
+pub fn create_keyboard_select<T, S>(
     ui: &mut eframe::egui::Ui,
     name: &str,
     keyboard_key: KeyBoardKey,
-    sender: &mut std::sync::mpsc::Sender<T>,
+    sender: &mut S,
     messages: &mut Vec<String>,
 ) where
     T: From<KeyBoardKeySetter>,
+    S: MessageSender<T>,
 {
-    ui.menu_button(name, |ui| {
-        if ui.button("Define...").clicked() {
+    ui.menu_button(tr(name), |ui| {
+        if ui.button(tr("Define...")).clicked() {
             if let Err(e) = sender.send(T::from(KeyBoardKeySetter::Set(keyboard_key))) {
                 messages.push(format!("Unable to send message to set {keyboard_key}: {e}"));
             } else {
                 ui.close_menu();
             }
         }
-        if ui.button("Clear").clicked() {
+        if ui.button(tr("Clear")).clicked() {
             if let Err(e) = sender.send(T::from(KeyBoardKeySetter::Clear(keyboard_key))) {
                 messages.push(format!(
                     "Unable to send message to clear {keyboard_key}: {e}"
@@ -134,44 +175,140 @@ pub fn create_keyboard_select<T>(
 
 ///Generate the common menu to lunch the different executables of this crate
 pub fn common_menu_luncher(ui: &mut eframe::egui::Ui, messages: &mut Vec<String>) {
-    ui.menu_button("Synths", |ui| {
-        if ui.button("Kick").clicked() {
+    ui.menu_button(tr("Synths"), |ui| {
+        if ui.button(tr("Clap")).clicked() {
+            start_command("clap", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Cymbal")).clicked() {
+            start_command("cymbal", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Drum kit")).clicked() {
+            start_command("drumkit", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Hi-hat")).clicked() {
+            start_command("hihat", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Kick")).clicked() {
             start_command("kick", messages);
             ui.close_menu();
         }
-        if ui.button("RSynth").clicked() {
+        if ui.button(tr("Pads")).clicked() {
+            start_command("pads", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Riser")).clicked() {
+            start_command("riser", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("RSynth")).clicked() {
             start_command("rsynth", messages);
             ui.close_menu();
         }
-        if ui.button("Snare").clicked() {
+        if ui.button(tr("Snare")).clicked() {
             start_command("snare", messages);
             ui.close_menu();
         }
+        if ui.button(tr("Tom")).clicked() {
+            start_command("tom", messages);
+            ui.close_menu();
+        }
+        registered_tools(ui, Category::Synths, messages);
     });
-    ui.menu_button("Effects", |ui| {
-        if ui.button("Smooth").clicked() {
+    ui.menu_button(tr("Effects"), |ui| {
+        if ui.button(tr("Compressor")).clicked() {
+            start_command("compressor", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("De-esser")).clicked() {
+            start_command("deesser", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Multiband")).clicked() {
+            start_command("multiband", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Smooth")).clicked() {
             start_command("smooth", messages);
             ui.close_menu();
         }
+        registered_tools(ui, Category::Effects, messages);
     });
-    ui.menu_button("Utils", |ui| {
-        if ui.button("Activator").clicked() {
+    ui.menu_button(tr("Utils"), |ui| {
+        if ui.button(tr("Activator")).clicked() {
             start_command("activator", messages);
             ui.close_menu();
         }
-        if ui.button("Metronome").clicked() {
+        if ui.button(tr("Bounce")).clicked() {
+            start_command("bounce", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Channels")).clicked() {
+            start_command("channels", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Generator")).clicked() {
+            start_command("generator", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Layers")).clicked() {
+            start_command("layers", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Master")).clicked() {
+            start_command("master", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Metronome")).clicked() {
             start_command("metronome", messages);
             ui.close_menu();
         }
-        if ui.button("Recorder").clicked() {
+        if ui.button(tr("MIDI script")).clicked() {
+            start_command("midiscript", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Pedal")).clicked() {
+            start_command("pedal", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Recorder")).clicked() {
             start_command("recorder", messages);
             ui.close_menu();
         }
-        if ui.button("Transposer").clicked() {
+        if ui.button(tr("Scenes")).clicked() {
+            start_command("scenes", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Setlist")).clicked() {
+            start_command("setlist", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Strum")).clicked() {
+            start_command("strum", messages);
+            ui.close_menu();
+        }
+        if ui.button(tr("Transposer")).clicked() {
             start_command("transposer", messages);
             ui.close_menu();
         }
+        registered_tools(ui, Category::Utils, messages);
     });
+    appearance::create_appearance_menu(ui, messages);
+    updates::create_updates_menu(ui, messages);
+    statistics::show_statistics(ui.ctx(), messages);
+}
+
+///Add the tools registered by the program to a menu of the launcher
+fn registered_tools(ui: &mut eframe::egui::Ui, category: Category, messages: &mut Vec<String>) {
+    for entry in crate::tool::registered(category) {
+        if ui.button(entry.name).clicked() {
+            crate::tool::start_registered(entry.name, messages);
+            ui.close_menu();
+        }
+    }
 }
 
 ///Generate a line that can be shown in a plot
@@ -188,7 +325,7 @@ pub fn create_plot_line(wave: &WaveType) -> Line {
 ///Create a slider from 0 to 128 with a DragValue to show the value
 pub fn create_u8_slider(ui: &mut eframe::egui::Ui, label: &str, value: &mut u8) {
     ui.horizontal(|ui| {
-        ui.label(label);
+        ui.label(tr(label));
         let range = std::ops::RangeInclusive::new(0 as u8, 128 as u8);
         ui.add_enabled(
             true,
@@ -211,7 +348,7 @@ pub fn create_usize_slider(
     range: std::ops::RangeInclusive<usize>,
 ) {
     ui.horizontal(|ui| {
-        ui.label(label);
+        ui.label(tr(label));
         ui.add_enabled(
             true,
             eframe::egui::Slider::new(value, range.clone()).show_value(false),
@@ -233,7 +370,7 @@ pub fn create_f64_slider(
     range: std::ops::RangeInclusive<f64>,
 ) {
     ui.horizontal(|ui| {
-        ui.label(label);
+        ui.label(tr(label));
         ui.add_enabled(
             true,
             eframe::egui::Slider::new(value, range.clone()).show_value(false),
@@ -249,7 +386,7 @@ pub fn create_f64_slider(
 
 ///Generate the common UI to show messages
 pub fn show_logs(ui: &mut eframe::egui::Ui, messages: &mut Vec<String>) {
-    if ui.button("Clear Messages").clicked() {
+    if ui.button(tr("Clear Messages")).clicked() {
         messages.clear();
     }
     let text_style = eframe::egui::TextStyle::Body;
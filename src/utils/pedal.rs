@@ -0,0 +1,405 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::tr, midiinput::MidiInput, scene::SceneListener, session::Autosave};
+
+use super::{ChannelWatchdog, CommonError, ConnectionType, JackStatus};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "pedal";
+///The highest value of a midi controller
+const MAX_VALUE: u8 = 127;
+///The midi control of the expression pedals
+const EXPRESSION_CONTROL: u8 = 11;
+
+///How the values sent by an expression pedal are rescaled
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct PedalConfiguration {
+    ///The midi control sent by the pedal
+    control: u8,
+    ///The lowest value actually sent by the pedal
+    min: u8,
+    ///The highest value actually sent by the pedal
+    max: u8,
+    ///Bend the response (negative: high values sooner, positive: high values
+    /// later, 0: linear)
+    curve: f64,
+}
+
+impl Default for PedalConfiguration {
+    fn default() -> Self {
+        Self {
+            control: EXPRESSION_CONTROL,
+            min: 0,
+            max: MAX_VALUE,
+            curve: 0.0,
+        }
+    }
+}
+
+impl PedalConfiguration {
+    ///Rescale a value sent by the pedal to the full midi range. The value is
+    /// left unchanged until a range has been learned.
+    fn calibrate(&self, value: u8) -> u8 {
+        if self.max <= self.min {
+            return value;
+        }
+        let normalized =
+            (value.clamp(self.min, self.max) - self.min) as f64 / (self.max - self.min) as f64;
+        let curved = normalized.powf(4.0_f64.powf(self.curve));
+        (curved * MAX_VALUE as f64).round() as u8
+    }
+
+    ///Widen the learned range with a value sent by the pedal. Return true if
+    /// the range changed.
+    fn learn(&mut self, value: u8) -> bool {
+        let range = (self.min, self.max);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        range != (self.min, self.max)
+    }
+}
+
+struct Pedal {
+    configuration: PedalConfiguration,
+    ///If true, the range of the pedal is learned from the values received
+    learning: bool,
+    /// The midi input, forwarded to the output
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The midi output, with the rescaled pedal
+    midi_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToPedal>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToPedalUI>,
+}
+
+impl Pedal {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToPedal>,
+        messages_out: std::sync::mpsc::Sender<MessageToPedalUI>,
+    ) -> Result<Pedal, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let m_out = match client.register_port("midi_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        Ok(Pedal {
+            configuration: PedalConfiguration::default(),
+            learning: false,
+            midi_in: m_in,
+            midi_out: m_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+
+    fn send_message(
+        msg: MessageToPedalUI,
+        messages_out: &mut std::sync::mpsc::Sender<MessageToPedalUI>,
+    ) {
+        if let Err(e) = messages_out.send(msg) {
+            eprintln!("Internal error: {e}");
+        }
+    }
+}
+
+impl jack::ProcessHandler for Pedal {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToPedal::NewConfiguration(conf) => self.configuration = conf,
+                MessageToPedal::Learn(learning) => {
+                    self.learning = learning;
+                    if learning {
+                        //the range is learned from scratch
+                        self.configuration.min = MAX_VALUE;
+                        self.configuration.max = 0;
+                    }
+                }
+            }
+        }
+
+        let mut writer = self.midi_out.writer(ps);
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            let result = match midi {
+                MidiInput::Controller {
+                    channel,
+                    control,
+                    value,
+                } if control == self.configuration.control => {
+                    if self.learning && self.configuration.learn(value) {
+                        Self::send_message(
+                            MessageToPedalUI::Learned(
+                                self.configuration.min,
+                                self.configuration.max,
+                            ),
+                            &mut self.messages_out,
+                        );
+                    }
+                    let mut bytes = [0; 3];
+                    let mut raw = MidiInput::Controller {
+                        channel,
+                        control,
+                        value: self.configuration.calibrate(value),
+                    }
+                    .to_raw(&mut bytes);
+                    raw.time = e.time;
+                    writer.write(&raw)
+                }
+                _ => writer.write(&e),
+            };
+            if let Err(e) = result {
+                Self::send_message(
+                    MessageToPedalUI::Message(format!("Unable to forward {:?}: {e}", midi)),
+                    &mut self.messages_out,
+                );
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToPedal {
+    NewConfiguration(PedalConfiguration),
+    ///Start (true) or stop (false) learning the range of the pedal
+    Learn(bool),
+}
+
+#[derive(Debug)]
+enum MessageToPedalUI {
+    Message(String),
+    ///The lowest and highest values sent by the pedal while learning
+    Learned(u8, u8),
+}
+
+struct PedalUI {
+    messages: Vec<String>,
+    conf: PedalConfiguration,
+    ///If true, the range of the pedal is being learned
+    learning: bool,
+    messages_in: std::sync::mpsc::Receiver<MessageToPedalUI>,
+    messages_out: std::sync::mpsc::Sender<MessageToPedal>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The calibration saved for crash recovery
+    session: Autosave<PedalConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl PedalUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToPedalUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToPedal>,
+    ) -> PedalUI {
+        PedalUI {
+            status,
+            messages: Vec::with_capacity(16),
+            conf: PedalConfiguration::default(),
+            learning: false,
+            messages_in,
+            messages_out,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToPedalUI::Message(msg) => self.messages.push(msg),
+                MessageToPedalUI::Learned(min, max) => {
+                    self.conf.min = min;
+                    self.conf.max = max;
+                }
+            }
+        }
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.set_learning(false);
+            self.send_message(MessageToPedal::NewConfiguration(self.conf.clone()));
+        }
+    }
+
+    fn set_learning(&mut self, learning: bool) {
+        if self.learning != learning {
+            self.learning = learning;
+            self.send_message(MessageToPedal::Learn(learning));
+        }
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        let mut control = self.conf.control as usize;
+        crate::utils::create_usize_slider(
+            ui,
+            "Pedal control: ",
+            &mut control,
+            RangeInclusive::new(0, MAX_VALUE as usize),
+        );
+        self.conf.control = control as u8;
+
+        if ui
+            .button(tr(if self.learning {
+                "Stop learning"
+            } else {
+                "Learn the range"
+            }))
+            .clicked()
+        {
+            self.set_learning(!self.learning);
+        }
+        ui.add_enabled_ui(!self.learning, |ui| {
+            crate::utils::create_u8_slider(ui, "Lowest value: ", &mut self.conf.min);
+            crate::utils::create_u8_slider(ui, "Highest value: ", &mut self.conf.max);
+        });
+        self.conf.min = self.conf.min.min(MAX_VALUE);
+        self.conf.max = self.conf.max.min(MAX_VALUE);
+        crate::utils::create_f64_slider(
+            ui,
+            "Curve: ",
+            &mut self.conf.curve,
+            RangeInclusive::new(-1.0, 1.0),
+        );
+
+        if self.conf != current_conf {
+            self.send_message(MessageToPedal::NewConfiguration(self.conf.clone()));
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_message(&mut self, msg: MessageToPedal) {
+        if let Err(e) = self.messages_out.send(msg) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for PedalUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn pedal() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the pedal and the UI
+    let (send_to_pedal, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_pedal) = std::sync::mpsc::channel();
+
+    let util = Pedal::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Pedal",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 480.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(PedalUI::new(
+                cc,
+                status,
+                rcv_from_pedal,
+                send_to_pedal,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PedalConfiguration;
+
+    #[test]
+    fn calibration() {
+        let mut conf = PedalConfiguration {
+            control: 11,
+            min: 10,
+            max: 110,
+            curve: 0.0,
+        };
+        assert_eq!(conf.calibrate(10), 0);
+        assert_eq!(conf.calibrate(0), 0);
+        assert_eq!(conf.calibrate(60), 64);
+        assert_eq!(conf.calibrate(110), 127);
+        assert_eq!(conf.calibrate(127), 127);
+        conf.curve = 0.5;
+        assert!(conf.calibrate(60) < 64);
+        assert_eq!(conf.calibrate(110), 127);
+        conf.max = conf.min;
+        assert_eq!(conf.calibrate(60), 60);
+
+        conf.min = 127;
+        conf.max = 0;
+        assert!(conf.learn(20));
+        assert!(conf.learn(100));
+        assert!(!conf.learn(50));
+        assert_eq!((conf.min, conf.max), (20, 100));
+    }
+}
@@ -0,0 +1,175 @@
+use eframe::egui::{self, Color32, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::{i18n::tr, midiinput::MidiInput};
+
+///The number of octaves shown
+const NB_OCTAVES: usize = 3;
+///The number of white keys of an octave
+const WHITE_KEYS: usize = 7;
+///The half-steps of the white keys, from C
+const WHITE_SEMITONES: [usize; WHITE_KEYS] = [0, 2, 4, 5, 7, 9, 11];
+///The half-step of the black key on the right of every white key, if any
+const BLACK_SEMITONES: [Option<usize>; WHITE_KEYS] =
+    [Some(1), Some(3), None, Some(6), Some(8), Some(10), None];
+///The width of the black keys, relative to the white ones
+const BLACK_WIDTH: f32 = 0.6;
+///The height of the black keys, relative to the white ones
+const BLACK_HEIGHT: f32 = 0.6;
+///The height of the keyboard
+const PIANO_HEIGHT: f32 = 60.0;
+///The velocity of the notes played with the mouse
+const VELOCITY: f64 = 100.0 / 127.0;
+///The index of the lowest C that can be shown
+const MIN_FIRST_NOTE: usize = 12;
+///The index of the highest C that can be shown
+const MAX_FIRST_NOTE: usize = 12 + 120 - 12 * NB_OCTAVES;
+
+const PRESSED: Color32 = Color32::from_rgb(40, 180, 70);
+
+///A clickable piano keyboard, to play notes without midi controller
+pub struct Piano {
+    ///The index of the first C of the keyboard
+    first_note: usize,
+    ///The note held with the mouse
+    pressed: Option<usize>,
+}
+
+impl Default for Piano {
+    fn default() -> Self {
+        Self {
+            first_note: 12 + 48,
+            pressed: None,
+        }
+    }
+}
+
+///The key at the position 'x','y' of a keyboard whose white keys are
+/// 'key_width' wide and 'height' high, in half-steps from its first C
+fn key_at(x: f32, y: f32, key_width: f32, height: f32) -> Option<usize> {
+    let nb_white = NB_OCTAVES * WHITE_KEYS;
+    if x < 0.0 || y < 0.0 || y > height || x >= key_width * nb_white as f32 {
+        return None;
+    }
+    let white = (x / key_width) as usize;
+    if y < height * BLACK_HEIGHT {
+        //the black keys are centered on the border between two white keys
+        let border = (x / key_width).round() as usize;
+        let distance = (x - border as f32 * key_width).abs();
+        if border > 0 && distance < key_width * BLACK_WIDTH / 2.0 {
+            let left = border - 1;
+            if let Some(semitone) = BLACK_SEMITONES[left % WHITE_KEYS] {
+                return Some(12 * (left / WHITE_KEYS) + semitone);
+            }
+        }
+    }
+    Some(12 * (white / WHITE_KEYS) + WHITE_SEMITONES[white % WHITE_KEYS])
+}
+
+impl Piano {
+    ///Show the keyboard and give to 'send' the notes started or ended with the mouse
+    pub fn show<F>(&mut self, ui: &mut egui::Ui, mut send: F)
+    where
+        F: FnMut(MidiInput),
+    {
+        ui.horizontal(|ui| {
+            if ui.button(tr("Octave down")).clicked() && self.first_note > MIN_FIRST_NOTE {
+                self.first_note -= 12;
+            }
+            ui.label(crate::midiinput::note_name(self.first_note));
+            if ui.button(tr("Octave up")).clicked() && self.first_note < MAX_FIRST_NOTE {
+                self.first_note += 12;
+            }
+        });
+
+        let (response, painter) = ui.allocate_painter(
+            Vec2::new(ui.available_width(), PIANO_HEIGHT),
+            Sense::click_and_drag(),
+        );
+        let rect = response.rect;
+        let key_width = rect.width() / (NB_OCTAVES * WHITE_KEYS) as f32;
+
+        let hovered = match response.interact_pointer_pos() {
+            Some(pos) if response.is_pointer_button_down_on() => key_at(
+                pos.x - rect.left(),
+                pos.y - rect.top(),
+                key_width,
+                rect.height(),
+            )
+            .map(|key| self.first_note + key),
+            _ => None,
+        };
+        if hovered != self.pressed {
+            if let Some(note_index) = self.pressed.take() {
+                send(MidiInput::NoteEnd {
+                    channel: 0,
+                    note_index,
+                    timing: 0,
+                    velocity: 0.0,
+                });
+            }
+            if let Some(note_index) = hovered {
+                send(MidiInput::NoteStart {
+                    channel: 0,
+                    note_index,
+                    timing: 0,
+                    velocity: VELOCITY,
+                });
+                self.pressed = Some(note_index);
+            }
+        }
+
+        let stroke = Stroke::new(1.0, Color32::BLACK);
+        for white in 0..NB_OCTAVES * WHITE_KEYS {
+            let note_index =
+                self.first_note + 12 * (white / WHITE_KEYS) + WHITE_SEMITONES[white % WHITE_KEYS];
+            let key = Rect::from_min_size(
+                Pos2::new(rect.left() + white as f32 * key_width, rect.top()),
+                Vec2::new(key_width, rect.height()),
+            );
+            let color = if self.pressed == Some(note_index) {
+                PRESSED
+            } else {
+                Color32::WHITE
+            };
+            painter.rect_filled(key, 0.0, color);
+            painter.rect_stroke(key, 0.0, stroke);
+        }
+        for white in 0..NB_OCTAVES * WHITE_KEYS - 1 {
+            if let Some(semitone) = BLACK_SEMITONES[white % WHITE_KEYS] {
+                let note_index = self.first_note + 12 * (white / WHITE_KEYS) + semitone;
+                let center = rect.left() + (white + 1) as f32 * key_width;
+                let key = Rect::from_min_size(
+                    Pos2::new(center - key_width * BLACK_WIDTH / 2.0, rect.top()),
+                    Vec2::new(key_width * BLACK_WIDTH, rect.height() * BLACK_HEIGHT),
+                );
+                let color = if self.pressed == Some(note_index) {
+                    PRESSED
+                } else {
+                    Color32::BLACK
+                };
+                painter.rect_filled(key, 0.0, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::key_at;
+
+    #[test]
+    fn keys() {
+        //white keys 10 wide and 100 high
+        assert_eq!(key_at(5.0, 90.0, 10.0, 100.0), Some(0));
+        assert_eq!(key_at(15.0, 90.0, 10.0, 100.0), Some(2));
+        //C# on the border between C and D
+        assert_eq!(key_at(11.0, 10.0, 10.0, 100.0), Some(1));
+        //no black key between E and F
+        assert_eq!(key_at(29.0, 10.0, 10.0, 100.0), Some(4));
+        assert_eq!(key_at(31.0, 10.0, 10.0, 100.0), Some(5));
+        //B of the second octave, then outside of the keyboard
+        assert_eq!(key_at(135.0, 90.0, 10.0, 100.0), Some(23));
+        assert_eq!(key_at(215.0, 90.0, 10.0, 100.0), None);
+        assert_eq!(key_at(5.0, 110.0, 10.0, 100.0), None);
+    }
+}
@@ -1,43 +1,277 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use eframe::egui::{self, ViewportBuilder};
 
 use crate::{
+    i18n::tr,
     midiinput::MidiInput,
+    mmc::{MmcCommand, ALL_DEVICES},
+    mtc::{MtcReader, Timecode},
+    spsc::{self, MESSAGES_CAPACITY},
     synth::hardware::{HardWare, KeyBoardKey},
-    utils::{CommonError, ConnectionType},
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus},
+    wavreader::{self, WavReadError},
+    wavwriter::{self, Dither, DitherMode, WavStream},
+};
+
+use super::{
+    meters::{self, Level},
+    KeyBoardKeySetter, ShortcutAction, Shortcuts,
 };
 
-use super::KeyBoardKeySetter;
+///The number of level measures sent to the user interface every second
+const LEVELS_PER_SECOND: usize = 20;
+///The number of level measures shown in the waveform strip
+const STRIP_LENGTH: usize = 5 * LEVELS_PER_SECOND;
+///The duration of audio, in seconds, written to the disk at once. At most
+/// this duration is lost if the program stops unexpectedly.
+const SYNC_SECONDS: usize = 1;
+///The number of sample buffers passed between the audio thread and the
+/// thread writing the recordings, so that the audio thread never allocates
+const RECORD_BUFFERS: usize = 4;
+///The delay between two checks of the samples to write, in milliseconds
+const WRITER_POLL_MS: u64 = 10;
+///The height of the list of takes, in points
+const TAKES_HEIGHT: f32 = 200.0;
+///The number of columns of the overviews of the takes
+const OVERVIEW_COLUMNS: usize = 200;
+///The fraction of a second without time code after which the device sending
+/// it is considered stopped
+const CHASE_TIMEOUT_DIVISOR: usize = 4;
+
+///The messages sent to the thread writing the recordings
+#[derive(Debug)]
+enum TakeMessage {
+    ///Create a new file, recorded with the given latency compensation
+    Start {
+        prefix: Arc<str>,
+        rate: u32,
+        latency: u32,
+    },
+    ///Append samples to the current file
+    Samples(Vec<i16>),
+    ///Mark a position of the current take, in frames
+    Marker(usize),
+    ///Complete the current file
+    Stop,
+}
+
+///Write the recordings to the disk, outside of the audio thread. The file
+/// is synchronized after every block of samples, so that a take survives a
+/// crash of the program or of the server. The buffers of samples are given
+/// back to the audio thread once written.
+fn write_takes(
+    takes: spsc::Receiver<TakeMessage>,
+    buffers: spsc::Sender<Vec<i16>>,
+    reports: std::sync::mpsc::Sender<TakeReport>,
+) {
+    let mut stream: Option<WavStream> = None;
+    //the markers of the current take
+    let mut markers = Vec::new();
+    let report = |message: TakeReport| {
+        if let Err(e) = reports.send(message) {
+            println!("Error: {e}");
+        }
+    };
+    let finish = |stream: WavStream, markers: Vec<usize>| {
+        let path = stream.path().to_path_buf();
+        match stream.finish() {
+            Ok(()) => report(TakeReport::Saved { path, markers }),
+            Err(e) => report(TakeReport::Error(format!(
+                "Error while saving the wav file: {e}"
+            ))),
+        }
+    };
+    loop {
+        let take = match takes.try_recv() {
+            Ok(take) => take,
+            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                std::thread::sleep(std::time::Duration::from_millis(WRITER_POLL_MS));
+                continue;
+            }
+            //the recorder was dropped
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
+        };
+        match take {
+            TakeMessage::Start {
+                prefix,
+                rate,
+                latency,
+            } => {
+                if let Some(previous) = stream.take() {
+                    finish(previous, std::mem::take(&mut markers));
+                }
+                markers.clear();
+                match WavStream::create(rate, Some(&prefix), latency) {
+                    Ok(new_stream) => stream = Some(new_stream),
+                    Err(e) => report(TakeReport::Error(format!(
+                        "Error while saving the wav file: {e}"
+                    ))),
+                }
+            }
+            TakeMessage::Samples(mut samples) => {
+                if let Some(current) = stream.as_mut() {
+                    if let Err(e) = current.append(&samples).and_then(|_| current.sync()) {
+                        report(TakeReport::Error(format!(
+                            "Error while saving the wav file: {e}"
+                        )));
+                    }
+                }
+                samples.clear();
+                //the recorder may already be gone
+                let _ = buffers.send(samples);
+            }
+            TakeMessage::Marker(position) => markers.push(position),
+            TakeMessage::Stop => {
+                if let Some(current) = stream.take() {
+                    finish(current, std::mem::take(&mut markers));
+                }
+            }
+        }
+    }
+    if let Some(current) = stream.take() {
+        finish(current, markers);
+    }
+}
+
+///The parts of a take between its markers, as ranges of frames. The parts
+/// follow each other, so that no frame is lost nor repeated.
+fn segments(markers: &[usize], frames: usize) -> Vec<std::ops::Range<usize>> {
+    let mut cuts: Vec<usize> = markers
+        .iter()
+        .copied()
+        .filter(|m| *m > 0 && *m < frames)
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    let mut start = 0;
+    let mut segments = Vec::with_capacity(cuts.len() + 1);
+    for cut in cuts.into_iter().chain(std::iter::once(frames)) {
+        segments.push(start..cut);
+        start = cut;
+    }
+    segments
+}
+
+///Split a take in one file per segment between its markers, named after the
+/// take followed by the number of the segment. Return the number of files.
+fn export_segments(path: &Path, markers: &[usize]) -> Result<usize, WavReadError> {
+    let audio = wavreader::load_wav(path)?;
+    //the takes are written with 16 bits, converting them back is exact
+    let samples: Vec<i16> = audio
+        .to_mono()
+        .iter()
+        .map(|s| {
+            (s * 32768.0)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        })
+        .collect();
+    let stem = path.with_extension("");
+    let segments = segments(markers, samples.len());
+    for (index, segment) in segments.iter().enumerate() {
+        let mut name = stem.clone().into_os_string();
+        name.push(format!("-{}.wav", index + 1));
+        wavwriter::write_wav(Path::new(&name), &samples[segment.clone()], audio.rate)?;
+    }
+    Ok(segments.len())
+}
+
+///The reports of the threads writing the files, shown by the user interface
+#[derive(Debug)]
+enum TakeReport {
+    Error(String),
+    ///A take is complete, with the positions of its markers in frames
+    Saved {
+        path: PathBuf,
+        markers: Vec<usize>,
+    },
+    ///The segments of a take were exported
+    Exported {
+        path: PathBuf,
+        files: usize,
+    },
+    ///A take was read, to be played
+    Loaded(Arc<[f32]>),
+    ///The overview of a take was computed
+    Overview {
+        path: PathBuf,
+        overview: Vec<(f32, f32)>,
+    },
+}
 
 struct Recorder {
     /// If false, the recorder will not listen to record events
     active: bool,
     /// The midi input to activate the recording
     midi_in: jack::Port<jack::MidiIn>,
+    /// The machine control commands sent when the recording starts or stops,
+    /// so that other recorders follow
+    mmc_out: jack::Port<jack::MidiOut>,
+    ///The machine control command to send at the end of the cycle
+    mmc_pending: Option<MmcCommand>,
+    ///The reader of the midi time code sent by another device
+    mtc: MtcReader,
+    ///If true, the recording follows the midi time code: it runs while the
+    /// time code is received
+    chase: bool,
+    ///If true, the current recording was started by the time code
+    chasing: bool,
+    ///The number of frames since the last time code
+    since_timecode: usize,
     /// The input audio port
     audio_mono_in: jack::Port<jack::AudioIn>,
+    /// The output playing a take
+    preview_out: jack::Port<jack::AudioOut>,
+    /// The take being played, if any
+    preview: Option<Arc<[f32]>>,
+    /// The position of the next frame of the take to play
+    preview_position: usize,
     ///The sample rate of the audio
     rate: usize,
-    ///The buffer containing the current recording
+    ///The samples recorded since the last ones sent to the writing thread
     record_buffer: Vec<i16>,
+    ///The reduction of the samples to 16 bits
+    dither: Dither,
+    ///The buffers given back by the writing thread
+    buffers: spsc::Receiver<Vec<i16>>,
+    ///The thread writing the recordings
+    takes: spsc::Sender<TakeMessage>,
     ///If true, we are currently recording
     recording: bool,
+    ///The number of frames recorded in the current take
+    take_frames: usize,
+    ///The number of frames still to skip at the start of the take: the
+    /// latency of the input, so that the take starts with the audio played
+    /// when the recording started
+    skip_frames: usize,
     ///The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToRecorder>,
+    messages_in: ChannelWatchdog<MessageToRecorder, spsc::Receiver<MessageToRecorder>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToRecorderUI>,
+    messages_out: spsc::Sender<MessageToRecorderUI>,
     ///The prefix of the audio file
-    audio_prefix: String,
+    audio_prefix: Arc<str>,
     ///If true, the next control will be used as key to start/stop the recording
     key_change: bool,
     ///The keyboard events we are listening to
     keyboard: HardWare,
+    ///The level of the input since the last one sent to the UI
+    level: Level,
+    ///The number of frames measured in the level
+    level_frames: usize,
 }
 
 impl Recorder {
     pub fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToRecorder>,
-        messages_out: std::sync::mpsc::Sender<MessageToRecorderUI>,
+        messages_in: spsc::Receiver<MessageToRecorder>,
+        messages_out: spsc::Sender<MessageToRecorderUI>,
+        takes: spsc::Sender<TakeMessage>,
+        buffers: spsc::Receiver<Vec<i16>>,
     ) -> Result<Recorder, CommonError> {
         let sample_rate = client.sample_rate();
         let a_in = match client.register_port("music_in", jack::AudioIn::default()) {
@@ -48,58 +282,193 @@ impl Recorder {
             Ok(v) => v,
             Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
         };
+        let mmc_out = match client.register_port("mmc_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+        let preview_out = match client.register_port("preview_out", jack::AudioOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::AudioOut, e)),
+        };
 
         Ok(Recorder {
             active: true,
             rate: sample_rate,
             midi_in: m_in,
+            mmc_out,
+            mmc_pending: None,
+            mtc: MtcReader::default(),
+            chase: false,
+            chasing: false,
+            since_timecode: 0,
             audio_mono_in: a_in,
-            record_buffer: Vec::with_capacity(sample_rate * 60 * 4), //have a buffer for 4 min of music
+            preview_out,
+            preview: None,
+            preview_position: 0,
+            record_buffer: Vec::with_capacity(2 * sample_rate * SYNC_SECONDS),
+            dither: Dither::new(DitherMode::Tpdf),
+            buffers,
+            takes,
             recording: false,
-            messages_in,
+            take_frames: 0,
+            skip_frames: 0,
+            messages_in: ChannelWatchdog::new("recorder", messages_in),
             messages_out,
-            audio_prefix: String::from("Rec"),
+            audio_prefix: Arc::from("Rec"),
             key_change: false,
             keyboard: HardWare::new(),
+            level: Level::default(),
+            level_frames: 0,
         })
     }
+
+    ///Stop playing the take. It is given back to the user interface, so that
+    /// the audio thread never frees it.
+    fn release_preview(&mut self) {
+        if let Some(take) = self.preview.take() {
+            if let Err(e) = self
+                .messages_out
+                .send(MessageToRecorderUI::PreviewReleased(take))
+            {
+                println!("Error: {e}");
+            }
+        }
+    }
+
+    ///Play the next frames of the take, if any
+    fn play_preview(&mut self, ps: &jack::ProcessScope) {
+        let out = self.preview_out.as_mut_slice(ps);
+        out.fill(0.0);
+        let Some(take) = &self.preview else {
+            return;
+        };
+        let start = self.preview_position.min(take.len());
+        let end = (start + out.len()).min(take.len());
+        out[..end - start].copy_from_slice(&take[start..end]);
+        self.preview_position = end;
+        if end == take.len() {
+            self.release_preview();
+            if let Err(e) = self
+                .messages_out
+                .send(MessageToRecorderUI::PreviewPosition(None))
+            {
+                println!("Error: {e}");
+            }
+        }
+    }
+
+    fn send_take(&mut self, take: TakeMessage) {
+        if let Err(e) = self.takes.send(take) {
+            println!("Error while saving the wav file: {e}");
+        }
+    }
+
+    ///Send the samples recorded so far to the writing thread, and continue
+    /// in a buffer it gave back. Only when it is late, the new buffer has to
+    /// grow while recording.
+    fn send_samples(&mut self) {
+        let buffer = self.buffers.try_recv().unwrap_or_default();
+        let samples = std::mem::replace(&mut self.record_buffer, buffer);
+        self.send_take(TakeMessage::Samples(samples));
+    }
+
+    fn start_recording(&mut self) {
+        self.recording = true;
+        self.mmc_pending = Some(MmcCommand::RecordStrobe);
+        self.record_buffer.clear();
+        self.take_frames = 0;
+        self.skip_frames = self
+            .audio_mono_in
+            .get_latency_range(jack::LatencyType::Capture)
+            .1 as usize;
+        self.send_take(TakeMessage::Start {
+            prefix: self.audio_prefix.clone(),
+            rate: self.rate as u32,
+            latency: self.skip_frames as u32,
+        });
+    }
+
+    fn stop_recording(&mut self) {
+        self.recording = false;
+        self.chasing = false;
+        self.mmc_pending = Some(MmcCommand::RecordExit);
+        self.send_samples();
+        self.send_take(TakeMessage::Stop);
+    }
 }
 
 impl jack::ProcessHandler for Recorder {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToRecorder::StartRecording => {
                     if !self.recording && self.active {
-                        self.recording = true
+                        self.start_recording();
                     }
                 }
                 MessageToRecorder::StopRecordeing => {
                     if self.recording && self.active {
-                        self.recording = false;
-                        let mut tmp_buf = Vec::<i16>::with_capacity(self.record_buffer.capacity());
-                        std::mem::swap(&mut self.record_buffer, &mut tmp_buf);
-                        if let Err(e) = crate::wavwriter::save_wav(
-                            tmp_buf,
-                            self.rate as u32,
-                            Some(&self.audio_prefix),
-                        ) {
-                            println!("Error while saving the wav file: {e}");
-                        }
+                        self.stop_recording();
+                    }
+                }
+                MessageToRecorder::NewPrefix(prefix) => {
+                    let prefix = std::mem::replace(&mut self.audio_prefix, prefix);
+                    if let Err(e) = self
+                        .messages_out
+                        .send(MessageToRecorderUI::PrefixReleased(prefix))
+                    {
+                        println!("Error: {e}");
                     }
                 }
-                MessageToRecorder::NewPrefix(prefix) => self.audio_prefix = prefix,
                 MessageToRecorder::ChangeRecord => self.key_change = true,
                 MessageToRecorder::DiscardRecordKey => {
                     self.key_change = false;
                     self.keyboard.clear_key(KeyBoardKey::Record);
                 }
                 MessageToRecorder::Active(value) => self.active = value,
+                MessageToRecorder::Chase(value) => self.chase = value,
+                MessageToRecorder::Dither(mode) => self.dither.set_mode(mode),
+                MessageToRecorder::Marker => {
+                    if self.recording {
+                        self.send_take(TakeMessage::Marker(self.take_frames));
+                    }
+                }
+                MessageToRecorder::Play(take) => {
+                    self.release_preview();
+                    self.preview = Some(take);
+                    self.preview_position = 0;
+                }
+                MessageToRecorder::StopPreview => {
+                    self.release_preview();
+                    if let Err(e) = self
+                        .messages_out
+                        .send(MessageToRecorderUI::PreviewPosition(None))
+                    {
+                        println!("Error: {e}");
+                    }
+                }
             }
         }
 
+        //the recording is started or stopped once the midi events are read
+        let mut toggle = false;
+        //the transport buttons sending machine control commands
+        let mut transport = None;
+        let mut timecode = None;
         let show_p = self.midi_in.iter(ps);
         for e in show_p {
+            if let Some(received) = self.mtc.read(e.bytes) {
+                timecode = Some(received);
+                continue;
+            }
+            if let Some(command) = MmcCommand::parse(e.bytes) {
+                match command.key() {
+                    Some(KeyBoardKey::Record) => transport = Some(true),
+                    Some(KeyBoardKey::Stop) => transport = Some(false),
+                    _ => {}
+                }
+                continue;
+            }
             let midi: MidiInput = e.into();
             match midi {
                 MidiInput::Controller {
@@ -115,47 +484,111 @@ impl jack::ProcessHandler for Recorder {
                             && value > 0
                             && self.active
                         {
-                            if self.recording {
-                                self.recording = false;
-                                if let Err(e) = self
-                                    .messages_out
-                                    .send(MessageToRecorderUI::ShowRecordingStopped)
-                                {
-                                    println!("Error: {e}");
-                                }
-                                let mut tmp_buf =
-                                    Vec::<i16>::with_capacity(self.record_buffer.capacity());
-                                std::mem::swap(&mut self.record_buffer, &mut tmp_buf);
-                                if let Err(e) = crate::wavwriter::save_wav(
-                                    tmp_buf,
-                                    self.rate as u32,
-                                    Some(&self.audio_prefix),
-                                ) {
-                                    println!("Error while saving the wav file: {e}");
-                                }
-                            } else {
-                                self.recording = true;
-                                if let Err(e) = self
-                                    .messages_out
-                                    .send(MessageToRecorderUI::ShowRecordingStarted)
-                                {
-                                    println!("Error: {e}");
-                                }
-                            }
+                            toggle = !toggle;
                         }
                     }
                 }
                 _ => {}
             }
         }
+        if self.chase {
+            if let Some(timecode) = timecode {
+                self.since_timecode = 0;
+                if let Err(e) = self
+                    .messages_out
+                    .send(MessageToRecorderUI::Timecode(timecode))
+                {
+                    println!("Error: {e}");
+                }
+                if !self.recording && self.active {
+                    transport = Some(true);
+                    self.chasing = true;
+                }
+            } else if self.chasing && self.since_timecode > self.rate / CHASE_TIMEOUT_DIVISOR {
+                //the device sending the time code stopped
+                transport = Some(false);
+            }
+            self.since_timecode = self.since_timecode.saturating_add(ps.n_frames() as usize);
+        }
+        if let Some(record) = transport {
+            //the command comes from another device: it is not sent back
+            toggle = self.active && record != self.recording;
+        }
+        if toggle {
+            if self.recording {
+                if let Err(e) = self
+                    .messages_out
+                    .send(MessageToRecorderUI::ShowRecordingStopped)
+                {
+                    println!("Error: {e}");
+                }
+                self.stop_recording();
+            } else {
+                self.start_recording();
+                if let Err(e) = self
+                    .messages_out
+                    .send(MessageToRecorderUI::ShowRecordingStarted)
+                {
+                    println!("Error: {e}");
+                }
+            }
+            if transport.is_some() {
+                self.mmc_pending = None;
+            }
+        }
+        if let Some(command) = self.mmc_pending.take() {
+            let sysex = command.to_sysex(ALL_DEVICES);
+            let raw = jack::RawMidi {
+                time: 0,
+                bytes: &sysex,
+            };
+            if let Err(e) = self.mmc_out.writer(ps).write(&raw) {
+                println!("Error: unable to send the machine control command: {e}");
+            }
+        }
+
+        let audio_in = self.audio_mono_in.as_slice(ps);
+        let level = Level::measure(audio_in);
+        self.level = if self.level_frames == 0 {
+            level
+        } else {
+            self.level.merge(&level)
+        };
+        self.level_frames += audio_in.len();
+        if self.level_frames >= self.rate / LEVELS_PER_SECOND {
+            if let Err(e) = self
+                .messages_out
+                .send(MessageToRecorderUI::Level(self.level))
+            {
+                println!("Error: {e}");
+            }
+            if self.preview.is_some() {
+                if let Err(e) = self
+                    .messages_out
+                    .send(MessageToRecorderUI::PreviewPosition(Some(
+                        self.preview_position,
+                    )))
+                {
+                    println!("Error: {e}");
+                }
+            }
+            self.level_frames = 0;
+        }
 
         if self.recording && self.active {
-            let audio_in = self.audio_mono_in.as_slice(ps);
-            for value in audio_in {
-                let sample = ((value) * 32768.0) as i16;
+            let skipped = self.skip_frames.min(audio_in.len());
+            self.skip_frames -= skipped;
+            for value in &audio_in[skipped..] {
+                let sample = self.dither.quantize(*value);
                 self.record_buffer.push(sample);
             }
+            self.take_frames += audio_in.len() - skipped;
+            super::statistics::frames_recorded(audio_in.len() - skipped);
+            if self.record_buffer.len() >= self.rate * SYNC_SECONDS {
+                self.send_samples();
+            }
         }
+        self.play_preview(ps);
         jack::Control::Continue
     }
 }
@@ -164,10 +597,20 @@ impl jack::ProcessHandler for Recorder {
 enum MessageToRecorder {
     StartRecording,
     StopRecordeing,
-    NewPrefix(String),
+    NewPrefix(Arc<str>),
     ChangeRecord,
     DiscardRecordKey,
     Active(bool),
+    ///Follow the midi time code
+    Chase(bool),
+    ///Change the reduction of the samples to 16 bits
+    Dither(DitherMode),
+    ///Mark the current position of the take
+    Marker,
+    ///Play a take on the preview output
+    Play(Arc<[f32]>),
+    ///Stop playing the take
+    StopPreview,
 }
 
 impl From<KeyBoardKeySetter> for MessageToRecorder {
@@ -183,36 +626,97 @@ impl From<KeyBoardKeySetter> for MessageToRecorder {
 enum MessageToRecorderUI {
     ShowRecordingStarted,
     ShowRecordingStopped,
+    Level(Level),
+    ///The last time code received while following it
+    Timecode(Timecode),
+    ///The position of the take played, in frames, None once stopped
+    PreviewPosition(Option<usize>),
+    ///A take no longer played, to be freed outside of the audio thread
+    PreviewReleased(Arc<[f32]>),
+    ///A prefix replaced, to be freed outside of the audio thread
+    PrefixReleased(Arc<str>),
 }
 
 struct RecorderUI {
-    messages_in: std::sync::mpsc::Receiver<MessageToRecorderUI>,
-    message_out: std::sync::mpsc::Sender<MessageToRecorder>,
+    messages_in: spsc::Receiver<MessageToRecorderUI>,
+    message_out: spsc::Sender<MessageToRecorder>,
+    ///The reports of the threads writing the files
+    reports: std::sync::mpsc::Receiver<TakeReport>,
+    ///The channel given to the threads exporting the segments of a take
+    send_reports: std::sync::mpsc::Sender<TakeReport>,
+    ///The takes saved since the start, with their markers
+    takes: Vec<(PathBuf, Vec<usize>)>,
+    ///The take selected in the list
+    selected: Option<usize>,
+    ///The overviews of the takes, computed once
+    overviews: HashMap<PathBuf, Vec<(f32, f32)>>,
+    ///The sample rate, to read the takes to play
+    rate: usize,
+    ///The number of frames of the take played
+    preview_frames: usize,
+    ///The position of the take played, if any
+    preview_position: Option<usize>,
+    ///The number of markers of the current take
+    markers: usize,
     messages: Vec<String>,
     record_pressed: bool,
     current_prefix: String,
     active: bool,
+    ///If true, the recording follows the midi time code
+    chase: bool,
+    ///The reduction of the samples to 16 bits
+    dither: DitherMode,
+    ///The last time code received
+    timecode: Option<Timecode>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+    ///The last level of the input
+    level: Level,
+    ///The peaks of the last levels, the oldest first
+    peaks: VecDeque<f32>,
 }
 
 impl RecorderUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToRecorderUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToRecorder>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToRecorderUI>,
+        messages_out: spsc::Sender<MessageToRecorder>,
+        reports: std::sync::mpsc::Receiver<TakeReport>,
+        send_reports: std::sync::mpsc::Sender<TakeReport>,
+        rate: usize,
     ) -> RecorderUI {
         RecorderUI {
+            status,
             messages_in,
             message_out: messages_out,
+            reports,
+            send_reports,
+            takes: Vec::new(),
+            selected: None,
+            overviews: HashMap::new(),
+            rate,
+            preview_frames: 0,
+            preview_position: None,
+            markers: 0,
             messages: Vec::new(),
             record_pressed: false,
             current_prefix: String::from(""),
             active: true,
+            chase: false,
+            dither: DitherMode::Tpdf,
+            timecode: None,
+            shortcuts: Shortcuts::load(),
+            level: Level::default(),
+            peaks: VecDeque::with_capacity(STRIP_LENGTH),
         }
     }
 
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("Settings", |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
                 if ui
                     .toggle_value(&mut self.active, String::from("Active"))
                     .clicked()
@@ -220,6 +724,28 @@ impl RecorderUI {
                     self.send_message(MessageToRecorder::Active(self.active));
                     ui.close_menu();
                 }
+                if ui
+                    .toggle_value(&mut self.chase, tr("Follow the midi time code"))
+                    .clicked()
+                {
+                    self.send_message(MessageToRecorder::Chase(self.chase));
+                    ui.close_menu();
+                }
+                ui.menu_button(tr("Dither"), |ui| {
+                    for (mode, name) in [
+                        (DitherMode::Off, "Off"),
+                        (DitherMode::Tpdf, "Triangular noise"),
+                        (DitherMode::NoiseShaped, "Noise shaping"),
+                    ] {
+                        if ui
+                            .selectable_value(&mut self.dither, mode, tr(name))
+                            .clicked()
+                        {
+                            self.send_message(MessageToRecorder::Dither(mode));
+                            ui.close_menu();
+                        }
+                    }
+                });
                 crate::utils::create_keyboard_select(
                     ui,
                     "Keyboard record key",
@@ -227,59 +753,279 @@ impl RecorderUI {
                     &mut self.message_out,
                     &mut self.messages,
                 );
+                self.shortcuts.create_menu(
+                    ui,
+                    &[ShortcutAction::RecordToggle, ShortcutAction::Marker],
+                    &mut self.messages,
+                );
             });
             crate::utils::common_menu_luncher(ui, &mut self.messages);
         });
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
-        let rich_text =
-            egui::RichText::new(format!("{}", if self.record_pressed { "REC" } else { "" }))
-                .color(egui::Color32::from_rgb(180, 19, 60));
+        let rich_text = egui::RichText::new(tr(if self.record_pressed { "REC" } else { "" }))
+            .color(egui::Color32::from_rgb(180, 19, 60));
         let _recording = ui.label(rich_text);
         ui.horizontal(|ui| {
-            ui.label("Recording: ");
+            ui.label(tr("Recording: "));
             if self.record_pressed {
-                if ui.button("In progress").clicked() {
+                if ui.button(tr("In progress")).clicked() {
                     self.record_pressed = false;
                     self.send_message(MessageToRecorder::StopRecordeing);
                 }
             } else {
-                if ui.button("Waiting").clicked() {
+                if ui.button(tr("Waiting")).clicked() {
                     self.record_pressed = true;
+                    self.markers = 0;
                     self.send_message(MessageToRecorder::StartRecording);
                 }
             }
         });
         ui.horizontal(|ui| {
-            ui.label("Audio file prefix: ");
+            ui.label(tr("Audio file prefix: "));
             ui.text_edit_singleline(&mut self.current_prefix);
         });
-        if ui.button("Change prefix").clicked() {
-            self.send_message(MessageToRecorder::NewPrefix(self.current_prefix.clone()));
+        if ui.button(tr("Change prefix")).clicked() {
+            self.send_message(MessageToRecorder::NewPrefix(Arc::from(
+                self.current_prefix.as_str(),
+            )));
         }
+        if self.record_pressed {
+            ui.horizontal(|ui| {
+                if ui.button(tr("Add marker")).clicked() {
+                    self.add_marker();
+                }
+                ui.label(format!("{}{}", tr("Markers: "), self.markers));
+            });
+        }
+        self.create_takes(ui);
+        if let (true, Some(timecode)) = (self.chase, self.timecode) {
+            ui.label(format!("{}{timecode}", tr("Time code: ")));
+        }
+        ui.label(tr("Input level: "));
+        meters::vu_meter(ui, &self.level);
+        meters::waveform_strip(ui, self.peaks.make_contiguous());
         crate::utils::show_logs(ui, &mut self.messages);
     }
 
+    ///The takes of the session, to play them or to export their segments
+    fn create_takes(&mut self, ui: &mut egui::Ui) {
+        if self.takes.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.label(tr("Takes: "));
+        let mut selected = self.selected;
+        egui::ScrollArea::vertical()
+            .max_height(TAKES_HEIGHT)
+            .show(ui, |ui| {
+                for (index, (path, _)) in self.takes.iter().enumerate() {
+                    let name = path.file_name().unwrap_or(path.as_os_str());
+                    if ui
+                        .selectable_label(selected == Some(index), name.to_string_lossy())
+                        .clicked()
+                    {
+                        selected = Some(index);
+                    }
+                    if let Some(overview) = self.overviews.get(path) {
+                        meters::waveform_overview(ui, overview);
+                    }
+                }
+            });
+        self.selected = selected;
+        let markers = self
+            .selected
+            .and_then(|index| self.takes.get(index))
+            .map(|(_, markers)| markers.len());
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(markers.is_some(), egui::Button::new(tr("Play")))
+                .clicked()
+            {
+                self.load_preview();
+            }
+            if ui
+                .add_enabled(
+                    self.preview_position.is_some(),
+                    egui::Button::new(tr("Stop")),
+                )
+                .clicked()
+            {
+                self.send_message(MessageToRecorder::StopPreview);
+            }
+            let segments = markers.unwrap_or(0) + 1;
+            let export = ui.add_enabled(
+                segments > 1,
+                egui::Button::new(format!("{} ({segments})", tr("Export segments"))),
+            );
+            if export.clicked() {
+                self.export_segments();
+            }
+        });
+        if let Some(position) = self.preview_position {
+            let rate = self.rate.max(1) as f32;
+            ui.add(
+                egui::ProgressBar::new(position as f32 / self.preview_frames.max(1) as f32).text(
+                    format!(
+                        "{:.1} / {:.1} s",
+                        position as f32 / rate,
+                        self.preview_frames as f32 / rate
+                    ),
+                ),
+            );
+        }
+    }
+
+    ///Compute the overview of a take in a thread of its own
+    fn load_overview(&mut self, path: PathBuf) {
+        if self.overviews.contains_key(&path) {
+            return;
+        }
+        let reports = self.send_reports.clone();
+        std::thread::spawn(move || {
+            let report = match wavreader::load_wav(&path) {
+                Ok(audio) => TakeReport::Overview {
+                    overview: meters::overview(&audio.to_mono(), OVERVIEW_COLUMNS),
+                    path,
+                },
+                Err(e) => TakeReport::Error(format!("Unable to read '{}': {e}", path.display())),
+            };
+            //the user interface may already be closed
+            let _ = reports.send(report);
+        });
+    }
+
+    ///Read the selected take in a thread of its own, it is played once read
+    fn load_preview(&mut self) {
+        let Some((path, _)) = self.selected.and_then(|index| self.takes.get(index)) else {
+            return;
+        };
+        let path = path.clone();
+        let rate = self.rate;
+        let reports = self.send_reports.clone();
+        std::thread::spawn(move || {
+            let report = match wavreader::load_mono(&path, rate) {
+                Ok(samples) => TakeReport::Loaded(Arc::from(samples)),
+                Err(e) => TakeReport::Error(format!("Unable to read '{}': {e}", path.display())),
+            };
+            //the user interface may already be closed
+            let _ = reports.send(report);
+        });
+    }
+
     fn send_message(&mut self, msg: MessageToRecorder) {
         if let Err(e) = self.message_out.send(msg) {
             self.messages.push(format!("Internal error: {e}"));
         }
     }
 
+    fn add_marker(&mut self) {
+        if self.record_pressed {
+            self.markers += 1;
+            self.send_message(MessageToRecorder::Marker);
+        }
+    }
+
+    ///Split the selected take at its markers, in a thread of its own
+    fn export_segments(&mut self) {
+        let Some((path, markers)) = self
+            .selected
+            .and_then(|index| self.takes.get(index))
+            .cloned()
+        else {
+            return;
+        };
+        let reports = self.send_reports.clone();
+        std::thread::spawn(move || {
+            let report = match export_segments(&path, &markers) {
+                Ok(files) => TakeReport::Exported { path, files },
+                Err(e) => TakeReport::Error(format!(
+                    "Unable to export the segments of '{}': {e}",
+                    path.display()
+                )),
+            };
+            //the user interface may already be closed
+            let _ = reports.send(report);
+        });
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            match action {
+                ShortcutAction::RecordToggle => {
+                    self.record_pressed = !self.record_pressed;
+                    if self.record_pressed {
+                        self.markers = 0;
+                    }
+                    self.send_message(if self.record_pressed {
+                        MessageToRecorder::StartRecording
+                    } else {
+                        MessageToRecorder::StopRecordeing
+                    });
+                }
+                ShortcutAction::Marker => self.add_marker(),
+                _ => {}
+            }
+        }
+    }
+
     fn read_input(&mut self) {
+        while let Ok(report) = self.reports.try_recv() {
+            match report {
+                TakeReport::Error(e) => self.messages.push(e),
+                TakeReport::Saved { path, markers } => {
+                    self.load_overview(path.clone());
+                    self.takes.push((path, markers));
+                    self.selected = Some(self.takes.len() - 1);
+                }
+                TakeReport::Loaded(take) => {
+                    self.preview_frames = take.len();
+                    self.preview_position = Some(0);
+                    self.send_message(MessageToRecorder::Play(take));
+                }
+                TakeReport::Overview { path, overview } => {
+                    self.overviews.insert(path, overview);
+                }
+                TakeReport::Exported { path, files } => self
+                    .messages
+                    .push(format!("'{}' exported in {files} files", path.display())),
+            }
+        }
         //read message queue
-        match self.messages_in.try_recv() {
-            Err(e) => match e {
-                std::sync::mpsc::TryRecvError::Empty => {}
-                std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(format!(
-                    "Internal error: lost connection between UI and logic"
-                )),
-            },
-            Ok(v) => match v {
-                MessageToRecorderUI::ShowRecordingStarted => self.record_pressed = true,
-                MessageToRecorderUI::ShowRecordingStopped => self.record_pressed = false,
-            },
+        loop {
+            match self.messages_in.try_recv() {
+                Err(e) => {
+                    match e {
+                        std::sync::mpsc::TryRecvError::Empty => {}
+                        std::sync::mpsc::TryRecvError::Disconnected => self.messages.push(format!(
+                            "Internal error: lost connection between UI and logic"
+                        )),
+                    }
+                    break;
+                }
+                Ok(v) => match v {
+                    MessageToRecorderUI::ShowRecordingStarted => {
+                        self.record_pressed = true;
+                        self.markers = 0;
+                    }
+                    MessageToRecorderUI::ShowRecordingStopped => self.record_pressed = false,
+                    MessageToRecorderUI::Level(level) => {
+                        if self.peaks.len() == STRIP_LENGTH {
+                            self.peaks.pop_front();
+                        }
+                        self.peaks.push_back(level.peak);
+                        self.level = level;
+                    }
+                    MessageToRecorderUI::Timecode(timecode) => self.timecode = Some(timecode),
+                    MessageToRecorderUI::PreviewPosition(position) => {
+                        self.preview_position = position
+                    }
+                    //the take is freed here
+                    MessageToRecorderUI::PreviewReleased(take) => drop(take),
+                    MessageToRecorderUI::PrefixReleased(prefix) => drop(prefix),
+                },
+            }
         }
     }
 }
@@ -292,6 +1038,9 @@ impl eframe::App for RecorderUI {
         ctx.request_repaint();
 
         self.read_input();
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -311,13 +1060,30 @@ pub fn record() -> Result<(), CommonError> {
                 return Err(CommonError::UnableToStartClient(e));
             }
         };
+    crate::storage::set_instance("recorder", client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
-    let synth = Recorder::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), synth) {
+    let (send_takes, rcv_takes) = spsc::channel(MESSAGES_CAPACITY);
+    //the buffers of samples go back and forth between the recorder and the
+    // writing thread, the recorder starts with one of them
+    let (send_buffers, rcv_buffers) = spsc::channel(RECORD_BUFFERS);
+    let buffer_length = 2 * client.sample_rate() * SYNC_SECONDS;
+    for _ in 1..RECORD_BUFFERS {
+        if let Err(e) = send_buffers.send(Vec::with_capacity(buffer_length)) {
+            println!("Error: {e}");
+        }
+    }
+    let (send_reports, rcv_reports) = std::sync::mpsc::channel();
+    let writer_reports = send_reports.clone();
+    let writer = std::thread::spawn(move || write_takes(rcv_takes, send_buffers, writer_reports));
+
+    let rate = client.sample_rate();
+    let synth = Recorder::new(&client, rcv_from_ui, send_to_ui, send_takes, rcv_buffers)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -329,16 +1095,44 @@ pub fn record() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(RecorderUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(RecorderUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+                rcv_reports,
+                send_reports,
+                rate,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
     }
 
     match active_client.deactivate() {
-        Ok(_) => return Ok(()),
+        Ok(_) => {
+            //the recorder is dropped: the writing thread completes the current take
+            if writer.join().is_err() {
+                println!("Error: the thread writing the recordings stopped unexpectedly");
+            }
+            return Ok(());
+        }
         Err(e) => {
             return Err(CommonError::UnableToDeActivateClient(e));
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::segments;
+
+    #[test]
+    fn segments_are_gapless() {
+        assert_eq!(segments(&[], 10), vec![0..10]);
+        //the markers out of the take or at the same position are ignored
+        assert_eq!(segments(&[7, 0, 3, 3, 12], 10), vec![0..3, 3..7, 7..10]);
+    }
+}
@@ -0,0 +1,307 @@
+use eframe::egui::{self, ViewportBuilder};
+
+use crate::{
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::{self, SceneAction},
+    utils::{CommonError, ConnectionType, JackStatus, ShortcutAction, Shortcuts},
+};
+
+struct SceneManager {
+    /// The midi input listening to program changes
+    midi_in: jack::Port<jack::MidiIn>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToSceneManagerUI>,
+}
+
+impl SceneManager {
+    pub fn new(
+        client: &jack::Client,
+        messages_out: std::sync::mpsc::Sender<MessageToSceneManagerUI>,
+    ) -> Result<SceneManager, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+
+        Ok(SceneManager {
+            midi_in: m_in,
+            messages_out,
+        })
+    }
+}
+
+impl jack::ProcessHandler for SceneManager {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::ProgramChange {
+                channel: _,
+                program,
+            } = midi
+            {
+                if let Err(e) = self
+                    .messages_out
+                    .send(MessageToSceneManagerUI::ProgramChange(program))
+                {
+                    eprintln!("Internal error: {e}");
+                }
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToSceneManagerUI {
+    ProgramChange(u8),
+}
+
+struct SceneManagerUI {
+    messages_in: std::sync::mpsc::Receiver<MessageToSceneManagerUI>,
+    messages: Vec<String>,
+    ///The known scenes, sorted alphabetically
+    scenes: Vec<String>,
+    ///The name of the scene to capture
+    new_scene: String,
+    ///The index of the last scene recalled
+    current: Option<usize>,
+    ///The path of the tar archive of the whole setup
+    archive_path: String,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl SceneManagerUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToSceneManagerUI>,
+    ) -> SceneManagerUI {
+        let mut ui = SceneManagerUI {
+            status,
+            messages_in,
+            messages: Vec::new(),
+            scenes: Vec::new(),
+            new_scene: String::new(),
+            current: None,
+            archive_path: String::new(),
+            shortcuts: Shortcuts::load(),
+        };
+        ui.refresh();
+        ui
+    }
+
+    fn refresh(&mut self) {
+        match scene::list() {
+            Ok(scenes) => self.scenes = scenes,
+            Err(e) => self.messages.push(format!("Unable to list scenes: {e}")),
+        }
+    }
+
+    fn send_request(&mut self, action: SceneAction, name: &str) {
+        if let Err(e) = scene::request(action, name) {
+            self.messages
+                .push(format!("Unable to send the scene request: {e}"));
+        }
+    }
+
+    ///Recall the scene at the given index of the list
+    fn recall(&mut self, index: usize) {
+        match self.scenes.get(index).cloned() {
+            Some(name) => {
+                self.current = Some(index);
+                self.send_request(SceneAction::Recall, &name);
+            }
+            None => self.messages.push(format!("No scene number {index}")),
+        }
+    }
+
+    ///Write every preset, mapping, scene and session into the archive
+    fn export_setup(&mut self) {
+        let path = std::path::Path::new(&self.archive_path);
+        match crate::archive::export(path) {
+            Ok(n) => self
+                .messages
+                .push(format!("{n} files exported to '{}'", path.display())),
+            Err(e) => self
+                .messages
+                .push(format!("Unable to export the setup: {e}")),
+        }
+    }
+
+    ///Replace the current setup by the content of the archive
+    fn import_setup(&mut self) {
+        let path = std::path::Path::new(&self.archive_path);
+        match crate::archive::import(path) {
+            Ok(n) => {
+                self.messages
+                    .push(format!("{n} files imported from '{}'", path.display()));
+                self.refresh();
+            }
+            Err(e) => self
+                .messages
+                .push(format!("Unable to import the setup: {e}")),
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("File"), |ui| {
+                if ui.button(tr("Refresh")).clicked() {
+                    self.refresh();
+                    ui.close_menu();
+                };
+                ui.separator();
+                ui.label(tr("Archive (tar file):"));
+                ui.text_edit_singleline(&mut self.archive_path);
+                if ui.button(tr("Export the whole setup")).clicked() {
+                    self.export_setup();
+                    ui.close_menu();
+                }
+                if ui.button(tr("Import a setup")).clicked() {
+                    self.import_setup();
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button(tr("Settings"), |ui| {
+                self.shortcuts.create_menu(
+                    ui,
+                    &[ShortcutAction::PresetNext, ShortcutAction::PresetPrevious],
+                    &mut self.messages,
+                );
+            });
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr("Name: "));
+            ui.text_edit_singleline(&mut self.new_scene);
+            if ui.button(tr("Capture")).clicked() && !self.new_scene.trim().is_empty() {
+                let name = self.new_scene.trim().to_string();
+                self.send_request(SceneAction::Capture, &name);
+                self.new_scene.clear();
+                self.refresh();
+            }
+        });
+        ui.separator();
+        let mut recall = None;
+        let mut capture = None;
+        egui::Grid::new("scenes").striped(true).show(ui, |ui| {
+            for (program, name) in self.scenes.iter().enumerate() {
+                ui.label(format!("{program}"));
+                ui.label(name);
+                if ui.button(tr("Recall")).clicked() {
+                    recall = Some(program);
+                }
+                if ui.button(tr("Update")).clicked() {
+                    capture = Some(name.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(index) = recall {
+            self.recall(index);
+        }
+        if let Some(name) = capture {
+            self.send_request(SceneAction::Capture, &name);
+        }
+        ui.separator();
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            if self.scenes.is_empty() {
+                continue;
+            }
+            let last = self.scenes.len() - 1;
+            match (action, self.current) {
+                (ShortcutAction::PresetNext, Some(current)) if current < last => {
+                    self.recall(current + 1)
+                }
+                (ShortcutAction::PresetNext, _) => self.recall(0),
+                (ShortcutAction::PresetPrevious, Some(current)) if current > 0 => {
+                    self.recall(current - 1)
+                }
+                (ShortcutAction::PresetPrevious, _) => self.recall(last),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_input(&mut self) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToSceneManagerUI::ProgramChange(program) => self.recall(program as usize),
+            }
+        }
+    }
+}
+
+impl eframe::App for SceneManagerUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn scene_manager() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new("scenes", jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance("scenes", client.name());
+
+    //open a message channel for the manager and the UI
+    let (send_to_ui, rcv_from_manager) = std::sync::mpsc::channel();
+
+    let manager = SceneManager::new(&client, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(manager))
+    {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Scenes",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| Ok(Box::new(SceneManagerUI::new(cc, status, rcv_from_manager)))),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
@@ -0,0 +1,490 @@
+use std::path::PathBuf;
+
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    midiinput::MidiInput,
+    scene::{self, SceneAction},
+    storage::{self, StorageError},
+    synth::hardware::{HardWare, KeyBoardKey},
+};
+
+use super::{
+    ChannelWatchdog, CommonError, ConnectionType, JackStatus, KeyBoardKeySetter, ShortcutAction,
+    Shortcuts,
+};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "setlist";
+///The name of the file containing the setlist
+const SETLIST_FILE: &str = "setlist.json";
+
+///A song of the setlist
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Song {
+    ///The name shown to the user
+    name: String,
+    ///The scene recalled when the song starts: it holds the presets, tempo,
+    /// transposition... of every running tool
+    scene: String,
+}
+
+///The songs played during a performance, in order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Setlist {
+    songs: Vec<Song>,
+}
+
+impl Setlist {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(SETLIST_FILE);
+        Ok(path)
+    }
+
+    ///The setlist saved the last time the tool was used, empty by default
+    fn load() -> Result<Setlist, StorageError> {
+        let path = Self::path()?;
+        if path.exists() {
+            storage::load(&path)
+        } else {
+            Ok(Setlist::default())
+        }
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path()?, self)
+    }
+
+    ///The song after the current one. The last song stays the current one,
+    /// so that pressing the footswitch once too often does not restart the set.
+    fn next(&self, current: Option<usize>) -> Option<usize> {
+        match current {
+            _ if self.songs.is_empty() => None,
+            Some(current) => Some((current + 1).min(self.songs.len() - 1)),
+            None => Some(0),
+        }
+    }
+
+    ///The song before the current one, the first song staying the current one
+    fn previous(&self, current: Option<usize>) -> Option<usize> {
+        match current {
+            _ if self.songs.is_empty() => None,
+            Some(current) => Some(current.saturating_sub(1).min(self.songs.len() - 1)),
+            None => Some(0),
+        }
+    }
+
+    ///Exchange a song with the one after it
+    fn move_down(&mut self, index: usize) -> bool {
+        if index + 1 < self.songs.len() {
+            self.songs.swap(index, index + 1);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+///Listen to the footswitches moving through the setlist
+struct SetlistFollower {
+    ///The keyboard events we are listening to
+    keyboard: HardWare,
+    /// The next key to map
+    next_key_map: Option<KeyBoardKey>,
+    /// The midi input of the footswitches
+    midi_in: jack::Port<jack::MidiIn>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<MessageToSetlist>,
+    ///The outgoing messages to the UI
+    messages_out: std::sync::mpsc::Sender<MessageToSetlistUI>,
+}
+
+impl SetlistFollower {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<MessageToSetlist>,
+        messages_out: std::sync::mpsc::Sender<MessageToSetlistUI>,
+    ) -> Result<SetlistFollower, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+
+        Ok(SetlistFollower {
+            keyboard: HardWare::new(),
+            next_key_map: None,
+            midi_in: m_in,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            messages_out,
+        })
+    }
+}
+
+impl jack::ProcessHandler for SetlistFollower {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(message) = self.messages_in.try_recv() {
+            match message {
+                MessageToSetlist::SetKey(key) => self.next_key_map = Some(key),
+                MessageToSetlist::ClearKey(key) => self.keyboard.clear_key(key),
+            }
+        }
+
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            if let MidiInput::Controller { control, value, .. } = midi {
+                if value == 0 {
+                    //the footswitch is released
+                    continue;
+                }
+                if let Some(key) = self.next_key_map.take() {
+                    self.keyboard.update_key(key, control);
+                    continue;
+                }
+                let message = match self.keyboard.get_keyboard_key(control) {
+                    Some(KeyBoardKey::SongNext) => MessageToSetlistUI::Next,
+                    Some(KeyBoardKey::SongPrevious) => MessageToSetlistUI::Previous,
+                    _ => continue,
+                };
+                if let Err(e) = self.messages_out.send(message) {
+                    eprintln!("Internal error: {e}");
+                }
+            }
+        }
+
+        jack::Control::Continue
+    }
+}
+
+#[derive(Debug)]
+enum MessageToSetlist {
+    SetKey(KeyBoardKey),
+    ClearKey(KeyBoardKey),
+}
+
+impl From<KeyBoardKeySetter> for MessageToSetlist {
+    fn from(value: KeyBoardKeySetter) -> Self {
+        match value {
+            KeyBoardKeySetter::Set(k) => MessageToSetlist::SetKey(k),
+            KeyBoardKeySetter::Clear(k) => MessageToSetlist::ClearKey(k),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum MessageToSetlistUI {
+    Next,
+    Previous,
+}
+
+struct SetlistUI {
+    messages: Vec<String>,
+    setlist: Setlist,
+    ///The index of the song being played
+    current: Option<usize>,
+    ///The name of the song to add
+    new_song: String,
+    messages_in: std::sync::mpsc::Receiver<MessageToSetlistUI>,
+    messages_out: std::sync::mpsc::Sender<MessageToSetlist>,
+    ///The computer keyboard shortcuts
+    shortcuts: Shortcuts,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl SetlistUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_in: std::sync::mpsc::Receiver<MessageToSetlistUI>,
+        messages_out: std::sync::mpsc::Sender<MessageToSetlist>,
+    ) -> SetlistUI {
+        let mut messages = Vec::new();
+        let setlist = match Setlist::load() {
+            Ok(setlist) => setlist,
+            Err(e) => {
+                messages.push(format!("Unable to load the setlist: {e}"));
+                Setlist::default()
+            }
+        };
+        SetlistUI {
+            status,
+            messages,
+            setlist,
+            current: None,
+            new_song: String::new(),
+            messages_in,
+            messages_out,
+            shortcuts: Shortcuts::load(),
+        }
+    }
+
+    fn save(&mut self) {
+        if let Err(e) = self.setlist.save() {
+            self.messages
+                .push(format!("Unable to save the setlist: {e}"));
+        }
+    }
+
+    fn send_request(&mut self, action: SceneAction, index: usize) {
+        let Some(song) = self.setlist.songs.get(index) else {
+            self.messages.push(format!("No song number {}", index + 1));
+            return;
+        };
+        if let Err(e) = scene::request(action, &song.scene) {
+            self.messages
+                .push(format!("Unable to send the scene request: {e}"));
+        }
+    }
+
+    ///Start a song: every running tool recalls the scene of the song
+    fn play(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            self.current = Some(index);
+            self.send_request(SceneAction::Recall, index);
+        }
+    }
+
+    fn read_input(&mut self) {
+        while let Ok(m) = self.messages_in.try_recv() {
+            match m {
+                MessageToSetlistUI::Next => self.play(self.setlist.next(self.current)),
+                MessageToSetlistUI::Previous => self.play(self.setlist.previous(self.current)),
+            }
+        }
+    }
+
+    fn read_shortcuts(&mut self, ctx: &egui::Context) {
+        for action in self.shortcuts.pressed(ctx, &mut self.messages) {
+            match action {
+                ShortcutAction::PresetNext => self.play(self.setlist.next(self.current)),
+                ShortcutAction::PresetPrevious => self.play(self.setlist.previous(self.current)),
+                _ => {}
+            }
+        }
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Next song",
+                    KeyBoardKey::SongNext,
+                    &mut self.messages_out,
+                    &mut self.messages,
+                );
+                crate::utils::create_keyboard_select(
+                    ui,
+                    "Previous song",
+                    KeyBoardKey::SongPrevious,
+                    &mut self.messages_out,
+                    &mut self.messages,
+                );
+                self.shortcuts.create_menu(
+                    ui,
+                    &[ShortcutAction::PresetNext, ShortcutAction::PresetPrevious],
+                    &mut self.messages,
+                );
+            });
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(tr("Previous song")).clicked() {
+                self.play(self.setlist.previous(self.current));
+            }
+            if ui.button(tr("Next song")).clicked() {
+                self.play(self.setlist.next(self.current));
+            }
+        });
+        match self.current.and_then(|c| self.setlist.songs.get(c)) {
+            Some(song) => ui.heading(&song.name),
+            None => ui.heading(tr("No song started")),
+        };
+        ui.separator();
+
+        let mut play = None;
+        let mut capture = None;
+        let mut move_down = None;
+        let mut remove = None;
+        let mut edited = false;
+        egui::Grid::new("songs").striped(true).show(ui, |ui| {
+            for (index, song) in self.setlist.songs.iter_mut().enumerate() {
+                ui.label(format!("{}", index + 1));
+                edited |= ui.text_edit_singleline(&mut song.name).lost_focus();
+                edited |= ui
+                    .text_edit_singleline(&mut song.scene)
+                    .on_hover_text(tr("The scene recalled by the song"))
+                    .lost_focus();
+                if ui.button(tr("Play")).clicked() {
+                    play = Some(index);
+                }
+                if ui
+                    .button(tr("Capture"))
+                    .on_hover_text(tr("Store the configuration of the running tools"))
+                    .clicked()
+                {
+                    capture = Some(index);
+                }
+                if ui.button("⬆").clicked() && index > 0 {
+                    move_down = Some(index - 1);
+                }
+                if ui.button("⬇").clicked() {
+                    move_down = Some(index);
+                }
+                if ui.button(tr("Remove")).clicked() {
+                    remove = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+        if edited {
+            self.save();
+        }
+        if let Some(index) = play {
+            self.play(Some(index));
+        }
+        if let Some(index) = capture {
+            self.send_request(SceneAction::Capture, index);
+        }
+        if let Some(index) = move_down {
+            if self.setlist.move_down(index) {
+                //the current song follows its position
+                self.current = match self.current {
+                    Some(c) if c == index => Some(index + 1),
+                    Some(c) if c == index + 1 => Some(index),
+                    c => c,
+                };
+                self.save();
+            }
+        }
+        if let Some(index) = remove {
+            self.setlist.songs.remove(index);
+            self.current = match self.current {
+                Some(c) if c == index => None,
+                Some(c) if c > index => Some(c - 1),
+                c => c,
+            };
+            self.save();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Song: "));
+            ui.text_edit_singleline(&mut self.new_song);
+            if ui.button(tr("Add")).clicked() && !self.new_song.trim().is_empty() {
+                let name = self.new_song.trim().to_string();
+                //the song recalls the scene of the same name, which can be
+                //captured from the list
+                self.setlist.songs.push(Song {
+                    scene: name.clone(),
+                    name,
+                });
+                self.new_song.clear();
+                self.save();
+            }
+        });
+        ui.separator();
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+}
+
+impl eframe::App for SetlistUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input();
+        self.read_shortcuts(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn setlist() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the follower and the UI
+    let (send_to_follower, rcv_from_ui) = std::sync::mpsc::channel();
+    let (send_to_ui, rcv_from_follower) = std::sync::mpsc::channel();
+
+    let follower = SetlistFollower::new(&client, rcv_from_ui, send_to_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client =
+        match client.activate_async(status.notifications(), status.monitor(follower)) {
+            Ok(client) => client,
+            Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+        };
+
+    match eframe::run_native(
+        "Setlist",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(480.0, 640.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| {
+            Ok(Box::new(SetlistUI::new(
+                cc,
+                status,
+                rcv_from_follower,
+                send_to_follower,
+            )))
+        }),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Setlist, Song};
+
+    #[test]
+    fn navigation() {
+        let mut setlist = Setlist::default();
+        assert_eq!(setlist.next(None), None);
+        for name in ["intro", "ballad", "encore"] {
+            setlist.songs.push(Song {
+                name: String::from(name),
+                scene: String::from(name),
+            });
+        }
+        assert_eq!(setlist.next(None), Some(0));
+        assert_eq!(setlist.next(Some(1)), Some(2));
+        assert_eq!(setlist.next(Some(2)), Some(2));
+        assert_eq!(setlist.previous(Some(0)), Some(0));
+        assert_eq!(setlist.previous(Some(2)), Some(1));
+
+        assert!(setlist.move_down(0));
+        assert_eq!(setlist.songs[0].name, "ballad");
+        assert!(!setlist.move_down(2));
+    }
+}
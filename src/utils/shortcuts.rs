@@ -0,0 +1,243 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Instant};
+
+use eframe::egui::{self, Key};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    storage::{self, StorageError},
+};
+
+///The name of the file containing the shortcuts
+const SHORTCUTS_FILE: &str = "shortcuts.json";
+///Taps further apart than this, in seconds, start a new tempo
+const TAP_TIMEOUT: f64 = 2.0;
+///The number of intervals averaged to compute the tempo
+const TAP_AVERAGE: usize = 4;
+
+///The actions that can be triggered from the computer keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ShortcutAction {
+    ///Start or stop the recording
+    RecordToggle,
+    ///Set the tempo from the delay between two key presses
+    TapTempo,
+    ///Stop every sound at once
+    Panic,
+    ///Recall the next scene
+    PresetNext,
+    ///Recall the previous scene
+    PresetPrevious,
+    ///Mark the current position of the recording
+    Marker,
+}
+
+impl std::fmt::Display for ShortcutAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShortcutAction::RecordToggle => write!(f, "Record"),
+            ShortcutAction::TapTempo => write!(f, "Tap tempo"),
+            ShortcutAction::Panic => write!(f, "Panic"),
+            ShortcutAction::PresetNext => write!(f, "Next scene"),
+            ShortcutAction::PresetPrevious => write!(f, "Previous scene"),
+            ShortcutAction::Marker => write!(f, "Marker"),
+        }
+    }
+}
+
+///The keys of the computer keyboard bound to the actions. The shortcuts are
+/// shared by every tool of the suite.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shortcuts {
+    ///The name of the key bound to every action
+    keys: BTreeMap<ShortcutAction, String>,
+    ///If set, the next key pressed will be bound to the action
+    #[serde(skip)]
+    learning: Option<ShortcutAction>,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        let keys = [
+            (ShortcutAction::RecordToggle, Key::R),
+            (ShortcutAction::TapTempo, Key::T),
+            (ShortcutAction::Panic, Key::Escape),
+            (ShortcutAction::PresetNext, Key::ArrowRight),
+            (ShortcutAction::PresetPrevious, Key::ArrowLeft),
+            (ShortcutAction::Marker, Key::M),
+        ];
+        Self {
+            keys: keys
+                .iter()
+                .map(|(action, key)| (*action, String::from(key.name())))
+                .collect(),
+            learning: None,
+        }
+    }
+}
+
+impl Shortcuts {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(SHORTCUTS_FILE);
+        Ok(path)
+    }
+
+    ///Load the shortcuts saved by the user, or the default ones
+    pub fn load() -> Shortcuts {
+        match Self::path() {
+            Ok(path) if path.exists() => storage::load(&path).unwrap_or_default(),
+            _ => Shortcuts::default(),
+        }
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path()?, self)
+    }
+
+    fn bind(&mut self, action: ShortcutAction, key: Option<Key>, messages: &mut Vec<String>) {
+        match key {
+            Some(key) => {
+                //a key triggers a single action
+                self.keys.retain(|_, name| name != key.name());
+                self.keys.insert(action, String::from(key.name()));
+            }
+            None => {
+                self.keys.remove(&action);
+            }
+        }
+        if let Err(e) = self.save() {
+            messages.push(format!("Unable to save the shortcuts: {e}"));
+        }
+    }
+
+    ///Retrieve the actions whose key was pressed since the last frame. Nothing
+    /// is triggered while the user is typing in a text field.
+    pub fn pressed(
+        &mut self,
+        ctx: &egui::Context,
+        messages: &mut Vec<String>,
+    ) -> Vec<ShortcutAction> {
+        if ctx.wants_keyboard_input() {
+            return Vec::new();
+        }
+        let keys: Vec<Key> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|e| match e {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        ..
+                    } => Some(*key),
+                    _ => None,
+                })
+                .collect()
+        });
+        if let Some(action) = self.learning {
+            if let Some(key) = keys.first() {
+                self.learning = None;
+                self.bind(action, Some(*key), messages);
+            }
+            return Vec::new();
+        }
+        self.keys
+            .iter()
+            .filter(|(_, name)| keys.iter().any(|key| key.name() == name.as_str()))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    ///Create the menu to change the keys of the given actions
+    pub fn create_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        actions: &[ShortcutAction],
+        messages: &mut Vec<String>,
+    ) {
+        ui.menu_button(tr("Shortcuts"), |ui| {
+            for action in actions {
+                let key = if self.learning == Some(*action) {
+                    tr("press a key...")
+                } else {
+                    match self.keys.get(action) {
+                        Some(name) => name.as_str(),
+                        None => tr("none"),
+                    }
+                };
+                ui.menu_button(format!("{}: {key}", tr(&action.to_string())), |ui| {
+                    if ui.button(tr("Define...")).clicked() {
+                        self.learning = Some(*action);
+                        ui.close_menu();
+                    }
+                    if ui.button(tr("Clear")).clicked() {
+                        self.bind(*action, None, messages);
+                        ui.close_menu();
+                    }
+                });
+            }
+        });
+    }
+}
+
+///Compute a tempo from the delay between taps
+#[derive(Debug, Default)]
+pub struct TapTempo {
+    ///The time of the last tap
+    last: Option<Instant>,
+    ///The last delays between two taps, in seconds
+    intervals: Vec<f64>,
+}
+
+impl TapTempo {
+    pub fn new() -> TapTempo {
+        TapTempo {
+            last: None,
+            intervals: Vec::with_capacity(TAP_AVERAGE),
+        }
+    }
+
+    ///Register a tap. Return the tempo (in quarters per minute) once there are
+    /// at least two taps close enough to each other.
+    pub fn tap(&mut self) -> Option<f64> {
+        self.tap_at(Instant::now())
+    }
+
+    fn tap_at(&mut self, now: Instant) -> Option<f64> {
+        let last = self.last.replace(now)?;
+        let interval = now.duration_since(last).as_secs_f64();
+        if interval > TAP_TIMEOUT || interval <= 0.0 {
+            self.intervals.clear();
+            return None;
+        }
+        if self.intervals.len() == TAP_AVERAGE {
+            self.intervals.remove(0);
+        }
+        self.intervals.push(interval);
+        let average = self.intervals.iter().sum::<f64>() / self.intervals.len() as f64;
+        Some(60.0 / average)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::TapTempo;
+
+    #[test]
+    fn tap_tempo() {
+        let mut tap = TapTempo::new();
+        let start = Instant::now();
+        assert_eq!(tap.tap_at(start), None);
+        for i in 1..8 {
+            let bpm = tap.tap_at(start + Duration::from_millis(500 * i)).unwrap();
+            assert!((bpm - 120.0).abs() < 1e-6);
+        }
+        //a long pause starts a new tempo
+        assert_eq!(tap.tap_at(start + Duration::from_secs(10)), None);
+        let bpm = tap.tap_at(start + Duration::from_millis(11000)).unwrap();
+        assert!((bpm - 60.0).abs() < 1e-6);
+    }
+}
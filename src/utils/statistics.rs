@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    storage::{self, StorageError},
+};
+
+///The name of the file containing the statistics
+const STATISTICS_FILE: &str = "statistics.json";
+///The delay between two additions of the statistics of a tool to the file
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+///The notes played by the tool since the statistics were last saved
+static NOTES: AtomicU64 = AtomicU64::new(0);
+///The frames recorded by the tool since the statistics were last saved
+static RECORDED_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+///Count a note played by the tool. This is called by the jack thread, which
+/// only increments a counter.
+pub fn note_played() {
+    NOTES.fetch_add(1, Ordering::Relaxed);
+}
+
+///Count the frames recorded by the tool, see [note_played]
+pub fn frames_recorded(frames: usize) {
+    RECORDED_FRAMES.fetch_add(frames as u64, Ordering::Relaxed);
+}
+
+///The use of the suite on this computer. The statistics never leave it:
+/// they are only saved in the user configuration directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Statistics {
+    ///The notes played by the synths
+    notes: u64,
+    ///The duration of the recordings, in seconds
+    recorded: f64,
+    ///The time every tool was running, in seconds
+    uptime: BTreeMap<String, f64>,
+}
+
+impl Statistics {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(STATISTICS_FILE);
+        Ok(path)
+    }
+
+    fn load() -> Statistics {
+        match Self::path() {
+            Ok(path) if path.exists() => storage::load(&path).unwrap_or_default(),
+            _ => Statistics::default(),
+        }
+    }
+
+    ///Add the use of a tool since the statistics were last saved
+    fn add(&mut self, tool: &str, notes: u64, recorded: f64, uptime: f64) {
+        self.notes += notes;
+        self.recorded += recorded;
+        *self.uptime.entry(String::from(tool)).or_default() += uptime;
+    }
+
+    fn draw(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("statistics").striped(true).show(ui, |ui| {
+            ui.label(tr("Notes played"));
+            ui.label(self.notes.to_string());
+            ui.end_row();
+            ui.label(tr("Minutes recorded"));
+            ui.label(format!("{:.1}", self.recorded / 60.0));
+            ui.end_row();
+            for (tool, uptime) in &self.uptime {
+                ui.label(tool);
+                ui.label(format_duration(*uptime));
+                ui.end_row();
+            }
+        });
+    }
+}
+
+///Format a duration in seconds as hours and minutes
+fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    format!("{} h {:02} min", minutes / 60, minutes % 60)
+}
+
+///Add the statistics of a tool to the saved ones once a minute, and when the
+/// tool stops
+#[derive(Debug)]
+pub(super) struct Collector {
+    ///The name of the tool, the running time is counted per tool
+    tool: String,
+    ///The sample rate of the recorded frames
+    rate: usize,
+    ///The last time the statistics were saved
+    flushed: Instant,
+}
+
+impl Collector {
+    pub(super) fn new(tool: &str, rate: usize) -> Collector {
+        Collector {
+            tool: String::from(tool),
+            rate,
+            flushed: Instant::now(),
+        }
+    }
+
+    ///Save the statistics if they were not saved for a while. This is called
+    /// by the user interface.
+    pub(super) fn update(&mut self) {
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let notes = NOTES.swap(0, Ordering::Relaxed);
+        let frames = RECORDED_FRAMES.swap(0, Ordering::Relaxed);
+        let recorded = if self.rate > 0 {
+            frames as f64 / self.rate as f64
+        } else {
+            0.0
+        };
+        let uptime = self.flushed.elapsed().as_secs_f64();
+        self.flushed = Instant::now();
+        //the statistics are a best effort: they are lost rather than
+        // bothering the user if they cannot be saved
+        let _ = Statistics::path().and_then(|path| {
+            storage::update(&path, Statistics::default(), |statistics| {
+                statistics.add(&self.tool, notes, recorded, uptime)
+            })
+        });
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+///The button of the help menu opening the statistics
+pub fn create_statistics_button(ui: &mut egui::Ui) {
+    if ui.button(tr("Statistics")).clicked() {
+        let statistics = Statistics::load();
+        ui.ctx()
+            .data_mut(|d| d.insert_temp(egui::Id::new(STATISTICS_FILE), Some(statistics)));
+        ui.close_menu();
+    }
+}
+
+///Show the statistics in a window of their own, once opened from the help menu
+pub fn show_statistics(ctx: &egui::Context, messages: &mut Vec<String>) {
+    let id = egui::Id::new(STATISTICS_FILE);
+    let Some(statistics) = ctx.data(|d| d.get_temp::<Option<Statistics>>(id)).flatten() else {
+        return;
+    };
+    let mut open = true;
+    let mut shown = Some(statistics);
+    egui::Window::new(tr("Statistics"))
+        .open(&mut open)
+        .show(ctx, |ui| {
+            if let Some(statistics) = &shown {
+                statistics.draw(ui);
+            }
+            ui.label(tr("Saved every minute, on this computer only."));
+            ui.horizontal(|ui| {
+                if ui.button(tr("Refresh")).clicked() {
+                    shown = Some(Statistics::load());
+                }
+                if ui.button(tr("Reset")).clicked() {
+                    let reset = Statistics::path()
+                        .and_then(|path| storage::save(&path, &Statistics::default()));
+                    if let Err(e) = reset {
+                        messages.push(format!("Unable to reset the statistics: {e}"));
+                    }
+                    shown = Some(Statistics::default());
+                }
+            });
+        });
+    if !open {
+        shown = None;
+    }
+    ctx.data_mut(|d| d.insert_temp(id, shown));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_duration, Statistics};
+
+    #[test]
+    fn statistics_per_tool() {
+        let mut statistics = Statistics::default();
+        statistics.add("kick", 10, 0.0, 60.0);
+        statistics.add("recorder", 0, 90.0, 120.0);
+        statistics.add("kick", 5, 0.0, 3600.0);
+        assert_eq!(statistics.notes, 15);
+        assert_eq!(statistics.recorded, 90.0);
+        assert_eq!(statistics.uptime["kick"], 3660.0);
+        assert_eq!(format_duration(statistics.uptime["kick"]), "1 h 01 min");
+        assert_eq!(format_duration(statistics.uptime["recorder"]), "0 h 02 min");
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+
+use crate::i18n::tr;
+
+use super::statistics::Collector;
+
+///The load, in percent, above which the bars turn red: the tool is close to
+/// cause xruns
+const LOAD_WARNING: f32 = 80.0;
+///The width of the load bars, in points
+const LOAD_BAR_WIDTH: f32 = 80.0;
+///The factor applied at every cycle to the highest load of the tool, so that
+/// a short peak stays visible
+const PROCESS_LOAD_DECAY: f32 = 0.99;
+
+///The state of the jack client, written by the jack threads and read by the user interface
+#[derive(Debug, Default)]
+struct SharedStatus {
+    ///The sample rate of the server
+    sample_rate: AtomicUsize,
+    ///The number of frames of the last cycle
+    buffer_size: AtomicU32,
+    ///The DSP load of the server, in percent (bits of a f32)
+    dsp_load: AtomicU32,
+    ///The part of the cycle used by the tool, in percent (bits of a f32)
+    process_load: AtomicU32,
+    ///The number of xruns since the client was started
+    xruns: AtomicUsize,
+    ///Set once the server has shut the client down
+    shutdown: AtomicBool,
+}
+
+///The information shown in the status bar of every tool
+#[derive(Debug, Clone)]
+pub struct JackStatus {
+    ///The name of the jack client
+    client_name: String,
+    shared: Arc<SharedStatus>,
+    ///The usage statistics of the tool, saved while it runs
+    statistics: Arc<Mutex<Collector>>,
+}
+
+impl JackStatus {
+    pub fn new(client: &jack::Client) -> JackStatus {
+        let shared = SharedStatus::default();
+        shared
+            .sample_rate
+            .store(client.sample_rate(), Ordering::Relaxed);
+        shared
+            .buffer_size
+            .store(client.buffer_size(), Ordering::Relaxed);
+        JackStatus {
+            client_name: String::from(client.name()),
+            shared: Arc::new(shared),
+            statistics: Arc::new(Mutex::new(Collector::new(
+                crate::storage::tool_name(client.name()),
+                client.sample_rate(),
+            ))),
+        }
+    }
+
+    ///The notification handler to give to the client when activating it
+    pub fn notifications(&self) -> StatusNotifications {
+        StatusNotifications {
+            shared: self.shared.clone(),
+        }
+    }
+
+    ///Wrap the process handler of a tool so that the load and the buffer size are monitored
+    pub fn monitor<P: jack::ProcessHandler>(&self, handler: P) -> Monitored<P> {
+        Monitored {
+            handler,
+            shared: self.shared.clone(),
+        }
+    }
+
+    ///Show the status bar at the bottom of the window. Must be called before
+    /// the central panel is shown.
+    pub fn show(&self, ctx: &egui::Context) {
+        let sample_rate = self.shared.sample_rate.load(Ordering::Relaxed);
+        let buffer_size = self.shared.buffer_size.load(Ordering::Relaxed);
+        let dsp_load = f32::from_bits(self.shared.dsp_load.load(Ordering::Relaxed));
+        let process_load = f32::from_bits(self.shared.process_load.load(Ordering::Relaxed));
+        let xruns = self.shared.xruns.load(Ordering::Relaxed);
+        if let Ok(mut statistics) = self.statistics.lock() {
+            statistics.update();
+        }
+        let latency = if sample_rate > 0 {
+            1000.0 * buffer_size as f64 / sample_rate as f64
+        } else {
+            0.0
+        };
+        egui::TopBottomPanel::bottom("jack_status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(&self.client_name);
+                ui.separator();
+                if self.shared.shutdown.load(Ordering::Relaxed) {
+                    ui.colored_label(egui::Color32::from_rgb(180, 19, 60), tr("disconnected"));
+                } else {
+                    ui.label(tr("connected"));
+                }
+                ui.separator();
+                ui.label(format!("{sample_rate} Hz"));
+                ui.separator();
+                ui.label(format!("{buffer_size} frames ({latency:.1} ms)"));
+                ui.separator();
+                ui.label(format!("{}{xruns}", tr("xruns: ")));
+                ui.separator();
+                load_bar(ui, dsp_load, format!("DSP {dsp_load:.1} %"))
+                    .on_hover_text(tr("The load of the whole server"));
+                load_bar(
+                    ui,
+                    process_load,
+                    format!("{}{process_load:.1} %", tr("tool: ")),
+                )
+                .on_hover_text(tr("The part of the cycle used by this tool"));
+            });
+        });
+    }
+}
+
+///A small bar showing a load in percent, red when it gets high
+fn load_bar(ui: &mut egui::Ui, load: f32, text: String) -> egui::Response {
+    let mut bar = egui::ProgressBar::new(load / 100.0)
+        .desired_width(LOAD_BAR_WIDTH)
+        .text(text);
+    if load >= LOAD_WARNING {
+        bar = bar.fill(egui::Color32::from_rgb(180, 19, 60));
+    }
+    ui.add(bar)
+}
+
+///The part of a cycle of 'frames' frames, in percent, used by a processing
+/// that lasted 'elapsed'
+fn cycle_load(elapsed: Duration, frames: u32, sample_rate: usize) -> f32 {
+    if frames == 0 || sample_rate == 0 {
+        return 0.0;
+    }
+    let cycle = frames as f64 / sample_rate as f64;
+    (100.0 * elapsed.as_secs_f64() / cycle) as f32
+}
+
+///Count the xruns and detect when the server stops
+pub struct StatusNotifications {
+    shared: Arc<SharedStatus>,
+}
+
+impl jack::NotificationHandler for StatusNotifications {
+    unsafe fn shutdown(&mut self, _status: jack::ClientStatus, _reason: &str) {
+        self.shared.shutdown.store(true, Ordering::Relaxed);
+    }
+
+    fn sample_rate(&mut self, _: &jack::Client, srate: jack::Frames) -> jack::Control {
+        self.shared
+            .sample_rate
+            .store(srate as usize, Ordering::Relaxed);
+        jack::Control::Continue
+    }
+
+    fn xrun(&mut self, _: &jack::Client) -> jack::Control {
+        self.shared.xruns.fetch_add(1, Ordering::Relaxed);
+        jack::Control::Continue
+    }
+}
+
+///A process handler recording the load of the server before processing the audio of a tool
+pub struct Monitored<P> {
+    handler: P,
+    shared: Arc<SharedStatus>,
+}
+
+impl<P: jack::ProcessHandler> jack::ProcessHandler for Monitored<P> {
+    fn process(&mut self, client: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        self.shared
+            .buffer_size
+            .store(ps.n_frames(), Ordering::Relaxed);
+        self.shared
+            .dsp_load
+            .store(client.cpu_load().to_bits(), Ordering::Relaxed);
+        let start = Instant::now();
+        let control = self.handler.process(client, ps);
+        let load = cycle_load(
+            start.elapsed(),
+            ps.n_frames(),
+            self.shared.sample_rate.load(Ordering::Relaxed),
+        );
+        let previous = f32::from_bits(self.shared.process_load.load(Ordering::Relaxed));
+        self.shared.process_load.store(
+            load.max(previous * PROCESS_LOAD_DECAY).to_bits(),
+            Ordering::Relaxed,
+        );
+        control
+    }
+
+    fn buffer_size(&mut self, client: &jack::Client, size: jack::Frames) -> jack::Control {
+        self.handler.buffer_size(client, size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::cycle_load;
+
+    #[test]
+    fn load_of_a_cycle() {
+        //256 frames at 48 kHz last 5.33 ms
+        let load = cycle_load(Duration::from_micros(2667), 256, 48000);
+        assert!((load - 50.0).abs() < 0.1, "{load}");
+        assert_eq!(cycle_load(Duration::from_millis(1), 0, 48000), 0.0);
+    }
+}
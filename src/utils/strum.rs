@@ -0,0 +1,488 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::tr, midiinput::MidiInput, scene::SceneListener, session::Autosave};
+
+use super::{ChannelWatchdog, CommonError, ConnectionType, JackStatus};
+
+///The name under which the settings are stored
+const TOOL_NAME: &str = "strum";
+///The notes started within this duration (in seconds) form a chord
+const CHORD_WINDOW: f64 = 0.02;
+///The longest strum, in seconds
+const MAX_STRUM_TIME: f64 = 0.5;
+///The most notes waiting to be sent
+const MAX_EVENTS: usize = 256;
+
+///The order in which the notes of a chord are played
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+enum StrumDirection {
+    ///From the lowest note to the highest one
+    #[default]
+    Up,
+    ///From the highest note to the lowest one
+    Down,
+    ///Up and down, one chord after the other
+    Alternate,
+}
+
+impl StrumDirection {
+    ///Cycle through the different directions
+    fn cycle(&self) -> StrumDirection {
+        match self {
+            StrumDirection::Up => StrumDirection::Down,
+            StrumDirection::Down => StrumDirection::Alternate,
+            StrumDirection::Alternate => StrumDirection::Up,
+        }
+    }
+}
+
+impl std::fmt::Display for StrumDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrumDirection::Up => write!(f, "Up"),
+            StrumDirection::Down => write!(f, "Down"),
+            StrumDirection::Alternate => write!(f, "Alternate"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StrumConfiguration {
+    ///The time between the first and the last note of a chord, in seconds
+    strum_time: f64,
+    direction: StrumDirection,
+}
+
+impl Default for StrumConfiguration {
+    fn default() -> Self {
+        Self {
+            strum_time: 0.05,
+            direction: StrumDirection::default(),
+        }
+    }
+}
+
+///The note index of a note-on or of a note-off
+fn note_of(midi: &MidiInput) -> Option<usize> {
+    match midi {
+        MidiInput::NoteStart { note_index, .. } | MidiInput::NoteEnd { note_index, .. } => {
+            Some(*note_index)
+        }
+        _ => None,
+    }
+}
+
+///Spread the notes of the chords over time. The times are in frames since
+/// the start of the client.
+struct Strummer {
+    ///The duration of the strum, in frames
+    strum: u64,
+    ///The duration during which the notes form a chord, in frames
+    window: u64,
+    direction: StrumDirection,
+    ///The direction of the next chord, when alternating
+    next_up: bool,
+    ///The notes of the chord being gathered
+    chord: Vec<MidiInput>,
+    ///The end of the chord being gathered
+    chord_end: Option<u64>,
+    ///The note-offs of the notes of the chord being gathered
+    released: Vec<MidiInput>,
+    ///The messages waiting to be sent, sorted by time
+    queue: Vec<(u64, MidiInput)>,
+}
+
+impl Strummer {
+    fn new(rate: usize) -> Strummer {
+        Strummer {
+            strum: 0,
+            window: (CHORD_WINDOW * rate as f64) as u64,
+            direction: StrumDirection::default(),
+            next_up: true,
+            chord: Vec::with_capacity(MAX_EVENTS),
+            chord_end: None,
+            released: Vec::with_capacity(MAX_EVENTS),
+            queue: Vec::with_capacity(MAX_EVENTS),
+        }
+    }
+
+    fn configure(&mut self, configuration: &StrumConfiguration, rate: usize) {
+        self.strum = (configuration.strum_time * rate as f64) as u64;
+        self.direction = configuration.direction;
+    }
+
+    ///Queue a message, after the ones of the same time
+    fn enqueue(&mut self, time: u64, midi: MidiInput) {
+        if self.queue.len() < MAX_EVENTS {
+            let position = self.queue.partition_point(|(t, _)| *t <= time);
+            self.queue.insert(position, (time, midi));
+        }
+    }
+
+    ///Receive a note-on or a note-off at the given time
+    fn push(&mut self, time: u64, midi: MidiInput) {
+        self.close_chord(time + 1);
+        match midi {
+            MidiInput::NoteStart { .. } => {
+                if self.chord_end.is_none() {
+                    self.chord_end = Some(time + self.window);
+                }
+                if self.chord.len() < MAX_EVENTS {
+                    self.chord.push(midi);
+                }
+            }
+            MidiInput::NoteEnd { note_index, .. } => {
+                if self.chord.iter().any(|m| note_of(m) == Some(note_index)) {
+                    //the note has not started yet
+                    if self.released.len() < MAX_EVENTS {
+                        self.released.push(midi);
+                    }
+                } else {
+                    //a note-off cannot be sent before its note-on
+                    let start = self
+                        .queue
+                        .iter()
+                        .filter(|(_, m)| note_of(m) == Some(note_index))
+                        .map(|(t, _)| *t)
+                        .max()
+                        .unwrap_or(time);
+                    self.enqueue(time.max(start), midi);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ///Schedule the notes of the chord gathered
+    fn strum(&mut self, start: u64) {
+        let up = match self.direction {
+            StrumDirection::Up => true,
+            StrumDirection::Down => false,
+            StrumDirection::Alternate => {
+                self.next_up = !self.next_up;
+                !self.next_up
+            }
+        };
+        let mut chord = std::mem::take(&mut self.chord);
+        chord.sort_by_key(|m| note_of(m).unwrap_or_default());
+        if !up {
+            chord.reverse();
+        }
+        let step = self.strum / (chord.len().max(2) as u64 - 1);
+        for (i, midi) in chord.drain(..).enumerate() {
+            self.enqueue(start + i as u64 * step, midi);
+        }
+        self.chord = chord;
+        let mut released = std::mem::take(&mut self.released);
+        for midi in released.drain(..) {
+            self.push(start, midi);
+        }
+        self.released = released;
+    }
+
+    ///Schedule the chord gathered if it ended before 'until'
+    fn close_chord(&mut self, until: u64) {
+        if let Some(end) = self.chord_end {
+            if end < until {
+                self.chord_end = None;
+                self.strum(end);
+            }
+        }
+    }
+
+    ///Retrieve the next note to send before 'until'
+    fn pop(&mut self, until: u64) -> Option<(u64, MidiInput)> {
+        self.close_chord(until);
+        if self.queue.first().is_some_and(|(t, _)| *t < until) {
+            Some(self.queue.remove(0))
+        } else {
+            None
+        }
+    }
+}
+
+struct Strum {
+    strummer: Strummer,
+    /// The midi input with the chords
+    midi_in: jack::Port<jack::MidiIn>,
+    /// The midi output with the strummed chords
+    midi_out: jack::Port<jack::MidiOut>,
+    ///The incoming messages from the UI
+    messages_in: ChannelWatchdog<StrumConfiguration>,
+    ///The audio rate (number of audio frames per seconds)
+    rate: usize,
+    ///The number of frames since the start of the client
+    time: u64,
+}
+
+impl Strum {
+    fn new(
+        client: &jack::Client,
+        messages_in: std::sync::mpsc::Receiver<StrumConfiguration>,
+    ) -> Result<Strum, CommonError> {
+        let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiIn, e)),
+        };
+        let m_out = match client.register_port("midi_out", jack::MidiOut::default()) {
+            Ok(v) => v,
+            Err(e) => return Err(CommonError::ConnectionError(ConnectionType::MidiOut, e)),
+        };
+
+        let rate = client.sample_rate();
+        let mut strummer = Strummer::new(rate);
+        strummer.configure(&StrumConfiguration::default(), rate);
+        Ok(Strum {
+            strummer,
+            midi_in: m_in,
+            midi_out: m_out,
+            messages_in: ChannelWatchdog::new(TOOL_NAME, messages_in),
+            rate,
+            time: 0,
+        })
+    }
+}
+
+impl Strum {
+    ///Write the notes due before 'until', in a cycle starting at 'start'
+    fn send_notes(
+        strummer: &mut Strummer,
+        writer: &mut jack::MidiWriter<'_>,
+        start: u64,
+        until: u64,
+    ) {
+        while let Some((time, midi)) = strummer.pop(until) {
+            let mut bytes = [0; 3];
+            let mut raw = midi.to_raw(&mut bytes);
+            raw.time = time.saturating_sub(start) as jack::Frames;
+            if let Err(e) = writer.write(&raw) {
+                eprintln!("Unable to send {:?}: {e}", midi);
+            }
+        }
+    }
+}
+
+impl jack::ProcessHandler for Strum {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        if let Some(conf) = self.messages_in.try_recv() {
+            self.strummer.configure(&conf, self.rate);
+        }
+
+        let mut writer = self.midi_out.writer(ps);
+        for e in self.midi_in.iter(ps) {
+            let midi: MidiInput = e.into();
+            let time = self.time + e.time as u64;
+            match midi {
+                MidiInput::NoteStart { .. } | MidiInput::NoteEnd { .. } => {
+                    self.strummer.push(time, midi)
+                }
+                _ => {
+                    //the other messages are forwarded as they are, after the
+                    // notes that are due
+                    Self::send_notes(&mut self.strummer, &mut writer, self.time, time);
+                    if let Err(err) = writer.write(&e) {
+                        eprintln!("Unable to forward {:?}: {err}", midi);
+                    }
+                }
+            }
+        }
+
+        let end = self.time + ps.n_frames() as u64;
+        Self::send_notes(&mut self.strummer, &mut writer, self.time, end);
+        self.time = end;
+
+        jack::Control::Continue
+    }
+}
+
+struct StrumUI {
+    messages: Vec<String>,
+    conf: StrumConfiguration,
+    messages_out: std::sync::mpsc::Sender<StrumConfiguration>,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The configuration saved for crash recovery
+    session: Autosave<StrumConfiguration>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
+}
+
+impl StrumUI {
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        status: JackStatus,
+        messages_out: std::sync::mpsc::Sender<StrumConfiguration>,
+    ) -> StrumUI {
+        StrumUI {
+            status,
+            messages: Vec::with_capacity(16),
+            conf: StrumConfiguration::default(),
+            messages_out,
+            scenes: SceneListener::new(),
+            session: Autosave::new(TOOL_NAME),
+        }
+    }
+
+    fn read_input(&mut self, ctx: &egui::Context) {
+        let session = self.session.update(&self.conf, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(conf) = self
+            .scenes
+            .update(TOOL_NAME, &self.conf, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.conf = conf;
+            self.send_configuration();
+        }
+    }
+
+    fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
+        let current_conf = self.conf.clone();
+
+        crate::utils::create_f64_slider(
+            ui,
+            "Strum time (s): ",
+            &mut self.conf.strum_time,
+            RangeInclusive::new(0.0, MAX_STRUM_TIME),
+        );
+        ui.horizontal(|ui| {
+            ui.label(tr("Direction: "));
+            if ui.button(format!("{}", self.conf.direction)).clicked() {
+                self.conf.direction = self.conf.direction.cycle();
+            }
+        });
+
+        if self.conf != current_conf {
+            self.send_configuration();
+        }
+
+        crate::utils::show_logs(ui, &mut self.messages);
+    }
+
+    fn create_menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            crate::utils::common_menu_luncher(ui, &mut self.messages);
+        });
+    }
+
+    fn send_configuration(&mut self) {
+        if let Err(e) = self.messages_out.send(self.conf.clone()) {
+            self.messages.push(format!("Internal error: {e}"));
+        }
+    }
+}
+
+impl eframe::App for StrumUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        //the following line should only occur if a repaint is really needed.
+        //To do this, we need to revise the architecture to make sure that it
+        //can be called whenever there is someting in the queue
+        ctx.request_repaint();
+
+        self.read_input(ctx);
+
+        self.status.show(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.create_menu(ui);
+                self.create_content(ui);
+            });
+        });
+    }
+}
+
+pub fn strum() -> Result<(), CommonError> {
+    // open client
+    let (client, _status) = match jack::Client::new(TOOL_NAME, jack::ClientOptions::NO_START_SERVER)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(CommonError::UnableToStartClient(e));
+        }
+    };
+    crate::storage::set_instance(TOOL_NAME, client.name());
+
+    //open a message channel for the strum and the UI
+    let (send_to_strum, rcv_from_ui) = std::sync::mpsc::channel();
+
+    let util = Strum::new(&client, rcv_from_ui)?;
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(util)) {
+        Ok(client) => client,
+        Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
+    };
+
+    match eframe::run_native(
+        "Strum",
+        eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size(egui::vec2(320.0, 320.0)),
+            run_and_return: true,
+            ..Default::default()
+        },
+        Box::new(|cc| Ok(Box::new(StrumUI::new(cc, status, send_to_strum)))),
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
+    }
+
+    match active_client.deactivate() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommonError::UnableToDeActivateClient(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midiinput::MidiInput;
+
+    use super::{note_of, StrumConfiguration, StrumDirection, Strummer};
+
+    fn note(note_index: usize, start: bool) -> MidiInput {
+        if start {
+            MidiInput::NoteStart {
+                channel: 0,
+                note_index,
+                timing: 0,
+                velocity: 0.5,
+            }
+        } else {
+            MidiInput::NoteEnd {
+                channel: 0,
+                note_index,
+                timing: 0,
+                velocity: 0.0,
+            }
+        }
+    }
+
+    #[test]
+    fn strummed_chord() {
+        //1000 frames per second: a window of 20 frames, a strum of 100 frames
+        let mut strummer = Strummer::new(1000);
+        let conf = StrumConfiguration {
+            strum_time: 0.1,
+            direction: StrumDirection::Down,
+        };
+        strummer.configure(&conf, 1000);
+        strummer.push(0, note(60, true));
+        strummer.push(5, note(67, true));
+        strummer.push(10, note(64, true));
+        strummer.push(12, note(64, false));
+        assert!(strummer.pop(20).is_none());
+
+        let mut sent = Vec::new();
+        while let Some((time, midi)) = strummer.pop(1000) {
+            sent.push((time, note_of(&midi).unwrap(), midi));
+        }
+        let times: Vec<(u64, usize)> = sent.iter().map(|(t, n, _)| (*t, *n)).collect();
+        assert_eq!(times, vec![(20, 67), (70, 64), (70, 64), (120, 60)]);
+        assert!(matches!(sent[1].2, MidiInput::NoteStart { .. }));
+        assert!(matches!(sent[2].2, MidiInput::NoteEnd { .. }));
+    }
+}
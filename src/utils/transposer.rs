@@ -2,9 +2,13 @@ use eframe::egui::{self, ViewportBuilder};
 use jack::{MidiWriter, RawMidi};
 
 use crate::{
-    midiinput::MidiInput,
+    i18n::tr,
+    midiinput::{MidiInput, MidiNote},
+    scene::SceneListener,
+    session::Autosave,
+    spsc::{self, MESSAGES_CAPACITY},
     synth::hardware::{HardWare, KeyBoardKey},
-    utils::{CommonError, ConnectionType},
+    utils::{ChannelWatchdog, CommonError, ConnectionType, JackStatus},
 };
 
 use super::KeyBoardKeySetter;
@@ -19,9 +23,9 @@ struct Transposer {
     /// The midi output
     midi_out: jack::Port<jack::MidiOut>,
     //The incoming messages from the UI
-    messages_in: std::sync::mpsc::Receiver<MessageToTransposer>,
+    messages_in: ChannelWatchdog<MessageToTransposer, spsc::Receiver<MessageToTransposer>>,
     ///The outgoing messages to the UI
-    messages_out: std::sync::mpsc::Sender<MessageToTransposerUI>,
+    messages_out: spsc::Sender<MessageToTransposerUI>,
     ///If true, the next control will be used as key to start/stop the recording
     key_change: Option<KeyBoardKey>,
     ///The keyboard events we are listening to
@@ -31,8 +35,8 @@ struct Transposer {
 impl Transposer {
     pub fn new(
         client: &jack::Client,
-        messages_in: std::sync::mpsc::Receiver<MessageToTransposer>,
-        messages_out: std::sync::mpsc::Sender<MessageToTransposerUI>,
+        messages_in: spsc::Receiver<MessageToTransposer>,
+        messages_out: spsc::Sender<MessageToTransposerUI>,
     ) -> Result<Transposer, CommonError> {
         let m_in = match client.register_port("midi_in", jack::MidiIn::default()) {
             Ok(v) => v,
@@ -47,16 +51,21 @@ impl Transposer {
             transpose: 0,
             midi_in: m_in,
             midi_out: m_out,
-            messages_in,
+            messages_in: ChannelWatchdog::new("transposer", messages_in),
             messages_out,
             key_change: None,
             keyboard: HardWare::new(),
         })
     }
 
+    ///The note played for a received one, if it is still a midi note
+    fn transposed(note_index: usize, transpose: usize) -> Option<MidiNote> {
+        MidiNote::from_index(note_index)?.transpose(transpose as i32)
+    }
+
     fn send_message(
         msg: MessageToTransposerUI,
-        messages_out: &mut std::sync::mpsc::Sender<MessageToTransposerUI>,
+        messages_out: &mut spsc::Sender<MessageToTransposerUI>,
     ) {
         if let Err(e) = messages_out.send(msg) {
             eprintln!("Internal error: {e}");
@@ -67,7 +76,7 @@ impl Transposer {
         writer: &mut MidiWriter<'_>,
         initial: &MidiInput,
         raw: &RawMidi<'_>,
-        messages_out: &mut std::sync::mpsc::Sender<MessageToTransposerUI>,
+        messages_out: &mut spsc::Sender<MessageToTransposerUI>,
     ) {
         if let Err(e) = writer.write(raw) {
             Self::send_message(
@@ -83,7 +92,7 @@ impl Transposer {
 
 impl jack::ProcessHandler for Transposer {
     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
-        if let Ok(message) = self.messages_in.try_recv() {
+        if let Some(message) = self.messages_in.try_recv() {
             match message {
                 MessageToTransposer::ChangeActivationMidiKey(key) => self.key_change = Some(key),
                 MessageToTransposer::TransposeLevel(lvl) => self.transpose = lvl % MAX_TRANSPOSE,
@@ -133,15 +142,18 @@ impl jack::ProcessHandler for Transposer {
                 velocity,
             } = midi
             {
-                let mut bytes = vec![0; 3];
-                let raw = MidiInput::NoteStart {
-                    channel: channel,
-                    note_index: note_index + self.transpose,
-                    timing: timing,
-                    velocity: velocity,
+                //the notes moved above the midi notes are not sent
+                if let Some(note) = Self::transposed(note_index, self.transpose) {
+                    let mut bytes = vec![0; 3];
+                    let raw = MidiInput::NoteStart {
+                        channel: channel,
+                        note_index: note.index(),
+                        timing: timing,
+                        velocity: velocity,
+                    }
+                    .to_raw(&mut bytes);
+                    Self::write(&mut writer, &midi, &raw, &mut self.messages_out);
                 }
-                .to_raw(&mut bytes);
-                Self::write(&mut writer, &midi, &raw, &mut self.messages_out);
             } else if let MidiInput::NoteEnd {
                 channel,
                 note_index,
@@ -149,15 +161,17 @@ impl jack::ProcessHandler for Transposer {
                 velocity,
             } = midi
             {
-                let mut bytes = vec![0; 4];
-                let raw = MidiInput::NoteEnd {
-                    channel: channel,
-                    note_index: note_index + self.transpose,
-                    timing: timing,
-                    velocity: velocity,
+                if let Some(note) = Self::transposed(note_index, self.transpose) {
+                    let mut bytes = vec![0; 4];
+                    let raw = MidiInput::NoteEnd {
+                        channel: channel,
+                        note_index: note.index(),
+                        timing: timing,
+                        velocity: velocity,
+                    }
+                    .to_raw(&mut bytes);
+                    Self::write(&mut writer, &midi, &raw, &mut self.messages_out);
                 }
-                .to_raw(&mut bytes);
-                Self::write(&mut writer, &midi, &raw, &mut self.messages_out);
             } else {
                 Self::write(&mut writer, &midi, &e, &mut self.messages_out);
             }
@@ -190,29 +204,39 @@ enum MessageToTransposerUI {
 }
 
 struct TransposerUI {
-    messages_in: std::sync::mpsc::Receiver<MessageToTransposerUI>,
-    message_out: std::sync::mpsc::Sender<MessageToTransposer>,
+    messages_in: spsc::Receiver<MessageToTransposerUI>,
+    message_out: spsc::Sender<MessageToTransposer>,
     messages: Vec<String>,
     transpose_amount: usize,
+    ///The scene requests we are listening to
+    scenes: SceneListener,
+    ///The transposition saved for crash recovery
+    session: Autosave<usize>,
+    ///The state of the jack client, shown in the status bar
+    status: JackStatus,
 }
 
 impl TransposerUI {
     pub fn new(
         _cc: &eframe::CreationContext<'_>,
-        messages_in: std::sync::mpsc::Receiver<MessageToTransposerUI>,
-        messages_out: std::sync::mpsc::Sender<MessageToTransposer>,
+        status: JackStatus,
+        messages_in: spsc::Receiver<MessageToTransposerUI>,
+        messages_out: spsc::Sender<MessageToTransposer>,
     ) -> TransposerUI {
         TransposerUI {
+            status,
             messages_in,
             message_out: messages_out,
             messages: Vec::new(),
             transpose_amount: 0,
+            scenes: SceneListener::new(),
+            session: Autosave::new("transposer"),
         }
     }
 
     fn create_menu(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
-            ui.menu_button("Settings", |ui| {
+            ui.menu_button(tr("Settings"), |ui| {
                 crate::utils::create_keyboard_select(
                     ui,
                     "Transpose up",
@@ -233,6 +257,7 @@ impl TransposerUI {
     }
 
     fn create_content(&mut self, ui: &mut egui::Ui) {
+        self.session.show(ui);
         ui.horizontal(|ui| {
             let initial_lvl = self.transpose_amount;
             crate::utils::create_usize_slider(
@@ -254,7 +279,7 @@ impl TransposerUI {
         }
     }
 
-    fn read_input(&mut self) {
+    fn read_input(&mut self, ctx: &egui::Context) {
         //read message queue
         match self.messages_in.try_recv() {
             Err(e) => match e {
@@ -268,6 +293,19 @@ impl TransposerUI {
                 MessageToTransposerUI::TransposeLevel(lvl) => self.transpose_amount = lvl,
             },
         }
+        let session = self
+            .session
+            .update(&self.transpose_amount, &mut self.messages);
+        let dropped = crate::utils::load_dropped(ctx, &mut self.messages);
+        if let Some(lvl) = self
+            .scenes
+            .update("transposer", &self.transpose_amount, &mut self.messages)
+            .or(session)
+            .or(dropped)
+        {
+            self.transpose_amount = lvl % MAX_TRANSPOSE;
+            self.send_message(MessageToTransposer::TransposeLevel(self.transpose_amount));
+        }
     }
 }
 
@@ -278,7 +316,9 @@ impl eframe::App for TransposerUI {
         //can be called whenever there is someting in the queue
         ctx.request_repaint();
 
-        self.read_input();
+        self.read_input(ctx);
+
+        self.status.show(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -298,13 +338,15 @@ pub fn transposer() -> Result<(), CommonError> {
                 return Err(CommonError::UnableToStartClient(e));
             }
         };
+    crate::storage::set_instance("transposer", client.name());
 
     //open a message channel for the recorder and the UI
-    let (send_to_rec, rcv_from_ui) = std::sync::mpsc::channel();
-    let (send_to_ui, rcv_from_rec) = std::sync::mpsc::channel();
+    let (send_to_rec, rcv_from_ui) = spsc::channel(MESSAGES_CAPACITY);
+    let (send_to_ui, rcv_from_rec) = spsc::channel(MESSAGES_CAPACITY);
 
     let synth = Transposer::new(&client, rcv_from_ui, send_to_ui)?;
-    let active_client = match client.activate_async((), synth) {
+    let status = JackStatus::new(&client);
+    let active_client = match client.activate_async(status.notifications(), status.monitor(synth)) {
         Ok(client) => client,
         Err(e) => return Err(CommonError::UnableToActivateTheClient(e)),
     };
@@ -316,7 +358,14 @@ pub fn transposer() -> Result<(), CommonError> {
             run_and_return: true,
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(TransposerUI::new(cc, rcv_from_rec, send_to_rec)))),
+        Box::new(|cc| {
+            Ok(Box::new(TransposerUI::new(
+                cc,
+                status,
+                rcv_from_rec,
+                send_to_rec,
+            )))
+        }),
     ) {
         Ok(_) => {}
         Err(e) => return Err(CommonError::UnableToStartUserInterface(e)),
@@ -0,0 +1,174 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once, OnceLock,
+    },
+};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::tr,
+    storage::{self, StorageError},
+};
+
+///The name of the file containing the choice of the user
+const UPDATES_FILE: &str = "updates.json";
+///The version of the suite currently running
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+///True if the suite was built with the network access needed by the check
+const CHECK_AVAILABLE: bool = cfg!(feature = "update-check");
+
+///Started once per program, the first time the check is enabled
+static CHECK: Once = Once::new();
+///The message reporting the result of the check, once it is done
+static RESULT: OnceLock<Option<String>> = OnceLock::new();
+///Set once the result was added to the messages of the tool
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+///The choice of the user to look for new releases when a tool starts. The
+/// check is disabled until the user enables it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct UpdateCheck {
+    enabled: bool,
+}
+
+impl UpdateCheck {
+    fn path() -> Result<PathBuf, StorageError> {
+        let mut path = storage::config_dir()?;
+        path.push(UPDATES_FILE);
+        Ok(path)
+    }
+
+    fn load() -> UpdateCheck {
+        match Self::path() {
+            Ok(path) if path.exists() => storage::load(&path).unwrap_or_default(),
+            _ => UpdateCheck::default(),
+        }
+    }
+
+    fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path()?, self)
+    }
+
+    ///The choice currently used by a user interface, loaded the first time
+    /// it is needed
+    fn current(ctx: &egui::Context) -> UpdateCheck {
+        let id = egui::Id::new(UPDATES_FILE);
+        match ctx.data(|d| d.get_temp::<UpdateCheck>(id)) {
+            Some(check) => check,
+            None => {
+                let check = UpdateCheck::load();
+                ctx.data_mut(|d| d.insert_temp(id, check));
+                check
+            }
+        }
+    }
+
+    fn set_current(&self, ctx: &egui::Context) {
+        ctx.data_mut(|d| d.insert_temp(egui::Id::new(UPDATES_FILE), *self));
+    }
+}
+
+///The numbers of a version like "v1.2.3", so that versions can be compared
+fn version_numbers(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|n| n.parse().ok())
+        .collect()
+}
+
+///True if the version 'latest' was released after 'current'
+fn is_newer(latest: &str, current: &str) -> bool {
+    version_numbers(latest) > version_numbers(current)
+}
+
+///The tag of the latest release of the suite
+#[cfg(feature = "update-check")]
+fn latest_release() -> Result<String, String> {
+    const RELEASES_URL: &str = "https://api.github.com/repos/benerjo/rsuite/releases/latest";
+    let body = ureq::get(RELEASES_URL)
+        .header("User-Agent", "rsuite")
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| e.to_string())?;
+    let release: serde_json::Value = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    release["tag_name"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| String::from("the release has no tag"))
+}
+
+#[cfg(not(feature = "update-check"))]
+fn latest_release() -> Result<String, String> {
+    Err(String::from("built without the update-check feature"))
+}
+
+///Look for a new release in a thread of its own, so that neither the audio
+/// nor the user interface wait for the network
+fn start_check(ctx: &egui::Context) {
+    CHECK.call_once(|| {
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let message = match latest_release() {
+                Ok(latest) if is_newer(&latest, CURRENT_VERSION) => Some(format!(
+                    "rsuite {latest} is available (running {CURRENT_VERSION})"
+                )),
+                Ok(_) => None,
+                Err(e) => Some(format!("Unable to check for new releases: {e}")),
+            };
+            let _ = RESULT.set(message);
+            ctx.request_repaint();
+        });
+    });
+}
+
+///Generate the menu to enable the check of new releases, and report the
+/// result of the check in the messages of the tool
+pub fn create_updates_menu(ui: &mut egui::Ui, messages: &mut Vec<String>) {
+    let current = UpdateCheck::current(ui.ctx());
+    if current.enabled && CHECK_AVAILABLE {
+        start_check(ui.ctx());
+    }
+    if let Some(result) = RESULT.get() {
+        if !REPORTED.swap(true, Ordering::Relaxed) {
+            messages.extend(result.clone());
+        }
+    }
+    ui.menu_button(tr("Help"), |ui| {
+        ui.label(format!("rsuite {CURRENT_VERSION}"));
+        let mut check = current;
+        ui.add_enabled(
+            CHECK_AVAILABLE,
+            egui::Checkbox::new(&mut check.enabled, tr("Check for new releases")),
+        )
+        .on_disabled_hover_text(tr("Built without the update-check feature"));
+        if check != current {
+            check.set_current(ui.ctx());
+            if let Err(e) = check.save() {
+                messages.push(format!("Unable to save the update check: {e}"));
+            }
+        }
+        ui.separator();
+        super::statistics::create_statistics_button(ui);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_newer;
+
+    #[test]
+    fn newer_versions() {
+        assert!(is_newer("v0.0.2", "0.0.1"));
+        assert!(is_newer("0.1.0", "0.0.9"));
+        assert!(is_newer("v1.0.0", "0.10.3"));
+        assert!(!is_newer("v0.0.1", "0.0.1"));
+        assert!(!is_newer("0.0.1", "0.1.0"));
+        assert!(!is_newer("nightly", "0.0.1"));
+    }
+}
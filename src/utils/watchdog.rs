@@ -0,0 +1,94 @@
+use std::{
+    marker::PhantomData,
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+use crate::spsc::MessageReceiver;
+
+///The state of the channel between the user interface and the audio thread
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelState {
+    #[default]
+    Connected,
+    Disconnected,
+}
+
+///Watch the channel receiving the messages of the user interface in the
+/// audio thread. The loss of the user interface is reported once, and the
+/// tool can be muted from then on. The channel is either a standard one or
+/// a real-time safe one from [crate::spsc].
+pub struct ChannelWatchdog<T, R = Receiver<T>> {
+    ///The name of the tool, used when reporting the disconnection
+    name: &'static str,
+    receiver: R,
+    state: ChannelState,
+    ///If true, the tool should be silent while the channel is disconnected
+    mute_when_disconnected: bool,
+    _message: PhantomData<T>,
+}
+
+impl<T, R> ChannelWatchdog<T, R>
+where
+    R: MessageReceiver<T>,
+{
+    pub fn new(name: &'static str, receiver: R) -> ChannelWatchdog<T, R> {
+        ChannelWatchdog {
+            name,
+            receiver,
+            state: ChannelState::Connected,
+            mute_when_disconnected: false,
+            _message: PhantomData,
+        }
+    }
+
+    ///Ask the tool to be silent while no user interface is connected
+    pub fn mute_when_disconnected(mut self) -> ChannelWatchdog<T, R> {
+        self.mute_when_disconnected = true;
+        self
+    }
+
+    pub fn state(&self) -> ChannelState {
+        self.state
+    }
+
+    ///True if the output of the tool should be silent
+    pub fn muted(&self) -> bool {
+        self.mute_when_disconnected && self.state == ChannelState::Disconnected
+    }
+
+    ///Retrieve the next message of the user interface, if any
+    pub fn try_recv(&mut self) -> Option<T> {
+        match self.receiver.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                if self.state == ChannelState::Connected {
+                    self.state = ChannelState::Disconnected;
+                    eprintln!("{}: lost the connection with the user interface", self.name);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChannelState, ChannelWatchdog};
+
+    #[test]
+    fn disconnection() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watchdog = ChannelWatchdog::new("test", receiver).mute_when_disconnected();
+        sender.send(1).unwrap();
+        assert_eq!(watchdog.try_recv(), Some(1));
+        assert_eq!(watchdog.try_recv(), None);
+        assert!(!watchdog.muted());
+
+        drop(sender);
+        assert_eq!(watchdog.try_recv(), None);
+        assert_eq!(watchdog.state(), ChannelState::Disconnected);
+        assert!(watchdog.muted());
+        assert_eq!(watchdog.try_recv(), None);
+    }
+}
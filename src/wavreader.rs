@@ -0,0 +1,278 @@
+use std::fmt::Display;
+
+const WAV_FORMAT_PCM: u16 = 0x01;
+const WAV_FORMAT_IEEE_FLOAT: u16 = 0x03;
+const WAV_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+///The errors that can occur while decoding an audio file
+#[derive(Debug)]
+pub enum WavReadError {
+    FileError(std::io::Error),
+    NotAWaveFile,
+    MissingChunk(&'static str),
+    UnsupportedFormat { format: u16, bits_per_sample: u16 },
+}
+
+impl From<std::io::Error> for WavReadError {
+    fn from(value: std::io::Error) -> Self {
+        WavReadError::FileError(value)
+    }
+}
+
+impl Display for WavReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WavReadError::FileError(e) => write!(f, "{e}"),
+            WavReadError::NotAWaveFile => write!(f, "Not a RIFF/WAVE file"),
+            WavReadError::MissingChunk(c) => write!(f, "Missing '{c}' chunk"),
+            WavReadError::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "Unsupported wav format {format:#06x} with {bits_per_sample} bits per sample"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WavReadError {}
+
+///Decoded audio, with samples normalized in the range [-1, 1]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioData {
+    ///The number of frames per seconds
+    pub rate: u32,
+    ///The number of interleaved channels
+    pub channels: u16,
+    ///The interleaved samples
+    pub samples: Vec<f32>,
+}
+
+impl AudioData {
+    ///The number of frames (one sample for every channel)
+    pub fn frames(&self) -> usize {
+        if self.channels == 0 {
+            0
+        } else {
+            self.samples.len() / self.channels as usize
+        }
+    }
+
+    ///Average every channel into a single one
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        self.samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+
+    ///Convert the audio to another rate, using a linear interpolation between frames
+    pub fn resampled(&self, rate: u32) -> AudioData {
+        if rate == self.rate || self.rate == 0 || self.frames() == 0 {
+            return AudioData {
+                rate,
+                channels: self.channels,
+                samples: self.samples.clone(),
+            };
+        }
+        let channels = self.channels as usize;
+        let frames = self.frames();
+        let step = self.rate as f64 / rate as f64;
+        let nb_frames = ((frames as f64) / step).ceil() as usize;
+        let mut samples = Vec::with_capacity(nb_frames * channels);
+        for frame in 0..nb_frames {
+            let position = frame as f64 * step;
+            let index = position.floor() as usize;
+            let fraction = (position - index as f64) as f32;
+            let next = std::cmp::min(index + 1, frames - 1);
+            for channel in 0..channels {
+                let a = self.samples[index * channels + channel];
+                let b = self.samples[next * channels + channel];
+                samples.push(a + (b - a) * fraction);
+            }
+        }
+        AudioData {
+            rate,
+            channels: self.channels,
+            samples,
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+///Convert the raw content of a data chunk into normalized samples
+fn decode_samples(
+    data: &[u8],
+    format: u16,
+    bits_per_sample: u16,
+) -> Result<Vec<f32>, WavReadError> {
+    let samples = match (format, bits_per_sample) {
+        (WAV_FORMAT_PCM, 8) => data.iter().map(|v| (*v as f32 - 128.0) / 128.0).collect(),
+        (WAV_FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|v| i16::from_le_bytes([v[0], v[1]]) as f32 / 32768.0)
+            .collect(),
+        (WAV_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|v| (i32::from_le_bytes([0, v[0], v[1], v[2]]) >> 8) as f32 / 8388608.0)
+            .collect(),
+        (WAV_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|v| i32::from_le_bytes([v[0], v[1], v[2], v[3]]) as f32 / 2147483648.0)
+            .collect(),
+        (WAV_FORMAT_IEEE_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+        (WAV_FORMAT_IEEE_FLOAT, 64) => data
+            .chunks_exact(8)
+            .map(|v| f64::from_le_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]) as f32)
+            .collect(),
+        _ => {
+            return Err(WavReadError::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            })
+        }
+    };
+    Ok(samples)
+}
+
+///Decode the content of a wav file
+pub fn decode(bytes: &[u8]) -> Result<AudioData, WavReadError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavReadError::NotAWaveFile);
+    }
+
+    let mut header: Option<&[u8]> = None;
+    let mut data: Option<&[u8]> = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let size = read_u32(bytes, offset + 4) as usize;
+        let start = offset + 8;
+        //tolerate truncated files (e.g. a recording that was not finalized)
+        let end = std::cmp::min(start + size, bytes.len());
+        match &bytes[offset..offset + 4] {
+            b"fmt " => header = Some(&bytes[start..end]),
+            b"data" => data = Some(&bytes[start..end]),
+            _ => {}
+        }
+        //chunks are aligned on two bytes
+        offset = start + size + (size & 1);
+    }
+
+    let header = header.ok_or(WavReadError::MissingChunk("fmt "))?;
+    let data = data.ok_or(WavReadError::MissingChunk("data"))?;
+    if header.len() < 16 {
+        return Err(WavReadError::MissingChunk("fmt "));
+    }
+
+    let mut format = read_u16(header, 0);
+    let channels = read_u16(header, 2);
+    let rate = read_u32(header, 4);
+    let bits_per_sample = read_u16(header, 14);
+    if format == WAV_FORMAT_EXTENSIBLE && header.len() >= 26 {
+        //the actual format is given by the first two bytes of the sub-format GUID
+        format = read_u16(header, 24);
+    }
+
+    Ok(AudioData {
+        rate,
+        channels,
+        samples: decode_samples(data, format, bits_per_sample)?,
+    })
+}
+
+///Read and decode a wav file
+pub fn load_wav(path: &std::path::Path) -> Result<AudioData, WavReadError> {
+    let bytes = std::fs::read(path)?;
+    decode(&bytes)
+}
+
+///Read a wav file, mixed down to a single channel and converted to the given rate
+pub fn load_mono(path: &std::path::Path, rate: usize) -> Result<Vec<f32>, WavReadError> {
+    Ok(load_wav(path)?.resampled(rate as u32).to_mono())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, AudioData, WavReadError};
+
+    fn wav_bytes(format: u16, channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        let block = channels * bits / 8;
+        bytes.extend_from_slice(&(rate * block as u32).to_le_bytes());
+        bytes.extend_from_slice(&block.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn decode_pcm16_stereo() {
+        let data: Vec<u8> = [16384i16, -16384, 0, 32767]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let audio = decode(&wav_bytes(1, 2, 44100, 16, &data)).unwrap();
+        assert_eq!(audio.rate, 44100);
+        assert_eq!(audio.frames(), 2);
+        assert_eq!(audio.samples[0], 0.5);
+        assert_eq!(audio.samples[1], -0.5);
+        assert_eq!(audio.to_mono(), vec![0.0, 32767.0 / 65536.0]);
+    }
+
+    #[test]
+    fn decode_float_and_unsupported() {
+        let data: Vec<u8> = [0.25f32, -1.0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let audio = decode(&wav_bytes(3, 1, 48000, 32, &data)).unwrap();
+        assert_eq!(audio.samples, vec![0.25, -1.0]);
+        assert!(matches!(
+            decode(&wav_bytes(2, 1, 48000, 4, &data)),
+            Err(WavReadError::UnsupportedFormat { .. })
+        ));
+        assert!(matches!(
+            decode(b"not a wav file"),
+            Err(WavReadError::NotAWaveFile)
+        ));
+    }
+
+    #[test]
+    fn resample_keeps_duration() {
+        let audio = AudioData {
+            rate: 22050,
+            channels: 1,
+            samples: vec![0.0, 1.0, 0.0, -1.0],
+        };
+        let up = audio.resampled(44100);
+        assert_eq!(up.frames(), 8);
+        assert_eq!(up.samples[1], 0.5);
+        assert_eq!(up.resampled(22050).samples, audio.samples);
+    }
+}
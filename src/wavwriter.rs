@@ -1,3 +1,7 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 struct PairIter<T> {
     a: Option<T>,
     b: Option<T>,
@@ -72,13 +76,23 @@ fn get_wav_header(
     v
 }
 
-pub fn save_wav(to_save: Vec<i16>, rate: u32, prefix: Option<&str>) -> Result<(), std::io::Error> {
+///The name of a new recording: the prefix followed by the current time
+fn file_name(prefix: Option<&str>) -> String {
     let now = chrono::offset::Local::now();
-    let mut out_file = std::fs::File::create(std::path::Path::new(&format!(
+    format!(
         "{}-{}.wav",
         prefix.unwrap_or("output"),
         now.format("%Y%m%d%H%M%S")
-    )))?;
+    )
+}
+
+pub fn save_wav(to_save: Vec<i16>, rate: u32, prefix: Option<&str>) -> Result<(), std::io::Error> {
+    write_wav(std::path::Path::new(&file_name(prefix)), &to_save, rate)
+}
+
+///Write mono samples to a wav file
+pub fn write_wav(path: &std::path::Path, to_save: &[i16], rate: u32) -> Result<(), std::io::Error> {
+    let mut out_file = std::fs::File::create(path)?;
 
     let header = get_wav_header(WAV_FORMAT_PCM, 1, rate, 16);
 
@@ -109,3 +123,245 @@ pub fn save_wav(to_save: Vec<i16>, rate: u32, prefix: Option<&str>) -> Result<()
 
     Ok(())
 }
+
+///The way the samples are rounded when they are reduced to 16 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMode {
+    ///The samples are truncated
+    Off,
+    ///A triangular noise of one bit is added before rounding, so that the
+    /// quantization error does not follow the signal
+    Tpdf,
+    ///Like [DitherMode::Tpdf], and the quantization error is fed back to
+    /// push the noise to the high frequencies, where it is less audible
+    NoiseShaped,
+}
+
+///Reduce floating point samples to 16 bits
+pub struct Dither {
+    mode: DitherMode,
+    ///The quantization error of the previous sample, in bits
+    error: f64,
+    ///The source of the noise
+    rng: StdRng,
+}
+
+impl Dither {
+    pub fn new(mode: DitherMode) -> Dither {
+        Dither::with_rng(mode, StdRng::from_entropy())
+    }
+
+    ///A dither whose noise is always the same for a given seed
+    pub fn seeded(mode: DitherMode, seed: u64) -> Dither {
+        Dither::with_rng(mode, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(mode: DitherMode, rng: StdRng) -> Dither {
+        Dither {
+            mode,
+            error: 0.0,
+            rng,
+        }
+    }
+
+    pub fn mode(&self) -> DitherMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: DitherMode) {
+        self.mode = mode;
+        self.error = 0.0;
+    }
+
+    ///Convert a sample, between -1 and 1, to 16 bits
+    pub fn quantize(&mut self, value: f32) -> i16 {
+        let scaled = value as f64 * 32768.0;
+        let target = match self.mode {
+            DitherMode::Off => return scaled as i16,
+            DitherMode::Tpdf => scaled,
+            DitherMode::NoiseShaped => scaled - self.error,
+        };
+        let noise = self.rng.gen::<f64>() - self.rng.gen::<f64>();
+        let quantized = (target + noise)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64);
+        self.error = quantized - target;
+        quantized as i16
+    }
+}
+
+///The size of the header of a wav file written by [WavStream], without
+/// metadata
+const STREAM_HEADER_SIZE: u32 = 44;
+///The position of the size of the RIFF chunk
+const RIFF_SIZE_OFFSET: u64 = 4;
+
+///A LIST chunk holding the comment of the file (INFO/ICMT), as written by
+/// most audio editors
+fn comment_chunk(comment: &str) -> Vec<u8> {
+    let mut text = comment.as_bytes().to_vec();
+    text.push(0);
+    let text_size = text.len() as u32;
+    //chunks are aligned on two bytes
+    if text.len() % 2 == 1 {
+        text.push(0);
+    }
+    let mut chunk = Vec::with_capacity(text.len() + 20);
+    chunk.extend_from_slice(b"LIST");
+    chunk.extend_from_slice(&(text.len() as u32 + 12).to_le_bytes());
+    chunk.extend_from_slice(b"INFOICMT");
+    chunk.extend_from_slice(&text_size.to_le_bytes());
+    chunk.extend_from_slice(&text);
+    chunk
+}
+
+///A mono wav file written while it is recorded. The sizes in the header are
+/// updated at every sync, so that the file stays readable up to the last
+/// sync if the program stops unexpectedly.
+pub struct WavStream {
+    file: std::io::BufWriter<std::fs::File>,
+    path: std::path::PathBuf,
+    ///The number of bytes of audio written
+    data_size: u32,
+    ///The number of bytes before the audio
+    header_size: u32,
+}
+
+impl WavStream {
+    ///Create a new wav file, named after the prefix and the current time.
+    /// The latency compensated while recording, in frames, is stored in the
+    /// comment of the file, so that the take can be aligned with the midi
+    /// recorded at the same time.
+    pub fn create(
+        rate: u32,
+        prefix: Option<&str>,
+        latency: u32,
+    ) -> Result<WavStream, std::io::Error> {
+        let path = std::path::PathBuf::from(file_name(prefix));
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        let metadata = if latency > 0 {
+            comment_chunk(&format!("Latency compensation: {latency} frames"))
+        } else {
+            Vec::new()
+        };
+        let header_size = STREAM_HEADER_SIZE + metadata.len() as u32;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(header_size - 8).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16_u32.to_le_bytes())?;
+        file.write_all(&get_wav_header(WAV_FORMAT_PCM, 1, rate, 16))?;
+        file.write_all(&metadata)?;
+        file.write_all(b"data")?;
+        file.write_all(&0_u32.to_le_bytes())?;
+        Ok(WavStream {
+            file,
+            path,
+            data_size: 0,
+            header_size,
+        })
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    ///Add samples at the end of the file
+    pub fn append(&mut self, samples: &[i16]) -> Result<(), std::io::Error> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += 2 * samples.len() as u32;
+        Ok(())
+    }
+
+    ///Write the pending samples to the disk and update the sizes in the header
+    pub fn sync(&mut self) -> Result<(), std::io::Error> {
+        self.file.flush()?;
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        file.write_all(&(self.header_size - 8 + self.data_size).to_le_bytes())?;
+        //the size of the data chunk ends the header
+        file.seek(SeekFrom::Start(self.header_size as u64 - 4))?;
+        file.write_all(&self.data_size.to_le_bytes())?;
+        file.seek(SeekFrom::End(0))?;
+        file.sync_data()
+    }
+
+    ///Complete the file
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        self.sync()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dither, DitherMode, WavStream};
+
+    #[test]
+    fn dither_keeps_quiet_signals() {
+        //a quarter of a bit is lost when truncating, kept on average when dithering
+        let value = 0.25 / 32768.0;
+        assert_eq!(Dither::new(DitherMode::Off).quantize(value), 0);
+        for mode in [DitherMode::Tpdf, DitherMode::NoiseShaped] {
+            let mut dither = Dither::seeded(mode, 1);
+            let count = 100000;
+            let sum: i64 = (0..count).map(|_| dither.quantize(value) as i64).sum();
+            let mean = sum as f64 / count as f64;
+            assert!((mean - 0.25).abs() < 0.02, "{mode:?}: {mean}");
+        }
+        let mut dither = Dither::seeded(DitherMode::Tpdf, 1);
+        assert_eq!(dither.quantize(1.0), i16::MAX);
+        assert_eq!(dither.quantize(-1.0), i16::MIN);
+    }
+
+    #[test]
+    fn stream_header() {
+        let mut prefix = std::env::temp_dir();
+        prefix.push("rsuite_stream_test");
+        let mut stream = WavStream::create(48000, prefix.to_str(), 0).unwrap();
+        let path = stream.path().to_path_buf();
+        stream.append(&[1, -1, 2]).unwrap();
+        stream.sync().unwrap();
+        //the file is valid before the end of the recording
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(content.len(), 44 + 6);
+        assert_eq!(&content[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(content[4..8].try_into().unwrap()),
+            36 + 6
+        );
+        assert_eq!(u32::from_le_bytes(content[40..44].try_into().unwrap()), 6);
+        stream.append(&[3]).unwrap();
+        stream.finish().unwrap();
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(u32::from_le_bytes(content[40..44].try_into().unwrap()), 8);
+        assert_eq!(&content[44..], &[1, 0, 255, 255, 2, 0, 3, 0]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn latency_in_the_comment() {
+        let mut prefix = std::env::temp_dir();
+        prefix.push("rsuite_latency_test");
+        let mut stream = WavStream::create(48000, prefix.to_str(), 256).unwrap();
+        let path = stream.path().to_path_buf();
+        stream.append(&[1, -1]).unwrap();
+        stream.finish().unwrap();
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let comment = b"Latency compensation: 256 frames\0";
+        let list = 12 + comment.len() + comment.len() % 2;
+        assert_eq!(&content[36..40], b"LIST");
+        assert_eq!(&content[44..52], b"INFOICMT");
+        assert_eq!(&content[56..56 + comment.len()], comment);
+        //the audio still follows the header, and is found by the readers
+        let data = 44 + list;
+        assert_eq!(&content[data..data + 4], b"data");
+        assert_eq!(
+            u32::from_le_bytes(content[4..8].try_into().unwrap()) as usize,
+            content.len() - 8
+        );
+        assert_eq!(crate::wavreader::decode(&content).unwrap().frames(), 2);
+    }
+}